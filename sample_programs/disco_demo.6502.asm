@@ -0,0 +1,84 @@
+; disco_demo.nes
+; Public domain. Released into the public domain by the disco5 authors; use
+; it for anything.
+;
+; NROM-128 (16 KB PRG at $C000, 8 KB CHR). Loads a palette, fills all four
+; nametables with the four tiles in CHR, and scrolls horizontally by one
+; pixel every frame from the NMI handler.
+;
+; Vectors ($FFFA): NMI = nmi, RESET = reset, IRQ = irq
+;
+; CHR tiles:
+;   0 | solid color 1
+;   1 | checkerboard of colors 1 and 2
+;   2 | diagonal line in color 3
+;   3 | framed square in colors 1 and 3
+
+reset:
+    SEI                      ; $c000: 78
+    CLD                      ; $c001: d8
+    LDX #$ff                 ; $c002: a2 ff
+    TXS                      ; $c004: 9a
+    LDA #$00                 ; $c005: a9 00
+    STA $2000                ; $c007: 8d 00 20
+    STA $2001                ; $c00a: 8d 01 20
+vblankwait1:
+    BIT $2002                ; $c00d: 2c 02 20
+    BPL vblankwait1          ; $c010: 10 fb
+vblankwait2:
+    BIT $2002                ; $c012: 2c 02 20
+    BPL vblankwait2          ; $c015: 10 fb
+    LDA #$3f                 ; $c017: a9 3f
+    STA $2006                ; $c019: 8d 06 20
+    LDA #$00                 ; $c01c: a9 00
+    STA $2006                ; $c01e: 8d 06 20
+    LDX #$00                 ; $c021: a2 00
+load_palette:
+    LDA palette,X            ; $c023: bd 70 c0
+    STA $2007                ; $c026: 8d 07 20
+    INX                      ; $c029: e8
+    CPX #$20                 ; $c02a: e0 20
+    BNE load_palette         ; $c02c: d0 f5
+    LDA #$20                 ; $c02e: a9 20
+    STA $2006                ; $c030: 8d 06 20
+    LDA #$00                 ; $c033: a9 00
+    STA $2006                ; $c035: 8d 06 20
+    LDY #$10                 ; $c038: a0 10
+fill_page:
+    LDX #$00                 ; $c03a: a2 00
+fill_byte:
+    TXA                      ; $c03c: 8a
+    AND #$03                 ; $c03d: 29 03
+    STA $2007                ; $c03f: 8d 07 20
+    INX                      ; $c042: e8
+    BNE fill_byte            ; $c043: d0 f7
+    DEY                      ; $c045: 88
+    BNE fill_page            ; $c046: d0 f2
+    BIT $2002                ; $c048: 2c 02 20
+    LDA #$00                 ; $c04b: a9 00
+    STA $2005                ; $c04d: 8d 05 20
+    STA $2005                ; $c050: 8d 05 20
+    LDA #$80                 ; $c053: a9 80
+    STA $2000                ; $c055: 8d 00 20
+    LDA #$0a                 ; $c058: a9 0a
+    STA $2001                ; $c05a: 8d 01 20
+forever:
+    JMP forever              ; $c05d: 4c 5d c0
+nmi:
+    INC $00                  ; $c060: e6 00
+    BIT $2002                ; $c062: 2c 02 20
+    LDA $00                  ; $c065: a5 00
+    STA $2005                ; $c067: 8d 05 20
+    LDA #$00                 ; $c06a: a9 00
+    STA $2005                ; $c06c: 8d 05 20
+irq:
+    RTI                      ; $c06f: 40
+palette:
+    .byte $0f, $21, $2a, $30 ; $c070: 0f 21 2a 30
+    .byte $0f, $16, $27, $38 ; $c074: 0f 16 27 38
+    .byte $0f, $12, $1c, $3c ; $c078: 0f 12 1c 3c
+    .byte $0f, $14, $24, $34 ; $c07c: 0f 14 24 34
+    .byte $0f, $21, $2a, $30 ; $c080: 0f 21 2a 30
+    .byte $0f, $16, $27, $38 ; $c084: 0f 16 27 38
+    .byte $0f, $12, $1c, $3c ; $c088: 0f 12 1c 3c
+    .byte $0f, $14, $24, $34 ; $c08c: 0f 14 24 34
@@ -1,12 +1,199 @@
+#[cfg(feature = "png")]
+use disco5::frame_diff::{compare_frames, read_png, save_png};
+use disco5::nes::memory_editor::{MemoryEditor, MemoryImport};
+use disco5::nes::ram_init::RamInit;
+use disco5::nes::region::Region;
+use disco5::nes::wav::save_wav;
 use disco5::nes::*;
+use disco5::selftest::{print_report, run_selftest};
 use speedy2d::Window;
+use std::env;
+use std::io;
+use std::process;
 
 fn main() {
+    if env::args().nth(1).as_deref() == Some("selftest") {
+        let passed = print_report(&run_selftest());
+        process::exit(if passed { 0 } else { 1 });
+    }
+
+    #[cfg(feature = "png")]
+    if env::args().nth(1).as_deref() == Some("diff") {
+        let args: Vec<String> = env::args().skip(2).collect();
+        if !(2..=3).contains(&args.len()) {
+            eprintln!("usage: disco5 diff <a.png> <b.png> [heatmap.png]");
+            process::exit(2);
+        }
+        process::exit(diff_screenshots(&args[0], &args[1], args.get(2)));
+    }
+
     let mut nes: NES = Default::default();
+    if let Some(pattern) = flag_value("--ram-init") {
+        match pattern.parse::<RamInit>() {
+            Ok(ram_init) => nes.address_space.ram_init = ram_init,
+            Err(error) => {
+                eprintln!("--ram-init: {error}");
+                process::exit(2);
+            }
+        }
+    }
 
-    nes.load_nrom_128(&String::from("sample_programs/Donkey Kong.nes"), 0x8000)
+    if env::args().any(|arg| arg == "--demo") {
+        nes.load_demo().unwrap();
+    } else {
+        let rom = String::from("sample_programs/Donkey Kong.nes");
+        match flag_value("--patch") {
+            Some(patch) => nes.load_nrom_128_with_patch(&rom, Some(&patch), 0x8000),
+            None => nes.load_nrom_128(&rom, 0x8000),
+        }
         .unwrap();
+    }
+    if let Some(seed) = flag_value("--fuzz-unmapped") {
+        match seed.parse() {
+            Ok(seed) => nes.address_space.fuzz_unmapped(seed),
+            Err(error) => {
+                eprintln!("--fuzz-unmapped: {error}");
+                process::exit(2);
+            }
+        }
+    }
+    for code in flag_values("--cheat") {
+        if let Err(error) = nes.address_space.cheats.add_code(&code) {
+            eprintln!("--cheat: {error}");
+            process::exit(2);
+        }
+    }
+    let mut editor: MemoryEditor = Default::default();
+    for import in flag_values("--import") {
+        let result = import
+            .parse::<MemoryImport>()
+            .map_err(|error| io::Error::new(io::ErrorKind::InvalidInput, error))
+            .and_then(|import| {
+                editor.import(
+                    &mut nes.address_space,
+                    import.region,
+                    import.address,
+                    &import.filename,
+                )
+            });
+        if let Err(error) = result {
+            eprintln!("--import {import}: {error}");
+            process::exit(2);
+        }
+    }
+    if env::args().any(|arg| arg == "--pal") {
+        nes.set_region(Region::Pal);
+    }
+    if let Some(palette) = flag_value("--palette") {
+        nes.load_palette(&palette).unwrap();
+    }
+    if env::args().any(|arg| arg == "--ntsc") {
+        nes.toggle_ntsc_filter();
+    }
+    if let Some(chr) = flag_value("--chr") {
+        nes.set_chr_override(&chr).unwrap();
+    }
+
+    if let Some(filename) = flag_value("--record-audio") {
+        process::exit(record_audio(&mut nes, &filename));
+    }
+
+    #[cfg(feature = "cpal")]
+    if !env::args().any(|arg| arg == "--mute") {
+        match nes.open_host_audio() {
+            Ok(()) if env::args().any(|arg| arg == "--sync-audio") => {
+                nes.sync = SyncMode::Audio;
+            }
+            Ok(()) => {}
+            Err(error) => eprintln!("audio: {error}, continuing without sound"),
+        }
+    }
 
-    let window = Window::new_centered("Disco5", (1024, 960)).unwrap();
+    let window = Window::new_centered(&nes.window.title, nes.window.size).unwrap();
     window.run_loop(nes);
 }
+
+/// returns the argument following flag
+fn flag_value(flag: &str) -> Option<String> {
+    let mut args = env::args().skip_while(|arg| arg != flag);
+    args.next();
+    args.next()
+}
+
+/// returns the argument following each use of flag
+fn flag_values(flag: &str) -> Vec<String> {
+    let args: Vec<String> = env::args().collect();
+    args.windows(2)
+        .filter(|pair| pair[0] == flag)
+        .map(|pair| pair[1].clone())
+        .collect()
+}
+
+/// frames --record-audio runs without --frames, 10 seconds at 60 fps
+const DEFAULT_RECORDED_FRAMES: usize = 600;
+
+/// runs headlessly for --frames frames, saving the audio to filename,
+/// returns the process exit status
+fn record_audio(nes: &mut NES, filename: &str) -> i32 {
+    let frames = match flag_value("--frames").map(|frames| frames.parse()) {
+        None => DEFAULT_RECORDED_FRAMES,
+        Some(Ok(frames)) => frames,
+        Some(Err(error)) => {
+            eprintln!("--frames: {error}");
+            return 2;
+        }
+    };
+    nes.headless = true;
+    let samples = nes.record_audio(frames);
+    match save_wav(
+        filename,
+        &samples,
+        nes.address_space.apu.resampler.sample_rate,
+    ) {
+        Ok(()) => 0,
+        Err(error) => {
+            eprintln!("{filename}: {error}");
+            2
+        }
+    }
+}
+
+/// compares two saved screenshots, returns the process exit status
+#[cfg(feature = "png")]
+fn diff_screenshots(a: &str, b: &str, heatmap: Option<&String>) -> i32 {
+    let frames = read_png(a).and_then(|a| Ok((a, read_png(b)?)));
+    let (a, b) = match frames {
+        Ok(frames) => frames,
+        Err(error) => {
+            eprintln!("{error}");
+            return 2;
+        }
+    };
+
+    if (a.width, a.height) != (b.width, b.height) {
+        eprintln!(
+            "screenshots differ in size, {}x{} and {}x{}",
+            a.width, a.height, b.width, b.height
+        );
+        return 2;
+    }
+    let diff = compare_frames(&a, &b);
+    match diff.bounding_box {
+        Some(bounds) => println!(
+            "{} pixels differ within ({}, {})..=({}, {})",
+            diff.differing_pixels, bounds.left, bounds.top, bounds.right, bounds.bottom
+        ),
+        None => println!("frames are identical"),
+    }
+    if let Some(filename) = heatmap {
+        if let Err(error) = save_png(filename, &diff.heatmap) {
+            eprintln!("{error}");
+            return 2;
+        }
+    }
+    if diff.is_identical() {
+        0
+    } else {
+        1
+    }
+}
@@ -0,0 +1,174 @@
+// Pixel level comparison of rendered frames, so regression tests can show
+// where rendering changed rather than only that a frame hash differs
+
+#[cfg(feature = "png")]
+use std::fs::File;
+#[cfg(feature = "png")]
+use std::io::{self, BufReader, BufWriter};
+
+use crate::nes::frame::Frame;
+
+/// smallest brightness of a differing pixel in the heatmap, so that even
+/// tiny color changes stand out against the dimmed background
+const HEATMAP_MINIMUM_RED: u16 = 0x80;
+
+/// Inclusive pixel bounds of a region of a frame
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub struct BoundingBox {
+    pub left: usize,
+    pub top: usize,
+    pub right: usize,
+    pub bottom: usize,
+}
+
+/// Differences between two frames
+#[derive(Clone, Debug)]
+pub struct FrameDiff {
+    /// number of pixels whose colors differ
+    pub differing_pixels: usize,
+    /// smallest box holding every differing pixel, None if the frames match
+    pub bounding_box: Option<BoundingBox>,
+    /// the first frame dimmed to greyscale, with differing pixels drawn in
+    /// red as bright as the difference is large
+    pub heatmap: Frame,
+}
+
+impl FrameDiff {
+    /// returns whether or not the frames were identical
+    pub fn is_identical(&self) -> bool {
+        self.differing_pixels == 0
+    }
+
+    /// encodes the heatmap as a PNG image
+    #[cfg(feature = "png")]
+    pub fn heatmap_png(&self) -> io::Result<Vec<u8>> {
+        let mut bytes = Vec::new();
+        write_png(&mut bytes, &self.heatmap)?;
+        Ok(bytes)
+    }
+}
+
+/// compares two frames of the same size
+pub fn compare_frames(a: &Frame, b: &Frame) -> FrameDiff {
+    assert_eq!(
+        (a.width, a.height),
+        (b.width, b.height),
+        "frames differ in size"
+    );
+
+    let mut differing_pixels = 0;
+    let mut bounding_box: Option<BoundingBox> = None;
+    let mut heatmap = Frame::new(a.width, a.height);
+
+    for (i, ((ar, ag, ab), (br, bg, bb))) in a.rgb_pixels().zip(b.rgb_pixels()).enumerate() {
+        let (x, y) = (i % a.width, i / a.width);
+        let difference =
+            u16::from(ar.abs_diff(br)) + u16::from(ag.abs_diff(bg)) + u16::from(ab.abs_diff(bb));
+        if difference == 0 {
+            let grey = ((u16::from(ar) + u16::from(ag) + u16::from(ab)) / 12) as u8;
+            heatmap.set_pixel(x, y, (grey, grey, grey));
+            continue;
+        }
+
+        differing_pixels += 1;
+        let red = (HEATMAP_MINIMUM_RED + difference).min(0xff) as u8;
+        heatmap.set_pixel(x, y, (red, 0, 0));
+
+        bounding_box = Some(match bounding_box {
+            Some(bounds) => BoundingBox {
+                left: bounds.left.min(x),
+                top: bounds.top.min(y),
+                right: bounds.right.max(x),
+                bottom: bounds.bottom.max(y),
+            },
+            None => BoundingBox {
+                left: x,
+                top: y,
+                right: x,
+                bottom: y,
+            },
+        });
+    }
+
+    FrameDiff {
+        differing_pixels,
+        bounding_box,
+        heatmap,
+    }
+}
+
+/// writes a frame as an 8-bit RGBA PNG image
+#[cfg(feature = "png")]
+pub fn write_png<W: io::Write>(writer: W, frame: &Frame) -> io::Result<()> {
+    write_png_with_text(writer, frame, &[])
+}
+
+/// writes a frame as an 8-bit RGBA PNG image with a tEXt chunk for each
+/// keyword and value in text, ahead of the image data
+#[cfg(feature = "png")]
+pub fn write_png_with_text<W: io::Write>(
+    writer: W,
+    frame: &Frame,
+    text: &[(&str, String)],
+) -> io::Result<()> {
+    let mut encoder = png::Encoder::new(writer, frame.width as u32, frame.height as u32);
+    encoder.set_color(png::ColorType::RGBA);
+    encoder.set_depth(png::BitDepth::Eight);
+    encoder
+        .write_header()
+        .and_then(|mut writer| {
+            for (keyword, value) in text {
+                let chunk = [keyword.as_bytes(), &[0], value.as_bytes()].concat();
+                writer.write_chunk(*b"tEXt", &chunk)?;
+            }
+            writer.write_image_data(&frame.rgba)
+        })
+        .map_err(|error| io::Error::other(error.to_string()))
+}
+
+/// reads a frame from an 8-bit RGB or RGBA PNG image
+#[cfg(feature = "png")]
+pub fn read_png(filename: &str) -> io::Result<Frame> {
+    let invalid = |reason: String| io::Error::new(io::ErrorKind::InvalidData, reason);
+
+    let decoder = png::Decoder::new(BufReader::new(File::open(filename)?));
+    let (info, mut reader) = decoder
+        .read_info()
+        .map_err(|error| invalid(format!("{filename}: {error}")))?;
+    let channels = match (info.color_type, info.bit_depth) {
+        (png::ColorType::RGB, png::BitDepth::Eight) => 3,
+        (png::ColorType::RGBA, png::BitDepth::Eight) => 4,
+        (color_type, bit_depth) => {
+            return Err(invalid(format!(
+                "{filename} is {color_type:?} {bit_depth:?}, expected 8-bit RGB or RGBA"
+            )))
+        }
+    };
+
+    let mut data = vec![0; info.buffer_size()];
+    reader
+        .next_frame(&mut data)
+        .map_err(|error| invalid(format!("{filename}: {error}")))?;
+    Ok(Frame::from_rgb(
+        info.width as usize,
+        info.height as usize,
+        data.chunks_exact(channels)
+            .map(|pixel| (pixel[0], pixel[1], pixel[2])),
+    ))
+}
+
+/// writes a frame to a PNG file
+#[cfg(feature = "png")]
+pub fn save_png(filename: &str, frame: &Frame) -> io::Result<()> {
+    write_png(BufWriter::new(File::create(filename)?), frame)
+}
+
+/// writes a frame to a PNG file with tEXt chunks, see write_png_with_text
+#[cfg(feature = "png")]
+pub fn save_png_with_text(
+    filename: &str,
+    frame: &Frame,
+    text: &[(&str, String)],
+) -> io::Result<()> {
+    write_png_with_text(BufWriter::new(File::create(filename)?), frame, text)
+}
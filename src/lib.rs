@@ -2,4 +2,7 @@
 // 6502 hexdump Decoder
 // Author: Will Howes
 
+pub mod frame_diff;
 pub mod nes;
+pub mod selftest;
+pub mod testing;
@@ -1,41 +1,274 @@
-use core::time;
 // #[allow(non_camel_case_types)]
 use std::fs::File;
 use std::io::prelude::*;
-use std::io::{self, BufReader, SeekFrom};
+use std::io::{self, BufReader, Cursor};
+use std::path::Path;
+use std::sync::{Arc, Mutex};
 use std::thread;
-use std::time::Instant;
+use std::time::{Duration, Instant};
 
+use speedy2d::dimen::UVec2;
 use speedy2d::image::{ImageDataType, ImageSmoothingMode};
 use speedy2d::shape::Rectangle;
-use speedy2d::window::{WindowHandler, WindowHelper};
+use speedy2d::window::{
+    KeyScancode, VirtualKeyCode, WindowHandler, WindowHelper, WindowStartupInfo,
+};
 use speedy2d::Graphics2D;
 
+pub mod apu;
+pub mod audio;
 pub mod bus;
+pub mod bus_trace;
+pub mod cartridge;
+pub mod cheats;
+pub mod checksum;
+pub mod chr_override;
+pub mod controller;
 pub mod cpu;
 pub mod cpu_structs;
+pub mod device;
+pub mod diagnostics;
+pub mod dma;
+pub mod frame;
+pub mod freeze;
+pub mod fuzz;
+#[cfg(feature = "cpal")]
+pub mod host_audio;
+pub mod irq;
+pub mod mapper;
+pub mod media_clock;
+pub mod memory_editor;
+pub mod memory_map;
+pub mod micro_op;
+pub mod ntsc;
+pub mod patch;
 pub mod ppu;
+pub mod ppu_bus;
 pub mod ppu_structs;
+pub mod ram_init;
+pub mod region;
+pub mod save_state;
+pub mod timer;
+pub mod watchpoint;
+pub mod wav;
 
+#[cfg(feature = "png")]
+use crate::frame_diff::save_png_with_text;
+use crate::nes::audio::{AudioCallback, SampleRing};
 use crate::nes::bus::Bus;
+use crate::nes::bus_trace::BusTracer;
+use crate::nes::cartridge::Cartridge;
+use crate::nes::checksum::{state_checksum, ChecksumChannel};
+use crate::nes::chr_override::ChrOverride;
+use crate::nes::controller::Buttons;
 use crate::nes::cpu::CPU;
 use crate::nes::cpu_structs::{decode_instruction, Instruction};
-use crate::nes::ppu::FRAME_BUFFER_SIZE;
-use crate::nes::ppu_structs::PPUCTRL;
+use crate::nes::frame::{Frame, FrameCallback};
+use crate::nes::freeze::FreezeTiming;
+#[cfg(feature = "cpal")]
+use crate::nes::host_audio::HostAudio;
+use crate::nes::irq::IrqSource;
+use crate::nes::media_clock::MediaClock;
+use crate::nes::micro_op::{InFlight, Replay};
+use crate::nes::patch::{apply_patch, find_patch};
+use crate::nes::ppu::{A12Callback, LoggedPpuWrite, ScanlineCallback, PPU};
+use crate::nes::ppu_bus::Mirroring;
+use crate::nes::ppu_structs::{read_palette_file, RenderMode, SYSTEM_COLOR_PALETTE};
+use crate::nes::region::{detect_region, Region, RegionSuggestion};
+use crate::nes::watchpoint::WatchpointHit;
 
-const PPU_SCANLINES_PER_FRAME: u64 = 262;
-const PPU_CYCLES_PER_SCANLINES: u64 = 341;
-const PPU_CYCLES_PER_FRAME: u64 = PPU_SCANLINES_PER_FRAME * PPU_CYCLES_PER_SCANLINES;
+const LOUD: bool = false;
 
-const CPU_CYCLES_PER_FRAME: u64 = PPU_CYCLES_PER_FRAME / 3;
-const LENGTH_OF_FRAME: f64 = 1.0 / 60.0;
+/// how often SyncMode::Audio checks on the audio output
+const AUDIO_SYNC_POLL_INTERVAL: Duration = Duration::from_millis(1);
 
-const LOUD: bool = false;
+/// keyword of the screenshot text chunk holding the emulated time
+pub const SCREENSHOT_TIME_KEYWORD: &str = "Emulated time";
+
+/// public domain NROM-128 demo, see sample_programs/disco_demo.6502.asm
+pub const DEMO_ROM: &[u8] = include_bytes!("../sample_programs/disco_demo.nes");
+const DEMO_TITLE: &str = "Disco5 Demo";
+
+const DEFAULT_WINDOW_TITLE: &str = "Disco5";
+const DEFAULT_WINDOW_SIZE: (u32, u32) = (1024, 960);
+const ICON_SIZE: u32 = 32;
 
 #[derive(Debug, Default)]
 pub struct NES {
     pub cpu: CPU,
     pub address_space: Bus,
+    pub window: WindowConfig,
+    pub speed: SpeedSetting,
+    /// what paces frames at full speed
+    pub sync: SyncMode,
+    /// samples queued for a host audio backend, which drains it from its
+    /// own thread. Each frame's samples are pushed as the frame completes
+    pub audio_output: Option<Arc<Mutex<SampleRing>>>,
+    /// the default output device, playing audio_output, see open_host_audio
+    #[cfg(feature = "cpal")]
+    pub host_audio: Option<HostAudio>,
+    /// called once with each completed frame's audio
+    pub audio_callback: Option<AudioCallback>,
+    /// disables frame sleeps and all console output so batch runs complete
+    /// as fast as the host allows
+    pub headless: bool,
+    /// counts frames in emulated time for recordings
+    pub media_clock: MediaClock,
+    pub region: Region,
+    /// region detected at load time that the user has not yet accepted
+    pub region_suggestion: Option<RegionSuggestion>,
+    /// tiles drawn over the cartridge's pattern tables
+    pub chr_override: Option<ChrOverride>,
+    /// periodic state checksums for detecting desyncs
+    pub checksum_channel: Option<ChecksumChannel>,
+    /// the last completed frame. The PPU draws the next frame into its own
+    /// pixel buffer, so this stays whole until the next frame replaces it
+    pub front_buffer: Option<Frame>,
+    /// called once with each completed frame
+    pub frame_callback: Option<FrameCallback>,
+    /// log of CPU bus accesses, see trace_bus
+    pub bus_tracer: Option<BusTracer>,
+    /// every CPU write to a PPU register while logging, see log_ppu_writes
+    pub ppu_write_log: Option<Vec<LoggedPpuWrite>>,
+    /// where battery-backed PRG RAM is saved, set when a ROM with a battery
+    /// is loaded
+    pub battery_file: Option<String>,
+}
+
+/// What the frontend paces frames by at full speed
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq)]
+pub enum SyncMode {
+    /// sleep out the rest of each frame's duration
+    #[default]
+    Video,
+    /// wait for the audio backend to drain the queued samples down to
+    /// AUDIO_SYNC_FRAMES of audio, so the video never drifts from a sound
+    /// card's clock and the queue never runs dry. Falls back to Video
+    /// without an audio_output or at other speeds, see pace_frame
+    Audio,
+}
+
+/// Why run_until returned
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum StopReason {
+    /// the cycle budget ran out
+    CyclesElapsed,
+    /// an instruction touched a watched address, it has completed
+    Watchpoint(WatchpointHit),
+}
+
+/// frames of audio SyncMode::Audio keeps queued
+pub const AUDIO_SYNC_FRAMES: f64 = 3.0;
+
+/// How pace_frame held a frame back to full speed
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum FramePacing {
+    /// headless or at a speed that is not paced
+    Unpaced,
+    /// the audio backend drained the queue
+    AudioDrained,
+    /// the audio backend did not drain the queue within two frames, which
+    /// is already longer than the frame, so nothing more is slept
+    AudioStalled,
+    /// slept for the rest of the frame's duration
+    Slept(Duration),
+}
+
+/// How fast the frontend runs emulation relative to a real console
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum SpeedSetting {
+    /// percentage of real console speed, 100 is full speed
+    Percent(u32),
+    /// run frames as fast as the host allows
+    Unlimited,
+}
+
+impl Default for SpeedSetting {
+    fn default() -> SpeedSetting {
+        SpeedSetting::Percent(100)
+    }
+}
+
+const SPEED_STEP_PERCENT: u32 = 25;
+const MINIMUM_SPEED_PERCENT: u32 = 25;
+
+impl SpeedSetting {
+    /// returns how long a frame should take on the host at a console frame
+    /// rate, or None if frames should not be throttled
+    pub fn frame_duration(&self, frame_rate: f64) -> Option<Duration> {
+        match *self {
+            SpeedSetting::Percent(percent) if percent > 0 => Some(Duration::from_secs_f64(
+                100.0 / (frame_rate * f64::from(percent)),
+            )),
+            _ => None,
+        }
+    }
+
+    pub fn faster(&self) -> SpeedSetting {
+        match *self {
+            SpeedSetting::Percent(percent) => SpeedSetting::Percent(percent + SPEED_STEP_PERCENT),
+            SpeedSetting::Unlimited => SpeedSetting::Unlimited,
+        }
+    }
+
+    pub fn slower(&self) -> SpeedSetting {
+        match *self {
+            SpeedSetting::Percent(percent) => SpeedSetting::Percent(
+                percent
+                    .saturating_sub(SPEED_STEP_PERCENT)
+                    .max(MINIMUM_SPEED_PERCENT),
+            ),
+            SpeedSetting::Unlimited => SpeedSetting::Percent(100),
+        }
+    }
+}
+
+/// Title, size, and icon applied to the frontend window. Loading a ROM names
+/// the window after the game; embedders may override any field afterwards.
+#[derive(Clone, Debug)]
+pub struct WindowConfig {
+    pub title: String,
+    pub size: (u32, u32),
+    /// RGBA pixels and their dimensions
+    pub icon: Option<(Vec<u8>, (u32, u32))>,
+}
+
+impl Default for WindowConfig {
+    fn default() -> WindowConfig {
+        WindowConfig {
+            title: String::from(DEFAULT_WINDOW_TITLE),
+            size: DEFAULT_WINDOW_SIZE,
+            icon: Some((default_icon(), (ICON_SIZE, ICON_SIZE))),
+        }
+    }
+}
+
+/// draws a ring in the disco palette for use as the default window icon
+fn default_icon() -> Vec<u8> {
+    let mut pixels = Vec::with_capacity((ICON_SIZE * ICON_SIZE * 4) as usize);
+    let center = ICON_SIZE as i32 / 2;
+    for y in 0..ICON_SIZE as i32 {
+        for x in 0..ICON_SIZE as i32 {
+            let distance_squared = (x - center).pow(2) + (y - center).pow(2);
+            // sweep diagonally through the bright row of the system palette
+            let (r, g, b) = SYSTEM_COLOR_PALETTE[0x21 + ((x + y) / 8) as usize % 12];
+            if distance_squared < center.pow(2) && distance_squared >= (center / 3).pow(2) {
+                pixels.extend_from_slice(&[r, g, b, 0xff]);
+            } else {
+                pixels.extend_from_slice(&[0, 0, 0, 0]);
+            }
+        }
+    }
+    pixels
+}
+
+impl WindowConfig {
+    /// names the window after a ROM file, e.g. "Disco5 - Donkey Kong"
+    pub fn set_title_from_rom(&mut self, filename: &str) {
+        if let Some(name) = Path::new(filename).file_stem() {
+            self.title = format!("{DEFAULT_WINDOW_TITLE} - {}", name.to_string_lossy());
+        }
+    }
 }
 
 fn byte_dump(memory: &[u8]) {
@@ -80,9 +313,11 @@ impl NES {
             };
 
             // Write instructions to memory
-            println!("WRITING TO LINE {}", cpu.pc);
+            if !self.headless {
+                println!("WRITING TO LINE {}", cpu.pc);
+            }
             for hex in &hexdump[1..] {
-                memory[usize::from(loc)] = u8::from_str_radix(hex, 16).unwrap();
+                memory.write(loc, u8::from_str_radix(hex, 16).unwrap());
                 loc += 1;
             }
         }
@@ -102,7 +337,9 @@ impl NES {
         let f = File::open(filename)?;
         let mut f = BufReader::new(f);
         let bytes_read = f.read(memory)?;
-        println!("{bytes_read} bytes read");
+        if !self.headless {
+            println!("{bytes_read} bytes read");
+        }
 
         self.cpu.pc = pc;
 
@@ -166,24 +403,111 @@ impl NES {
         println!("--------------------");
     }
 
+    /// loads an NROM-128 or NROM-256 ROM, soft-patching it with an IPS or
+    /// BPS patch sharing its filename if there is one
     pub fn load_nrom_128(&mut self, filename: &str, memory_entry_point: usize) -> io::Result<()> {
+        let patch = find_patch(filename);
+        self.load_nrom_128_with_patch(filename, patch.as_deref(), memory_entry_point)
+    }
+
+    /// loads an NROM-128 or NROM-256 ROM, applying an IPS or BPS patch in
+    /// memory first. The ROM file itself is never modified
+    pub fn load_nrom_128_with_patch(
+        &mut self,
+        filename: &str,
+        patch: Option<&str>,
+        memory_entry_point: usize,
+    ) -> io::Result<()> {
         // Load file contents into memory array
-        let f = File::open(filename)?;
-        let mut f = BufReader::new(f);
-        f.seek(SeekFrom::Start(16))?;
+        let mut rom = Vec::new();
+        File::open(filename)?.read_to_end(&mut rom)?;
+        if let Some(patch) = patch {
+            let mut patch_bytes = Vec::new();
+            File::open(patch)?.read_to_end(&mut patch_bytes)?;
+            rom = apply_patch(&rom, &patch_bytes)
+                .map_err(|error| io::Error::new(error.kind(), format!("{patch}: {error}")))?;
+            if !self.headless {
+                println!("Applied patch {patch}");
+            }
+        }
+        self.load_nrom_128_from_reader(&mut Cursor::new(rom), filename, memory_entry_point)?;
+        self.window.set_title_from_rom(filename);
+        self.battery_file = None;
+        if self.address_space.cartridge.battery {
+            let battery_file = Path::new(filename).with_extension("sav");
+            let battery_file = battery_file.to_string_lossy().into_owned();
+            if Path::new(&battery_file).exists() {
+                self.address_space.cartridge.load_prg_ram(&battery_file)?;
+            }
+            self.battery_file = Some(battery_file);
+        }
+        Ok(())
+    }
 
-        let cpu_memory_0 =
-            &mut self.address_space.bytes[memory_entry_point..memory_entry_point + 0x4000];
-        f.read_exact(cpu_memory_0)?;
+    /// writes battery-backed PRG RAM to battery_file, if the ROM has one
+    pub fn save_battery(&self) -> io::Result<()> {
+        match self.battery_file {
+            Some(ref battery_file) => self.address_space.cartridge.save_prg_ram(battery_file),
+            None => Ok(()),
+        }
+    }
 
-        f.seek(SeekFrom::Start(16))?;
-        let cpu_memory_1 =
-            &mut self.address_space.bytes[memory_entry_point + 0x4000..memory_entry_point + 0x8000];
-        f.read_exact(cpu_memory_1)?;
+    /// boots the embedded public domain demo ROM
+    pub fn load_demo(&mut self) -> io::Result<()> {
+        self.load_nrom_128_from_reader(&mut Cursor::new(DEMO_ROM), DEMO_TITLE, 0x8000)?;
+        self.window.title = String::from(DEMO_TITLE);
+        Ok(())
+    }
+
+    /// loads an NROM image, name is used to guess the ROM's region. A 16 KB
+    /// NROM-128 bank is mirrored into both halves of $8000-$FFFF, and the two
+    /// banks of a 32 KB NROM-256 image fill it. Images with more PRG ROM or
+    /// another mapper are rejected rather than loaded wrong
+    fn load_nrom_128_from_reader<R: Read>(
+        &mut self,
+        f: &mut R,
+        name: &str,
+        memory_entry_point: usize,
+    ) -> io::Result<()> {
+        let mut header = [0; 16];
+        f.read_exact(&mut header)?;
+        let (prg_banks, mapper) = (header[4], header[6] >> 4);
+        if !matches!(prg_banks, 1 | 2) || mapper != 0 {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidData,
+                format!(
+                    "{name} has {} KB of PRG ROM on mapper {mapper}, only 16 or 32 KB NROM images can be loaded",
+                    u32::from(prg_banks) * 16
+                ),
+            ));
+        }
+        self.address_space.cartridge = Cartridge::from_ines_header(&header);
+        self.address_space.power_on_ram();
+        self.address_space.region_detector = Default::default();
+        self.region_suggestion = None;
+        if let Some(suggestion) = detect_region(&header, name) {
+            self.suggest_region(suggestion);
+        }
+
+        let prg_rom =
+            &mut self.address_space.bytes[memory_entry_point..memory_entry_point + 0x8000];
+        if prg_banks == 2 {
+            f.read_exact(prg_rom)?;
+        } else {
+            f.read_exact(&mut prg_rom[..0x4000])?;
+            prg_rom.copy_within(..0x4000, 0x4000);
+        }
 
         // This should be the only time the PPU's memory is directly addressed
-        let ppu_memory = &mut self.address_space.ppu.address_space[..0x2000];
-        f.read_exact(ppu_memory)?;
+        let ppu_memory = &mut self.address_space.ppu.address_space;
+        // no CHR ROM means the cartridge has 8 KB of CHR RAM instead
+        if header[5] == 0 {
+            ppu_memory.chr.bytes.fill(0);
+        } else {
+            f.read_exact(&mut ppu_memory.chr.bytes)?;
+        }
+        ppu_memory.chr.writable = header[5] == 0;
+        ppu_memory.mirroring = Mirroring::from_ines_header(&header);
 
         let lo = self.address_space.bytes[0xfffc];
         let hi = self.address_space.bytes[0xfffd];
@@ -194,113 +518,673 @@ impl NES {
         Ok(())
     }
 
+    /// switches the PPU timing and frame pacing to a television system
+    pub fn set_region(&mut self, region: Region) {
+        self.region = region;
+        self.media_clock.frame_rate = region.frame_rate();
+        self.address_space.ppu.region = region;
+        self.address_space.apu.region = region;
+        self.address_space.ppu_dot_remainder = 0;
+    }
+
+    /// presses the reset button. The CPU jumps through the reset vector with
+    /// interrupts disabled, pushing nothing but still moving the stack
+    /// pointer down 3, and RAM survives
+    pub fn reset(&mut self) {
+        let lo = self.address_space.bytes[0xfffc];
+        let hi = self.address_space.bytes[0xfffd];
+        self.cpu.pc = (u16::from(hi) << 8) + u16::from(lo);
+        self.cpu.sp = self.cpu.sp.wrapping_sub(3);
+        self.cpu.p.i = true;
+        self.cpu.tick(7);
+        self.address_space.ppu.reset();
+    }
+
+    /// offers suggestion for the user to accept, unless the NES already
+    /// runs as that region
+    fn suggest_region(&mut self, suggestion: RegionSuggestion) {
+        if suggestion.region == self.region {
+            return;
+        }
+        if !self.headless {
+            println!(
+                "{:?} suggested because the {}, press P to accept",
+                suggestion.region, suggestion.reason
+            );
+        }
+        self.region_suggestion = Some(suggestion);
+    }
+
+    /// switches to the suggested region, if any
+    pub fn accept_region_suggestion(&mut self) {
+        if let Some(suggestion) = self.region_suggestion.take() {
+            self.set_region(suggestion.region);
+        }
+    }
+
+    pub fn set_speed(&mut self, speed: SpeedSetting) {
+        self.speed = speed;
+    }
+
+    /// fetches, decodes, and executes a single instruction, returns the
+    /// number of CPU cycles taken including any DMA stalls
+    pub fn step(&mut self, loud: bool) -> u64 {
+        if self.cpu.in_flight.is_some() {
+            // finish the instruction cycle stepping left partway through
+            let start = self.cpu.clock;
+            while self.cpu.in_flight.is_some() {
+                self.step_cycle();
+            }
+            return self.cpu.clock - start;
+        }
+        let loud = loud && !self.headless;
+        self.address_space.sync_timer(self.cpu.clock);
+        let start = self.cpu.clock;
+        self.address_space.cycle = start;
+        if loud {
+            println!("--------------------");
+            let ppu = &self.address_space.ppu;
+            println!(
+                "Clock = {} | PPU: {:>3},{:>3} frame {}",
+                self.cpu.clock,
+                ppu.scanline(),
+                ppu.dot(),
+                ppu.frame_count()
+            );
+            self.cpu.print_state();
+        }
+        let (instruction, minimum_ticks) = if self.address_space.ppu.nmi_pending {
+            self.address_space.ppu.nmi_pending = false;
+            (Instruction::NMI, 7)
+        } else if self.irq_line() && !self.cpu.p.i {
+            (Instruction::IRQ, 7)
+        } else {
+            let opcode = self.cpu.fetch_instruction(&mut self.address_space);
+            decode_instruction(opcode)
+        };
+        if loud {
+            println!("NEXT: {:?}, minimum {:?} ticks", instruction, minimum_ticks);
+            println!("--------------------");
+        }
+
+        let ticks =
+            self.cpu
+                .execute_instruction(instruction, minimum_ticks, &mut self.address_space);
+        self.finish_instruction(loud);
+        let ticks = u64::from(ticks);
+        // every access ticked the bus, which leaves the cycles the
+        // instruction spent off the bus, and then the CPU halts for any DMA
+        let idle = ticks.saturating_sub(self.address_space.cycle - start);
+        for _ in 0..idle {
+            self.address_space.tick();
+        }
+        self.address_space.run_dma();
+        self.cpu.clock = self.address_space.cycle;
+        let ticks = self.cpu.clock - start;
+        self.drain_bus_trace();
+        ticks
+    }
+
+    /// logs the PPU write an instruction made, if any
+    fn finish_instruction(&mut self, loud: bool) {
+        if let Some(write) = self.address_space.last_ppu_register_write.take() {
+            let ppu = &self.address_space.ppu;
+            let logged = LoggedPpuWrite {
+                frame: ppu.frame_count(),
+                scanline: ppu.scanline(),
+                dot: ppu.dot(),
+                write,
+            };
+            if loud {
+                println!("PPU WRITE @ {logged}");
+            }
+            if let Some(ref mut log) = self.ppu_write_log {
+                log.push(logged);
+            }
+        }
+    }
+
+    /// hands the accesses recorded since the last call to the bus tracer
+    fn drain_bus_trace(&mut self) {
+        if let Some(ref mut tracer) = self.bus_tracer {
+            for access in self.address_space.accesses.entries() {
+                tracer.record(access);
+            }
+            self.address_space.accesses.clear();
+        }
+    }
+
+    /// starts recording CPU bus accesses into tracer, replacing any previous
+    /// one
+    pub fn trace_bus(&mut self, tracer: BusTracer) {
+        self.address_space.accesses.enabled = true;
+        self.bus_tracer = Some(tracer);
+    }
+
+    /// starts or stops logging CPU writes to $2000-$2007 and $4014, stopping
+    /// discards the log
+    pub fn log_ppu_writes(&mut self, enabled: bool) {
+        self.ppu_write_log = enabled.then(Vec::new);
+    }
+
+    /// returns the PPU writes logged so far and clears the log
+    pub fn take_ppu_write_log(&mut self) -> Vec<LoggedPpuWrite> {
+        self.ppu_write_log
+            .as_mut()
+            .map(std::mem::take)
+            .unwrap_or_default()
+    }
+
+    /// stops tracing the bus, returning the tracer with its log
+    pub fn stop_bus_trace(&mut self) -> Option<BusTracer> {
+        self.address_space.accesses.enabled = false;
+        self.address_space.accesses.clear();
+        self.bus_tracer.take()
+    }
+
+    /// draws the tiles in a raw CHR file over the pattern tables, reloading
+    /// them every frame the file has changed
+    pub fn set_chr_override(&mut self, filename: &str) -> io::Result<()> {
+        let mut chr_override = ChrOverride::new(filename);
+        chr_override.reload_if_changed(&mut self.address_space.ppu)?;
+        self.chr_override = Some(chr_override);
+        Ok(())
+    }
+
+    /// replaces the system colors with those in a .pal file
+    pub fn load_palette(&mut self, filename: &str) -> io::Result<()> {
+        self.address_space.ppu.palettes = read_palette_file(filename)?;
+        Ok(())
+    }
+
+    /// describes each stubbed feature the program has touched so far, one
+    /// `unimplemented <key>: <consequence>` line apiece
+    pub fn unimplemented_report(&self) -> Vec<String> {
+        diagnostics::report(self.address_space.unimplemented)
+    }
+
+    /// saves the last completed frame, or what the PPU has drawn so far if
+    /// no frame has completed yet, as a PNG image. Its SCREENSHOT_TIME_KEYWORD
+    /// text chunk holds the emulated time the frame began, in seconds
+    #[cfg(feature = "png")]
+    pub fn screenshot(&self, filename: &str) -> io::Result<()> {
+        let frames = self.media_clock.frames;
+        let (frame, index) = match self.front_buffer {
+            Some(ref frame) => (frame.clone(), frames.saturating_sub(1)),
+            None => (self.address_space.ppu.framebuffer(), frames),
+        };
+        let timestamp = self.media_clock.timestamp(index);
+        save_png_with_text(
+            filename,
+            &frame,
+            &[(
+                SCREENSHOT_TIME_KEYWORD,
+                format!("{:.6}", timestamp.as_secs_f64()),
+            )],
+        )
+    }
+
+    /// switches between palette colors and the priority debug view
+    pub fn toggle_priority_debug(&mut self) {
+        let ppu = &mut self.address_space.ppu;
+        ppu.render_mode = match ppu.render_mode {
+            RenderMode::Normal | RenderMode::Ntsc => RenderMode::PriorityDebug(Default::default()),
+            RenderMode::PriorityDebug(_) => RenderMode::Normal,
+        };
+    }
+
+    /// switches between palette colors and the NTSC composite video filter
+    pub fn toggle_ntsc_filter(&mut self) {
+        let ppu = &mut self.address_space.ppu;
+        ppu.render_mode = match ppu.render_mode {
+            RenderMode::Ntsc => RenderMode::Normal,
+            _ => RenderMode::Ntsc,
+        };
+    }
+
+    /// pulls the /IRQ line low on behalf of source
+    pub fn assert_irq(&mut self, source: IrqSource) {
+        self.address_space.irq_sources.insert(source);
+    }
+
+    /// lets go of the /IRQ line on behalf of source, the line stays asserted
+    /// while any other source holds it
+    pub fn release_irq(&mut self, source: IrqSource) {
+        self.address_space.irq_sources.remove(source);
+    }
+
+    /// returns whether or not any source is asserting the /IRQ line
+    pub fn irq_line(&self) -> bool {
+        !self.address_space.irq_sources.is_empty()
+    }
+
+    /// advances emulation by a single CPU cycle, making the one bus access
+    /// the in-flight instruction is due to make on it, or idling through
+    /// its internal cycles and DMA stalls once it has made them all
+    pub fn step_cycle(&mut self) {
+        let mut in_flight = match self.cpu.in_flight {
+            Some(in_flight) => in_flight,
+            None => self.begin_instruction(),
+        };
+        let bus = &mut self.address_space;
+        match in_flight.remaining {
+            None => {
+                // rerun the instruction against what it latched so far,
+                // letting only this cycle's access reach the bus
+                in_flight.start.restore(&mut self.cpu);
+                bus.replay = Some(Replay::new(&in_flight));
+                let (instruction, minimum_ticks) = match in_flight.interrupt {
+                    Some(interrupt) => (interrupt, 7),
+                    None => decode_instruction(self.cpu.fetch_instruction(bus)),
+                };
+                let ticks = self
+                    .cpu
+                    .execute_instruction(instruction, minimum_ticks, bus);
+                let replay = bus.replay.take().unwrap();
+                in_flight.latched = replay.latched;
+                in_flight.micro_op += 1;
+                if replay.ran_ahead() {
+                    in_flight.start.restore(&mut self.cpu);
+                } else {
+                    // that was the last access, the registers are final
+                    let idle = u64::from(ticks).saturating_sub(in_flight.micro_op as u64);
+                    in_flight.remaining = Some(idle);
+                    self.finish_instruction(false);
+                }
+            }
+            Some(0) => bus.dma_cycle(),
+            Some(ref mut remaining) => {
+                bus.tick();
+                *remaining -= 1;
+            }
+        }
+        self.cpu.clock = self.address_space.cycle;
+        self.cpu.in_flight = match in_flight.remaining {
+            Some(0) if !self.address_space.dma.busy() => {
+                self.drain_bus_trace();
+                None
+            }
+            _ => Some(in_flight),
+        };
+    }
+
+    /// picks the opcode or interrupt the next instruction will run
+    fn begin_instruction(&mut self) -> InFlight {
+        self.address_space.sync_timer(self.cpu.clock);
+        self.address_space.cycle = self.cpu.clock;
+        let interrupt = if self.address_space.ppu.nmi_pending {
+            self.address_space.ppu.nmi_pending = false;
+            Some(Instruction::NMI)
+        } else if self.irq_line() && !self.cpu.p.i {
+            Some(Instruction::IRQ)
+        } else {
+            None
+        };
+        InFlight::new(&self.cpu, interrupt)
+    }
+
+    /// captures the CPU between any two cycles, including the micro-op the
+    /// in-flight instruction is on and the bytes it has latched
+    pub fn snapshot_cpu(&self) -> CPU {
+        self.cpu
+    }
+
+    /// restores a CPU captured by snapshot_cpu, winding the master clock
+    /// back with it. A snapshot taken mid-instruction resumes with the
+    /// access after the last one it made, so pair it with the bus state
+    /// from the same cycle
+    pub fn restore_cpu(&mut self, snapshot: CPU) {
+        self.cpu = snapshot;
+        self.address_space.cycle = snapshot.clock;
+    }
+
+    /// returns how long the emulated console has been running, derived from
+    /// the cycles it has executed. Unaffected by pausing, fast-forward, or
+    /// how fast the host is, so overlays, recordings, and scripts should use
+    /// it rather than host time
+    pub fn emulated_time(&self) -> Duration {
+        let nanoseconds =
+            u128::from(self.cpu.clock) * 1_000_000_000 / u128::from(self.region.cpu_clock_rate());
+        Duration::from_nanos(nanoseconds as u64)
+    }
+
+    /// returns the (scanline, dot) the PPU is drawing
+    pub fn beam_position(&self) -> (usize, usize) {
+        (
+            self.address_space.ppu.scanline(),
+            self.address_space.ppu.dot(),
+        )
+    }
+
     pub fn run_cpu_program(&mut self, loud: bool, exit_condition: fn(u16) -> bool) {
         while exit_condition(self.cpu.pc) == false {
-            if loud {
-                println!("--------------------");
-                println!("Clock = {}", self.cpu.clock);
-                self.cpu.print_state();
+            self.step(loud);
+        }
+        if !self.headless {
+            println!("SUCCESS");
+            println!("CLOCK = {}", self.cpu.clock);
+            println!("PC    = 0x{:0>4x}", self.cpu.pc);
+        }
+    }
+
+    /// runs instructions until one hits a watchpoint or at least cycles CPU
+    /// cycles have passed. Hits from before the call are discarded
+    pub fn run_until(&mut self, cycles: u64) -> StopReason {
+        self.address_space.watchpoints.hit = None;
+        let mut elapsed = 0;
+        while elapsed < cycles {
+            elapsed += self.step(false);
+            if let Some(hit) = self.address_space.watchpoints.hit.take() {
+                return StopReason::Watchpoint(hit);
             }
-            let instruction = self.cpu.fetch_instruction(&self.address_space);
-            let (instruction, minimum_ticks) = decode_instruction(instruction);
-            if loud {
-                println!("NEXT: {:?}, minimum {:?} ticks", instruction, minimum_ticks);
-                println!("--------------------");
+        }
+        StopReason::CyclesElapsed
+    }
+
+    /// hands every completed frame to callback, replacing any previous one
+    pub fn set_frame_callback<F: FnMut(&Frame) + 'static>(&mut self, callback: F) {
+        self.frame_callback = Some(FrameCallback(Box::new(callback)));
+    }
+
+    /// hands the audio produced during each completed frame to callback, at
+    /// the APU's sample rate, replacing any previous one
+    pub fn set_audio_callback<F: FnMut(&[f32]) + 'static>(&mut self, callback: F) {
+        self.audio_callback = Some(AudioCallback(Box::new(callback)));
+    }
+
+    /// calls callback on every rise of PPU A12 that gets past the MMC3's
+    /// filter, replacing any previous one
+    pub fn set_a12_callback<F: FnMut(&PPU) + 'static>(&mut self, callback: F) {
+        self.address_space.a12_callback = Some(A12Callback(Box::new(callback)));
+    }
+
+    /// calls callback with the scanline number when the PPU reaches dot on
+    /// every scanline, replacing any previous one
+    pub fn on_scanline<F: FnMut(u16, &PPU) + 'static>(&mut self, dot: usize, callback: F) {
+        self.address_space.scanline_callback = Some(ScanlineCallback {
+            dot,
+            callback: Box::new(callback),
+        });
+    }
+
+    /// sets the buttons held on the controller in port, 0 for $4016 and 1
+    /// for $4017
+    pub fn set_buttons(&mut self, port: usize, buttons: Buttons) {
+        self.address_space.controllers[port].buttons = buttons;
+    }
+
+    /// starts playing audio through the host's default output device,
+    /// resampling the APU to the device's rate
+    #[cfg(feature = "cpal")]
+    pub fn open_host_audio(&mut self) -> io::Result<()> {
+        let host_audio = HostAudio::open_default()?;
+        self.address_space
+            .apu
+            .set_sample_rate(host_audio.sample_rate);
+        self.audio_output = Some(host_audio.ring.clone());
+        self.host_audio = Some(host_audio);
+        Ok(())
+    }
+
+    /// waits until the audio output has no more than AUDIO_SYNC_FRAMES of
+    /// audio queued, returns false if it did not drain within timeout or
+    /// there is no audio output
+    pub fn wait_for_audio(&self, timeout: Duration) -> bool {
+        let Some(ref output) = self.audio_output else {
+            return false;
+        };
+        let sample_rate = f64::from(self.address_space.apu.resampler.sample_rate);
+        let target = (AUDIO_SYNC_FRAMES * sample_rate / self.region.frame_rate()) as usize;
+        let started = Instant::now();
+        loop {
+            if output.lock().unwrap().len() <= target {
+                return true;
+            }
+            if started.elapsed() >= timeout {
+                return false;
             }
+            thread::sleep(AUDIO_SYNC_POLL_INTERVAL);
+        }
+    }
 
-            let _ =
-                self.cpu
-                    .execute_instruction(instruction, minimum_ticks, &mut self.address_space);
+    /// holds back a frame that began at started until it is due, by the
+    /// audio backend with SyncMode::Audio at full speed and an audio_output,
+    /// or else by sleeping out the rest of the frame
+    pub fn pace_frame(&self, started: Instant) -> FramePacing {
+        let frame_duration = if self.headless {
+            None
+        } else {
+            self.speed.frame_duration(self.region.frame_rate())
+        };
+        let Some(frame_duration) = frame_duration else {
+            return FramePacing::Unpaced;
+        };
+        if self.sync == SyncMode::Audio
+            && self.speed == SpeedSetting::default()
+            && self.audio_output.is_some()
+        {
+            return if self.wait_for_audio(frame_duration * 2) {
+                FramePacing::AudioDrained
+            } else {
+                FramePacing::AudioStalled
+            };
+        }
+        let time_to_sleep = frame_duration.saturating_sub(started.elapsed());
+        if LOUD {
+            println!("---- SLEEPING FOR {:?} ----", time_to_sleep);
         }
-        println!("SUCCESS");
-        println!("CLOCK = {}", self.cpu.clock);
-        println!("PC    = 0x{:0>4x}", self.cpu.pc);
+        thread::sleep(time_to_sleep);
+        FramePacing::Slept(time_to_sleep)
+    }
+
+    /// runs the given number of frames, returns the audio they produced at
+    /// the APU's sample rate. Samples not yet taken before the call are
+    /// dropped, and with an audio_output or audio_callback set they get the
+    /// samples instead
+    pub fn record_audio(&mut self, frames: usize) -> Vec<f32> {
+        let mut samples = Vec::new();
+        self.address_space.apu.take_samples(&mut samples);
+        samples.clear();
+        for _ in 0..frames {
+            self.run_frame(false);
+            self.address_space.apu.take_samples(&mut samples);
+        }
+        samples
+    }
+
+    /// runs the CPU until vblank begins, returns the frame rendered at the
+    /// start of vblank
+    pub fn run_frame(&mut self, loud: bool) -> Frame {
+        loop {
+            self.step(loud);
+
+            if self.address_space.ppu.frame_ready {
+                self.address_space.ppu.frame_ready = false;
+                if let Some(ref mut chr_override) = self.chr_override {
+                    if let Err(error) = chr_override.reload_if_changed(&mut self.address_space.ppu)
+                    {
+                        if !self.headless {
+                            println!("CHR override not reloaded: {error}");
+                        }
+                    }
+                }
+                let frame = self.address_space.ppu.framebuffer();
+                if self.address_space.freezer.timing == FreezeTiming::EveryFrame {
+                    self.address_space.apply_freezer();
+                }
+                self.address_space.apply_cheats();
+                if self.region == Region::Ntsc && self.region_suggestion.is_none() {
+                    if let Some(suggestion) = self.address_space.region_detector.end_frame() {
+                        self.suggest_region(suggestion);
+                    }
+                }
+                self.media_clock.frame_produced();
+                if self.checksum_channel.is_some() {
+                    let checksum = state_checksum(self);
+                    let buttons = self
+                        .address_space
+                        .controllers
+                        .map(|controller| controller.buttons);
+                    if let Some(ref mut channel) = self.checksum_channel {
+                        channel.record(self.media_clock.frames, checksum, buttons);
+                    }
+                }
+                if self.audio_output.is_some() || self.audio_callback.is_some() {
+                    let mut samples = Vec::new();
+                    self.address_space.apu.take_samples(&mut samples);
+                    if let Some(ref output) = self.audio_output {
+                        let mut output = output.lock().unwrap();
+                        for &sample in &samples {
+                            output.push(sample);
+                        }
+                    }
+                    if let Some(AudioCallback(ref mut callback)) = self.audio_callback {
+                        callback(&samples);
+                    }
+                }
+                if let Some(FrameCallback(ref mut callback)) = self.frame_callback {
+                    callback(&frame);
+                }
+                self.front_buffer = Some(frame.clone());
+                return frame;
+            }
+        }
+    }
+}
+
+/// returns the button of the first controller a key stands in for: the
+/// arrow keys for the D-pad, X for A, Z for B, Enter for Start, and right
+/// Shift for Select
+fn key_button(key: VirtualKeyCode) -> Option<Buttons> {
+    match key {
+        VirtualKeyCode::X => Some(Buttons::A),
+        VirtualKeyCode::Z => Some(Buttons::B),
+        VirtualKeyCode::RShift => Some(Buttons::SELECT),
+        VirtualKeyCode::Return => Some(Buttons::START),
+        VirtualKeyCode::Up => Some(Buttons::UP),
+        VirtualKeyCode::Down => Some(Buttons::DOWN),
+        VirtualKeyCode::Left => Some(Buttons::LEFT),
+        VirtualKeyCode::Right => Some(Buttons::RIGHT),
+        _ => None,
     }
 }
 
 impl WindowHandler for NES {
+    fn on_start(&mut self, helper: &mut WindowHelper, _info: WindowStartupInfo) {
+        match self.region_suggestion {
+            Some(ref suggestion) => helper.set_title(format!(
+                "{} [{:?}? press P]",
+                self.window.title, suggestion.region
+            )),
+            None => helper.set_title(&self.window.title),
+        }
+        if let Some((pixels, size)) = self.window.icon.clone() {
+            // not all platforms support window icons, so failing here is not fatal
+            let _ = helper.set_icon_from_rgba_pixels(pixels, size);
+        }
+    }
+
     fn on_draw(&mut self, helper: &mut WindowHelper, graphics: &mut Graphics2D) {
-        let mut cpu_clockspeed_manager = Instant::now();
-        loop {
-            if LOUD {
-                println!("--------------------");
-                println!("Clock = {}", self.cpu.clock);
-                self.cpu.print_state();
+        let cpu_clockspeed_manager = Instant::now();
+        let frame = self.run_frame(LOUD);
+        self.pace_frame(cpu_clockspeed_manager);
+
+        let image = graphics
+            .create_image_from_raw_pixels(
+                ImageDataType::RGBA,
+                ImageSmoothingMode::NearestNeighbor,
+                (frame.width as u32, frame.height as u32),
+                &frame.rgba,
+            )
+            .unwrap();
+
+        let (width, height) = self.window.size;
+        graphics.draw_rectangle_image(
+            Rectangle::from_tuples((0.0, 0.0), (width as f32, height as f32)),
+            &image,
+        );
+
+        helper.request_redraw();
+    }
+
+    /// keeps the frame stretched over the whole window
+    fn on_resize(&mut self, _helper: &mut WindowHelper, size_pixels: UVec2) {
+        self.window.size = (size_pixels.x, size_pixels.y);
+    }
+
+    /// Controller keys press their button, see key_button. Tab toggles
+    /// fast-forward, minus/equals slow down or speed up emulation, 0 returns
+    /// to full speed, P accepts a suggested region, F12 saves a screenshot,
+    /// and Escape saves any battery-backed RAM and quits after reporting any
+    /// unimplemented features used
+    fn on_key_down(
+        &mut self,
+        helper: &mut WindowHelper,
+        virtual_key_code: Option<VirtualKeyCode>,
+        _scancode: KeyScancode,
+    ) {
+        if let Some(button) = virtual_key_code.and_then(key_button) {
+            self.address_space.controllers[0].buttons.insert(button);
+            return;
+        }
+        let speed = match virtual_key_code {
+            Some(VirtualKeyCode::Tab) if self.speed == SpeedSetting::Unlimited => {
+                SpeedSetting::default()
+            }
+            Some(VirtualKeyCode::Tab) => SpeedSetting::Unlimited,
+            Some(VirtualKeyCode::Minus) => self.speed.slower(),
+            Some(VirtualKeyCode::Equals) => self.speed.faster(),
+            Some(VirtualKeyCode::Key0) => SpeedSetting::default(),
+            Some(VirtualKeyCode::P) => {
+                self.accept_region_suggestion();
+                helper.set_title(&self.window.title);
+                return;
             }
-            let instruction = self.cpu.fetch_instruction(&self.address_space);
-            let (instruction, minimum_ticks) = decode_instruction(instruction);
-            if LOUD {
-                println!("NEXT: {:?}, minimum {:?} ticks", instruction, minimum_ticks);
-                println!("--------------------");
+            Some(VirtualKeyCode::F2) => {
+                self.toggle_priority_debug();
+                return;
+            }
+            Some(VirtualKeyCode::F3) => {
+                self.toggle_ntsc_filter();
+                return;
+            }
+            #[cfg(feature = "png")]
+            Some(VirtualKeyCode::F12) => {
+                let filename = format!("screenshot-{}.png", self.address_space.ppu.frame_count());
+                match self.screenshot(&filename) {
+                    Ok(()) => println!("Saved {filename}"),
+                    Err(error) => println!("Screenshot not saved: {error}"),
+                }
+                return;
             }
-            let ticks =
-                self.cpu
-                    .execute_instruction(instruction, minimum_ticks, &mut self.address_space);
-            self.cpu.time_since_last_frame += u64::from(ticks);
-
-            if self.cpu.time_since_last_frame >= CPU_CYCLES_PER_FRAME {
-                // TODO: Adjust how frame sleeping works, probably going to be end up sleeping
-                // for too long the way it currently is
-
-                // let elapsed_time = cpu_clockspeed_manager.elapsed().as_secs_f64();
-                // if elapsed_time < LENGTH_OF_FRAME {
-                //     let time_to_sleep =
-                //         time::Duration::from_secs_f64(LENGTH_OF_FRAME - elapsed_time);
-                //         if LOUD {
-                //             println!("---- SLEEPING FOR {:?} ----", time_to_sleep);
-                //         }
-                //     thread::sleep(time_to_sleep);
-                // }
-                self.cpu.time_since_last_frame = 0;
-                cpu_clockspeed_manager = Instant::now();
-
-                if self.address_space.ppu.ppu_ctrl & PPUCTRL::GEN_NMI.bits()
-                    == PPUCTRL::GEN_NMI.bits()
-                {
-                    let buffer: [(u8, u8, u8); FRAME_BUFFER_SIZE] =
-                        self.address_space.ppu.render_frame();
-
-                    // uncomment to pause when entering NMI
-                    // println!("---- NMI ----");
-                    // let mut line = String::new();
-                    // let b1 = std::io::stdin().read_line(&mut line).unwrap();
-
-                    let mut new_buffer: [u8; FRAME_BUFFER_SIZE * 3] = [0; FRAME_BUFFER_SIZE * 3];
-
-                    let mut j = 0;
-                    for i in 0..FRAME_BUFFER_SIZE {
-                        let (x, y, z) = buffer[i];
-                        new_buffer[j] = x;
-                        j += 1;
-                        new_buffer[j] = y;
-                        j += 1;
-                        new_buffer[j] = z;
-                        j += 1;
+            Some(VirtualKeyCode::Escape) => {
+                if let Err(error) = self.save_battery() {
+                    eprintln!("Save data not written: {error}");
+                }
+                for line in self.unimplemented_report() {
+                    eprintln!("{line}");
+                }
+                if let Some(ref fuzz) = self.address_space.unmapped_fuzz {
+                    for line in fuzz.report() {
+                        eprintln!("{line}");
                     }
-
-                    let frame = graphics
-                        .create_image_from_raw_pixels(
-                            ImageDataType::RGB,
-                            ImageSmoothingMode::NearestNeighbor,
-                            (256, 240),
-                            &new_buffer,
-                        )
-                        .unwrap();
-
-                    graphics.draw_rectangle_image(
-                        Rectangle::from_tuples((0.0, 0.0), (1024.0, 960.0)),
-                        &frame,
-                    );
-
-                    let instruction = Instruction::NMI;
-                    let ticks =
-                        self.cpu
-                            .execute_instruction(instruction, 7, &mut self.address_space);
-                    self.cpu.time_since_last_frame += u64::from(ticks);
-                    break;
                 }
+                helper.terminate_loop();
+                return;
             }
+            _ => return,
+        };
+        self.set_speed(speed);
+    }
+
+    fn on_key_up(
+        &mut self,
+        _helper: &mut WindowHelper,
+        virtual_key_code: Option<VirtualKeyCode>,
+        _scancode: KeyScancode,
+    ) {
+        if let Some(button) = virtual_key_code.and_then(key_button) {
+            self.address_space.controllers[0].buttons.remove(button);
         }
-        helper.request_redraw();
     }
 }
@@ -0,0 +1,254 @@
+pub mod dmc;
+pub mod envelope;
+pub mod filter;
+pub mod frame_counter;
+pub mod length_counter;
+pub mod mixer;
+pub mod noise;
+pub mod pulse;
+pub mod resampler;
+pub mod sweep;
+pub mod triangle;
+
+use crate::nes::apu::dmc::Dmc;
+use crate::nes::apu::filter::OutputFilters;
+use crate::nes::apu::frame_counter::{FrameCounter, FrameSignal};
+use crate::nes::apu::length_counter::LengthCounter;
+use crate::nes::apu::mixer::{pulse_out, tnd_out, MixerConfig};
+use crate::nes::apu::noise::Noise;
+use crate::nes::apu::pulse::Pulse;
+use crate::nes::apu::resampler::Resampler;
+use crate::nes::apu::triangle::Triangle;
+use crate::nes::device::Device;
+use crate::nes::region::Region;
+
+/// The 2A03's audio processing unit, clocked once per CPU cycle. Its mixed
+/// output is resampled to the host's rate, see take_samples
+#[derive(Copy, Clone, Debug)]
+pub struct APU {
+    pub pulse: [Pulse; 2],
+    pub triangle: Triangle,
+    pub noise: Noise,
+    pub dmc: Dmc,
+    pub frame_counter: FrameCounter,
+    /// selects the rate tables
+    pub region: Region,
+    /// the pulse timers run at half the CPU clock, on odd cycles
+    pub odd_cycle: bool,
+    /// per-channel volumes, can be changed at any time
+    pub mixer: MixerConfig,
+    pub resampler: Resampler,
+    /// the console's analog filters, applied to resampled output
+    pub filters: OutputFilters,
+}
+
+impl Default for APU {
+    fn default() -> APU {
+        APU {
+            pulse: [Pulse::pulse_1(), Default::default()],
+            triangle: Default::default(),
+            noise: Default::default(),
+            dmc: Default::default(),
+            frame_counter: Default::default(),
+            region: Default::default(),
+            odd_cycle: Default::default(),
+            mixer: Default::default(),
+            resampler: Default::default(),
+            filters: Default::default(),
+        }
+    }
+}
+
+/// $4015 bits. Writes enable each channel, reads report whether each is
+/// still active along with both IRQ flags
+const STATUS_PULSE_1: u8 = 0b0000_0001;
+const STATUS_PULSE_2: u8 = 0b0000_0010;
+const STATUS_TRIANGLE: u8 = 0b0000_0100;
+const STATUS_NOISE: u8 = 0b0000_1000;
+const STATUS_DMC: u8 = 0b0001_0000;
+const STATUS_FRAME_INTERRUPT: u8 = 0b0100_0000;
+const STATUS_DMC_INTERRUPT: u8 = 0b1000_0000;
+
+/// $4015 bits of the channels with length counters, in length_counters order
+const STATUS_LENGTHS: [u8; 4] = [
+    STATUS_PULSE_1,
+    STATUS_PULSE_2,
+    STATUS_TRIANGLE,
+    STATUS_NOISE,
+];
+
+impl APU {
+    /// writes one of the APU's registers, $4000-$4013, $4015, or $4017
+    pub fn write_register(&mut self, address: u16, data: u8) {
+        match address {
+            0x4000 => self.pulse[0].control_write(data),
+            0x4001 => self.pulse[0].sweep_write(data),
+            0x4002 => self.pulse[0].timer_low_write(data),
+            0x4003 => self.pulse[0].length_write(data),
+            0x4004 => self.pulse[1].control_write(data),
+            0x4005 => self.pulse[1].sweep_write(data),
+            0x4006 => self.pulse[1].timer_low_write(data),
+            0x4007 => self.pulse[1].length_write(data),
+            0x4008 => self.triangle.control_write(data),
+            0x400a => self.triangle.timer_low_write(data),
+            0x400b => self.triangle.length_write(data),
+            0x400c => self.noise.control_write(data),
+            0x400e => self.noise.period_write(data),
+            0x400f => self.noise.length_write(data),
+            0x4010 => self.dmc.flags_write(data),
+            0x4011 => self.dmc.direct_load_write(data),
+            0x4012 => self.dmc.sample_address_write(data),
+            0x4013 => self.dmc.sample_length_write(data),
+            0x4015 => {
+                for (length_counter, bit) in self.length_counters().into_iter().zip(STATUS_LENGTHS)
+                {
+                    length_counter.set_enabled(data & bit != 0);
+                }
+                self.dmc.set_enabled(data & STATUS_DMC != 0);
+            }
+            0x4017 => self.frame_counter.write(data),
+            _ => {}
+        }
+    }
+
+    /// returns the length counters of the pulse, triangle, and noise channels
+    pub fn length_counters(&mut self) -> [&mut LengthCounter; 4] {
+        let [pulse_1, pulse_2] = &mut self.pulse;
+        [
+            &mut pulse_1.length_counter,
+            &mut pulse_2.length_counter,
+            &mut self.triangle.length_counter,
+            &mut self.noise.length_counter,
+        ]
+    }
+
+    /// $4015 read. Reports which channels are active and the IRQ flags,
+    /// acknowledging the frame IRQ. Bit 5 is not driven, so it is left clear
+    /// for the Bus to fill in from open bus
+    pub fn status_read(&mut self) -> u8 {
+        let status = self.status_peek();
+        self.frame_counter.interrupt = false;
+        status
+    }
+
+    /// returns what a $4015 read would without acknowledging the frame
+    /// interrupt
+    pub fn status_peek(&self) -> u8 {
+        let length_counters = [
+            &self.pulse[0].length_counter,
+            &self.pulse[1].length_counter,
+            &self.triangle.length_counter,
+            &self.noise.length_counter,
+        ];
+        let mut status = 0;
+        for (length_counter, bit) in length_counters.into_iter().zip(STATUS_LENGTHS) {
+            if length_counter.active() {
+                status |= bit;
+            }
+        }
+        if self.dmc.active() {
+            status |= STATUS_DMC;
+        }
+        if self.frame_counter.interrupt {
+            status |= STATUS_FRAME_INTERRUPT;
+        }
+        if self.dmc.interrupt {
+            status |= STATUS_DMC_INTERRUPT;
+        }
+        status
+    }
+
+    /// advances the frame counter and every channel by one CPU cycle
+    pub fn clock(&mut self) {
+        let signal = self.frame_counter.clock(self.region);
+        if signal.contains(FrameSignal::QUARTER) {
+            self.clock_quarter_frame();
+        }
+        if signal.contains(FrameSignal::HALF) {
+            self.clock_half_frame();
+        }
+        self.odd_cycle = !self.odd_cycle;
+        if self.odd_cycle {
+            for pulse in &mut self.pulse {
+                pulse.clock_timer();
+            }
+        }
+        self.triangle.clock_timer();
+        self.noise.clock_timer(self.region);
+        self.dmc.clock(self.region);
+        self.resampler.add(self.mix(), self.region.cpu_clock_rate());
+    }
+
+    /// returns the channels' levels, scaled by their mixer volumes, mixed
+    /// into one by the 2A03's non-linear output stage. Full scale is about 1
+    pub fn mix(&self) -> f32 {
+        let mixer = &self.mixer;
+        let pulse = mixer.pulse_1 * f32::from(self.pulse[0].output())
+            + mixer.pulse_2 * f32::from(self.pulse[1].output());
+        let tnd = 3.0 * mixer.triangle * f32::from(self.triangle.output())
+            + 2.0 * mixer.noise * f32::from(self.noise.output())
+            + mixer.dmc * f32::from(self.dmc.output_level);
+        pulse_out(pulse) + tnd_out(tnd)
+    }
+
+    /// appends the output produced since the last call to samples, at
+    /// resampler.sample_rate
+    pub fn take_samples(&mut self, samples: &mut Vec<f32>) {
+        let start = samples.len();
+        self.resampler.take_samples(samples);
+        self.filters.apply(&mut samples[start..]);
+    }
+
+    /// changes the output rate, dropping any samples not yet taken
+    pub fn set_sample_rate(&mut self, sample_rate: u32) {
+        self.resampler.set_sample_rate(sample_rate);
+        let enabled = self.filters.enabled;
+        self.filters = OutputFilters::new(sample_rate);
+        self.filters.enabled = enabled;
+    }
+
+    /// clocks the envelopes and the triangle's linear counter
+    fn clock_quarter_frame(&mut self) {
+        for pulse in &mut self.pulse {
+            pulse.envelope.clock();
+        }
+        self.noise.envelope.clock();
+        self.triangle.clock_linear_counter();
+    }
+
+    /// clocks the length counters and sweep units
+    fn clock_half_frame(&mut self) {
+        for length_counter in self.length_counters() {
+            length_counter.clock();
+        }
+        for pulse in &mut self.pulse {
+            pulse.clock_sweep();
+        }
+    }
+}
+
+/// The APU's registers, of which only $4015 can be read. Bits the APU does
+/// not drive read as 0, the Bus fills them in from open bus
+impl Device for APU {
+    fn read(&mut self, address: u16) -> u8 {
+        match address {
+            0x4015 => self.status_read(),
+            _ => 0,
+        }
+    }
+
+    fn write(&mut self, address: u16, data: u8) {
+        self.write_register(address, data);
+    }
+
+    fn peek(&self, address: u16) -> Option<u8> {
+        match address {
+            0x4015 => Some(self.status_peek()),
+            _ => None,
+        }
+    }
+
+    fn tick(&mut self) {
+        self.clock();
+    }
+}
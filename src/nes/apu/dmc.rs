@@ -0,0 +1,185 @@
+use crate::nes::region::Region;
+
+/// CPU cycles between output level changes for each $4010 rate index
+const NTSC_RATES: [u16; 16] = [
+    428, 380, 340, 320, 286, 254, 226, 214, 190, 160, 142, 128, 106, 84, 72, 54,
+];
+const PAL_RATES: [u16; 16] = [
+    398, 354, 316, 298, 276, 236, 210, 198, 176, 148, 132, 118, 98, 78, 66, 50,
+];
+
+/// $4010 bits
+const IRQ_ENABLE: u8 = 0b1000_0000;
+const LOOP: u8 = 0b0100_0000;
+const RATE_INDEX: u8 = 0b0000_1111;
+
+/// largest output level, the counter is 7 bits
+const MAX_OUTPUT_LEVEL: u8 = 0x7f;
+
+/// Delta modulation channel. Plays 1-bit delta encoded samples read from
+/// $8000-$FFFF, each bit moving the 7-bit output level up or down by 2.
+/// Sample bytes are fetched by DMA, which the Bus performs on the DMC's
+/// behalf, see dma_address
+#[derive(Copy, Clone, Debug)]
+pub struct Dmc {
+    /// $4010 bit 7, raise an IRQ when a non-looping sample ends
+    pub irq_enabled: bool,
+    /// $4010 bit 6, restart the sample when it ends
+    pub loop_sample: bool,
+    /// $4010 bits 0-3, index into the rate table
+    pub rate_index: u8,
+    /// 7-bit output level, the channel's contribution to the mix
+    pub output_level: u8,
+    /// $4012, start of the sample at $C000 + 64 * value
+    pub sample_address: u16,
+    /// $4013, length of the sample in bytes is 16 * value + 1
+    pub sample_length: u16,
+    /// address of the next sample byte to fetch
+    pub current_address: u16,
+    /// bytes of the sample left to fetch
+    pub bytes_remaining: u16,
+    /// byte fetched by DMA, waiting to be moved into the shift register
+    pub sample_buffer: Option<u8>,
+    /// bits being played, lowest first
+    pub shift_register: u8,
+    /// bits left in the shift register
+    pub bits_remaining: u8,
+    /// set when the sample buffer was empty as the last byte ran out, the
+    /// output level holds until a byte arrives
+    pub silence: bool,
+    /// CPU cycles until the next output bit
+    pub timer: u16,
+    /// the DMC's IRQ flag, reported in bit 7 of $4015
+    pub interrupt: bool,
+}
+
+impl Default for Dmc {
+    fn default() -> Dmc {
+        Dmc {
+            irq_enabled: Default::default(),
+            loop_sample: Default::default(),
+            rate_index: Default::default(),
+            output_level: Default::default(),
+            sample_address: 0xc000,
+            sample_length: 1,
+            current_address: 0xc000,
+            bytes_remaining: Default::default(),
+            sample_buffer: Default::default(),
+            shift_register: Default::default(),
+            bits_remaining: 8,
+            // nothing has been fetched at power on
+            silence: true,
+            timer: Default::default(),
+            interrupt: Default::default(),
+        }
+    }
+}
+
+impl Dmc {
+    /// $4010 write
+    pub fn flags_write(&mut self, data: u8) {
+        self.irq_enabled = data & IRQ_ENABLE != 0;
+        self.loop_sample = data & LOOP != 0;
+        self.rate_index = data & RATE_INDEX;
+        if !self.irq_enabled {
+            self.interrupt = false;
+        }
+    }
+
+    /// $4011 write, sets the output level directly
+    pub fn direct_load_write(&mut self, data: u8) {
+        self.output_level = data & MAX_OUTPUT_LEVEL;
+    }
+
+    /// $4012 write
+    pub fn sample_address_write(&mut self, data: u8) {
+        self.sample_address = 0xc000 | (u16::from(data) << 6);
+    }
+
+    /// $4013 write
+    pub fn sample_length_write(&mut self, data: u8) {
+        self.sample_length = (u16::from(data) << 4) | 1;
+    }
+
+    /// bit 4 of a $4015 write. Disabling stops the sample once the bits
+    /// already fetched have played, enabling starts it over only if it had
+    /// finished. Either way the IRQ flag is cleared
+    pub fn set_enabled(&mut self, enabled: bool) {
+        self.interrupt = false;
+        if !enabled {
+            self.bytes_remaining = 0;
+        } else if self.bytes_remaining == 0 {
+            self.restart();
+        }
+    }
+
+    fn restart(&mut self) {
+        self.current_address = self.sample_address;
+        self.bytes_remaining = self.sample_length;
+    }
+
+    /// returns the address of the sample byte the DMC needs next, if any.
+    /// The fetch stalls the CPU, and its result goes to fill_sample_buffer
+    pub fn dma_address(&self) -> Option<u16> {
+        match self.sample_buffer {
+            None if self.bytes_remaining > 0 => Some(self.current_address),
+            _ => None,
+        }
+    }
+
+    /// takes a byte fetched from dma_address, moving on to the next. At the
+    /// end of the sample it loops or raises its IRQ
+    pub fn fill_sample_buffer(&mut self, byte: u8) {
+        self.sample_buffer = Some(byte);
+        // the address wraps from $FFFF around to $8000
+        self.current_address = self.current_address.checked_add(1).unwrap_or(0x8000);
+        self.bytes_remaining -= 1;
+        if self.bytes_remaining == 0 {
+            if self.loop_sample {
+                self.restart();
+            } else if self.irq_enabled {
+                self.interrupt = true;
+            }
+        }
+    }
+
+    /// advances the output unit by one CPU cycle
+    pub fn clock(&mut self, region: Region) {
+        if self.timer > 0 {
+            self.timer -= 1;
+            return;
+        }
+        let rates = match region {
+            Region::Ntsc => &NTSC_RATES,
+            Region::Pal => &PAL_RATES,
+        };
+        self.timer = rates[usize::from(self.rate_index)] - 1;
+
+        if !self.silence {
+            if self.shift_register & 1 != 0 {
+                if self.output_level <= MAX_OUTPUT_LEVEL - 2 {
+                    self.output_level += 2;
+                }
+            } else if self.output_level >= 2 {
+                self.output_level -= 2;
+            }
+        }
+        self.shift_register >>= 1;
+        self.bits_remaining -= 1;
+        if self.bits_remaining == 0 {
+            self.bits_remaining = 8;
+            match self.sample_buffer.take() {
+                Some(byte) => {
+                    self.silence = false;
+                    self.shift_register = byte;
+                }
+                None => self.silence = true,
+            }
+        }
+    }
+
+    /// returns whether or not sample bytes are left to fetch, bit 4 of $4015
+    pub fn active(&self) -> bool {
+        self.bytes_remaining > 0
+    }
+}
@@ -0,0 +1,64 @@
+/// $4000/$4004/$400C bits. The loop flag is shared with the length counter's
+/// halt flag
+const LOOP: u8 = 0b0010_0000;
+const CONSTANT_VOLUME: u8 = 0b0001_0000;
+const VOLUME: u8 = 0b0000_1111;
+
+/// loudest level, where decay starts
+const MAX_DECAY: u8 = 15;
+
+/// Volume unit of the pulse and noise channels. Plays either a constant
+/// volume or a sawtooth decaying from 15 to 0 once per divider period,
+/// optionally looping
+#[derive(Copy, Clone, Debug, Default)]
+pub struct Envelope {
+    /// set by writes to the channel's last register, restarts the decay
+    pub start: bool,
+    pub loop_decay: bool,
+    pub constant_volume: bool,
+    /// the constant volume, or the divider period in quarter frames less 1
+    pub volume: u8,
+    pub divider: u8,
+    pub decay: u8,
+}
+
+impl Envelope {
+    /// control register write, bits 0-5
+    pub fn write(&mut self, data: u8) {
+        self.loop_decay = data & LOOP != 0;
+        self.constant_volume = data & CONSTANT_VOLUME != 0;
+        self.volume = data & VOLUME;
+    }
+
+    /// restarts the decay on the next quarter frame
+    pub fn restart(&mut self) {
+        self.start = true;
+    }
+
+    /// quarter frame clock
+    pub fn clock(&mut self) {
+        if self.start {
+            self.start = false;
+            self.decay = MAX_DECAY;
+            self.divider = self.volume;
+        } else if self.divider == 0 {
+            self.divider = self.volume;
+            if self.decay > 0 {
+                self.decay -= 1;
+            } else if self.loop_decay {
+                self.decay = MAX_DECAY;
+            }
+        } else {
+            self.divider -= 1;
+        }
+    }
+
+    /// returns the channel's 4-bit volume
+    pub fn output(&self) -> u8 {
+        if self.constant_volume {
+            self.volume
+        } else {
+            self.decay
+        }
+    }
+}
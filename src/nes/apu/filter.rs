@@ -0,0 +1,97 @@
+use std::f32::consts::PI;
+
+use crate::nes::apu::resampler::DEFAULT_SAMPLE_RATE;
+
+/// corner frequencies of the filters between the 2A03 and the NES's audio
+/// jack, in Hz
+const HIGH_PASS_1: f32 = 90.0;
+const HIGH_PASS_2: f32 = 440.0;
+const LOW_PASS: f32 = 14_000.0;
+
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum FilterKind {
+    HighPass,
+    LowPass,
+}
+
+/// First-order RC filter, run at the output sample rate
+#[derive(Copy, Clone, Debug)]
+pub struct Filter {
+    pub kind: FilterKind,
+    /// smoothing factor derived from the corner frequency and sample rate
+    alpha: f32,
+    previous_input: f32,
+    previous_output: f32,
+}
+
+impl Filter {
+    pub fn new(kind: FilterKind, cutoff: f32, sample_rate: u32) -> Filter {
+        let rc = 1.0 / (2.0 * PI * cutoff);
+        let dt = 1.0 / sample_rate as f32;
+        let alpha = match kind {
+            FilterKind::HighPass => rc / (rc + dt),
+            FilterKind::LowPass => dt / (rc + dt),
+        };
+        Filter {
+            kind,
+            alpha,
+            previous_input: 0.0,
+            previous_output: 0.0,
+        }
+    }
+
+    /// returns the filter's output for the next input sample
+    pub fn process(&mut self, input: f32) -> f32 {
+        let output = match self.kind {
+            FilterKind::HighPass => {
+                self.alpha * (self.previous_output + input - self.previous_input)
+            }
+            FilterKind::LowPass => {
+                self.previous_output + self.alpha * (input - self.previous_output)
+            }
+        };
+        self.previous_input = input;
+        self.previous_output = output;
+        output
+    }
+}
+
+/// The NES's output stage: two high-passes, which remove the mixer's DC
+/// offset and thin out the bass, then a low-pass that softens the square
+/// waves' edges. Disable it to hear the raw mix
+#[derive(Copy, Clone, Debug)]
+pub struct OutputFilters {
+    pub enabled: bool,
+    stages: [Filter; 3],
+}
+
+impl OutputFilters {
+    pub fn new(sample_rate: u32) -> OutputFilters {
+        OutputFilters {
+            enabled: true,
+            stages: [
+                Filter::new(FilterKind::HighPass, HIGH_PASS_1, sample_rate),
+                Filter::new(FilterKind::HighPass, HIGH_PASS_2, sample_rate),
+                Filter::new(FilterKind::LowPass, LOW_PASS, sample_rate),
+            ],
+        }
+    }
+
+    /// filters samples in place, unless disabled
+    pub fn apply(&mut self, samples: &mut [f32]) {
+        if !self.enabled {
+            return;
+        }
+        for sample in samples {
+            for stage in &mut self.stages {
+                *sample = stage.process(*sample);
+            }
+        }
+    }
+}
+
+impl Default for OutputFilters {
+    fn default() -> OutputFilters {
+        OutputFilters::new(DEFAULT_SAMPLE_RATE)
+    }
+}
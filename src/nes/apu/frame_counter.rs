@@ -0,0 +1,122 @@
+use bitflags::bitflags;
+
+use crate::nes::region::Region;
+
+/// $4017 bits
+const FIVE_STEP_MODE: u8 = 0b1000_0000;
+const IRQ_INHIBIT: u8 = 0b0100_0000;
+
+/// CPU cycles into a sequence at which its steps land
+#[derive(Copy, Clone, Debug)]
+struct Timing {
+    /// the first three steps, shared by both modes
+    steps: [u32; 3],
+    /// the last step of each mode, the sequence starting over a cycle later
+    four_step_end: u32,
+    five_step_end: u32,
+}
+
+const NTSC_TIMING: Timing = Timing {
+    steps: [7457, 14913, 22371],
+    four_step_end: 29829,
+    five_step_end: 37281,
+};
+const PAL_TIMING: Timing = Timing {
+    steps: [8313, 16627, 24939],
+    four_step_end: 33253,
+    five_step_end: 41565,
+};
+
+bitflags! {
+    /// Clocks the frame counter sends to the channels on a single cycle
+    #[derive(Debug, Default, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+    pub struct FrameSignal: u8 {
+        /// clocks the envelopes and the triangle's linear counter
+        const QUARTER = 0b0000_0001;
+        /// clocks the length counters and sweep units
+        const HALF    = 0b0000_0010;
+    }
+}
+
+/// The APU's frame counter, a divider of the CPU clock stepping through a
+/// 4 or 5 step sequence about 240 times a second. It clocks the channels'
+/// envelopes, length counters, and sweeps, and in 4-step mode raises the
+/// frame IRQ at the end of each sequence
+#[derive(Copy, Clone, Debug, Default)]
+pub struct FrameCounter {
+    /// $4017 bit 7, the 5-step sequence, which never raises the IRQ
+    pub five_step: bool,
+    /// $4017 bit 6
+    pub irq_inhibit: bool,
+    /// CPU cycles since the sequence started
+    pub cycle: u32,
+    /// the frame IRQ flag, reported in bit 6 of $4015
+    pub interrupt: bool,
+    /// CPU cycles until a $4017 write restarts the sequence
+    pub reset_delay: Option<u8>,
+    /// CPU cycles the APU has run, for the parity of the reset delay
+    pub cpu_cycles: u64,
+}
+
+impl FrameCounter {
+    /// $4017 write. Inhibiting the IRQ clears the flag at once, while the
+    /// sequence restarts 3 or 4 cycles later depending on whether the write
+    /// lands on an even or odd CPU cycle
+    pub fn write(&mut self, data: u8) {
+        self.five_step = data & FIVE_STEP_MODE != 0;
+        self.irq_inhibit = data & IRQ_INHIBIT != 0;
+        if self.irq_inhibit {
+            self.interrupt = false;
+        }
+        self.reset_delay = Some(if self.cpu_cycles.is_multiple_of(2) {
+            3
+        } else {
+            4
+        });
+    }
+
+    /// advances one CPU cycle, returns the channel clocks due on it
+    pub fn clock(&mut self, region: Region) -> FrameSignal {
+        self.cpu_cycles += 1;
+        if let Some(delay) = self.reset_delay {
+            if delay > 1 {
+                self.reset_delay = Some(delay - 1);
+            } else {
+                self.reset_delay = None;
+                self.cycle = 0;
+                // restarting in 5-step mode clocks everything straight away
+                return if self.five_step {
+                    FrameSignal::QUARTER | FrameSignal::HALF
+                } else {
+                    FrameSignal::empty()
+                };
+            }
+        }
+
+        self.cycle += 1;
+        let timing = match region {
+            Region::Ntsc => NTSC_TIMING,
+            Region::Pal => PAL_TIMING,
+        };
+        let end = if self.five_step {
+            timing.five_step_end
+        } else {
+            timing.four_step_end
+        };
+        // the 4-step IRQ is raised on the cycles either side of the last step
+        if !self.five_step && !self.irq_inhibit && (end - 1..=end + 1).contains(&self.cycle) {
+            self.interrupt = true;
+        }
+        let signal = match self.cycle {
+            cycle if cycle == timing.steps[0] || cycle == timing.steps[2] => FrameSignal::QUARTER,
+            cycle if cycle == timing.steps[1] || cycle == end => {
+                FrameSignal::QUARTER | FrameSignal::HALF
+            }
+            _ => FrameSignal::empty(),
+        };
+        if self.cycle > end {
+            self.cycle = 0;
+        }
+        signal
+    }
+}
@@ -0,0 +1,46 @@
+/// lengths, in half frames, for each 5-bit index loaded into a counter
+const LENGTH_TABLE: [u8; 32] = [
+    10, 254, 20, 2, 40, 4, 80, 6, 160, 8, 60, 10, 14, 12, 26, 14, //
+    12, 16, 24, 18, 48, 20, 96, 22, 192, 24, 72, 26, 16, 28, 32, 30,
+];
+
+/// Counts down a note's length on each half frame, silencing the channel
+/// when it reaches 0. Shared by the pulse, triangle, and noise channels
+#[derive(Copy, Clone, Debug, Default)]
+pub struct LengthCounter {
+    /// the channel's $4015 enable bit, counters can only be loaded while set
+    pub enabled: bool,
+    /// stops the count, the channel plays until it is cleared
+    pub halt: bool,
+    pub value: u8,
+}
+
+impl LengthCounter {
+    /// $4015 write bit. Disabling clears the count at once
+    pub fn set_enabled(&mut self, enabled: bool) {
+        self.enabled = enabled;
+        if !enabled {
+            self.value = 0;
+        }
+    }
+
+    /// loads the count from bits 3-7 of a channel's last register
+    pub fn load(&mut self, data: u8) {
+        if self.enabled {
+            self.value = LENGTH_TABLE[usize::from(data >> 3)];
+        }
+    }
+
+    /// half frame clock
+    pub fn clock(&mut self) {
+        if !self.halt && self.value > 0 {
+            self.value -= 1;
+        }
+    }
+
+    /// returns whether or not the channel is still sounding, reported in
+    /// $4015 reads
+    pub fn active(&self) -> bool {
+        self.value > 0
+    }
+}
@@ -0,0 +1,56 @@
+/// Output of the pulse channels for the sum of their levels, 0-30. This is
+/// the formula nesdev's pulse_table is built from, evaluated directly so
+/// attenuated channels' fractional levels mix correctly
+pub fn pulse_out(pulse: f32) -> f32 {
+    if pulse <= 0.0 {
+        0.0
+    } else {
+        95.52 / (8128.0 / pulse + 100.0)
+    }
+}
+
+/// Output of the triangle, noise, and DMC channels for 3 * triangle + 2 *
+/// noise + dmc, 0-202, the formula behind nesdev's tnd_table
+pub fn tnd_out(tnd: f32) -> f32 {
+    if tnd <= 0.0 {
+        0.0
+    } else {
+        163.67 / (24329.0 / tnd + 100.0)
+    }
+}
+
+/// Volume of each channel going into the mix, 1 as on hardware and 0 to
+/// mute it. Useful for ripping music or finding which channel makes a sound
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct MixerConfig {
+    pub pulse_1: f32,
+    pub pulse_2: f32,
+    pub triangle: f32,
+    pub noise: f32,
+    pub dmc: f32,
+}
+
+impl Default for MixerConfig {
+    fn default() -> MixerConfig {
+        MixerConfig {
+            pulse_1: 1.0,
+            pulse_2: 1.0,
+            triangle: 1.0,
+            noise: 1.0,
+            dmc: 1.0,
+        }
+    }
+}
+
+impl MixerConfig {
+    /// returns a config with every channel muted, to unmute one at a time
+    pub fn silent() -> MixerConfig {
+        MixerConfig {
+            pulse_1: 0.0,
+            pulse_2: 0.0,
+            triangle: 0.0,
+            noise: 0.0,
+            dmc: 0.0,
+        }
+    }
+}
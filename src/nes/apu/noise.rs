@@ -0,0 +1,93 @@
+use crate::nes::apu::envelope::Envelope;
+use crate::nes::apu::length_counter::LengthCounter;
+use crate::nes::region::Region;
+
+/// CPU cycles between shifts for each $400E period index
+const NTSC_PERIODS: [u16; 16] = [
+    4, 8, 16, 32, 64, 96, 128, 160, 202, 254, 380, 508, 762, 1016, 2034, 4068,
+];
+const PAL_PERIODS: [u16; 16] = [
+    4, 8, 14, 30, 60, 88, 118, 148, 188, 236, 354, 472, 708, 944, 1890, 3778,
+];
+
+/// $400C bits
+const LENGTH_HALT: u8 = 0b0010_0000;
+/// $400E bits
+const SHORT_MODE: u8 = 0b1000_0000;
+const PERIOD_INDEX: u8 = 0b0000_1111;
+
+/// Pseudo-random noise channel, $400C-$400F. A 15-bit linear feedback shift
+/// register gates the envelope's volume
+#[derive(Copy, Clone, Debug)]
+pub struct Noise {
+    pub envelope: Envelope,
+    pub length_counter: LengthCounter,
+    /// $400E bit 7, feeds back from bit 6 instead of bit 1 for a short,
+    /// metallic sounding sequence
+    pub short_mode: bool,
+    /// $400E bits 0-3, index into the period table
+    pub period_index: u8,
+    /// CPU cycles until the next shift
+    pub timer: u16,
+    pub shift_register: u16,
+}
+
+impl Default for Noise {
+    fn default() -> Noise {
+        Noise {
+            envelope: Default::default(),
+            length_counter: Default::default(),
+            short_mode: Default::default(),
+            period_index: Default::default(),
+            timer: Default::default(),
+            // loaded with 1 at power on, an all zero register never changes
+            shift_register: 1,
+        }
+    }
+}
+
+impl Noise {
+    /// $400C write
+    pub fn control_write(&mut self, data: u8) {
+        self.length_counter.halt = data & LENGTH_HALT != 0;
+        self.envelope.write(data);
+    }
+
+    /// $400E write
+    pub fn period_write(&mut self, data: u8) {
+        self.short_mode = data & SHORT_MODE != 0;
+        self.period_index = data & PERIOD_INDEX;
+    }
+
+    /// $400F write
+    pub fn length_write(&mut self, data: u8) {
+        self.length_counter.load(data);
+        self.envelope.restart();
+    }
+
+    /// advances the timer by one CPU cycle
+    pub fn clock_timer(&mut self, region: Region) {
+        if self.timer > 0 {
+            self.timer -= 1;
+            return;
+        }
+        let periods = match region {
+            Region::Ntsc => &NTSC_PERIODS,
+            Region::Pal => &PAL_PERIODS,
+        };
+        self.timer = periods[usize::from(self.period_index)] - 1;
+
+        let tap = if self.short_mode { 6 } else { 1 };
+        let feedback = (self.shift_register ^ (self.shift_register >> tap)) & 1;
+        self.shift_register = (self.shift_register >> 1) | (feedback << 14);
+    }
+
+    /// returns the channel's 4-bit output level
+    pub fn output(&self) -> u8 {
+        if self.shift_register & 1 == 0 && self.length_counter.active() {
+            self.envelope.output()
+        } else {
+            0
+        }
+    }
+}
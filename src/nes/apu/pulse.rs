@@ -0,0 +1,97 @@
+use crate::nes::apu::envelope::Envelope;
+use crate::nes::apu::length_counter::LengthCounter;
+use crate::nes::apu::sweep::Sweep;
+
+/// $4000/$4004 bits
+const DUTY: u8 = 0b1100_0000;
+const LENGTH_HALT: u8 = 0b0010_0000;
+/// $4003/$4007 bits
+const TIMER_HIGH: u8 = 0b0000_0111;
+
+/// waveforms for each duty cycle, 12.5%, 25%, 50%, and 25% inverted
+const DUTY_SEQUENCES: [[u8; 8]; 4] = [
+    [0, 1, 0, 0, 0, 0, 0, 0],
+    [0, 1, 1, 0, 0, 0, 0, 0],
+    [0, 1, 1, 1, 1, 0, 0, 0],
+    [1, 0, 0, 1, 1, 1, 1, 1],
+];
+
+/// One of the two square wave channels, $4000-$4003 and $4004-$4007
+#[derive(Copy, Clone, Debug, Default)]
+pub struct Pulse {
+    pub envelope: Envelope,
+    pub length_counter: LengthCounter,
+    pub sweep: Sweep,
+    /// 11-bit period of the channel's timer, which sets its pitch
+    pub timer_period: u16,
+    /// APU cycles until the sequencer steps
+    pub timer: u16,
+    /// $4000/$4004 bits 6-7, index into DUTY_SEQUENCES
+    pub duty: u8,
+    /// position in the 8 step waveform
+    pub sequence_step: u8,
+}
+
+impl Pulse {
+    /// returns pulse 1, whose sweep negates with one's complement
+    pub fn pulse_1() -> Pulse {
+        let mut pulse: Pulse = Default::default();
+        pulse.sweep.ones_complement = true;
+        pulse
+    }
+
+    /// $4000/$4004 write
+    pub fn control_write(&mut self, data: u8) {
+        self.duty = (data & DUTY) >> 6;
+        self.length_counter.halt = data & LENGTH_HALT != 0;
+        self.envelope.write(data);
+    }
+
+    /// $4001/$4005 write
+    pub fn sweep_write(&mut self, data: u8) {
+        self.sweep.write(data);
+    }
+
+    /// $4002/$4006 write
+    pub fn timer_low_write(&mut self, data: u8) {
+        self.timer_period = (self.timer_period & 0x0700) | u16::from(data);
+    }
+
+    /// $4003/$4007 write
+    pub fn length_write(&mut self, data: u8) {
+        self.timer_period = (u16::from(data & TIMER_HIGH) << 8) | (self.timer_period & 0x00ff);
+        self.length_counter.load(data);
+        self.envelope.restart();
+        self.sequence_step = 0;
+    }
+
+    /// advances the timer by one APU cycle, every other CPU cycle
+    pub fn clock_timer(&mut self) {
+        if self.timer == 0 {
+            self.timer = self.timer_period;
+            self.sequence_step = (self.sequence_step + 1) % 8;
+        } else {
+            self.timer -= 1;
+        }
+    }
+
+    /// half frame clock of the sweep unit
+    pub fn clock_sweep(&mut self) {
+        self.sweep.clock(&mut self.timer_period);
+    }
+
+    /// returns whether or not the sweep unit is silencing the channel
+    pub fn muted(&self) -> bool {
+        self.sweep.mutes(self.timer_period)
+    }
+
+    /// returns the channel's 4-bit output level
+    pub fn output(&self) -> u8 {
+        let high = DUTY_SEQUENCES[usize::from(self.duty)][usize::from(self.sequence_step)] != 0;
+        if high && self.length_counter.active() && !self.muted() {
+            self.envelope.output()
+        } else {
+            0
+        }
+    }
+}
@@ -0,0 +1,146 @@
+use std::f64::consts::PI;
+
+/// output rate used until a frontend asks for another
+pub const DEFAULT_SAMPLE_RATE: u32 = 48_000;
+
+/// output samples each level change is spread across
+const KERNEL_TAPS: usize = 16;
+/// fractional positions between output samples a change can land on
+const KERNEL_PHASES: usize = 64;
+/// cutoff of the kernel's low-pass, as a fraction of the output rate
+const CUTOFF: f64 = 0.45;
+/// output samples held before the oldest are dropped, about 85ms at 48 kHz
+const BUFFER_SIZE: usize = 4096;
+/// output samples still being written into by the kernel, which can't be
+/// taken yet
+const LATENCY: usize = KERNEL_TAPS / 2 - 1;
+
+/// Converts the mixer's level, sampled every CPU cycle, to the host's
+/// sample rate without aliasing. In the style of blip_buf, each change in
+/// level is added to the output as a band-limited step: its delta is spread
+/// across neighbouring samples by a windowed sinc kernel, and the samples
+/// are integrated as they are taken
+#[derive(Copy, Clone, Debug)]
+pub struct Resampler {
+    pub sample_rate: u32,
+    /// windowed sinc impulse for each phase, each summing to 1
+    kernel: [[f32; KERNEL_TAPS]; KERNEL_PHASES],
+    /// deltas not yet integrated
+    buffer: [f32; BUFFER_SIZE],
+    /// position of the current CPU cycle, in output samples from buffer[0]
+    time: f64,
+    /// output samples per CPU cycle
+    step: f64,
+    /// CPU clock step was computed for
+    clock_rate: u64,
+    /// last level added
+    level: f32,
+    /// sum of the deltas taken so far, the current output level
+    integrator: f32,
+}
+
+impl Default for Resampler {
+    fn default() -> Resampler {
+        Resampler::new(DEFAULT_SAMPLE_RATE)
+    }
+}
+
+/// returns the windowed sinc low-pass impulse at x output samples from its
+/// centre
+fn windowed_sinc(x: f64) -> f64 {
+    let half_width = (KERNEL_TAPS / 2) as f64;
+    if x.abs() >= half_width {
+        return 0.0;
+    }
+    let sinc = if x == 0.0 {
+        2.0 * CUTOFF
+    } else {
+        (2.0 * PI * CUTOFF * x).sin() / (PI * x)
+    };
+    // Blackman window
+    let window =
+        0.42 + 0.5 * (PI * x / half_width).cos() + 0.08 * (2.0 * PI * x / half_width).cos();
+    sinc * window
+}
+
+impl Resampler {
+    pub fn new(sample_rate: u32) -> Resampler {
+        let mut kernel = [[0.0; KERNEL_TAPS]; KERNEL_PHASES];
+        for (phase, taps) in kernel.iter_mut().enumerate() {
+            let fraction = phase as f64 / KERNEL_PHASES as f64;
+            let impulse: Vec<f64> = (0..KERNEL_TAPS)
+                .map(|tap| windowed_sinc(tap as f64 - LATENCY as f64 - fraction))
+                .collect();
+            let sum: f64 = impulse.iter().sum();
+            for (tap, value) in taps.iter_mut().zip(impulse) {
+                *tap = (value / sum) as f32;
+            }
+        }
+        Resampler {
+            sample_rate,
+            kernel,
+            buffer: [0.0; BUFFER_SIZE],
+            time: LATENCY as f64,
+            step: 0.0,
+            clock_rate: 0,
+            level: 0.0,
+            integrator: 0.0,
+        }
+    }
+
+    /// adds one CPU cycle of output at level, clock_rate being the CPU
+    /// cycles per second
+    pub fn add(&mut self, level: f32, clock_rate: u64) {
+        if clock_rate != self.clock_rate {
+            self.clock_rate = clock_rate;
+            self.step = f64::from(self.sample_rate) / clock_rate as f64;
+        }
+        // nobody is taking samples, drop the oldest half
+        if self.time as usize + KERNEL_TAPS >= BUFFER_SIZE {
+            self.read(BUFFER_SIZE / 2, None);
+        }
+        if level != self.level {
+            let delta = level - self.level;
+            self.level = level;
+            let position = self.time.floor();
+            let phase = ((self.time - position) * KERNEL_PHASES as f64) as usize;
+            let start = position as usize - LATENCY;
+            for (sample, tap) in self.buffer[start..start + KERNEL_TAPS]
+                .iter_mut()
+                .zip(self.kernel[phase])
+            {
+                *sample += delta * tap;
+            }
+        }
+        self.time += self.step;
+    }
+
+    /// integrates the first count samples, appending them to samples if
+    /// given, and moves the rest of the buffer down
+    fn read(&mut self, count: usize, mut samples: Option<&mut Vec<f32>>) {
+        for delta in &self.buffer[..count] {
+            self.integrator += delta;
+            if let Some(samples) = samples.as_mut() {
+                samples.push(self.integrator);
+            }
+        }
+        self.buffer.copy_within(count.., 0);
+        self.buffer[BUFFER_SIZE - count..].fill(0.0);
+        self.time -= count as f64;
+    }
+
+    /// appends every finished output sample to samples
+    pub fn take_samples(&mut self, samples: &mut Vec<f32>) {
+        let count = (self.time as usize).saturating_sub(LATENCY);
+        self.read(count, Some(samples));
+    }
+
+    /// changes the output rate, dropping any samples not yet taken
+    pub fn set_sample_rate(&mut self, sample_rate: u32) {
+        *self = Resampler {
+            level: self.level,
+            integrator: self.level,
+            ..Resampler::new(sample_rate)
+        };
+    }
+}
@@ -0,0 +1,72 @@
+/// $4001/$4005 bits
+const ENABLED: u8 = 0b1000_0000;
+const DIVIDER_PERIOD: u8 = 0b0111_0000;
+const NEGATE: u8 = 0b0000_1000;
+const SHIFT: u8 = 0b0000_0111;
+
+/// largest timer period, periods beyond it mute the channel
+const MAX_TIMER_PERIOD: u16 = 0x7ff;
+/// smallest timer period that does not mute the channel
+const MIN_TIMER_PERIOD: u16 = 8;
+
+/// A pulse channel's sweep unit, which slides its pitch by moving the timer
+/// period a fraction of itself up or down every few half frames
+#[derive(Copy, Clone, Debug, Default)]
+pub struct Sweep {
+    pub enabled: bool,
+    /// half frames between adjustments, less 1
+    pub divider_period: u8,
+    pub negate: bool,
+    /// the change is the timer period shifted right this many bits
+    pub shift: u8,
+    pub divider: u8,
+    /// set by writes, reloads the divider on the next half frame
+    pub reload: bool,
+    /// pulse 1 negates with one's complement, subtracting 1 more than pulse
+    /// 2 does
+    pub ones_complement: bool,
+}
+
+impl Sweep {
+    /// $4001/$4005 write
+    pub fn write(&mut self, data: u8) {
+        self.enabled = data & ENABLED != 0;
+        self.divider_period = (data & DIVIDER_PERIOD) >> 4;
+        self.negate = data & NEGATE != 0;
+        self.shift = data & SHIFT;
+        self.reload = true;
+    }
+
+    /// returns the period the sweep is moving the timer towards, computed
+    /// continuously whether or not the unit is enabled
+    pub fn target_period(&self, timer_period: u16) -> u16 {
+        let change = timer_period >> self.shift;
+        if !self.negate {
+            timer_period + change
+        } else if self.ones_complement {
+            timer_period.saturating_sub(change + 1)
+        } else {
+            timer_period.saturating_sub(change)
+        }
+    }
+
+    /// returns whether or not the channel is silenced, either because its
+    /// period is too short or the target period overflows 11 bits. This
+    /// applies even when the sweep is disabled
+    pub fn mutes(&self, timer_period: u16) -> bool {
+        timer_period < MIN_TIMER_PERIOD || self.target_period(timer_period) > MAX_TIMER_PERIOD
+    }
+
+    /// half frame clock, adjusting timer_period when the divider expires
+    pub fn clock(&mut self, timer_period: &mut u16) {
+        if self.divider == 0 && self.enabled && self.shift > 0 && !self.mutes(*timer_period) {
+            *timer_period = self.target_period(*timer_period);
+        }
+        if self.divider == 0 || self.reload {
+            self.divider = self.divider_period;
+            self.reload = false;
+        } else {
+            self.divider -= 1;
+        }
+    }
+}
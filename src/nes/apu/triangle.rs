@@ -0,0 +1,82 @@
+use crate::nes::apu::length_counter::LengthCounter;
+
+/// $4008 bits, the flag also controls the linear counter
+const LENGTH_HALT: u8 = 0b1000_0000;
+const LINEAR_RELOAD_VALUE: u8 = 0b0111_1111;
+/// $400B bits
+const TIMER_HIGH: u8 = 0b0000_0111;
+
+/// levels of the 32 step waveform, down from 15 and back up
+const SEQUENCE: [u8; 32] = [
+    15, 14, 13, 12, 11, 10, 9, 8, 7, 6, 5, 4, 3, 2, 1, 0, //
+    0, 1, 2, 3, 4, 5, 6, 7, 8, 9, 10, 11, 12, 13, 14, 15,
+];
+
+/// Triangle wave channel, $4008-$400B. Besides the length counter, notes
+/// are timed by a finer grained linear counter clocked every quarter frame
+#[derive(Copy, Clone, Debug, Default)]
+pub struct Triangle {
+    pub length_counter: LengthCounter,
+    /// $4008 bits 0-6, quarter frames a note plays for
+    pub linear_reload_value: u8,
+    pub linear_counter: u8,
+    /// set by $400B writes, reloads the linear counter on the next quarter
+    /// frame and every one after while the halt flag is set
+    pub linear_reload: bool,
+    /// 11-bit period of the channel's timer, which sets its pitch
+    pub timer_period: u16,
+    /// CPU cycles until the sequencer steps
+    pub timer: u16,
+    /// position in SEQUENCE
+    pub sequence_step: u8,
+}
+
+impl Triangle {
+    /// $4008 write
+    pub fn control_write(&mut self, data: u8) {
+        self.length_counter.halt = data & LENGTH_HALT != 0;
+        self.linear_reload_value = data & LINEAR_RELOAD_VALUE;
+    }
+
+    /// $400A write
+    pub fn timer_low_write(&mut self, data: u8) {
+        self.timer_period = (self.timer_period & 0x0700) | u16::from(data);
+    }
+
+    /// $400B write
+    pub fn length_write(&mut self, data: u8) {
+        self.timer_period = (u16::from(data & TIMER_HIGH) << 8) | (self.timer_period & 0x00ff);
+        self.length_counter.load(data);
+        self.linear_reload = true;
+    }
+
+    /// quarter frame clock
+    pub fn clock_linear_counter(&mut self) {
+        if self.linear_reload {
+            self.linear_counter = self.linear_reload_value;
+        } else if self.linear_counter > 0 {
+            self.linear_counter -= 1;
+        }
+        if !self.length_counter.halt {
+            self.linear_reload = false;
+        }
+    }
+
+    /// advances the timer by one CPU cycle. The sequencer only moves while
+    /// both counters are running, so a silenced triangle holds its level
+    pub fn clock_timer(&mut self) {
+        if self.timer == 0 {
+            self.timer = self.timer_period;
+            if self.linear_counter > 0 && self.length_counter.active() {
+                self.sequence_step = (self.sequence_step + 1) % 32;
+            }
+        } else {
+            self.timer -= 1;
+        }
+    }
+
+    /// returns the channel's 4-bit output level
+    pub fn output(&self) -> u8 {
+        SEQUENCE[usize::from(self.sequence_step)]
+    }
+}
@@ -0,0 +1,82 @@
+use std::collections::VecDeque;
+use std::fmt;
+
+/// Samples queued between the emulator, which pushes them as the APU runs,
+/// and a host audio callback, which drains them on its own schedule. An
+/// output backend shares one behind a mutex
+#[derive(Clone, Debug)]
+pub struct SampleRing {
+    samples: VecDeque<f32>,
+    capacity: usize,
+    /// last sample handed to the host, repeated while the ring is empty so an
+    /// underrun holds the level instead of clicking back to silence
+    last: f32,
+    /// host callbacks that asked for more samples than were queued
+    pub underruns: u64,
+    /// samples dropped because the host fell behind and the ring was full
+    pub overruns: u64,
+}
+
+impl SampleRing {
+    pub fn new(capacity: usize) -> SampleRing {
+        SampleRing {
+            samples: VecDeque::with_capacity(capacity),
+            capacity,
+            last: 0.0,
+            underruns: 0,
+            overruns: 0,
+        }
+    }
+
+    /// queues a sample, dropping the oldest one if the ring is full
+    pub fn push(&mut self, sample: f32) {
+        if self.samples.len() == self.capacity {
+            self.samples.pop_front();
+            self.overruns += 1;
+        }
+        self.samples.push_back(sample);
+    }
+
+    /// fills a host buffer with queued samples, padding it with the last
+    /// sample played if there are not enough
+    pub fn fill(&mut self, buffer: &mut [f32]) {
+        self.fill_frames(buffer, 1);
+    }
+
+    /// fills an interleaved host buffer with queued samples, copying each
+    /// one to every channel of its frame and padding like fill
+    pub fn fill_frames(&mut self, buffer: &mut [f32], channels: usize) {
+        let mut short = false;
+        for frame in buffer.chunks_mut(channels.max(1)) {
+            match self.samples.pop_front() {
+                Some(sample) => self.last = sample,
+                None => short = true,
+            }
+            frame.fill(self.last);
+        }
+        if short {
+            self.underruns += 1;
+        }
+    }
+
+    /// returns the number of samples queued
+    pub fn len(&self) -> usize {
+        self.samples.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.samples.is_empty()
+    }
+}
+
+/// Consumer handed each frame's audio by the core, for embedders playing it
+/// through their own backend
+pub struct AudioCallback(pub Box<AudioFn>);
+
+pub type AudioFn = dyn FnMut(&[f32]);
+
+impl fmt::Debug for AudioCallback {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str("AudioCallback")
+    }
+}
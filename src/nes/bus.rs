@@ -1,131 +1,665 @@
-use crate::nes::{cpu::ReadWrite, ppu::PPU, ppu_structs::PPUCTRL};
-use std::ops::{Index, IndexMut};
+use std::ops::RangeInclusive;
+
+use crate::nes::{
+    apu::APU,
+    bus_trace::{AccessBuffer, AccessKind, BusAccess, TraceCallback},
+    cartridge::Cartridge,
+    cheats::Cheats,
+    controller::Controller,
+    cpu::ReadWrite,
+    device::{Device, MappedDevice},
+    diagnostics::Unimplemented,
+    dma::{DmaController, DmaCycle},
+    freeze::{FreezeTiming, MemoryFreezer},
+    fuzz::UnmappedFuzz,
+    irq::IrqSource,
+    memory_map::{MemoryMapEntry, RegionKind},
+    micro_op::{Replay, ReplayAccess},
+    ppu::{A12Callback, PpuRegisterWrite, ScanlineCallback, PPU},
+    ram_init::RamInit,
+    region::RegionDetector,
+    timer::{CycleTimer, TIMER_REGISTER_COUNT},
+    watchpoint::Watchpoints,
+};
 
 const CPU_MEMORY_SIZE: usize = 0x10000;
+/// bytes of internal RAM, mirrored through $1FFF
+pub const RAM_SIZE: usize = 0x800;
+
+/// Memory map the Bus decodes
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq)]
+pub enum BusConfig {
+    /// 64 KB of plain RAM with nothing else mapped but an optional
+    /// CycleTimer and any mapped devices, for bare 6502 programs such as
+    /// the functional test. The PPU and APU are not clocked
+    FlatRam,
+    /// the NES's memory map, with the PPU, APU, and controllers
+    #[default]
+    Nes,
+}
 
-#[derive(Copy, Clone, Debug)]
+#[derive(Debug)]
 pub struct Bus {
-    pub bytes: [u8; CPU_MEMORY_SIZE],
+    pub bytes: Box<[u8]>,
+    /// the last value driven onto the CPU's data bus, by the CPU or DMA on
+    /// every read and write. Open bus reads, the undriven bits of $4015 and
+    /// the controller ports, and the expansion area all return it
     pub data_bus: u8,
+    /// the address of the last read or write, by the CPU or DMA
     pub address_bus: u16,
+    /// the R/W pin, low for the last access if it was a write
+    pub read_write: ReadWrite,
     pub ppu: PPU,
-    pub cpu_only_mode: bool,
+    pub apu: APU,
+    pub config: BusConfig,
+    /// what internal RAM holds at power-on, see power_on_ram
+    pub ram_init: RamInit,
+    /// optional cycle-derived timer, only mapped in BusConfig::FlatRam
+    pub timer: Option<CycleTimer>,
+    /// sequences OAM DMA and DMC fetches and the CPU cycles they steal
+    pub dma: DmaController,
+    /// most recent CPU write to a PPU register, taken by the tracer
+    pub last_ppu_register_write: Option<PpuRegisterWrite>,
+    /// sources currently asserting the CPU's /IRQ line
+    pub irq_sources: IrqSource,
+    /// stubbed hardware the running program has touched
+    pub unimplemented: Unimplemented,
+    /// the board's PRG RAM
+    pub cartridge: Cartridge,
+    /// controllers read through $4016 and $4017
+    pub controllers: [Controller; 2],
+    /// accesses recorded for the NES's bus tracer
+    pub accesses: AccessBuffer,
+    /// addresses locked to fixed values, reapplied after every CPU write
+    /// or once per frame depending on its timing
+    pub freezer: MemoryFreezer,
+    /// values held in memory, applied once per frame by apply_cheats
+    pub cheats: Cheats,
+    /// data breakpoints the run loop stops on
+    pub watchpoints: Watchpoints,
+    /// fed every PPUDATA write, see NES::run_frame
+    pub region_detector: RegionDetector,
+    /// the master clock, in CPU cycles. Every read and write advances it by
+    /// one, see tick, and accesses are stamped with it
+    pub cycle: u64,
+    /// part of a PPU dot owed by CPU cycles that did not divide evenly into
+    /// dots, counted in fifths on PAL
+    pub ppu_dot_remainder: u64,
+    /// called on every filtered rise of PPU A12
+    pub a12_callback: Option<A12Callback>,
+    /// called at a fixed dot of every scanline
+    pub scanline_callback: Option<ScanlineCallback>,
+    /// when set, unmapped reads return random bytes and unmapped writes are
+    /// logged, see fuzz_unmapped
+    pub unmapped_fuzz: Option<UnmappedFuzz>,
+    /// set while NES::step_cycle replays an instruction, letting only the
+    /// access due on this cycle through
+    pub replay: Option<Replay>,
+    /// called with every access as it happens, see on_transaction
+    pub transaction_callback: Option<TraceCallback>,
+    /// devices mapped with map_device, which take priority over the memory
+    /// map
+    pub devices: Vec<MappedDevice>,
 }
 
 impl Default for Bus {
     fn default() -> Bus {
         Bus {
-            bytes: [0; CPU_MEMORY_SIZE],
+            bytes: vec![0; CPU_MEMORY_SIZE].into_boxed_slice(),
             data_bus: Default::default(),
             address_bus: Default::default(),
+            read_write: Default::default(),
             ppu: Default::default(),
-            cpu_only_mode: Default::default(),
+            apu: Default::default(),
+            config: Default::default(),
+            ram_init: Default::default(),
+            timer: Default::default(),
+            dma: Default::default(),
+            last_ppu_register_write: Default::default(),
+            irq_sources: Default::default(),
+            unimplemented: Default::default(),
+            cartridge: Default::default(),
+            controllers: Default::default(),
+            accesses: Default::default(),
+            freezer: Default::default(),
+            cheats: Default::default(),
+            watchpoints: Default::default(),
+            region_detector: Default::default(),
+            cycle: Default::default(),
+            ppu_dot_remainder: Default::default(),
+            a12_callback: Default::default(),
+            scanline_callback: Default::default(),
+            unmapped_fuzz: Default::default(),
+            replay: Default::default(),
+            transaction_callback: Default::default(),
+            devices: Default::default(),
         }
     }
 }
 
-impl Index<usize> for Bus {
-    type Output = u8;
-
-    fn index(&self, index: usize) -> &Self::Output {
-        // println!("Accessing 0x{index:x} in bus immutably");
-        if self.cpu_only_mode == false {
-            match index {
-                // oam_addr_first_write needs to be reset when 0x2002 is read
-                0x2002 => &self.ppu.ppu_status,
-                0x2004 => &self.ppu.oam_data,
-                0x2007 => {
-                    let lo = self.ppu.ppu_addr_low;
-                    let hi = self.ppu.ppu_addr_high;
-                    let address = (u16::from(hi) << 8) + u16::from(lo);
-                    &self.ppu.address_space[usize::from(address)]
+/// bit of a $4015 read the APU leaves undriven
+const OPEN_BUS_STATUS_BIT: u8 = 0b0010_0000;
+
+/// bits of a $4016/$4017 read the controller ports leave undriven
+const OPEN_BUS_CONTROLLER_BITS: u8 = 0b1110_0000;
+
+/// folds the mirrors of the 2 KB of internal RAM, which repeat through
+/// $1FFF, onto $0000-$07FF, and those of the PPU registers, which repeat
+/// every 8 bytes through $3FFF, onto $2000-$2007
+pub fn mirror_address(address: u16) -> u16 {
+    match address {
+        0x0800..=0x1fff => address & 0x07ff,
+        0x2008..=0x3fff => 0x2000 | (address & 0x0007),
+        _ => address,
+    }
+}
+
+impl Bus {
+    /// maps a cycle-derived timer at base_address, see CycleTimer for layout
+    pub fn attach_timer(&mut self, base_address: u16) {
+        self.timer = Some(CycleTimer::new(base_address));
+    }
+
+    /// updates any attached timer to reflect the current CPU clock
+    pub fn sync_timer(&mut self, clock: u64) {
+        let clock_rate = self.ppu.region.cpu_clock_rate();
+        if let Some(ref mut timer) = self.timer {
+            timer.sync(clock, clock_rate);
+        }
+    }
+
+    /// fills internal RAM with ram_init's pattern, as though the console
+    /// had just been switched on
+    pub fn power_on_ram(&mut self) {
+        self.ram_init.fill(&mut self.bytes[..RAM_SIZE]);
+    }
+
+    /// writes every frozen value back into memory, see MemoryFreezer::apply
+    pub fn apply_freezer(&mut self) {
+        if self.freezer.is_empty() {
+            return;
+        }
+        let freezer = std::mem::take(&mut self.freezer);
+        freezer.apply(self);
+        self.freezer = freezer;
+    }
+
+    /// writes the value of every enabled cheat to its address through the
+    /// cheats' freezer, see MemoryFreezer::apply
+    pub fn apply_cheats(&mut self) {
+        let cheats = std::mem::take(&mut self.cheats);
+        cheats.freezer().apply(self);
+        self.cheats = cheats;
+    }
+
+    /// calls callback with every read and write, by the CPU or DMA, as it
+    /// happens, stamped with the cycle it happened on. Unlike the
+    /// AccessBuffer this sees each access of an instruction before the next
+    pub fn on_transaction<F: FnMut(&BusAccess) + 'static>(&mut self, callback: F) {
+        self.transaction_callback = Some(TraceCallback(Box::new(callback)));
+    }
+
+    /// maps device over range, in front of whatever the memory map or
+    /// earlier devices put there
+    pub fn map_device(&mut self, range: RangeInclusive<u16>, device: Box<dyn Device>) {
+        self.devices.insert(0, MappedDevice { range, device });
+    }
+
+    /// describes what answers each range of the address space, for
+    /// debuggers. Ranges may overlap, the first entry covering an address
+    /// is the one that answers it
+    pub fn memory_map(&self) -> Vec<MemoryMapEntry> {
+        let mut entries: Vec<MemoryMapEntry> = self
+            .devices
+            .iter()
+            .map(|mapped| {
+                MemoryMapEntry::new(
+                    mapped.range.clone(),
+                    mapped.device.name(),
+                    RegionKind::Device,
+                )
+            })
+            .collect();
+        if self.config == BusConfig::FlatRam {
+            if let Some(ref timer) = self.timer {
+                let end = timer.base_address + TIMER_REGISTER_COUNT as u16 - 1;
+                entries.push(MemoryMapEntry::new(
+                    timer.base_address..=end,
+                    "cycle timer",
+                    RegionKind::Registers,
+                ));
+            }
+            entries.push(MemoryMapEntry::new(0x0000..=0xffff, "RAM", RegionKind::Ram));
+            return entries;
+        }
+        let mapper = self.cartridge.mapper.name();
+        let prg_ram = match (self.cartridge.prg_ram_enabled, self.cartridge.battery) {
+            (true, true) => (String::from("battery-backed PRG RAM"), RegionKind::Ram),
+            (true, false) => (String::from("PRG RAM"), RegionKind::Ram),
+            (false, _) => (String::from("no PRG RAM"), RegionKind::OpenBus),
+        };
+        entries.extend([
+            MemoryMapEntry::new(0x0000..=0x07ff, "internal RAM", RegionKind::Ram),
+            MemoryMapEntry::new(0x0800..=0x1fff, "internal RAM mirrors", RegionKind::Mirror),
+            MemoryMapEntry::new(0x2000..=0x2007, "PPU registers", RegionKind::Registers),
+            MemoryMapEntry::new(0x2008..=0x3fff, "PPU register mirrors", RegionKind::Mirror),
+            MemoryMapEntry::new(0x4000..=0x4013, "APU registers", RegionKind::Registers),
+            MemoryMapEntry::new(0x4014..=0x4014, "OAM DMA", RegionKind::Registers),
+            MemoryMapEntry::new(0x4015..=0x4015, "APU status", RegionKind::Registers),
+            MemoryMapEntry::new(0x4016..=0x4017, "controller ports", RegionKind::Registers),
+            MemoryMapEntry::new(0x4018..=0x401f, "APU test registers", RegionKind::OpenBus),
+            MemoryMapEntry::new(
+                0x4020..=0x5fff,
+                &format!("{mapper} expansion area"),
+                RegionKind::Cartridge,
+            ),
+            MemoryMapEntry::new(0x6000..=0x7fff, &prg_ram.0, prg_ram.1),
+            MemoryMapEntry::new(
+                0x8000..=0xffff,
+                &format!("{mapper} PRG ROM"),
+                RegionKind::Rom,
+            ),
+        ]);
+        entries
+    }
+
+    /// returns the mapped device answering address, if any
+    fn device_at(&mut self, address: u16) -> Option<&mut Box<dyn Device>> {
+        self.devices
+            .iter_mut()
+            .find(|mapped| mapped.range.contains(&address))
+            .map(|mapped| &mut mapped.device)
+    }
+
+    /// advances every mapped device by one CPU cycle
+    pub fn tick_devices(&mut self) {
+        for mapped in &mut self.devices {
+            mapped.device.tick();
+        }
+    }
+
+    /// reads a byte from the CPU's address space, triggering any side
+    /// effects of reading a memory mapped register, then ticks the master
+    /// clock through the cycle. The byte is left on the data bus, where
+    /// unmapped reads find it, except for $4015, which is read inside the
+    /// 2A03 without driving the external bus
+    pub fn read(&mut self, address: u16) -> u8 {
+        if let Some(ref mut replay) = self.replay {
+            match replay.access() {
+                ReplayAccess::Latched(data) => return data,
+                // the replay is thrown away before this matters
+                ReplayAccess::Ahead => return 0,
+                ReplayAccess::Live => {}
+            }
+        }
+        let data = self.dma_read(address);
+        self.tick();
+        if let Some(ref mut replay) = self.replay {
+            replay.latch(data);
+        }
+        data
+    }
+
+    /// reads a little endian word from address and the byte after it
+    pub fn read_u16(&mut self, address: u16) -> u16 {
+        let lo = self.read(address);
+        let hi = self.read(address.wrapping_add(1));
+        u16::from_le_bytes([lo, hi])
+    }
+
+    /// reads a little endian pointer from the zero page. A pointer at $FF
+    /// takes its high byte from $00, as the 6502 adds within the page
+    pub fn read_u16_zp_wrapped(&mut self, zp: u8) -> u16 {
+        let lo = self.read(u16::from(zp));
+        let hi = self.read(u16::from(zp.wrapping_add(1)));
+        u16::from_le_bytes([lo, hi])
+    }
+
+    /// reads a little endian word without carrying into the high byte of
+    /// the address, so a word at $xxFF takes its high byte from $xx00. This
+    /// is the JMP ($xxFF) bug
+    /// https://www.pagetable.com/c64ref/6502/?tab=3
+    pub fn read_u16_page_wrapped(&mut self, address: u16) -> u16 {
+        let lo = self.read(address);
+        let hi = self.read((address & 0xff00) | (address.wrapping_add(1) & 0x00ff));
+        u16::from_le_bytes([lo, hi])
+    }
+
+    /// reads a byte like read without ticking the master clock, for DMA,
+    /// whose cycle dma_cycle ticks once the access is made
+    fn dma_read(&mut self, address: u16) -> u8 {
+        self.address_bus = address;
+        self.read_write = ReadWrite::Read;
+        let data = self.decode_read(address);
+        if address != 0x4015 {
+            self.data_bus = data;
+        }
+        self.observe(address, data, AccessKind::Read);
+        data
+    }
+
+    fn decode_read(&mut self, address: u16) -> u8 {
+        if let Some(device) = self.device_at(address) {
+            return device.read(address);
+        }
+        let index = usize::from(address);
+        if self.config == BusConfig::FlatRam {
+            return match self.timer {
+                Some(ref mut timer) if timer.contains(index) => timer.read(index),
+                _ => self.bytes[index],
+            };
+        }
+        if let Some(data) = self.cartridge.mapper.cpu_read(address) {
+            return data;
+        }
+        let address = mirror_address(address);
+        let index = usize::from(address);
+        match address {
+            0x2000..=0x2007 => self.ppu.read(address),
+            0x4015 => {
+                let status = self.apu.read(address) | (self.data_bus & OPEN_BUS_STATUS_BIT);
+                self.sync_apu_irq();
+                status
+            }
+            // the other APU registers and OAM DMA are write-only, reading
+            // them returns whatever was last on the data bus
+            0x4000..=0x4014 => self.data_bus,
+            0x4016 | 0x4017 => {
+                self.controllers.read(address) | (self.data_bus & OPEN_BUS_CONTROLLER_BITS)
+            }
+            // the 2A03's disabled test registers answer nothing, so the bus
+            // keeps the last value driven onto it. So does the expansion
+            // area, which the mapper has already answered if its board puts
+            // anything there
+            0x4018..=0x5fff => self.unmapped_read(),
+            0x6000..=0x7fff if self.cartridge.prg_ram_enabled => {
+                self.cartridge.prg_ram[index - 0x6000]
+            }
+            0x6000..=0x7fff => self.unmapped_read(),
+            _ => self.bytes[index],
+        }
+    }
+
+    /// read of an address nothing answers, which sees open bus unless
+    /// unmapped accesses are being fuzzed
+    fn unmapped_read(&mut self) -> u8 {
+        match self.unmapped_fuzz {
+            Some(ref mut fuzz) => fuzz.read(),
+            None => self.data_bus,
+        }
+    }
+
+    /// logs a write nothing answers if unmapped accesses are being fuzzed
+    fn unmapped_write(&mut self, address: u16, data: u8) {
+        if let Some(ref mut fuzz) = self.unmapped_fuzz {
+            fuzz.log_write(BusAccess {
+                cycle: self.cycle,
+                address,
+                data,
+                kind: AccessKind::Write,
+            });
+        }
+    }
+
+    /// makes reads of unmapped addresses return random bytes from seed
+    /// rather than open bus, and logs writes to them, to flush out code
+    /// that depends on unmapped memory. Replaces any earlier log
+    pub fn fuzz_unmapped(&mut self, seed: u64) {
+        self.unmapped_fuzz = Some(UnmappedFuzz::new(seed));
+    }
+
+    /// writes a byte to the CPU's address space, triggering any side effects
+    /// of writing a memory mapped register, then ticks the master clock
+    /// through the cycle
+    pub fn write(&mut self, address: u16, data: u8) {
+        if let Some(ref mut replay) = self.replay {
+            if replay.access() != ReplayAccess::Live {
+                return;
+            }
+            replay.latch(data);
+        }
+        self.decode_write(address, data);
+        if self.freezer.timing == FreezeTiming::EveryWrite {
+            self.apply_freezer();
+        }
+        self.tick();
+    }
+
+    fn decode_write(&mut self, address: u16, data: u8) {
+        self.address_bus = address;
+        self.read_write = ReadWrite::Write;
+        self.data_bus = data;
+        self.observe(address, data, AccessKind::Write);
+        if let Some(device) = self.device_at(address) {
+            device.write(address, data);
+            return;
+        }
+        let index = usize::from(address);
+        if self.config == BusConfig::FlatRam {
+            self.bytes[index] = data;
+            return;
+        }
+        if self.cartridge.mapper.cpu_write(address, data) {
+            return;
+        }
+        let address = mirror_address(address);
+        let index = usize::from(address);
+        if let 0x2000..=0x2007 | 0x4014 = address {
+            self.last_ppu_register_write = Some(PpuRegisterWrite {
+                address,
+                data,
+                second_write: self.ppu.w,
+                vram_address: self.ppu.ppu_addr(),
+            });
+        }
+        self.record_unimplemented_write(address);
+        match address {
+            0x2000..=0x2007 => {
+                if address == 0x2007 {
+                    let rendering = self.ppu.rendering_in_progress();
+                    self.region_detector.ppu_data_write(rendering);
                 }
-                // 0x4016 => todo!(),
-                // 0x4017 => todo!(),
-                _ => {
-                    // println!("LOADING: 0x{:0>2x}", self.bytes[index]);
-                    &self.bytes[index]
+                self.ppu.write(address, data);
+            }
+            0x4014 => {
+                self.ppu.oam_dma = data;
+                self.dma.start_oam(data);
+            }
+            0x4016 => self.controllers.write(address, data),
+            0x4000..=0x4013 | 0x4015 | 0x4017 => {
+                self.apu.write(address, data);
+                self.sync_apu_irq();
+            }
+            // the test registers and the expansion area, which the mapper
+            // has already taken the write for if its board has anything there
+            0x4018..=0x5fff => self.unmapped_write(address, data),
+            0x6000..=0x7fff => {
+                if self.cartridge.prg_ram_enabled {
+                    self.cartridge.prg_ram[index - 0x6000] = data;
+                } else {
+                    self.unmapped_write(address, data);
                 }
             }
-        } else {
-            &self.bytes[index]
+            _ => self.bytes[index] = data,
+        }
+    }
+
+    /// returns the byte read would from the CPU's address space without any
+    /// of its side effects: registers are not acknowledged, the data bus is
+    /// left alone, and neither the tracer nor watchpoints see the access.
+    /// For debugger memory views
+    pub fn peek(&self, address: u16) -> u8 {
+        if let Some(mapped) = self
+            .devices
+            .iter()
+            .find(|mapped| mapped.range.contains(&address))
+        {
+            return mapped.device.peek(address).unwrap_or(self.data_bus);
+        }
+        let index = usize::from(address);
+        if self.config == BusConfig::FlatRam {
+            return match self.timer {
+                Some(ref timer) if timer.contains(index) => timer.register(index),
+                _ => self.bytes[index],
+            };
+        }
+        if let Some(data) = self.cartridge.mapper.cpu_peek(address) {
+            return data;
+        }
+        let address = mirror_address(address);
+        let index = usize::from(address);
+        match address {
+            0x2000..=0x2007 => self.ppu.peek(address).unwrap_or(self.data_bus),
+            0x4015 => self.apu.status_peek() | (self.data_bus & OPEN_BUS_STATUS_BIT),
+            0x4016 | 0x4017 => {
+                self.controllers.peek(address).unwrap_or(0)
+                    | (self.data_bus & OPEN_BUS_CONTROLLER_BITS)
+            }
+            0x4000..=0x5fff => self.data_bus,
+            0x6000..=0x7fff if self.cartridge.prg_ram_enabled => {
+                self.cartridge.prg_ram[index - 0x6000]
+            }
+            0x6000..=0x7fff => self.data_bus,
+            _ => self.bytes[index],
         }
     }
-}
 
-impl IndexMut<usize> for Bus {
-    fn index_mut(&mut self, index: usize) -> &mut Self::Output {
-        // println!("Accessing 0x{index:x} in bus mutably");
-        if self.cpu_only_mode == false {
-            match index {
-                0x2000 => &mut self.ppu.ppu_ctrl,
-                0x2001 => &mut self.ppu.ppu_mask,
-                0x2003 => &mut self.ppu.oam_addr,
-                0x2004 => &mut self.ppu.oam_data,
-                0x2005 => &mut self.ppu.ppu_scroll,
-                0x2006 => {
-                    if self.ppu.ppu_addr_received_first_write == false {
-                        self.ppu.ppu_addr_received_first_write =
-                            !self.ppu.ppu_addr_received_first_write;
-                        &mut self.ppu.ppu_addr_high
-                    } else {
-                        self.ppu.ppu_addr_received_first_write =
-                            !self.ppu.ppu_addr_received_first_write;
-                        &mut self.ppu.ppu_addr_low
-                    }
+    /// stores a byte in the CPU's address space without any side effects of
+    /// writing it, see Device::poke for the PPU registers. The APU,
+    /// controller, and DMA registers have no storage to poke, so pokes to
+    /// them are ignored, as are those to the cartridge's expansion area
+    pub fn poke(&mut self, address: u16, data: u8) {
+        if let Some(device) = self.device_at(address) {
+            device.poke(address, data);
+            return;
+        }
+        let index = usize::from(address);
+        if self.config == BusConfig::FlatRam {
+            self.bytes[index] = data;
+            return;
+        }
+        let address = mirror_address(address);
+        let index = usize::from(address);
+        match address {
+            0x2000..=0x2007 => self.ppu.poke(address, data),
+            0x4000..=0x5fff => {}
+            0x6000..=0x7fff => {
+                if self.cartridge.prg_ram_enabled {
+                    self.cartridge.prg_ram[index - 0x6000] = data;
                 }
-                0x2007 => {
-                    // calculate full ppu_addr address
-                    let lo = self.ppu.ppu_addr_low;
-                    let hi = self.ppu.ppu_addr_high;
-                    let address = (u16::from(hi) << 8) + u16::from(lo);
-
-                    // increment address in ppu_addr register
-                    let increment = if self.ppu.ppu_ctrl & PPUCTRL::VRAM_INCR.bits()
-                        == PPUCTRL::VRAM_INCR.bits()
-                    {
-                        32
-                    } else {
-                        1
-                    };
-                    let new_address = address.wrapping_add(increment);
-                    self.ppu.ppu_addr_low = new_address as u8;
-                    self.ppu.ppu_addr_high = (new_address >> 8) as u8;
-
-                    // uncomment to print address in 0x2006 being written to
-                    // println!("--------------------- 0x2007, to 0x{:0>4x}", address);
-                    // let mut line = String::new();
-                    // let b1 = std::io::stdin().read_line(&mut line).unwrap();
-                    // println!("{:?}", &self.ppu.memory[0x2000..0x2400]);
-
-                    // return address from ppu_addr before it was incremented
-                    &mut self.ppu.address_space[usize::from(address)]
+            }
+            _ => self.bytes[index] = data,
+        }
+    }
+
+    /// hands an access to the tracer and watchpoints
+    fn observe(&mut self, address: u16, data: u8, kind: AccessKind) {
+        let access = BusAccess {
+            cycle: self.cycle,
+            address,
+            data,
+            kind,
+        };
+        self.accesses.record(access);
+        self.watchpoints.check(access);
+        if let Some(TraceCallback(ref mut callback)) = self.transaction_callback {
+            callback(&access);
+        }
+    }
+
+    /// notes writes that depend on hardware the emulator only stubs out
+    fn record_unimplemented_write(&mut self, address: u16) {
+        let feature = match address {
+            0x4000..=0x400f | 0x4015 => Unimplemented::APU,
+            0x8000..=0xffff => Unimplemented::MAPPER_REGISTERS,
+            _ => return,
+        };
+        self.unimplemented |= feature;
+    }
+
+    /// runs one cycle with the CPU halted for DMA, making the access the
+    /// DMA unit is due to make, see DmaController::clock
+    pub fn dma_cycle(&mut self) {
+        match self.dma.clock(self.cycle) {
+            DmaCycle::Idle => {}
+            DmaCycle::DmcRead(address) => {
+                let byte = self.dma_read(address);
+                self.apu.dmc.fill_sample_buffer(byte);
+            }
+            DmaCycle::OamRead(address) => {
+                let byte = self.dma_read(address);
+                self.dma.latch_oam_byte(byte);
+            }
+            DmaCycle::OamWrite(byte) => self.ppu.oam_data_write(byte),
+        }
+        self.tick();
+    }
+
+    /// runs DMA cycles until the DMA unit lets go of the bus, returns the
+    /// cycles the CPU was halted for
+    pub fn run_dma(&mut self) -> u64 {
+        let start = self.cycle;
+        while self.dma.busy() {
+            self.dma_cycle();
+        }
+        self.cycle - start
+    }
+
+    /// advances the master clock by one CPU cycle, clocking the APU, mapped
+    /// devices, and the PPU through it. The PPU runs 3 dots per cycle, or
+    /// 3.2 on PAL. Nothing but the clock advances in BusConfig::FlatRam
+    pub fn tick(&mut self) {
+        self.cycle += 1;
+        self.tick_devices();
+        if self.config == BusConfig::FlatRam {
+            return;
+        }
+        self.clock_apu();
+        let (dots, cycles) = self.ppu.region.ppu_dots_per_cpu_cycle();
+        let owed = dots + self.ppu_dot_remainder;
+        self.ppu_dot_remainder = owed % cycles;
+        for _ in 0..owed / cycles {
+            self.ppu.tick();
+            if self.ppu.a12_rose {
+                if let Some(A12Callback(ref mut callback)) = self.a12_callback {
+                    callback(&self.ppu);
                 }
-                // 0x4014 => todo!(),
-                // 0x4016 => todo!(),
-                _ => {
-                    // println!("WRITING TO: 0x{:0>4x}", index);
-                    &mut self.bytes[index]
+            }
+            if let Some(ref mut hook) = self.scanline_callback {
+                if self.ppu.dot == hook.dot {
+                    (hook.callback)(self.ppu.scanline as u16, &self.ppu);
                 }
             }
-        } else {
-            &mut self.bytes[index]
         }
     }
-}
 
-impl Bus {
-    /// low is write, high is read
+    /// advances the APU by one CPU cycle, passing on the DMC's request for
+    /// a sample byte to the DMA unit and updating the APU's hold on the
+    /// /IRQ line
+    pub fn clock_apu(&mut self) {
+        self.apu.clock();
+        self.dma.dmc_address = self.apu.dmc.dma_address();
+        self.sync_apu_irq();
+    }
+
+    /// updates the APU's hold on the /IRQ line from its frame and DMC
+    /// flags, which register accesses can acknowledge as well as clocks set
+    fn sync_apu_irq(&mut self) {
+        self.irq_sources
+            .set(IrqSource::APU_FRAME, self.apu.frame_counter.interrupt);
+        self.irq_sources.set(IrqSource::DMC, self.apu.dmc.interrupt);
+    }
+
+    /// runs one access from the pins: a read of address_bus onto data_bus,
+    /// or a write of data_bus to address_bus. The same as read and write,
+    /// which set the pins the same way, so either can drive the bus
     pub fn execute(&mut self, readwrite: ReadWrite) {
         match readwrite {
             ReadWrite::Read => {
-                let address = self.address_bus;
-                self.data_bus = self[usize::from(address)];
+                self.data_bus = self.read(self.address_bus);
             }
             ReadWrite::Write => {
-                let address = self.address_bus;
-                let data = self.data_bus;
-                self[usize::from(address)] = data;
+                self.write(self.address_bus, self.data_bus);
             }
         }
     }
@@ -0,0 +1,130 @@
+use std::fmt;
+use std::io::{self, Write};
+use std::ops::RangeInclusive;
+
+/// accesses the bus buffers between steps, enough for an instruction
+/// interrupted by OAM DMA and DMC fetches
+const ACCESS_BUFFER_SIZE: usize = 1024;
+
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq)]
+pub enum AccessKind {
+    #[default]
+    Read,
+    Write,
+}
+
+/// A single CPU bus transaction
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq)]
+pub struct BusAccess {
+    /// master clock cycle the access was made on
+    pub cycle: u64,
+    pub address: u16,
+    pub data: u8,
+    pub kind: AccessKind,
+}
+
+impl fmt::Display for BusAccess {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let kind = match self.kind {
+            AccessKind::Read => 'R',
+            AccessKind::Write => 'W',
+        };
+        write!(
+            f,
+            "{:>10} {kind} ${:04X} = ${:02X}",
+            self.cycle, self.address, self.data
+        )
+    }
+}
+
+/// Accesses recorded by the Bus while tracing is enabled, until the NES
+/// hands them to its BusTracer at the end of each step
+#[derive(Clone, Debug, Default)]
+pub struct AccessBuffer {
+    pub enabled: bool,
+    entries: Vec<BusAccess>,
+    /// accesses lost because the buffer was full
+    pub dropped: u64,
+}
+
+impl AccessBuffer {
+    pub fn record(&mut self, access: BusAccess) {
+        if !self.enabled {
+            return;
+        }
+        if self.entries.len() == ACCESS_BUFFER_SIZE {
+            self.dropped += 1;
+            return;
+        }
+        self.entries.push(access);
+    }
+
+    /// returns the accesses recorded since the last clear
+    pub fn entries(&self) -> &[BusAccess] {
+        &self.entries
+    }
+
+    pub fn clear(&mut self) {
+        self.entries.clear();
+    }
+}
+
+/// Called with each traced access instead of logging it
+pub struct TraceCallback(pub Box<dyn FnMut(&BusAccess)>);
+
+impl fmt::Debug for TraceCallback {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str("TraceCallback")
+    }
+}
+
+/// Log of the CPU bus accesses within a set of address ranges, such as
+/// $2000-$3FFF to watch a game talk to the PPU. Accesses are kept in log
+/// for dumping later, or streamed to a callback as they happen
+#[derive(Debug, Default)]
+pub struct BusTracer {
+    /// addresses traced, every address if empty
+    pub ranges: Vec<RangeInclusive<u16>>,
+    pub log: Vec<BusAccess>,
+    stream: Option<TraceCallback>,
+}
+
+impl BusTracer {
+    /// returns a tracer logging accesses to ranges, or to every address if
+    /// there are none
+    pub fn new(ranges: Vec<RangeInclusive<u16>>) -> BusTracer {
+        BusTracer {
+            ranges,
+            ..Default::default()
+        }
+    }
+
+    /// streams traced accesses to callback rather than logging them
+    pub fn set_stream<F: FnMut(&BusAccess) + 'static>(&mut self, callback: F) {
+        self.stream = Some(TraceCallback(Box::new(callback)));
+    }
+
+    /// returns whether or not accesses to address are traced
+    pub fn traces(&self, address: u16) -> bool {
+        self.ranges.is_empty() || self.ranges.iter().any(|range| range.contains(&address))
+    }
+
+    /// logs or streams access if it falls within the traced ranges
+    pub fn record(&mut self, access: &BusAccess) {
+        if !self.traces(access.address) {
+            return;
+        }
+        match self.stream {
+            Some(TraceCallback(ref mut callback)) => callback(access),
+            None => self.log.push(*access),
+        }
+    }
+
+    /// writes the log, one `cycle R/W $address = $data` line per access
+    pub fn dump<W: Write>(&self, mut writer: W) -> io::Result<()> {
+        for access in &self.log {
+            writeln!(writer, "{access}")?;
+        }
+        writer.flush()
+    }
+}
@@ -0,0 +1,59 @@
+use std::fs::File;
+use std::io::{self, prelude::*};
+
+use crate::nes::mapper::{Mapper, Nrom};
+
+/// bytes of work RAM mapped at $6000-$7FFF
+pub const PRG_RAM_SIZE: usize = 0x2000;
+
+/// iNES flags 6 bit set when PRG RAM is battery-backed
+const INES_BATTERY: u8 = 0b0000_0010;
+
+/// The parts of the board plugged into the console that the CPU sees besides
+/// its program ROM
+#[derive(Debug)]
+pub struct Cartridge {
+    /// work RAM at $6000-$7FFF
+    pub prg_ram: Box<[u8]>,
+    /// cleared for boards without PRG RAM, leaving $6000-$7FFF open bus
+    pub prg_ram_enabled: bool,
+    /// set when a battery keeps PRG RAM's contents with the console off, so
+    /// it holds save data worth writing to disk
+    pub battery: bool,
+    /// the board's logic, which sees every CPU access first
+    pub mapper: Box<dyn Mapper>,
+}
+
+impl Default for Cartridge {
+    fn default() -> Cartridge {
+        Cartridge {
+            prg_ram: vec![0; PRG_RAM_SIZE].into_boxed_slice(),
+            prg_ram_enabled: true,
+            battery: false,
+            mapper: Box::new(Nrom),
+        }
+    }
+}
+
+impl Cartridge {
+    pub fn from_ines_header(header: &[u8; 16]) -> Cartridge {
+        Cartridge {
+            battery: header[6] & INES_BATTERY != 0,
+            ..Default::default()
+        }
+    }
+
+    /// fills PRG RAM from a save file, which may be shorter than PRG RAM
+    pub fn load_prg_ram(&mut self, filename: &str) -> io::Result<()> {
+        let mut bytes = Vec::new();
+        File::open(filename)?.read_to_end(&mut bytes)?;
+        let length = bytes.len().min(PRG_RAM_SIZE);
+        self.prg_ram[..length].copy_from_slice(&bytes[..length]);
+        Ok(())
+    }
+
+    /// writes PRG RAM to a save file
+    pub fn save_prg_ram(&self, filename: &str) -> io::Result<()> {
+        File::create(filename)?.write_all(&self.prg_ram)
+    }
+}
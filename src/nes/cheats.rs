@@ -0,0 +1,115 @@
+use crate::nes::freeze::{FreezeTiming, MemoryFreezer};
+
+/// A value written to a CPU address once per frame, holding it there
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub struct Cheat {
+    pub address: u16,
+    pub value: u8,
+    /// disabled cheats are kept but not applied
+    pub enabled: bool,
+}
+
+/// parses a Pro Action Replay style RAM code: four hex digits of address
+/// then two of value, as `AAAAVV` or `AAAA:VV`. The address has to be
+/// internal RAM or its mirrors, $0000-$1FFF, or PRG RAM, $6000-$7FFF
+pub fn parse_par_code(code: &str) -> Result<(u16, u8), String> {
+    let digits: String = code.chars().filter(|&c| c != ':').collect();
+    if digits.len() != 6 || !digits.chars().all(|c| c.is_ascii_hexdigit()) {
+        return Err(format!("{code} is not a code of the form AAAAVV"));
+    }
+    let address = u16::from_str_radix(&digits[..4], 16).unwrap();
+    let value = u8::from_str_radix(&digits[4..], 16).unwrap();
+    if !matches!(address, 0x0000..=0x1fff | 0x6000..=0x7fff) {
+        return Err(format!(
+            "{code} targets ${address:04X}, RAM codes only reach $0000-$1FFF and $6000-$7FFF"
+        ));
+    }
+    Ok((address, value))
+}
+
+/// Cheats the Bus applies at the end of every frame. Each keeps the index
+/// it was added at until it is removed. The enabled ones are held in a
+/// MemoryFreezer, which does the writing
+#[derive(Clone, Debug)]
+pub struct Cheats {
+    slots: Vec<Option<Cheat>>,
+    freezer: MemoryFreezer,
+}
+
+impl Default for Cheats {
+    fn default() -> Cheats {
+        let mut freezer = MemoryFreezer::default();
+        freezer.timing = FreezeTiming::EveryFrame;
+        Cheats {
+            slots: Default::default(),
+            freezer,
+        }
+    }
+}
+
+impl Cheats {
+    /// freezes address to value, returning the cheat's index
+    pub fn add(&mut self, address: u16, value: u8) -> usize {
+        let cheat = Cheat {
+            address,
+            value,
+            enabled: true,
+        };
+        let index = match self.slots.iter().position(Option::is_none) {
+            Some(index) => {
+                self.slots[index] = Some(cheat);
+                index
+            }
+            None => {
+                self.slots.push(Some(cheat));
+                self.slots.len() - 1
+            }
+        };
+        self.refreeze();
+        index
+    }
+
+    /// adds a Pro Action Replay style code, see parse_par_code
+    pub fn add_code(&mut self, code: &str) -> Result<usize, String> {
+        let (address, value) = parse_par_code(code)?;
+        Ok(self.add(address, value))
+    }
+
+    pub fn remove(&mut self, index: usize) {
+        if let Some(slot) = self.slots.get_mut(index) {
+            *slot = None;
+        }
+        self.refreeze();
+    }
+
+    /// turns a cheat on or off without forgetting it
+    pub fn set_enabled(&mut self, index: usize, enabled: bool) {
+        if let Some(Some(cheat)) = self.slots.get_mut(index) {
+            cheat.enabled = enabled;
+        }
+        self.refreeze();
+    }
+
+    pub fn get(&self, index: usize) -> Option<Cheat> {
+        self.slots.get(index).copied().flatten()
+    }
+
+    pub fn clear(&mut self) {
+        self.slots.clear();
+        self.refreeze();
+    }
+
+    /// returns the freezer holding every enabled cheat
+    pub fn freezer(&self) -> &MemoryFreezer {
+        &self.freezer
+    }
+
+    /// rebuilds the freezer from the enabled cheats, later slots winning
+    /// where two share an address
+    fn refreeze(&mut self) {
+        self.freezer.clear();
+        for cheat in self.slots.iter().flatten().filter(|cheat| cheat.enabled) {
+            self.freezer.freeze(cheat.address, cheat.value);
+        }
+    }
+}
@@ -0,0 +1,143 @@
+use crate::nes::bus::RAM_SIZE;
+use crate::nes::controller::Buttons;
+use crate::nes::NES;
+
+const FNV_OFFSET_BASIS: u64 = 0xcbf2_9ce4_8422_2325;
+const FNV_PRIME: u64 = 0x0000_0100_0000_01b3;
+
+/// Checksum of the emulated state after a frame
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub struct FrameChecksum {
+    pub frame: u64,
+    pub checksum: u64,
+}
+
+/// Two runs that should have matched diverged at or before frame
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub struct Desync {
+    pub frame: u64,
+    pub expected: u64,
+    pub actual: u64,
+}
+
+/// Buttons held on both controllers from frame on, until the next change
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub struct FrameInput {
+    pub frame: u64,
+    pub buttons: [Buttons; 2],
+}
+
+/// Why a replay stopped matching its recording
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum ReplayError {
+    /// the states diverged at or before the frame
+    Desync(Desync),
+    /// a checksum was for frame, which the replay had already run past
+    FrameAlreadyRun { frame: u64, current: u64 },
+}
+
+/// Records a state checksum every interval frames, along with the
+/// controller input that led to it, for movies and netplay peers to
+/// compare against
+#[derive(Clone, Debug)]
+pub struct ChecksumChannel {
+    pub interval: u64,
+    pub checksums: Vec<FrameChecksum>,
+    /// each change in the buttons held, so a replay can feed them back
+    pub inputs: Vec<FrameInput>,
+}
+
+impl ChecksumChannel {
+    pub fn new(interval: u64) -> ChecksumChannel {
+        ChecksumChannel {
+            interval: interval.max(1),
+            checksums: Vec::new(),
+            inputs: Vec::new(),
+        }
+    }
+
+    /// keeps the checksum if frame falls on the interval, and the buttons
+    /// held during frame if they changed
+    pub fn record(&mut self, frame: u64, checksum: u64, buttons: [Buttons; 2]) {
+        if self.inputs.last().map(|input| input.buttons) != Some(buttons) {
+            self.inputs.push(FrameInput { frame, buttons });
+        }
+        if frame.is_multiple_of(self.interval) {
+            self.checksums.push(FrameChecksum { frame, checksum });
+        }
+    }
+}
+
+/// FNV-1a hash of the CPU registers, internal RAM, and PPU registers.
+/// Cheap enough to compute every frame, and deterministic across hosts
+pub fn state_checksum(nes: &NES) -> u64 {
+    let cpu = &nes.cpu;
+    let ppu = &nes.address_space.ppu;
+    let [pc_hi, pc_lo] = cpu.pc.to_be_bytes();
+    let [v_hi, v_lo] = ppu.v.to_be_bytes();
+    let [t_hi, t_lo] = ppu.t.to_be_bytes();
+    let registers = [
+        cpu.a,
+        cpu.x,
+        cpu.y,
+        cpu.sp,
+        pc_hi,
+        pc_lo,
+        cpu.p.serialize(),
+        ppu.ppu_ctrl,
+        ppu.ppu_mask,
+        ppu.ppu_status,
+        ppu.oam_addr,
+        v_hi,
+        v_lo,
+        t_hi,
+        t_lo,
+        ppu.fine_x,
+        u8::from(ppu.w),
+        ppu.ppu_data_buffer,
+    ];
+
+    registers
+        .iter()
+        .chain(&nes.address_space.bytes[..RAM_SIZE])
+        .fold(FNV_OFFSET_BASIS, |hash, &byte| {
+            (hash ^ u64::from(byte)).wrapping_mul(FNV_PRIME)
+        })
+}
+
+/// runs nes forward from where the recording started, holding the
+/// buttons it recorded and comparing its state against each recorded
+/// checksum. Returns the first frame where they differ, or an error if nes
+/// has already run past a recorded frame
+pub fn verify_checksums(nes: &mut NES, recording: &ChecksumChannel) -> Result<(), ReplayError> {
+    let mut inputs = recording.inputs.iter().peekable();
+    for expected in &recording.checksums {
+        let current = nes.media_clock.frames;
+        if current > expected.frame {
+            return Err(ReplayError::FrameAlreadyRun {
+                frame: expected.frame,
+                current,
+            });
+        }
+        while nes.media_clock.frames < expected.frame {
+            let frame = nes.media_clock.frames + 1;
+            while let Some(input) = inputs.next_if(|input| input.frame <= frame) {
+                for (controller, buttons) in
+                    nes.address_space.controllers.iter_mut().zip(input.buttons)
+                {
+                    controller.buttons = buttons;
+                }
+            }
+            nes.run_frame(false);
+        }
+        let actual = state_checksum(nes);
+        if actual != expected.checksum {
+            return Err(ReplayError::Desync(Desync {
+                frame: expected.frame,
+                expected: expected.checksum,
+                actual,
+            }));
+        }
+    }
+    Ok(())
+}
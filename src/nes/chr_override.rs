@@ -0,0 +1,49 @@
+use std::fs;
+use std::io;
+use std::time::SystemTime;
+
+use crate::nes::ppu::PPU;
+use crate::nes::ppu_bus::CHR_SIZE;
+
+/// Replaces pattern table data with tiles from a raw CHR file, the 16 bytes
+/// per tile layout exported by tile editors. The file is re-read whenever it
+/// changes so artists can iterate against a running game.
+#[derive(Clone, Debug)]
+pub struct ChrOverride {
+    pub filename: String,
+    modified: Option<SystemTime>,
+}
+
+impl ChrOverride {
+    pub fn new(filename: &str) -> ChrOverride {
+        ChrOverride {
+            filename: String::from(filename),
+            modified: None,
+        }
+    }
+
+    /// copies the file's tiles over the pattern tables if the file changed
+    /// since it was last applied, returns whether or not it was applied
+    pub fn reload_if_changed(&mut self, ppu: &mut PPU) -> io::Result<bool> {
+        let modified = fs::metadata(&self.filename)?.modified()?;
+        if self.modified == Some(modified) {
+            return Ok(false);
+        }
+        // a rejected file is not retried until it changes again
+        self.modified = Some(modified);
+
+        let tiles = fs::read(&self.filename)?;
+        if tiles.len() > CHR_SIZE || tiles.len() % 16 != 0 {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidData,
+                format!(
+                    "{} is {} bytes, expected whole 16 byte tiles up to {CHR_SIZE}",
+                    self.filename,
+                    tiles.len()
+                ),
+            ));
+        }
+        ppu.address_space.chr.bytes[..tiles.len()].copy_from_slice(&tiles);
+        Ok(true)
+    }
+}
@@ -0,0 +1,79 @@
+use bitflags::bitflags;
+
+use crate::nes::device::Device;
+
+bitflags! {
+    /// Buttons of a standard controller, in the order its shift register
+    /// reports them
+    #[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Hash)]
+    pub struct Buttons: u8 {
+        const A      = 0b0000_0001;
+        const B      = 0b0000_0010;
+        const SELECT = 0b0000_0100;
+        const START  = 0b0000_1000;
+        const UP     = 0b0001_0000;
+        const DOWN   = 0b0010_0000;
+        const LEFT   = 0b0100_0000;
+        const RIGHT  = 0b1000_0000;
+    }
+}
+
+/// Standard controller plugged into $4016 or $4017. Writing 1 then 0 to bit
+/// 0 of $4016 latches the buttons held into a shift register, which reads
+/// then return one at a time
+#[derive(Copy, Clone, Debug, Default)]
+pub struct Controller {
+    /// buttons currently held, set by the frontend
+    pub buttons: Buttons,
+    /// while set the shift register keeps reloading, so reads only ever
+    /// return A
+    pub strobe: bool,
+    pub shift_register: u8,
+}
+
+impl Controller {
+    /// $4016 write, bit 0 of which drives the strobe line. The buttons held
+    /// as it falls are the ones shifted out
+    pub fn strobe_write(&mut self, data: u8) {
+        if self.strobe || data & 1 != 0 {
+            self.shift_register = self.buttons.bits();
+        }
+        self.strobe = data & 1 != 0;
+    }
+
+    /// returns the next button in bit 0. Once all 8 have been read, an
+    /// official controller returns 1s
+    pub fn read(&mut self) -> u8 {
+        if self.strobe {
+            return self.buttons.bits() & 1;
+        }
+        let bit = self.peek();
+        self.shift_register = (self.shift_register >> 1) | 0x80;
+        bit
+    }
+
+    /// returns the button the next read will without shifting it out
+    pub fn peek(&self) -> u8 {
+        if self.strobe {
+            return self.buttons.bits() & 1;
+        }
+        self.shift_register & 1
+    }
+}
+
+/// Both controller ports, $4016 and $4017. Writes to $4016 strobe both
+impl Device for [Controller; 2] {
+    fn read(&mut self, address: u16) -> u8 {
+        self[usize::from(address & 1)].read()
+    }
+
+    fn write(&mut self, _address: u16, data: u8) {
+        for controller in self {
+            controller.strobe_write(data);
+        }
+    }
+
+    fn peek(&self, address: u16) -> Option<u8> {
+        Some(self[usize::from(address & 1)].peek())
+    }
+}
@@ -1,5 +1,6 @@
 use crate::nes::bus::Bus;
 use crate::nes::cpu_structs::{AddressingMode, Instruction};
+use crate::nes::micro_op::InFlight;
 
 /// Type for storing CPU registers as fields
 #[derive(Copy, Clone, Default, Debug)]
@@ -17,7 +18,9 @@ pub struct CPU {
     /// status register
     pub p: StatusRegister,
     pub clock: u64,
-    pub time_since_last_frame: u64,
+    /// the instruction partway through its cycles while cycle stepping,
+    /// None on instruction boundaries
+    pub in_flight: Option<InFlight>,
 }
 
 impl CPU {
@@ -41,131 +44,73 @@ impl CPU {
     }
 
     /// loads instruction at address of pc, increments pc
-    pub fn fetch_instruction(&mut self, memory: &Bus) -> u8 {
+    pub fn fetch_instruction(&mut self, memory: &mut Bus) -> u8 {
         let index = self.pc;
         self.step_pc();
-        memory[index as usize]
+        memory.read(index)
     }
 
     /// returns the address and whether or not a page was crossed
-    pub fn resolve_address_fetch(&mut self, am: AddressingMode, memory: &Bus) -> (u16, bool) {
-        let output = {
-            match am {
-                AddressingMode::Absolute => {
-                    let lo = self.fetch_instruction(memory);
-                    let hi = self.fetch_instruction(memory);
-                    let address = (u16::from(hi) << 8) + u16::from(lo);
-                    (address, false)
-                }
-                AddressingMode::AbsoluteX => {
-                    let lo = self.fetch_instruction(memory);
-                    let hi = self.fetch_instruction(memory);
-                    let address = (u16::from(hi) << 8) + u16::from(lo);
-                    let address_plus_x = address.wrapping_add(u16::from(self.x));
-                    // bitmask the high 8 bits and compare. If they are different,
-                    // then a page boundary has been crossed
-                    let boundary_crossed = (address & 0xff00) != (address_plus_x & 0xff00);
-                    (address_plus_x, boundary_crossed)
-                }
-                AddressingMode::AbsoluteY => {
-                    let lo = self.fetch_instruction(memory);
-                    let hi = self.fetch_instruction(memory);
-                    let address = (u16::from(hi) << 8) + u16::from(lo);
-                    let address_plus_y = address.wrapping_add(u16::from(self.y));
-                    // bitmask the high 8 bits and compare. If they are different,
-                    // then a page boundary has been crossed
-                    let boundary_crossed = (address & 0xff00) != (address_plus_y & 0xff00);
-                    (address_plus_y, boundary_crossed)
-                }
-                AddressingMode::Indirect => {
-                    let lo = self.fetch_instruction(memory);
-                    let hi = self.fetch_instruction(memory);
-                    let address = (u16::from(hi) << 8) + u16::from(lo);
-
-                    let lo = memory[usize::from(address)];
-                    // The indirect jump instruction does not increment the page
-                    // address when the indirect pointer crosses a page boundary.
-                    // JMP ($xxFF) will fetch the address from $xxFF and $xx00.
-                    // https://www.pagetable.com/c64ref/6502/?tab=3
-                    let address = if address & 0x00ff == 0x00ff {
-                        address & 0xff00
-                    } else {
-                        address + 1
-                    };
-                    let hi = memory[usize::from(address)];
-                    let address = (u16::from(hi) << 8) + u16::from(lo);
-                    (address, false)
-                }
-                AddressingMode::IndirectX => {
-                    let zpg = self.fetch_instruction(memory);
-                    let lo = zpg.wrapping_add(self.x);
-                    let hi: u8 = 0x00;
-                    let address = (u16::from(hi) << 8) + u16::from(lo);
-
-                    let lo = memory[usize::from(address)];
-                    // IndirectX wraps around the zeropage
-                    let hi = memory[usize::from(address + 1) % 256];
-                    let address = (u16::from(hi) << 8) + u16::from(lo);
-                    (address, false)
-                }
-                AddressingMode::IndirectY => {
-                    let lo = self.fetch_instruction(memory);
-                    let hi: u8 = 0x00;
-                    let address = (u16::from(hi) << 8) + u16::from(lo);
-
-                    let lo = memory[usize::from(address)];
-                    let hi = memory[usize::from(address.wrapping_add(1))];
-                    let address = (u16::from(hi) << 8) + u16::from(lo);
-                    let address_plus_y = address.wrapping_add(u16::from(self.y));
-                    // bitmask the high 8 bits and compare. If they are different,
-                    // then a page boundary has been crossed
-                    let boundary_crossed = (address & 0xff00) != (address_plus_y & 0xff00);
-                    (address_plus_y, boundary_crossed)
-                }
-                AddressingMode::ZeroPage => {
-                    let lo = self.fetch_instruction(memory);
-                    let hi: u8 = 0x00;
-                    let address = (u16::from(hi) << 8) + u16::from(lo);
-                    (address, false)
-                }
-                AddressingMode::ZeroPageX => {
-                    let zpg = self.fetch_instruction(memory);
-                    let lo = zpg.wrapping_add(self.x);
-                    let hi: u8 = 0x00;
-                    let address = (u16::from(hi) << 8) + u16::from(lo);
-                    (address, false)
-                }
-                AddressingMode::ZeroPageY => {
-                    let zpg = self.fetch_instruction(memory);
-                    let lo = zpg.wrapping_add(self.y);
-                    let hi: u8 = 0x00;
-                    let address = (u16::from(hi) << 8) + u16::from(lo);
-                    (address, false)
-                }
-                AddressingMode::Accumulator
-                | AddressingMode::Implied
-                | AddressingMode::Immediate
-                | AddressingMode::Relative => {
-                    panic!("Attempted to fetch an AddressingMode that is intended to be handled on a per instruction basis")
-                }
+    pub fn resolve_address_fetch(&mut self, am: AddressingMode, memory: &mut Bus) -> (u16, bool) {
+        match am {
+            AddressingMode::Absolute => (self.fetch_address(memory), false),
+            AddressingMode::AbsoluteX => {
+                let address = self.fetch_address(memory);
+                let address_plus_x = address.wrapping_add(u16::from(self.x));
+                // bitmask the high 8 bits and compare. If they are different,
+                // then a page boundary has been crossed
+                let boundary_crossed = (address & 0xff00) != (address_plus_x & 0xff00);
+                (address_plus_x, boundary_crossed)
             }
-        };
-        // uncomment to pause when accessing certain memory mapped registers
-        // if  (   output.0 == 0x2000
-        //     || output.0 == 0x2001
-        //     || output.0 == 0x2002
-        //     || output.0 == 0x2003
-        //     || output.0 == 0x2004
-        //     || output.0 == 0x2005
-        //     || output.0 == 0x2006
-        //     || output.0 == 0x2007
-        //     || output.0 == 0x4014)
-        //     && self.clock > 70_000 {
-        //     println!("--------------------- HIT PPU REGISTER = 0x{:x}", output.0);
-        //     let mut line = String::new();
-        //     let b1 = std::io::stdin().read_line(&mut line).unwrap();
-        // }
-        output
+            AddressingMode::AbsoluteY => {
+                let address = self.fetch_address(memory);
+                let address_plus_y = address.wrapping_add(u16::from(self.y));
+                // bitmask the high 8 bits and compare. If they are different,
+                // then a page boundary has been crossed
+                let boundary_crossed = (address & 0xff00) != (address_plus_y & 0xff00);
+                (address_plus_y, boundary_crossed)
+            }
+            AddressingMode::Indirect => {
+                let pointer = self.fetch_address(memory);
+                (memory.read_u16_page_wrapped(pointer), false)
+            }
+            AddressingMode::IndirectX => {
+                let zpg = self.fetch_instruction(memory);
+                (memory.read_u16_zp_wrapped(zpg.wrapping_add(self.x)), false)
+            }
+            AddressingMode::IndirectY => {
+                let zpg = self.fetch_instruction(memory);
+                let address = memory.read_u16_zp_wrapped(zpg);
+                let address_plus_y = address.wrapping_add(u16::from(self.y));
+                // bitmask the high 8 bits and compare. If they are different,
+                // then a page boundary has been crossed
+                let boundary_crossed = (address & 0xff00) != (address_plus_y & 0xff00);
+                (address_plus_y, boundary_crossed)
+            }
+            AddressingMode::ZeroPage => (u16::from(self.fetch_instruction(memory)), false),
+            AddressingMode::ZeroPageX => {
+                let zpg = self.fetch_instruction(memory);
+                (u16::from(zpg.wrapping_add(self.x)), false)
+            }
+            AddressingMode::ZeroPageY => {
+                let zpg = self.fetch_instruction(memory);
+                (u16::from(zpg.wrapping_add(self.y)), false)
+            }
+            AddressingMode::Accumulator
+            | AddressingMode::Implied
+            | AddressingMode::Immediate
+            | AddressingMode::Relative => {
+                panic!("Attempted to fetch an AddressingMode that is intended to be handled on a per instruction basis")
+            }
+        }
+    }
+
+    /// loads the little endian address following the opcode, advancing pc
+    /// past it
+    fn fetch_address(&mut self, memory: &mut Bus) -> u16 {
+        let lo = self.fetch_instruction(memory);
+        let hi = self.fetch_instruction(memory);
+        u16::from_le_bytes([lo, hi])
     }
 
     fn set_status_nz(&mut self, test_val: u8) {
@@ -193,7 +138,7 @@ impl CPU {
     }
 
     /// returns whether or not a page was crossed
-    fn branch_if(&mut self, condition: bool, memory: &Bus) -> bool {
+    fn branch_if(&mut self, condition: bool, memory: &mut Bus) -> bool {
         let offset = self.fetch_instruction(memory);
         let offset: i16 = i16::from(offset as i8);
         let mut negative = false;
@@ -217,14 +162,14 @@ impl CPU {
 
     fn push_stack(&mut self, byte: u8, memory: &mut Bus) {
         let address = (u16::from(0x01_u8) << 8) + u16::from(self.sp);
-        memory[usize::from(address)] = byte;
+        memory.write(address, byte);
         self.sp = self.sp.wrapping_sub(1);
     }
 
-    fn pop_stack(&mut self, memory: &Bus) -> u8 {
+    fn pop_stack(&mut self, memory: &mut Bus) -> u8 {
         self.sp = self.sp.wrapping_add(1);
         let address = (u16::from(0x01_u8) << 8) + u16::from(self.sp);
-        memory[usize::from(address)]
+        memory.read(address)
     }
 
     pub fn execute_instruction(
@@ -244,14 +189,14 @@ impl CPU {
                 | AddressingMode::ZeroPage
                 | AddressingMode::ZeroPageX => {
                     let (address, boundary_crossed) = self.resolve_address_fetch(am, memory);
-                    let addend = memory[usize::from(address)];
+                    let addend = memory.read(address);
                     self.adc_logic(addend);
                     if boundary_crossed == true {
                         num_ticks += 1;
                     }
                 }
                 AddressingMode::Immediate => {
-                    let immediate = self.fetch_instruction(&memory);
+                    let immediate = self.fetch_instruction(memory);
                     self.adc_logic(immediate);
                 }
                 _ => {
@@ -268,7 +213,7 @@ impl CPU {
                     | AddressingMode::ZeroPage
                     | AddressingMode::ZeroPageX => {
                         let (address, boundary_crossed) = self.resolve_address_fetch(am, memory);
-                        let value = memory[usize::from(address)];
+                        let value = memory.read(address);
                         self.a = self.a & value;
                         if boundary_crossed == true {
                             num_ticks += 1;
@@ -292,10 +237,10 @@ impl CPU {
                     | AddressingMode::ZeroPage
                     | AddressingMode::ZeroPageX => {
                         let (address, boundary_crossed) = self.resolve_address_fetch(am, memory);
-                        let value = memory[usize::from(address)];
+                        let value = memory.read(address);
                         self.p.c = if value & 0x80 == 0x80 { true } else { false };
                         shift_result = self.a << 1;
-                        memory[usize::from(address)] = shift_result;
+                        memory.write(address, shift_result);
                         if boundary_crossed == true {
                             num_ticks += 1;
                         }
@@ -357,7 +302,7 @@ impl CPU {
                 match am {
                     AddressingMode::Absolute | AddressingMode::ZeroPage => {
                         let (address, boundary_crossed) = self.resolve_address_fetch(am, memory);
-                        let value = memory[usize::from(address)];
+                        let value = memory.read(address);
                         let result = self.a & value;
                         // v register <- bit 6 of value
                         self.p.v = if value & 0x40 == 0x40 { true } else { false };
@@ -435,9 +380,7 @@ impl CPU {
                     self.push_stack(p, memory);
 
                     // fetch address of interrupt handler
-                    let lo = memory[0xfffe];
-                    let hi = memory[0xffff];
-                    let address = (u16::from(hi) << 8) + u16::from(lo);
+                    let address = memory.read_u16(0xfffe);
                     self.pc = address;
 
                     // set interrupt disable flag
@@ -513,7 +456,7 @@ impl CPU {
                     | AddressingMode::ZeroPage
                     | AddressingMode::ZeroPageX => {
                         let (address, boundary_crossed) = self.resolve_address_fetch(am, memory);
-                        test_val = memory[usize::from(address)];
+                        test_val = memory.read(address);
                         if boundary_crossed == true {
                             num_ticks += 1;
                         }
@@ -534,7 +477,7 @@ impl CPU {
                         if boundary_crossed == true {
                             num_ticks += 1;
                         }
-                        test_val = memory[usize::from(address)];
+                        test_val = memory.read(address);
                     }
                     AddressingMode::Immediate => {
                         test_val = self.fetch_instruction(memory);
@@ -552,7 +495,7 @@ impl CPU {
                         if boundary_crossed == true {
                             num_ticks += 1;
                         }
-                        test_val = memory[usize::from(address)];
+                        test_val = memory.read(address);
                     }
                     AddressingMode::Immediate => {
                         test_val = self.fetch_instruction(memory);
@@ -571,9 +514,9 @@ impl CPU {
                     if boundary_crossed == true {
                         num_ticks += 1;
                     }
-                    let mut to_modify = memory[usize::from(address)];
+                    let mut to_modify = memory.read(address);
                     to_modify = to_modify.wrapping_sub(1);
-                    memory[usize::from(address)] = to_modify;
+                    memory.write(address, to_modify);
                     self.set_status_nz(to_modify);
                 }
                 _ => panic!("Attempted to execute instruction with invalid AddressingMode"),
@@ -607,7 +550,7 @@ impl CPU {
                         if boundary_crossed == true {
                             num_ticks += 1;
                         }
-                        let value = memory[usize::from(address)];
+                        let value = memory.read(address);
                         self.a = self.a ^ value;
                     }
                     AddressingMode::Immediate => {
@@ -629,9 +572,9 @@ impl CPU {
                     if boundary_crossed == true {
                         num_ticks += 1;
                     }
-                    let mut to_modify = memory[usize::from(address)];
+                    let mut to_modify = memory.read(address);
                     to_modify = to_modify.wrapping_add(1);
-                    memory[usize::from(address)] = to_modify;
+                    memory.write(address, to_modify);
                     self.set_status_nz(to_modify);
                 }
                 _ => panic!("Attempted to execute instruction with invalid AddressingMode"),
@@ -697,7 +640,7 @@ impl CPU {
                         if boundary_crossed == true {
                             num_ticks += 1;
                         }
-                        self.a = memory[usize::from(address)];
+                        self.a = memory.read(address);
                     }
                     AddressingMode::Immediate => {
                         self.a = self.fetch_instruction(memory);
@@ -716,7 +659,7 @@ impl CPU {
                         if boundary_crossed == true {
                             num_ticks += 1;
                         }
-                        self.x = memory[usize::from(address)];
+                        self.x = memory.read(address);
                     }
                     AddressingMode::Immediate => {
                         self.x = self.fetch_instruction(memory);
@@ -735,7 +678,7 @@ impl CPU {
                         if boundary_crossed == true {
                             num_ticks += 1;
                         }
-                        self.y = memory[usize::from(address)];
+                        self.y = memory.read(address);
                     }
                     AddressingMode::Immediate => {
                         self.y = self.fetch_instruction(memory);
@@ -755,10 +698,10 @@ impl CPU {
                         if boundary_crossed == true {
                             num_ticks += 1;
                         }
-                        let value = memory[usize::from(address)];
+                        let value = memory.read(address);
                         self.p.c = if value & 0x01 == 0x01 { true } else { false };
                         shift_result = self.a >> 1;
-                        memory[usize::from(address)] = shift_result;
+                        memory.write(address, shift_result);
                     }
                     AddressingMode::Accumulator => {
                         self.p.c = if self.a & 0x01 == 0x01 { true } else { false };
@@ -790,7 +733,7 @@ impl CPU {
                         if boundary_crossed == true {
                             num_ticks += 1;
                         }
-                        let value = memory[usize::from(address)];
+                        let value = memory.read(address);
                         self.a = self.a | value;
                     }
                     AddressingMode::Immediate => {
@@ -847,12 +790,12 @@ impl CPU {
                         if boundary_crossed == true {
                             num_ticks += 1;
                         }
-                        let mut value = memory[usize::from(address)];
+                        let mut value = memory.read(address);
                         let tail = self.p.c;
                         self.p.c = if value & 0x80 == 0x80 { true } else { false };
                         value = self.a << 1;
                         shift_result = if tail == true { value | 0x01 } else { value };
-                        memory[usize::from(address)] = shift_result;
+                        memory.write(address, shift_result);
                     }
                     AddressingMode::Accumulator => {
                         let tail = self.p.c;
@@ -878,12 +821,12 @@ impl CPU {
                         if boundary_crossed == true {
                             num_ticks += 1;
                         }
-                        let mut value = memory[usize::from(address)];
+                        let mut value = memory.read(address);
                         let tail = self.p.c;
                         self.p.c = if value & 0x01 == 0x01 { true } else { false };
                         value = self.a >> 1;
                         shift_result = if tail == true { value | 0x80 } else { value };
-                        memory[usize::from(address)] = shift_result;
+                        memory.write(address, shift_result);
                     }
                     AddressingMode::Accumulator => {
                         let tail = self.p.c;
@@ -934,7 +877,7 @@ impl CPU {
                     if boundary_crossed == true {
                         num_ticks += 1;
                     }
-                    let complement = !memory[usize::from(address)];
+                    let complement = !memory.read(address);
                     self.adc_logic(complement);
                 }
                 AddressingMode::Immediate => {
@@ -978,7 +921,7 @@ impl CPU {
                     if boundary_crossed == true {
                         num_ticks += 1;
                     }
-                    memory[usize::from(address)] = self.a;
+                    memory.write(address, self.a);
                 }
                 _ => panic!("Attempted to execute instruction with invalid AddressingMode"),
             },
@@ -988,7 +931,7 @@ impl CPU {
                     if boundary_crossed == true {
                         num_ticks += 1;
                     }
-                    memory[usize::from(address)] = self.x;
+                    memory.write(address, self.x);
                 }
                 _ => panic!("Attempted to execute instruction with invalid AddressingMode"),
             },
@@ -998,7 +941,7 @@ impl CPU {
                     if boundary_crossed == true {
                         num_ticks += 1;
                     }
-                    memory[usize::from(address)] = self.y;
+                    memory.write(address, self.y);
                 }
                 _ => panic!("Attempted to execute instruction with invalid AddressingMode"),
             },
@@ -1061,9 +1004,25 @@ impl CPU {
                 self.push_stack(p, memory);
 
                 // fetch address of NMI vector
-                let lo = memory[0xfffa];
-                let hi = memory[0xfffb];
-                let address = (u16::from(hi) << 8) + u16::from(lo);
+                let address = memory.read_u16(0xfffa);
+                self.pc = address;
+
+                // set interrupt disable flag
+                self.p.i = true;
+            }
+            Instruction::IRQ => {
+                let to_be_pushed = self.pc;
+                let lo = to_be_pushed as u8;
+                let hi = (to_be_pushed >> 8) as u8;
+                self.push_stack(hi, memory);
+                self.push_stack(lo, memory);
+
+                let p = self.p.serialize();
+
+                self.push_stack(p, memory);
+
+                // fetch address of interrupt handler
+                let address = memory.read_u16(0xfffe);
                 self.pc = address;
 
                 // set interrupt disable flag
@@ -1079,7 +1038,8 @@ impl CPU {
     }
 }
 
-#[derive(Copy, Clone, Default, Debug)]
+/// the CPU's R/W pin: low is write, high is read
+#[derive(Copy, Clone, Default, Debug, PartialEq, Eq)]
 pub enum ReadWrite {
     Write,
     #[default]
@@ -15,7 +15,7 @@ pub enum AddressingMode {
     ZeroPageY,
 }
 
-#[derive(Debug)]
+#[derive(Copy, Clone, Debug)]
 pub enum Instruction {
     /// add with carry
     ADC(AddressingMode),
@@ -131,6 +131,8 @@ pub enum Instruction {
     TYA(AddressingMode),
     /// execute NMI, not a true instruction
     NMI,
+    /// execute IRQ, not a true instruction
+    IRQ,
     Invalid(u8),
 }
 
@@ -0,0 +1,66 @@
+use std::fmt;
+use std::io;
+use std::ops::RangeInclusive;
+
+use crate::nes::save_state::{StateReader, StateWriter};
+
+/// Hardware answering CPU accesses to part of the address space, mapped
+/// over any range with Bus::map_device. This is the extension point for
+/// add-on hardware. The PPU, APU, and controller ports implement it for
+/// their registers too, but stay fields of the Bus decoded at their fixed
+/// addresses, since DMA, the /IRQ line, the PPU's dot clock, and save
+/// states need them by type. The cartridge hooks in through Mapper instead
+pub trait Device {
+    /// describes the device in Bus::memory_map
+    fn name(&self) -> &str {
+        "mapped device"
+    }
+
+    /// returns the byte at address, triggering any side effects of reading it
+    fn read(&mut self, address: u16) -> u8;
+
+    /// writes a byte to address
+    fn write(&mut self, address: u16, data: u8);
+
+    /// returns the byte read would without any side effects, for debuggers.
+    /// None for devices that cannot tell, which peek as open bus
+    fn peek(&self, _address: u16) -> Option<u8> {
+        None
+    }
+
+    /// stores a byte at address without any side effects of writing it.
+    /// Ignored by devices with no plain storage behind address
+    fn poke(&mut self, _address: u16, _data: u8) {}
+
+    /// advances the device by one CPU cycle. The Bus ticks mapped devices
+    /// on every cycle of its master clock under either BusConfig. The
+    /// built-in PPU and APU are clocked by Bus::tick itself, the PPU by the
+    /// dot rather than by this
+    fn tick(&mut self) {}
+
+    /// appends whatever the device needs to resume where it is to a Bus
+    /// save state
+    fn write_state(&self, _state: &mut StateWriter) {}
+
+    /// restores state written by write_state
+    fn read_state(&mut self, _state: &mut StateReader) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+/// A device and the addresses it answers
+pub struct MappedDevice {
+    pub range: RangeInclusive<u16>,
+    pub device: Box<dyn Device>,
+}
+
+impl fmt::Debug for MappedDevice {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "MappedDevice(${:04X}-${:04X})",
+            self.range.start(),
+            self.range.end()
+        )
+    }
+}
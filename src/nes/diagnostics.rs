@@ -0,0 +1,42 @@
+use bitflags::bitflags;
+
+bitflags! {
+    /// Hardware the emulator only stubs out. Each is recorded the first time
+    /// a program touches it, so a misbehaving game can be traced back to what
+    /// it needed.
+    #[derive(Debug, Default, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+    pub struct Unimplemented: u8 {
+        /// writes to the pulse, triangle, noise, or status registers
+        const APU              = 0b0000_0001;
+        /// writes to $8000-$FFFF, which only have an effect on boards with a mapper
+        const MAPPER_REGISTERS = 0b0000_1000;
+    }
+}
+
+impl Unimplemented {
+    /// stable identifier for a single feature, for tools reading the report
+    pub fn key(self) -> &'static str {
+        match self {
+            Unimplemented::APU => "apu",
+            Unimplemented::MAPPER_REGISTERS => "mapper_registers",
+            _ => "unknown",
+        }
+    }
+
+    /// what a game relying on a single feature will be missing
+    pub fn consequence(self) -> &'static str {
+        match self {
+            Unimplemented::APU => "no sound is produced",
+            Unimplemented::MAPPER_REGISTERS => "bank switching is ignored, ROM is overwritten",
+            _ => "",
+        }
+    }
+}
+
+/// returns one `unimplemented <key>: <consequence>` line per feature touched
+pub fn report(features: Unimplemented) -> Vec<String> {
+    features
+        .iter()
+        .map(|feature| format!("unimplemented {}: {}", feature.key(), feature.consequence()))
+        .collect()
+}
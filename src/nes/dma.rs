@@ -0,0 +1,122 @@
+/// bytes an OAM transfer copies, a page
+pub const OAM_DMA_BYTES: u16 = 0x100;
+
+/// returns whether or not the DMA unit may read on cycle. The 2A03 splits
+/// its cycles into alternating get and put halves of an APU cycle, reading
+/// only on gets and writing only on puts
+pub fn is_get_cycle(cycle: u64) -> bool {
+    cycle & 1 == 1
+}
+
+/// What the DMA unit does with the bus on a cycle the CPU is halted for
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum DmaCycle {
+    /// halting the CPU, the DMC's dummy cycle, or waiting for a get cycle
+    Idle,
+    /// the DMC reads its sample byte from the address
+    DmcRead(u16),
+    /// OAM DMA reads the byte at the address
+    OamRead(u16),
+    /// OAM DMA writes the byte it read to OAMDATA
+    OamWrite(u8),
+}
+
+/// An OAM transfer a $4014 write scheduled
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq)]
+pub struct OamTransfer {
+    pub page: u8,
+    /// bytes written to OAM so far
+    pub copied: u16,
+    /// the byte read on the last get cycle, waiting for a put cycle
+    pub latch: Option<u8>,
+}
+
+/// The 2A03's DMA unit. OAM DMA and DMC sample fetches both halt the CPU and
+/// take over the bus; this sequences them a cycle at a time, interleaving a
+/// DMC fetch into an OAM transfer's get cycles, see clock
+#[derive(Copy, Clone, Debug, Default)]
+pub struct DmaController {
+    /// the OAM transfer, from its $4014 write until its last byte is written
+    pub oam: Option<OamTransfer>,
+    /// address of the sample byte the DMC is waiting on
+    pub dmc_address: Option<u16>,
+    /// whether or not the CPU has been halted for the DMA under way
+    pub halted: bool,
+    /// whether or not a DMC fetch outside OAM DMA has had its dummy cycle
+    pub dmc_dummy: bool,
+}
+
+impl DmaController {
+    /// schedules a transfer of page to OAM, the CPU halts for it once its
+    /// instruction ends
+    pub fn start_oam(&mut self, page: u8) {
+        self.oam = Some(OamTransfer {
+            page,
+            ..Default::default()
+        });
+    }
+
+    /// returns whether or not the DMA unit needs the bus, halting the CPU
+    pub fn busy(&self) -> bool {
+        self.oam.is_some() || self.dmc_address.is_some()
+    }
+
+    /// holds the byte an OamRead cycle read until the next put cycle
+    pub fn latch_oam_byte(&mut self, byte: u8) {
+        if let Some(ref mut oam) = self.oam {
+            oam.latch = Some(byte);
+        }
+    }
+
+    /// returns what the DMA unit does on cycle, a CPU cycle it is halted
+    /// for. The first cycle halts the CPU. OAM DMA then reads on each get
+    /// cycle and writes on the following put, waiting a cycle to align
+    /// first when the halt lands on a put, 513 or 514 cycles in all. A DMC
+    /// fetch on its own takes a dummy cycle and the next get, 3 or 4
+    /// cycles with the halt, while during OAM DMA it takes over a get
+    /// cycle, pushing the transfer back by a read and a write
+    pub fn clock(&mut self, cycle: u64) -> DmaCycle {
+        let action = self.next_cycle(cycle);
+        if !self.busy() {
+            self.halted = false;
+            self.dmc_dummy = false;
+        }
+        action
+    }
+
+    fn next_cycle(&mut self, cycle: u64) -> DmaCycle {
+        if !self.halted {
+            self.halted = true;
+            return DmaCycle::Idle;
+        }
+        let get = is_get_cycle(cycle);
+        if let Some(address) = self.dmc_address {
+            if self.oam.is_none() && !self.dmc_dummy {
+                self.dmc_dummy = true;
+                return DmaCycle::Idle;
+            }
+            if get {
+                self.dmc_address = None;
+                self.dmc_dummy = false;
+                return DmaCycle::DmcRead(address);
+            }
+        }
+        let Some(ref mut oam) = self.oam else {
+            return DmaCycle::Idle;
+        };
+        match (get, oam.latch) {
+            (true, None) => DmaCycle::OamRead(u16::from(oam.page) << 8 | oam.copied),
+            (false, Some(byte)) => {
+                oam.latch = None;
+                oam.copied += 1;
+                if oam.copied == OAM_DMA_BYTES {
+                    self.oam = None;
+                }
+                DmaCycle::OamWrite(byte)
+            }
+            // a put with nothing read yet, or a get with the byte still to
+            // write, waits a cycle to line up
+            _ => DmaCycle::Idle,
+        }
+    }
+}
@@ -0,0 +1,92 @@
+use std::fmt;
+
+/// bytes per RGBA pixel
+const BYTES_PER_PIXEL: usize = 4;
+
+/// An RGBA image produced by the PPU, laid out row by row so frontends can
+/// upload it without repacking
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Frame {
+    pub width: usize,
+    pub height: usize,
+    pub rgba: Vec<u8>,
+}
+
+impl Frame {
+    /// returns an opaque black frame
+    pub fn new(width: usize, height: usize) -> Frame {
+        let mut rgba = vec![0; width * height * BYTES_PER_PIXEL];
+        for pixel in rgba.chunks_exact_mut(BYTES_PER_PIXEL) {
+            pixel[3] = 0xff;
+        }
+        Frame {
+            width,
+            height,
+            rgba,
+        }
+    }
+
+    /// builds an opaque frame from RGB pixels, row by row
+    pub fn from_rgb<I>(width: usize, height: usize, pixels: I) -> Frame
+    where
+        I: IntoIterator<Item = (u8, u8, u8)>,
+    {
+        let rgba: Vec<u8> = pixels
+            .into_iter()
+            .flat_map(|(r, g, b)| [r, g, b, 0xff])
+            .collect();
+        assert_eq!(
+            rgba.len(),
+            width * height * BYTES_PER_PIXEL,
+            "pixels do not fill a {width}x{height} frame"
+        );
+        Frame {
+            width,
+            height,
+            rgba,
+        }
+    }
+
+    /// returns the color of the pixel at (x, y)
+    pub fn pixel(&self, x: usize, y: usize) -> (u8, u8, u8) {
+        let i = (y * self.width + x) * BYTES_PER_PIXEL;
+        (self.rgba[i], self.rgba[i + 1], self.rgba[i + 2])
+    }
+
+    /// sets the color of the pixel at (x, y)
+    pub fn set_pixel(&mut self, x: usize, y: usize, (r, g, b): (u8, u8, u8)) {
+        let i = (y * self.width + x) * BYTES_PER_PIXEL;
+        self.rgba[i..i + BYTES_PER_PIXEL].copy_from_slice(&[r, g, b, 0xff]);
+    }
+
+    /// returns the color of every pixel, row by row
+    pub fn rgb_pixels(&self) -> impl Iterator<Item = (u8, u8, u8)> + '_ {
+        self.rgba
+            .chunks_exact(BYTES_PER_PIXEL)
+            .map(|pixel| (pixel[0], pixel[1], pixel[2]))
+    }
+
+    /// FNV-1a hash of the frame's RGB values, for comparing frames against
+    /// golden values without storing them. Alpha is left out since frames
+    /// are always opaque
+    pub fn hash(&self) -> u64 {
+        let mut hash: u64 = 0xcbf2_9ce4_8422_2325;
+        for (r, g, b) in self.rgb_pixels() {
+            for byte in [r, g, b] {
+                hash ^= u64::from(byte);
+                hash = hash.wrapping_mul(0x0000_0100_0000_01b3);
+            }
+        }
+        hash
+    }
+}
+
+/// Consumer handed each completed frame by the core, for recording,
+/// streaming, or running without a window
+pub struct FrameCallback(pub Box<dyn FnMut(&Frame)>);
+
+impl fmt::Debug for FrameCallback {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str("FrameCallback")
+    }
+}
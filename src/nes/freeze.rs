@@ -0,0 +1,51 @@
+use std::collections::BTreeMap;
+
+use crate::nes::bus::Bus;
+
+/// When frozen values are written back to memory
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq)]
+pub enum FreezeTiming {
+    /// after every CPU write, so no write to a frozen address sticks
+    #[default]
+    EveryWrite,
+    /// once per frame, letting the game briefly see its own writes
+    EveryFrame,
+}
+
+/// Locks CPU addresses to fixed values
+#[derive(Clone, Debug, Default)]
+pub struct MemoryFreezer {
+    pub timing: FreezeTiming,
+    frozen: BTreeMap<u16, u8>,
+}
+
+impl MemoryFreezer {
+    pub fn freeze(&mut self, address: u16, value: u8) {
+        self.frozen.insert(address, value);
+    }
+
+    pub fn unfreeze(&mut self, address: u16) {
+        self.frozen.remove(&address);
+    }
+
+    pub fn clear(&mut self) {
+        self.frozen.clear();
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.frozen.is_empty()
+    }
+
+    /// returns the value an address is frozen to, if any
+    pub fn frozen_value(&self, address: u16) -> Option<u8> {
+        self.frozen.get(&address).copied()
+    }
+
+    /// writes every frozen value back into memory through Bus::poke, so
+    /// mirrors and PRG RAM are frozen where the CPU would see them
+    pub fn apply(&self, bus: &mut Bus) {
+        for (&address, &value) in &self.frozen {
+            bus.poke(address, value);
+        }
+    }
+}
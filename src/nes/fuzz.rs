@@ -0,0 +1,60 @@
+use crate::nes::bus_trace::BusAccess;
+use crate::nes::ram_init::random_byte;
+
+/// unmapped writes kept, later ones are only counted
+const UNMAPPED_LOG_SIZE: usize = 256;
+
+/// Strict mode for homebrew testing. Reads of addresses nothing answers
+/// return seeded random bytes instead of open bus, so code that leans on
+/// unmapped memory misbehaves visibly, and writes there are logged
+#[derive(Clone, Debug, Default)]
+pub struct UnmappedFuzz {
+    state: u64,
+    /// unmapped reads answered so far
+    pub reads: u64,
+    pub writes: Vec<BusAccess>,
+    /// writes not logged because the log was full
+    pub dropped: u64,
+}
+
+impl UnmappedFuzz {
+    pub fn new(seed: u64) -> UnmappedFuzz {
+        UnmappedFuzz {
+            state: seed,
+            ..Default::default()
+        }
+    }
+
+    /// returns the next random byte for an unmapped read
+    pub fn read(&mut self) -> u8 {
+        self.reads += 1;
+        random_byte(&mut self.state)
+    }
+
+    pub fn log_write(&mut self, access: BusAccess) {
+        if self.writes.len() == UNMAPPED_LOG_SIZE {
+            self.dropped += 1;
+            return;
+        }
+        self.writes.push(access);
+    }
+
+    /// summarizes the unmapped accesses made so far, then lists each
+    /// logged write
+    pub fn report(&self) -> Vec<String> {
+        let writes = self.writes.len() as u64 + self.dropped;
+        let mut lines = vec![format!(
+            "unmapped accesses: {} reads, {writes} writes",
+            self.reads
+        )];
+        lines.extend(
+            self.writes
+                .iter()
+                .map(|access| format!("unmapped {access}")),
+        );
+        if self.dropped > 0 {
+            lines.push(format!("unmapped writes not logged: {}", self.dropped));
+        }
+        lines
+    }
+}
@@ -0,0 +1,88 @@
+use std::fmt;
+use std::io;
+use std::sync::{Arc, Mutex};
+
+use cpal::traits::{DeviceTrait, HostTrait, StreamTrait};
+use cpal::{Device, FromSample, SampleFormat, SizedSample, Stream, StreamConfig};
+
+use crate::nes::audio::SampleRing;
+
+/// seconds of audio queued before the oldest samples are dropped
+const RING_SECONDS: u32 = 1;
+
+/// Plays a SampleRing through the host's default output device. Dropping it
+/// stops the stream
+pub struct HostAudio {
+    /// samples for the stream to drain, shared with the NES as its
+    /// audio_output
+    pub ring: Arc<Mutex<SampleRing>>,
+    /// the device's sample rate, which the APU has to resample to
+    pub sample_rate: u32,
+    /// plays for as long as it is kept
+    _stream: Stream,
+}
+
+impl fmt::Debug for HostAudio {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("HostAudio")
+            .field("sample_rate", &self.sample_rate)
+            .finish_non_exhaustive()
+    }
+}
+
+impl HostAudio {
+    /// opens the default output device at its default configuration and
+    /// starts streaming from an empty ring. The stream plays the last sample
+    /// through underruns, see SampleRing::fill
+    pub fn open_default() -> io::Result<HostAudio> {
+        let device = cpal::default_host()
+            .default_output_device()
+            .ok_or_else(|| io::Error::new(io::ErrorKind::NotFound, "no audio output device"))?;
+        let supported = device.default_output_config().map_err(io::Error::other)?;
+        let sample_format = supported.sample_format();
+        let config: StreamConfig = supported.into();
+        let sample_rate = config.sample_rate.0;
+        let ring = Arc::new(Mutex::new(SampleRing::new(
+            (sample_rate * RING_SECONDS) as usize,
+        )));
+        let stream = match sample_format {
+            SampleFormat::F32 => build_stream::<f32>(&device, &config, ring.clone()),
+            SampleFormat::I16 => build_stream::<i16>(&device, &config, ring.clone()),
+            SampleFormat::U16 => build_stream::<u16>(&device, &config, ring.clone()),
+            format => Err(io::Error::new(
+                io::ErrorKind::Unsupported,
+                format!("unsupported sample format {format}"),
+            )),
+        }?;
+        stream.play().map_err(io::Error::other)?;
+        Ok(HostAudio {
+            ring,
+            sample_rate,
+            _stream: stream,
+        })
+    }
+}
+
+/// builds a stream of samples of type T that drains ring
+fn build_stream<T: SizedSample + FromSample<f32>>(
+    device: &Device,
+    config: &StreamConfig,
+    ring: Arc<Mutex<SampleRing>>,
+) -> io::Result<Stream> {
+    let channels = usize::from(config.channels);
+    let mut buffer = Vec::new();
+    device
+        .build_output_stream(
+            config,
+            move |output: &mut [T], _: &cpal::OutputCallbackInfo| {
+                buffer.resize(output.len(), 0.0);
+                ring.lock().unwrap().fill_frames(&mut buffer, channels);
+                for (output, &sample) in output.iter_mut().zip(&buffer) {
+                    *output = T::from_sample(sample);
+                }
+            },
+            |error| eprintln!("audio output: {error}"),
+            None,
+        )
+        .map_err(io::Error::other)
+}
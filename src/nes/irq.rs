@@ -0,0 +1,13 @@
+use bitflags::bitflags;
+
+bitflags! {
+    /// Devices that can pull the CPU's /IRQ line low. The line is wired-OR,
+    /// so it stays asserted while any source holds it.
+    #[derive(Debug, Default, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+    pub struct IrqSource: u8 {
+        const APU_FRAME = 0b0000_0001;
+        const DMC       = 0b0000_0010;
+        const MAPPER    = 0b0000_0100;
+        const EXTERNAL  = 0b0000_1000;
+    }
+}
@@ -0,0 +1,50 @@
+use std::fmt;
+use std::io;
+
+use crate::nes::save_state::{StateReader, StateWriter};
+
+/// Board logic that sees every CPU access before the Bus decodes it, so it
+/// can shadow RAM, answer its own registers and RAM in the $4020-$5FFF
+/// expansion area, or catch the bank switching writes boards put over PRG
+/// ROM at $8000-$FFFF. Addresses arrive as the CPU put them out, mirrors
+/// included
+pub trait Mapper: fmt::Debug {
+    /// the board's name, as shown in Bus::memory_map
+    fn name(&self) -> &str;
+
+    /// returns the byte the board drives for a read of address, or None to
+    /// leave the read to the memory map
+    fn cpu_read(&mut self, _address: u16) -> Option<u8> {
+        None
+    }
+
+    /// returns the byte cpu_read would without any side effects, for
+    /// debuggers
+    fn cpu_peek(&self, _address: u16) -> Option<u8> {
+        None
+    }
+
+    /// returns true if the board took the write, keeping it from the
+    /// memory map
+    fn cpu_write(&mut self, _address: u16, _data: u8) -> bool {
+        false
+    }
+
+    /// appends the board's registers to a Bus save state
+    fn write_state(&self, _state: &mut StateWriter) {}
+
+    /// restores state written by write_state
+    fn read_state(&mut self, _state: &mut StateReader) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+/// NROM, which wires PRG ROM straight to the CPU and has no registers
+#[derive(Copy, Clone, Debug, Default)]
+pub struct Nrom;
+
+impl Mapper for Nrom {
+    fn name(&self) -> &str {
+        "NROM"
+    }
+}
@@ -0,0 +1,45 @@
+use std::time::Duration;
+
+/// frames per second of an NTSC console
+pub const NTSC_FRAME_RATE: f64 = 60.0988;
+/// frames per second of a PAL console
+pub const PAL_FRAME_RATE: f64 = 50.007;
+
+/// Timestamps frames in emulated time so recordings play back at real game
+/// speed no matter how fast the host ran emulation while recording
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct MediaClock {
+    pub frame_rate: f64,
+    /// frames produced by the core so far
+    pub frames: u64,
+}
+
+impl Default for MediaClock {
+    fn default() -> MediaClock {
+        MediaClock::new(NTSC_FRAME_RATE)
+    }
+}
+
+impl MediaClock {
+    pub fn new(frame_rate: f64) -> MediaClock {
+        MediaClock {
+            frame_rate,
+            frames: 0,
+        }
+    }
+
+    /// records that the core completed a frame
+    pub fn frame_produced(&mut self) {
+        self.frames += 1;
+    }
+
+    /// returns the emulated time at which a frame began
+    pub fn timestamp(&self, frame: u64) -> Duration {
+        Duration::from_secs_f64(frame as f64 / self.frame_rate)
+    }
+
+    /// returns the emulated time of the most recently completed frame
+    pub fn now(&self) -> Duration {
+        self.timestamp(self.frames)
+    }
+}
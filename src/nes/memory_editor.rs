@@ -0,0 +1,262 @@
+use std::fs::File;
+use std::io::{self, prelude::*};
+use std::ops::Range;
+use std::str::FromStr;
+
+use crate::nes::bus::Bus;
+use crate::nes::cartridge::PRG_RAM_SIZE;
+
+/// Memory a MemoryEditor can modify
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum MemoryRegion {
+    /// CPU address space, through its mirrors, as Bus::peek and Bus::poke
+    /// see it
+    Ram,
+    /// nametable RAM, four 1 KB nametables before mirroring
+    Vram,
+    /// pattern tables on the cartridge, PPU $0000-$1FFF
+    Chr,
+    /// 32 bytes of palette RAM, PPU $3F00-$3F1F before mirroring
+    Palette,
+    /// Object Attribute Memory
+    Oam,
+    /// cartridge work RAM, CPU $6000-$7FFF
+    PrgRam,
+}
+
+impl FromStr for MemoryRegion {
+    type Err = String;
+
+    fn from_str(text: &str) -> Result<MemoryRegion, String> {
+        match text {
+            "ram" => Ok(MemoryRegion::Ram),
+            "vram" => Ok(MemoryRegion::Vram),
+            "chr" => Ok(MemoryRegion::Chr),
+            "palette" => Ok(MemoryRegion::Palette),
+            "oam" => Ok(MemoryRegion::Oam),
+            "prg-ram" => Ok(MemoryRegion::PrgRam),
+            _ => Err(format!("unknown memory region {text}")),
+        }
+    }
+}
+
+/// A file to write into memory, given as region:address:file with the
+/// address in hex, e.g. chr:1000:tiles.chr
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct MemoryImport {
+    pub region: MemoryRegion,
+    pub address: usize,
+    pub filename: String,
+}
+
+impl FromStr for MemoryImport {
+    type Err = String;
+
+    fn from_str(text: &str) -> Result<MemoryImport, String> {
+        let mut parts = text.splitn(3, ':');
+        let (Some(region), Some(address), Some(filename)) =
+            (parts.next(), parts.next(), parts.next())
+        else {
+            return Err(format!("{text} is not region:address:file"));
+        };
+        Ok(MemoryImport {
+            region: region.parse()?,
+            address: usize::from_str_radix(address, 16)
+                .map_err(|error| format!("bad address {address}: {error}"))?,
+            filename: filename.to_string(),
+        })
+    }
+}
+
+/// Cartridge memory as the CPU and PPU currently see it, after bank mapping
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum CartridgeMemory {
+    /// battery-backed or work RAM at $6000-$7FFF
+    PrgRam,
+    /// pattern tables at PPU $0000-$1FFF, RAM or ROM depending on the board
+    ChrRam,
+    /// linear view of the program ROM banks mapped to $8000-$FFFF
+    PrgRom,
+}
+
+impl CartridgeMemory {
+    /// returns where the memory lives within the bus
+    pub fn location(self) -> (MemoryRegion, Range<usize>) {
+        match self {
+            CartridgeMemory::PrgRam => (MemoryRegion::PrgRam, 0x0000..PRG_RAM_SIZE),
+            CartridgeMemory::ChrRam => (MemoryRegion::Chr, 0x0000..0x2000),
+            CartridgeMemory::PrgRom => (MemoryRegion::Ram, 0x8000..0x10000),
+        }
+    }
+}
+
+/// bytes overwritten by a single edit, kept so the edit can be undone
+#[derive(Clone, Debug)]
+struct Edit {
+    region: MemoryRegion,
+    address: usize,
+    previous: Vec<u8>,
+}
+
+/// Live editor for RAM, VRAM, palette RAM, CHR, and OAM. Edits bypass register side effects,
+/// and every edit can be undone.
+#[derive(Clone, Debug, Default)]
+pub struct MemoryEditor {
+    history: Vec<Edit>,
+}
+
+/// size of the CPU address space
+const CPU_ADDRESS_SPACE_SIZE: usize = 0x10000;
+
+fn region(bus: &Bus, region: MemoryRegion) -> &[u8] {
+    match region {
+        MemoryRegion::Ram => &bus.bytes,
+        MemoryRegion::Vram => &bus.ppu.address_space.nametables,
+        MemoryRegion::Chr => &bus.ppu.address_space.chr.bytes,
+        MemoryRegion::Palette => &bus.ppu.address_space.palette,
+        MemoryRegion::Oam => &bus.ppu.oam_ram,
+        MemoryRegion::PrgRam => &bus.cartridge.prg_ram,
+    }
+}
+
+fn region_mut(bus: &mut Bus, region: MemoryRegion) -> &mut [u8] {
+    match region {
+        MemoryRegion::Ram => &mut bus.bytes,
+        MemoryRegion::Vram => &mut bus.ppu.address_space.nametables,
+        MemoryRegion::Chr => &mut bus.ppu.address_space.chr.bytes,
+        MemoryRegion::Palette => &mut bus.ppu.address_space.palette,
+        MemoryRegion::Oam => &mut bus.ppu.oam_ram,
+        MemoryRegion::PrgRam => &mut bus.cartridge.prg_ram,
+    }
+}
+
+/// returns the bytes within range, reading the CPU address space through
+/// Bus::peek so mirrors and PRG RAM read as the CPU sees them
+fn read_bytes(bus: &Bus, memory: MemoryRegion, range: Range<usize>) -> Vec<u8> {
+    match memory {
+        MemoryRegion::Ram => range.map(|address| bus.peek(address as u16)).collect(),
+        _ => region(bus, memory)[range].to_vec(),
+    }
+}
+
+/// stores bytes starting at address, writing the CPU address space through
+/// Bus::poke
+fn write_bytes(bus: &mut Bus, memory: MemoryRegion, address: usize, bytes: &[u8]) {
+    match memory {
+        MemoryRegion::Ram => {
+            for (offset, &byte) in bytes.iter().enumerate() {
+                bus.poke((address + offset) as u16, byte);
+            }
+        }
+        _ => region_mut(bus, memory)[address..address + bytes.len()].copy_from_slice(bytes),
+    }
+}
+
+fn region_len(bus: &Bus, memory: MemoryRegion) -> usize {
+    match memory {
+        MemoryRegion::Ram => CPU_ADDRESS_SPACE_SIZE,
+        _ => region(bus, memory).len(),
+    }
+}
+
+/// returns the range of len bytes starting at address, or an error if
+/// it runs past the end of the region
+fn span(bus: &Bus, memory: MemoryRegion, address: usize, len: usize) -> io::Result<Range<usize>> {
+    match address.checked_add(len) {
+        Some(end) if end <= region_len(bus, memory) => Ok(address..end),
+        _ => Err(io::Error::new(
+            io::ErrorKind::InvalidInput,
+            format!("0x{len:x} bytes do not fit at 0x{address:0>4x} in {memory:?}"),
+        )),
+    }
+}
+
+impl MemoryEditor {
+    /// writes bytes starting at address
+    pub fn write(
+        &mut self,
+        bus: &mut Bus,
+        memory: MemoryRegion,
+        address: usize,
+        bytes: &[u8],
+    ) -> io::Result<()> {
+        let range = span(bus, memory, address, bytes.len())?;
+        self.history.push(Edit {
+            region: memory,
+            address,
+            previous: read_bytes(bus, memory, range),
+        });
+        write_bytes(bus, memory, address, bytes);
+        Ok(())
+    }
+
+    /// sets every byte within range to value
+    pub fn fill(
+        &mut self,
+        bus: &mut Bus,
+        memory: MemoryRegion,
+        range: Range<usize>,
+        value: u8,
+    ) -> io::Result<()> {
+        let bytes = vec![value; range.len()];
+        self.write(bus, memory, range.start, &bytes)
+    }
+
+    /// copies the bytes within source to destination, overlapping ranges are
+    /// copied as if through a temporary buffer
+    pub fn copy(
+        &mut self,
+        bus: &mut Bus,
+        memory: MemoryRegion,
+        source: Range<usize>,
+        destination: usize,
+    ) -> io::Result<()> {
+        let source = span(bus, memory, source.start, source.len())?;
+        let bytes = read_bytes(bus, memory, source);
+        self.write(bus, memory, destination, &bytes)
+    }
+
+    /// writes the contents of a file starting at address, returns the number
+    /// of bytes written
+    pub fn import(
+        &mut self,
+        bus: &mut Bus,
+        memory: MemoryRegion,
+        address: usize,
+        filename: &str,
+    ) -> io::Result<usize> {
+        let mut bytes = Vec::new();
+        File::open(filename)?.read_to_end(&mut bytes)?;
+        self.write(bus, memory, address, &bytes)?;
+        Ok(bytes.len())
+    }
+
+    /// saves the bytes within range to a file
+    pub fn export(
+        bus: &Bus,
+        memory: MemoryRegion,
+        range: Range<usize>,
+        filename: &str,
+    ) -> io::Result<()> {
+        let range = span(bus, memory, range.start, range.len())?;
+        File::create(filename)?.write_all(&read_bytes(bus, memory, range))
+    }
+
+    /// saves cartridge memory to a file, for extracting save data or
+    /// modified graphics
+    pub fn export_cartridge(bus: &Bus, memory: CartridgeMemory, filename: &str) -> io::Result<()> {
+        let (region, range) = memory.location();
+        MemoryEditor::export(bus, region, range, filename)
+    }
+
+    /// reverts the most recent edit, returns false if there was nothing to undo
+    pub fn undo(&mut self, bus: &mut Bus) -> bool {
+        match self.history.pop() {
+            Some(edit) => {
+                write_bytes(bus, edit.region, edit.address, &edit.previous);
+                true
+            }
+            None => false,
+        }
+    }
+}
@@ -0,0 +1,50 @@
+use std::fmt;
+use std::ops::RangeInclusive;
+
+/// What answers accesses to a range of the CPU's address space
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum RegionKind {
+    Ram,
+    Rom,
+    /// memory mapped registers with side effects
+    Registers,
+    /// repeats of another range
+    Mirror,
+    /// nothing drives the bus, so reads see open bus
+    OpenBus,
+    /// hardware on the cartridge besides its memory
+    Cartridge,
+    /// a device mapped with Bus::map_device
+    Device,
+}
+
+/// One range of the memory map returned by Bus::memory_map
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct MemoryMapEntry {
+    pub range: RangeInclusive<u16>,
+    pub name: String,
+    pub kind: RegionKind,
+}
+
+impl MemoryMapEntry {
+    pub fn new(range: RangeInclusive<u16>, name: &str, kind: RegionKind) -> MemoryMapEntry {
+        MemoryMapEntry {
+            range,
+            name: String::from(name),
+            kind,
+        }
+    }
+}
+
+impl fmt::Display for MemoryMapEntry {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "${:04X}-${:04X} {:<9} {}",
+            self.range.start(),
+            self.range.end(),
+            format!("{:?}", self.kind),
+            self.name
+        )
+    }
+}
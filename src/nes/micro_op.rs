@@ -0,0 +1,128 @@
+use crate::nes::cpu::{StatusRegister, CPU};
+use crate::nes::cpu_structs::Instruction;
+
+/// most bus accesses a single instruction makes, BRK and interrupts with
+/// their three pushes and two vector reads
+pub const MAX_ACCESSES: usize = 8;
+
+/// The registers an instruction starts from
+#[derive(Copy, Clone, Debug, Default)]
+pub struct Registers {
+    pub a: u8,
+    pub x: u8,
+    pub y: u8,
+    pub sp: u8,
+    pub pc: u16,
+    pub p: StatusRegister,
+}
+
+impl Registers {
+    pub fn of(cpu: &CPU) -> Registers {
+        Registers {
+            a: cpu.a,
+            x: cpu.x,
+            y: cpu.y,
+            sp: cpu.sp,
+            pc: cpu.pc,
+            p: cpu.p,
+        }
+    }
+
+    /// puts the registers back into cpu, leaving its clock alone
+    pub fn restore(&self, cpu: &mut CPU) {
+        cpu.a = self.a;
+        cpu.x = self.x;
+        cpu.y = self.y;
+        cpu.sp = self.sp;
+        cpu.pc = self.pc;
+        cpu.p = self.p;
+    }
+}
+
+/// An instruction partway through its cycles: what the 6502 holds in its
+/// timing state and internal latches between two cycles, see
+/// NES::step_cycle
+#[derive(Copy, Clone, Debug)]
+pub struct InFlight {
+    /// registers as the instruction found them
+    pub start: Registers,
+    /// NMI or IRQ when servicing an interrupt rather than an opcode
+    pub interrupt: Option<Instruction>,
+    /// bus accesses made so far, the index of the next micro-op
+    pub micro_op: usize,
+    /// the byte each of those accesses read or wrote, starting with the
+    /// opcode
+    pub latched: [u8; MAX_ACCESSES],
+    /// internal cycles left once every access has been made, after which
+    /// the CPU stays halted while the DMA unit is busy. None until then
+    pub remaining: Option<u64>,
+}
+
+impl InFlight {
+    pub fn new(cpu: &CPU, interrupt: Option<Instruction>) -> InFlight {
+        InFlight {
+            start: Registers::of(cpu),
+            interrupt,
+            micro_op: 0,
+            latched: [0; MAX_ACCESSES],
+            remaining: None,
+        }
+    }
+}
+
+/// What the Bus does with an access while replaying an instruction
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum ReplayAccess {
+    /// made on an earlier cycle, answered from the latch without touching
+    /// the bus
+    Latched(u8),
+    /// this cycle's access, made for real
+    Live,
+    /// belongs to a later cycle, skipped
+    Ahead,
+}
+
+/// Runs an instruction from its start against the bytes latched on
+/// earlier cycles, so that only the access due on this cycle reaches the
+/// bus. The executor stays a plain function of its inputs while the
+/// instruction advances a cycle at a time
+#[derive(Copy, Clone, Debug)]
+pub struct Replay {
+    pub latched: [u8; MAX_ACCESSES],
+    /// accesses made on earlier cycles
+    made: usize,
+    /// accesses the instruction has asked for so far in this replay
+    position: usize,
+}
+
+impl Replay {
+    pub fn new(in_flight: &InFlight) -> Replay {
+        Replay {
+            latched: in_flight.latched,
+            made: in_flight.micro_op,
+            position: 0,
+        }
+    }
+
+    /// classifies the instruction's next access
+    pub fn access(&mut self) -> ReplayAccess {
+        let position = self.position;
+        self.position += 1;
+        match position.cmp(&self.made) {
+            std::cmp::Ordering::Less => ReplayAccess::Latched(self.latched[position]),
+            std::cmp::Ordering::Equal => ReplayAccess::Live,
+            std::cmp::Ordering::Greater => ReplayAccess::Ahead,
+        }
+    }
+
+    /// latches the byte of this cycle's access
+    pub fn latch(&mut self, data: u8) {
+        self.latched[self.made] = data;
+    }
+
+    /// returns whether or not the instruction asked for accesses beyond
+    /// this cycle's, meaning it has not finished
+    pub fn ran_ahead(&self) -> bool {
+        self.position > self.made + 1
+    }
+}
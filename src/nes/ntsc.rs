@@ -0,0 +1,128 @@
+//! Composite video simulation, after https://www.nesdev.org/wiki/NTSC_video
+//!
+//! Each pixel is turned into the square wave the PPU puts on the composite
+//! output, then decoded back into RGB the way a television would. Colors bleed
+//! into their neighbors (fringing), and because the color subcarrier does not
+//! line up with the dots, the fringes shift from line to line and frame to
+//! frame (dot crawl).
+
+use crate::nes::frame::Frame;
+use crate::nes::ppu::{DOTS_PER_SCANLINE, FRAME_HEIGHT, FRAME_WIDTH};
+
+/// signal samples generated per dot
+const SAMPLES_PER_DOT: usize = 8;
+/// signal samples per cycle of the color subcarrier
+const SAMPLES_PER_CYCLE: usize = 12;
+
+/// voltages of the low then high half of the wave for each luma level, then
+/// the same attenuated by color emphasis
+const LEVELS: [f32; 16] = [
+    0.228, 0.312, 0.552, 0.880, // low
+    0.616, 0.840, 1.100, 1.100, // high
+    0.192, 0.256, 0.448, 0.712, // low, attenuated
+    0.500, 0.676, 0.896, 0.896, // high, attenuated
+];
+const BLACK: f32 = 0.312;
+const WHITE: f32 = 1.100;
+/// rotates decoded hues to line up with the standard palette, in samples
+const HUE_SHIFT: f32 = 3.9;
+
+/// YIQ to RGB, per the FCC NTSC standard
+const I_TO_RGB: [f32; 3] = [0.946_882, -0.274_788, -1.108_545];
+const Q_TO_RGB: [f32; 3] = [0.623_557, -0.635_691, 1.709_007];
+
+/// returns whether or not the wave for a hue is high at a sample phase
+fn in_color_phase(hue: usize, phase: usize) -> bool {
+    (hue + phase) % SAMPLES_PER_CYCLE < SAMPLES_PER_CYCLE / 2
+}
+
+/// returns the normalized composite voltage of a pixel at a sample phase,
+/// emphasis is the BGR bits of PPUMASK shifted down to the bottom
+fn signal(color: u8, emphasis: u8, phase: usize) -> f32 {
+    let hue = usize::from(color & 0x0f);
+    let mut level = usize::from(color >> 4 & 0x03);
+    // columns $xE and $xF output the blackest grey
+    if hue > 0x0d {
+        level = 1;
+    }
+    let attenuated = hue < 0x0e
+        && ((emphasis & 0b001 != 0 && in_color_phase(0x0c, phase))
+            || (emphasis & 0b010 != 0 && in_color_phase(0x04, phase))
+            || (emphasis & 0b100 != 0 && in_color_phase(0x08, phase)));
+    let offset = if attenuated { 8 } else { 0 };
+    let mut low = LEVELS[offset + level];
+    let mut high = LEVELS[offset + 4 + level];
+    // hue $0 is a flat grey at the high level, hues $D-$F flat at the low
+    if hue == 0 {
+        low = high;
+    }
+    if hue > 0x0c {
+        high = low;
+    }
+    let voltage = if in_color_phase(hue, phase) {
+        high
+    } else {
+        low
+    };
+    (voltage - BLACK) / (WHITE - BLACK)
+}
+
+fn gamma(channel: f32) -> u8 {
+    let corrected = if channel <= 0.0 {
+        0.0
+    } else {
+        channel.powf(2.2 / 1.8)
+    };
+    (corrected * 255.95).clamp(0.0, 255.0) as u8
+}
+
+/// encodes then decodes a single scanline, phase is the subcarrier phase in
+/// samples at its first pixel
+fn filter_line(pixels: &[u8], mask: u8, phase: usize) -> impl Iterator<Item = (u8, u8, u8)> {
+    let emphasis = mask >> 5;
+    let samples: Vec<f32> = pixels
+        .iter()
+        .enumerate()
+        .flat_map(|(x, &pixel)| {
+            (0..SAMPLES_PER_DOT)
+                .map(move |p| signal(pixel & 0x3f, emphasis, phase + x * SAMPLES_PER_DOT + p))
+        })
+        .collect();
+
+    (0..pixels.len()).map(move |x| {
+        let center = x * SAMPLES_PER_DOT + SAMPLES_PER_DOT / 2;
+        let begin = center.saturating_sub(SAMPLES_PER_CYCLE / 2);
+        let end = (center + SAMPLES_PER_CYCLE / 2).min(samples.len());
+        // the window is cut short at the edges of the line
+        let window = (end - begin) as f32;
+        let (mut y, mut i, mut q) = (0.0, 0.0, 0.0);
+        for (p, &sample) in samples.iter().enumerate().take(end).skip(begin) {
+            let level = sample / window;
+            let angle = std::f32::consts::PI * ((phase + p) as f32 + HUE_SHIFT)
+                / (SAMPLES_PER_CYCLE / 2) as f32;
+            y += level;
+            i += level * angle.cos();
+            q += level * angle.sin();
+        }
+        (
+            gamma(y + I_TO_RGB[0] * i + Q_TO_RGB[0] * q),
+            gamma(y + I_TO_RGB[1] * i + Q_TO_RGB[1] * q),
+            gamma(y + I_TO_RGB[2] * i + Q_TO_RGB[2] * q),
+        )
+    })
+}
+
+/// renders PPU pixels as a television would show them, line_masks holds
+/// PPUMASK for each line and frame_phase is the dot count at the start of the
+/// frame, modulo 3
+pub fn filter_frame(pixels: &[u8], line_masks: &[u8], frame_phase: usize) -> Frame {
+    let rows = pixels.chunks_exact(FRAME_WIDTH).zip(line_masks).enumerate();
+    Frame::from_rgb(
+        FRAME_WIDTH,
+        FRAME_HEIGHT,
+        rows.flat_map(|(y, (line, &mask))| {
+            let dots = frame_phase + y * DOTS_PER_SCANLINE;
+            filter_line(line, mask, dots * SAMPLES_PER_DOT % SAMPLES_PER_CYCLE)
+        }),
+    )
+}
@@ -0,0 +1,237 @@
+use std::io;
+use std::path::Path;
+
+const IPS_MAGIC: &[u8] = b"PATCH";
+const IPS_EOF: &[u8] = b"EOF";
+const BPS_MAGIC: &[u8] = b"BPS1";
+/// source, target, and patch CRC32s end every BPS patch
+const BPS_FOOTER_SIZE: usize = 12;
+
+/// patch formats in the order a patch next to a ROM is looked for
+const PATCH_EXTENSIONS: [&str; 2] = ["ips", "bps"];
+
+fn invalid(reason: &str) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, reason.to_string())
+}
+
+/// Cursor over the bytes of a patch
+struct PatchReader<'a> {
+    bytes: &'a [u8],
+    position: usize,
+}
+
+impl<'a> PatchReader<'a> {
+    fn take(&mut self, count: usize) -> io::Result<&'a [u8]> {
+        let bytes = self
+            .position
+            .checked_add(count)
+            .and_then(|end| self.bytes.get(self.position..end))
+            .ok_or_else(|| invalid("patch ends mid-record"))?;
+        self.position += count;
+        Ok(bytes)
+    }
+
+    /// big-endian number of count bytes, as used by IPS
+    fn big_endian(&mut self, count: usize) -> io::Result<usize> {
+        let bytes = self.take(count)?;
+        Ok(bytes
+            .iter()
+            .fold(0, |value, &b| value << 8 | usize::from(b)))
+    }
+
+    /// variable-length number, as used by BPS
+    fn number(&mut self) -> io::Result<usize> {
+        let overflow = || invalid("BPS number is too large");
+        let mut value: usize = 0;
+        let mut shift: usize = 1;
+        loop {
+            let byte = self.take(1)?[0];
+            value = usize::from(byte & 0x7f)
+                .checked_mul(shift)
+                .and_then(|digit| value.checked_add(digit))
+                .ok_or_else(overflow)?;
+            if byte & 0x80 != 0 {
+                return Ok(value);
+            }
+            shift = shift.checked_mul(0x80).ok_or_else(overflow)?;
+            value = value.checked_add(shift).ok_or_else(overflow)?;
+        }
+    }
+
+    /// variable-length number whose lowest bit is the sign, as used by BPS
+    /// copy offsets
+    fn signed_number(&mut self) -> io::Result<isize> {
+        let value = self.number()?;
+        let magnitude = (value >> 1) as isize;
+        Ok(if value & 1 == 1 {
+            -magnitude
+        } else {
+            magnitude
+        })
+    }
+}
+
+/// returns the IPS or BPS patch sharing a ROM's filename, if there is one
+pub fn find_patch(rom_filename: &str) -> Option<String> {
+    PATCH_EXTENSIONS
+        .iter()
+        .map(|extension| Path::new(rom_filename).with_extension(extension))
+        .find(|path| path.is_file())
+        .and_then(|path| path.to_str().map(String::from))
+}
+
+/// applies an IPS or BPS patch to a ROM image, detecting the format from
+/// the patch's header
+pub fn apply_patch(rom: &[u8], patch: &[u8]) -> io::Result<Vec<u8>> {
+    if patch.starts_with(IPS_MAGIC) {
+        apply_ips(rom, patch)
+    } else if patch.starts_with(BPS_MAGIC) {
+        apply_bps(rom, patch)
+    } else {
+        Err(invalid("patch is neither IPS nor BPS"))
+    }
+}
+
+/// IPS: records of a 24-bit offset and 16-bit size followed by that many
+/// bytes, or by a 16-bit run length and fill byte when the size is 0. The
+/// records end at "EOF", optionally followed by a 24-bit length to truncate to
+pub fn apply_ips(rom: &[u8], patch: &[u8]) -> io::Result<Vec<u8>> {
+    let mut target = rom.to_vec();
+    let mut reader = PatchReader {
+        bytes: patch,
+        position: IPS_MAGIC.len(),
+    };
+
+    loop {
+        if reader.bytes[reader.position..].starts_with(IPS_EOF) {
+            reader.take(IPS_EOF.len())?;
+            break;
+        }
+        let offset = reader.big_endian(3)?;
+        let size = reader.big_endian(2)?;
+        let data = if size == 0 {
+            let length = reader.big_endian(2)?;
+            vec![reader.take(1)?[0]; length]
+        } else {
+            reader.take(size)?.to_vec()
+        };
+        if target.len() < offset + data.len() {
+            target.resize(offset + data.len(), 0);
+        }
+        target[offset..offset + data.len()].copy_from_slice(&data);
+    }
+    if let Ok(length) = reader.big_endian(3) {
+        target.truncate(length);
+    }
+    Ok(target)
+}
+
+/// CRC-32 (IEEE) as used by BPS footers
+fn crc32(bytes: &[u8]) -> u32 {
+    let mut crc = !0u32;
+    for &byte in bytes {
+        crc ^= u32::from(byte);
+        for _ in 0..8 {
+            crc = if crc & 1 == 1 {
+                (crc >> 1) ^ 0xedb8_8320
+            } else {
+                crc >> 1
+            };
+        }
+    }
+    !crc
+}
+
+/// moves offset by a BPS relative offset, failing if it leaves length
+fn seek(offset: usize, relative: isize, length: usize) -> io::Result<usize> {
+    offset
+        .checked_add_signed(relative)
+        .filter(|&offset| offset <= length)
+        .ok_or_else(|| invalid("BPS copy reaches outside the ROM"))
+}
+
+/// BPS: a header of variable-length sizes and metadata, then actions that
+/// build the target from the source, the patch, or earlier target bytes,
+/// checked against the CRC32s in the footer
+pub fn apply_bps(rom: &[u8], patch: &[u8]) -> io::Result<Vec<u8>> {
+    if patch.len() < BPS_MAGIC.len() + BPS_FOOTER_SIZE {
+        return Err(invalid("BPS patch is truncated"));
+    }
+    let actions_end = patch.len() - BPS_FOOTER_SIZE;
+    let footer = |index: usize| {
+        let start = actions_end + index * 4;
+        u32::from_le_bytes([
+            patch[start],
+            patch[start + 1],
+            patch[start + 2],
+            patch[start + 3],
+        ])
+    };
+    if crc32(&patch[..patch.len() - 4]) != footer(2) {
+        return Err(invalid("BPS patch is corrupt"));
+    }
+    if crc32(rom) != footer(0) {
+        return Err(invalid("BPS patch was made for a different ROM"));
+    }
+
+    let mut reader = PatchReader {
+        bytes: &patch[..actions_end],
+        position: BPS_MAGIC.len(),
+    };
+    let source_size = reader.number()?;
+    let target_size = reader.number()?;
+    let metadata_size = reader.number()?;
+    reader.take(metadata_size)?;
+    if source_size != rom.len() {
+        return Err(invalid("BPS patch was made for a ROM of a different size"));
+    }
+
+    let mut target: Vec<u8> = Vec::new();
+    target
+        .try_reserve(target_size)
+        .map_err(|_| invalid("BPS target is too large"))?;
+    let mut source_offset = 0;
+    let mut target_offset = 0;
+    while reader.position < actions_end {
+        let action = reader.number()?;
+        let length = (action >> 2) + 1;
+        match action & 0x03 {
+            // source read, copies the source byte at the same position
+            0 => {
+                let start = target.len();
+                let bytes = rom
+                    .get(start..start + length)
+                    .ok_or_else(|| invalid("BPS read reaches outside the ROM"))?;
+                target.extend_from_slice(bytes);
+            }
+            // target read, copies bytes stored in the patch
+            1 => target.extend_from_slice(reader.take(length)?),
+            // source copy, copies from anywhere in the source
+            2 => {
+                source_offset = seek(source_offset, reader.signed_number()?, rom.len())?;
+                let bytes = rom
+                    .get(source_offset..source_offset + length)
+                    .ok_or_else(|| invalid("BPS copy reaches outside the ROM"))?;
+                target.extend_from_slice(bytes);
+                source_offset += length;
+            }
+            // target copy, copies earlier output one byte at a time so runs
+            // can overlap what they are writing
+            _ => {
+                target_offset = seek(target_offset, reader.signed_number()?, target.len())?;
+                for _ in 0..length {
+                    let byte = *target
+                        .get(target_offset)
+                        .ok_or_else(|| invalid("BPS copy reaches past the output"))?;
+                    target.push(byte);
+                    target_offset += 1;
+                }
+            }
+        }
+    }
+
+    if target.len() != target_size || crc32(&target) != footer(1) {
+        return Err(invalid("BPS patch produced the wrong ROM"));
+    }
+    Ok(target)
+}
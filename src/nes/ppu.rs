@@ -1,19 +1,90 @@
-use crate::nes::ppu_structs::{PPUCTRL, SYSTEM_COLOR_PALETTE};
+pub mod viewers;
+
+use std::fmt;
+
+use crate::nes::device::Device;
+use crate::nes::frame::Frame;
+use crate::nes::ntsc;
+use crate::nes::ppu_bus::PpuBus;
+use crate::nes::ppu_structs::{
+    emphasis_palettes, AccuracyProfile, EmphasisPalettes, RenderMode, PPUCTRL, PPUMASK, PPUSTATUS,
+    SYSTEM_COLOR_PALETTE,
+};
+use crate::nes::region::Region;
 
-const PPU_MEMORY_SIZE: usize = 0x4000;
 const OAM_SIZE: usize = 0x100;
 
-const FRAME_WIDTH: usize = 256;
-const FRAME_HEIGHT: usize = 240;
+pub const FRAME_WIDTH: usize = 256;
+pub const FRAME_HEIGHT: usize = 240;
 pub const FRAME_BUFFER_SIZE: usize = FRAME_WIDTH * FRAME_HEIGHT;
 
 const TILE_SIZE: usize = 8;
 const FRAME_WIDTH_IN_TILES: usize = FRAME_WIDTH / TILE_SIZE;
 const FRAME_HEIGHT_IN_TILES: usize = FRAME_HEIGHT / TILE_SIZE;
 
-const ATTRIBUTE_TABLE_COVERAGE_SIZE: usize = TILE_SIZE * 4;
+pub const DOTS_PER_SCANLINE: usize = 341;
+pub const NTSC_SCANLINES_PER_FRAME: usize = 262;
+/// PAL's longer vblank adds 50 scanlines before the pre-render line
+pub const PAL_SCANLINES_PER_FRAME: usize = 312;
+/// scanline on which vblank begins, at dot 1
+const VBLANK_SCANLINE: usize = 241;
 
-#[derive(Copy, Clone, Debug)]
+/// last dot of a visible scanline, where the line is drawn and v moves down
+const LINE_END_DOT: usize = 256;
+/// dot where the horizontal scroll in t is copied back into v
+const HORIZONTAL_RELOAD_DOT: usize = 257;
+/// dot of the pre-render scanline where the vertical scroll in t is copied
+/// back into v, really repeated through dot 304
+const VERTICAL_RELOAD_DOT: usize = 280;
+
+/// coarse X and the horizontal nametable bit of v and t
+const HORIZONTAL_SCROLL_BITS: u16 = 0x041f;
+/// fine Y, coarse Y, and the vertical nametable bit of v and t
+const VERTICAL_SCROLL_BITS: u16 = 0x7be0;
+
+/// each pixel is a system palette index in the low six bits, tagged with the
+/// source of the pixel in the top two
+const PIXEL_COLOR: u8 = 0b0011_1111;
+const PIXEL_BACKDROP: u8 = 0b0000_0000;
+const PIXEL_BACKGROUND: u8 = 0b0100_0000;
+const PIXEL_SPRITE_FRONT: u8 = 0b1000_0000;
+/// sprite with the behind-background priority bit, showing through a
+/// transparent background pixel
+const PIXEL_SPRITE_BEHIND: u8 = 0b1100_0000;
+
+/// attribute bits of a sprite, OAM byte 2
+const SPRITE_PALETTE: u8 = 0b0000_0011;
+const SPRITE_BEHIND_BACKGROUND: u8 = 0b0010_0000;
+const SPRITE_FLIP_HORIZONTAL: u8 = 0b0100_0000;
+const SPRITE_FLIP_VERTICAL: u8 = 0b1000_0000;
+
+/// frames a bit of the I/O latch holds its charge, about 600ms
+const IO_LATCH_DECAY_FRAMES: u8 = 36;
+
+/// A12 must be low for this many dots before a rise counts. The MMC3 waits
+/// for three falling edges of M2, so the 4 dot gaps between pattern fetches
+/// and the 9 dot gap around the start of a line are ignored
+const A12_FILTER_DOTS: usize = 10;
+/// first dot of the sprite pattern fetches for the next scanline
+const SPRITE_FETCH_DOT: usize = 257;
+/// first and last dots of the fetches for the first two tiles of the next
+/// scanline
+const TILE_PREFETCH_DOT: usize = 321;
+const TILE_PREFETCH_END_DOT: usize = 336;
+
+const OAM_SPRITE_COUNT: usize = 64;
+/// byte of each 4 byte sprite holding palette, priority, and flip bits
+const OAM_ATTRIBUTE_BYTE: u8 = 2;
+/// bits of the attribute byte that exist in OAM
+const OAM_ATTRIBUTE_BITS: u8 = 0b1110_0011;
+const SPRITES_PER_SCANLINE: usize = 8;
+const SECONDARY_OAM_SIZE: usize = SPRITES_PER_SCANLINE * 4;
+
+/// The picture processing unit. With the serde feature it serializes
+/// everything write_state saves, leaving out the same settings: accuracy,
+/// render mode, and palettes come back as their defaults
+#[derive(Clone, Debug)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct PPU {
     /// VPHB SINN | NMI enable (V), PPU master/slave (P), sprite height (H), background tile select (B), sprite tile select (S), increment mode (I), nametable select (NN)
     pub ppu_ctrl: u8,
@@ -25,19 +96,88 @@ pub struct PPU {
     pub oam_addr: u8,
     /// dddd dddd | OAM data read/write
     pub oam_data: u8,
-    /// xxxx xxxx | fine scroll position (two writes: X scroll, Y scroll)
-    pub ppu_scroll: u8,
-    /// aaaa aaaa | PPU read/write address (two writes: most significant byte, least significant byte)
-    pub ppu_addr_low: u8,
-    pub ppu_addr_high: u8,
-    // This needs to be a mutex
-    pub ppu_addr_received_first_write: bool,
+    /// yyy NN YYYYY XXXXX | current VRAM address, doubles as the scroll
+    /// position while rendering: fine Y (y), nametable (NN), coarse Y (Y),
+    /// coarse X (X)
+    pub v: u16,
+    /// yyy NN YYYYY XXXXX | temporary VRAM address, the scroll position
+    /// copied into v at the start of each frame and scanline
+    pub t: u16,
+    /// xxx | fine X scroll
+    pub fine_x: u8,
+    /// write latch shared by $2005 and $2006, set after the first write of a pair
+    pub w: bool,
+    /// internal buffer returned by the next $2007 read
+    pub ppu_data_buffer: u8,
+    /// dddd dddd | the PPU's I/O data bus, holding the last value written to
+    /// or read from a register, returned for write-only registers and the
+    /// unused bits of $2002
+    pub io_latch: u8,
+    /// frames until each bit of io_latch decays to 0 without a refresh, only
+    /// counted down under AccuracyProfile::Accurate
+    pub io_latch_decay: [u8; 8],
     /// OAM DMA high address
     pub oam_dma: u8,
-    /// PPU address space
-    pub address_space: [u8; PPU_MEMORY_SIZE],
+    /// PPU address space, decoded onto the cartridge, nametable RAM, and
+    /// palette RAM
+    pub address_space: PpuBus,
     /// Object Attribute Memory (OAM) array
+    #[cfg_attr(feature = "serde", serde(with = "crate::nes::save_state::byte_array"))]
     pub oam_ram: [u8; OAM_SIZE],
+    /// sprites found by evaluation for the next scanline, unused slots are $FF
+    pub secondary_oam: [u8; SECONDARY_OAM_SIZE],
+    /// number of sprites in secondary_oam
+    pub secondary_oam_sprites: usize,
+    /// set when sprite 0 is among the sprites in secondary_oam
+    pub sprite_zero_on_line: bool,
+    #[cfg_attr(feature = "serde", serde(skip))]
+    pub accuracy: AccuracyProfile,
+    /// set from the start of vblank until the pre-render scanline, unlike
+    /// the vblank flag it is not cleared by reading $2002
+    pub in_vblank_period: bool,
+    #[cfg_attr(feature = "serde", serde(skip))]
+    pub render_mode: RenderMode,
+    /// scanline the PPU is on, 0-239 visible, 241-260 vblank, 261 pre-render,
+    /// PAL runs vblank through 310 and pre-renders on 311
+    pub scanline: usize,
+    /// PPU cycle within the scanline, 0-340
+    pub dot: usize,
+    /// set when the PPU pulls /NMI low, cleared once the CPU takes the NMI
+    pub nmi_pending: bool,
+    /// set when a finished frame is ready to display, at the start of vblank
+    pub frame_ready: bool,
+    /// flips every frame, odd frames skip a dot when rendering is enabled
+    pub odd_frame: bool,
+    /// dots since power on at the start of this frame, modulo 3, which sets
+    /// the phase of the color subcarrier for the NTSC filter
+    pub frame_phase: usize,
+    /// frames completed since power on, counted as the PPU wraps back to
+    /// scanline 0
+    pub frame_count: u64,
+    /// level of bit 12 of the PPU address bus, which selects the pattern
+    /// table being fetched from
+    pub a12: bool,
+    /// dots A12 has been low for, see A12_FILTER_DOTS
+    pub a12_low_dots: usize,
+    /// set on a dot where A12 rose after being low long enough to get past
+    /// the MMC3's filter, which is what clocks scanline counters
+    pub a12_rose: bool,
+    /// pixels drawn so far this frame, see PIXEL_COLOR
+    pub pixels: Box<[u8]>,
+    /// PPUMASK as each line was drawn, for its color emphasis
+    #[cfg_attr(feature = "serde", serde(with = "crate::nes::save_state::byte_array"))]
+    pub line_masks: [u8; FRAME_HEIGHT],
+    /// RGB color of each system palette index under each combination of
+    /// emphasis bits, see emphasis_palettes
+    #[cfg_attr(feature = "serde", serde(skip, default = "default_palettes"))]
+    pub palettes: EmphasisPalettes,
+    /// television system, which sets the number of scanlines per frame
+    pub region: Region,
+}
+
+/// palettes built from the standard system palette
+fn default_palettes() -> EmphasisPalettes {
+    emphasis_palettes(&SYSTEM_COLOR_PALETTE)
 }
 
 impl Default for PPU {
@@ -48,68 +188,634 @@ impl Default for PPU {
             ppu_status: 0x80,
             oam_addr: Default::default(),
             oam_data: Default::default(),
-            ppu_scroll: Default::default(),
-            ppu_addr_low: Default::default(),
-            ppu_addr_high: Default::default(),
-            ppu_addr_received_first_write: Default::default(),
+            v: Default::default(),
+            t: Default::default(),
+            fine_x: Default::default(),
+            w: Default::default(),
+            ppu_data_buffer: Default::default(),
+            io_latch: Default::default(),
+            io_latch_decay: Default::default(),
             oam_dma: Default::default(),
-            address_space: [0; PPU_MEMORY_SIZE],
+            address_space: Default::default(),
             oam_ram: [0; OAM_SIZE],
+            secondary_oam: [0xff; SECONDARY_OAM_SIZE],
+            secondary_oam_sprites: Default::default(),
+            sprite_zero_on_line: Default::default(),
+            accuracy: Default::default(),
+            in_vblank_period: Default::default(),
+            render_mode: Default::default(),
+            scanline: Default::default(),
+            dot: Default::default(),
+            nmi_pending: Default::default(),
+            frame_ready: Default::default(),
+            odd_frame: Default::default(),
+            frame_phase: Default::default(),
+            frame_count: Default::default(),
+            a12: Default::default(),
+            a12_low_dots: Default::default(),
+            a12_rose: Default::default(),
+            pixels: vec![0; FRAME_BUFFER_SIZE].into_boxed_slice(),
+            line_masks: [0; FRAME_HEIGHT],
+            palettes: default_palettes(),
+            region: Default::default(),
         }
     }
 }
 
+/// A CPU write to one of the PPU's registers, along with the state needed to
+/// decode it
+#[derive(Copy, Clone, Debug)]
+pub struct PpuRegisterWrite {
+    pub address: u16,
+    pub data: u8,
+    /// state of the $2005/$2006 write latch before the write
+    pub second_write: bool,
+    /// PPUADDR before the write
+    pub vram_address: u16,
+}
+
+/// A PpuRegisterWrite and where the PPU was drawing when the instruction
+/// making it started
+#[derive(Copy, Clone, Debug)]
+pub struct LoggedPpuWrite {
+    pub frame: u64,
+    pub scanline: usize,
+    pub dot: usize,
+    pub write: PpuRegisterWrite,
+}
+
+impl fmt::Display for LoggedPpuWrite {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "frame {} scanline {:>3} dot {:>3} | {}",
+            self.frame,
+            self.scanline,
+            self.dot,
+            self.write.describe()
+        )
+    }
+}
+
+fn on_off(flag: bool) -> &'static str {
+    if flag {
+        "on"
+    } else {
+        "off"
+    }
+}
+
+impl PpuRegisterWrite {
+    /// decodes the write into a human readable description, e.g.
+    /// "PPUCTRL: NMI on, NT=1, increment 1, sprites 8x16, BG $0000, sprites $1000"
+    pub fn describe(&self) -> String {
+        let data = self.data;
+        match self.address {
+            0x2000 => {
+                let ctrl = PPUCTRL::from_bits_truncate(data);
+                format!(
+                    "PPUCTRL: NMI {}, NT={}, increment {}, sprites {}, BG ${:0>4x}, sprites ${:0>4x}",
+                    on_off(ctrl.contains(PPUCTRL::GEN_NMI)),
+                    data & PPUCTRL::NATETABLE_ADDR.bits(),
+                    if ctrl.contains(PPUCTRL::VRAM_INCR) { 32 } else { 1 },
+                    if ctrl.contains(PPUCTRL::SPRITE_SIZE) { "8x16" } else { "8x8" },
+                    if ctrl.contains(PPUCTRL::BG_PATTERN_TABLE) { 0x1000 } else { 0 },
+                    if ctrl.contains(PPUCTRL::SPRITE_PATTERN_TABLE) { 0x1000 } else { 0 },
+                )
+            }
+            0x2001 => {
+                let mask = PPUMASK::from_bits_truncate(data);
+                format!(
+                    "PPUMASK: BG {}, sprites {}, left BG {}, left sprites {}, greyscale {}, emphasis {}{}{}",
+                    on_off(mask.contains(PPUMASK::SHOW_BG)),
+                    on_off(mask.contains(PPUMASK::SHOW_SPRITE)),
+                    on_off(mask.contains(PPUMASK::SHOW_BG_LEFT)),
+                    on_off(mask.contains(PPUMASK::SHOW_SPRITE_LEFT)),
+                    on_off(mask.contains(PPUMASK::GREYSCALE)),
+                    if mask.contains(PPUMASK::EMPH_RED) { "R" } else { "-" },
+                    if mask.contains(PPUMASK::EMPH_GREEN) { "G" } else { "-" },
+                    if mask.contains(PPUMASK::EMPH_BLUE) { "B" } else { "-" },
+                )
+            }
+            0x2003 => format!("OAMADDR: ${data:0>2x}"),
+            0x2004 => format!("OAMDATA: ${data:0>2x}"),
+            0x2005 if self.second_write => format!("PPUSCROLL: Y={data}"),
+            0x2005 => format!("PPUSCROLL: X={data}"),
+            0x2006 if self.second_write => format!("PPUADDR: low byte ${data:0>2x}"),
+            0x2006 => format!("PPUADDR: high byte ${:0>2x}", data & 0x3f),
+            0x2007 => format!("PPUDATA: ${data:0>2x} to ${:0>4x}", self.vram_address),
+            0x4014 => format!("OAMDMA: page ${data:0>2x}00"),
+            _ => format!("${:0>4x}: ${data:0>2x}", self.address),
+        }
+    }
+}
+
+/// Consumer of the PPU's filtered A12 rises, given the PPU as it is on the
+/// dot of the rise, for prototyping scanline counting mappers
+pub struct A12Callback(pub Box<dyn FnMut(&PPU)>);
+
+impl fmt::Debug for A12Callback {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str("A12Callback")
+    }
+}
+
+/// called with the scanline number and the PPU
+pub type ScanlineFn = dyn FnMut(u16, &PPU);
+
+/// Consumer of the PPU at a fixed dot of every scanline, given the scanline
+/// number and the PPU, for raster effects, debugging overlays, and
+/// prototyping mappers
+pub struct ScanlineCallback {
+    /// dot the callback runs on, 0-340. The pre-render line's dot 340 is
+    /// skipped on odd NTSC frames while rendering
+    pub dot: usize,
+    pub callback: Box<ScanlineFn>,
+}
+
+impl fmt::Debug for ScanlineCallback {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("ScanlineCallback")
+            .field("dot", &self.dot)
+            .finish_non_exhaustive()
+    }
+}
+
+/// steps v to the next tile horizontally, wrapping into the neighboring
+/// nametable at the edge of the current one
+fn increment_coarse_x(v: &mut u16) {
+    if *v & 0x001f == 31 {
+        *v &= !0x001f;
+        *v ^= 0x0400;
+    } else {
+        *v += 1;
+    }
+}
+
+/// steps v down one pixel, moving to the next row of tiles after fine Y
+/// overflows and wrapping into the neighboring nametable after row 29
+fn increment_y(v: &mut u16) {
+    if *v & 0x7000 != 0x7000 {
+        *v += 0x1000;
+    } else {
+        *v &= !0x7000;
+        let mut coarse_y = (*v & 0x03e0) >> 5;
+        if coarse_y == 29 {
+            coarse_y = 0;
+            *v ^= 0x0800;
+        } else if coarse_y == 31 {
+            // coarse Y can be pointed into the attribute table, in which
+            // case it wraps without switching nametables
+            coarse_y = 0;
+        } else {
+            coarse_y += 1;
+        }
+        *v = (*v & !0x03e0) | (coarse_y << 5);
+    }
+}
+
 impl PPU {
-    // (X,Y) (256,240) (32,30)
-    fn fetch_nametable_byte(&self, x_pixel: &mut usize, y_pixel: &mut usize) -> u8 {
-        // calculate nametable coordinate
-        let x_nametable = *x_pixel / TILE_SIZE;
-        let y_nametable = *y_pixel / TILE_SIZE;
-        let index = y_nametable * FRAME_WIDTH_IN_TILES + x_nametable;
-        // TODO: add support for all 4 nametables
-        let index = index + 0x2000; // add nametable address to index
-        self.address_space[index]
-    }
-
-    fn fetch_attribute_byte(&self, x_pixel: &mut usize, y_pixel: &mut usize) -> u8 {
-        // calculate attribute table coordinate
-        let x_attribute_table = *x_pixel / ATTRIBUTE_TABLE_COVERAGE_SIZE;
-        let y_attribute_table = *y_pixel / ATTRIBUTE_TABLE_COVERAGE_SIZE;
-        let index = y_attribute_table * 8 + x_attribute_table;
-        // TODO: add support for all 4 nametables
-        let index = index + 0x23C0; // add attribute table address to index
-        self.address_space[index]
+    /// $2000 write
+    pub fn ppu_ctrl_write(&mut self, data: u8) {
+        let nmi_enabled = PPUCTRL::GEN_NMI.bits();
+        // enabling NMI during vblank pulls /NMI low straight away
+        if self.ppu_ctrl & nmi_enabled == 0
+            && data & nmi_enabled == nmi_enabled
+            && self.ppu_status & PPUSTATUS::IN_VBLANK.bits() == PPUSTATUS::IN_VBLANK.bits()
+        {
+            self.nmi_pending = true;
+        }
+        self.ppu_ctrl = data;
+        // the nametable select bits live in t
+        self.t = (self.t & !0x0c00) | ((u16::from(data) & 0x03) << 10);
     }
 
-    /// returns back subpalette index in the lowest two bytes of a u8
-    fn fetch_palette_index_from_attribute_byte(
-        &self,
-        attribute_byte: u8,
-        x_pixel: &mut usize,
-        y_pixel: &mut usize,
-    ) -> u8 {
-        let x = *x_pixel % ATTRIBUTE_TABLE_COVERAGE_SIZE;
-        let y = *y_pixel % ATTRIBUTE_TABLE_COVERAGE_SIZE;
-        // deconstruct the attribute byte to determine subpalette index
-        // and wipe upper six bits if necessary. We check if our variables
-        // are greater than 15 because 0-15 represents the first 16 bytes,
-        // and 16-32 represents the next 16 bytes.
-        if x > 15 && y > 15 {
-            // bottom right quadrant
-            attribute_byte >> 6
-        } else if y > 15 {
-            // bottom left quadrant
-            (attribute_byte >> 4) & 0b00000011
-        } else if x > 15 {
-            // top right quadrant
-            (attribute_byte >> 2) & 0b00000011
+    /// $2001 write
+    pub fn ppu_mask_write(&mut self, data: u8) {
+        self.ppu_mask = data;
+    }
+
+    /// $2002 read, clears the vblank flag and resets the $2005/$2006 write
+    /// latch. The low five bits are not driven and read back the I/O latch
+    pub fn ppu_status_read(&mut self) -> u8 {
+        let status = self.ppu_status;
+        self.ppu_status &= !PPUSTATUS::IN_VBLANK.bits();
+        self.w = false;
+        self.refresh_io_latch(status, 0xe0);
+        self.io_latch
+    }
+
+    /// returns what a $2002 read would without clearing anything
+    pub fn ppu_status_peek(&self) -> u8 {
+        (self.ppu_status & 0xe0) | (self.io_latch & 0x1f)
+    }
+
+    /// read of a write-only register, which returns the I/O latch
+    pub fn open_bus_read(&self) -> u8 {
+        self.io_latch
+    }
+
+    /// charges the bits of the I/O latch selected by mask with those of data
+    pub fn refresh_io_latch(&mut self, data: u8, mask: u8) {
+        self.io_latch = (self.io_latch & !mask) | (data & mask);
+        for (bit, decay) in self.io_latch_decay.iter_mut().enumerate() {
+            if mask & 1 << bit != 0 {
+                *decay = IO_LATCH_DECAY_FRAMES;
+            }
+        }
+    }
+
+    /// drains the I/O latch by a frame, clearing bits left unrefreshed for
+    /// IO_LATCH_DECAY_FRAMES
+    fn decay_io_latch(&mut self) {
+        if self.accuracy != AccuracyProfile::Accurate {
+            return;
+        }
+        for (bit, decay) in self.io_latch_decay.iter_mut().enumerate() {
+            if *decay > 0 {
+                *decay -= 1;
+                if *decay == 0 {
+                    self.io_latch &= !(1 << bit);
+                }
+            }
+        }
+    }
+
+    /// pressing reset clears the write-only registers, the $2005/$2006 write
+    /// latch, and the read buffer. VRAM, OAM, and the position of the beam
+    /// are left alone
+    pub fn reset(&mut self) {
+        self.ppu_ctrl = 0;
+        self.ppu_mask = 0;
+        self.t = 0;
+        self.fine_x = 0;
+        self.w = false;
+        self.ppu_data_buffer = 0;
+        self.odd_frame = false;
+    }
+
+    /// sets the vblank flag at the start of vertical blank, raising an NMI if
+    /// PPUCTRL enables them
+    pub fn start_vblank(&mut self) {
+        self.ppu_status |= PPUSTATUS::IN_VBLANK.bits();
+        self.in_vblank_period = true;
+        if self.ppu_ctrl & PPUCTRL::GEN_NMI.bits() == PPUCTRL::GEN_NMI.bits() {
+            self.nmi_pending = true;
+        }
+    }
+
+    /// clears the vblank and sprite overflow flags on the pre-render
+    /// scanline. Sprites are not evaluated on this line, so none are drawn on
+    /// the first visible line
+    pub fn end_vblank(&mut self) {
+        self.ppu_status &= !(PPUSTATUS::IN_VBLANK.bits() | PPUSTATUS::SPRITE_OVERFLOW.bits());
+        self.in_vblank_period = false;
+        self.secondary_oam = [0xff; SECONDARY_OAM_SIZE];
+        self.secondary_oam_sprites = 0;
+        self.sprite_zero_on_line = false;
+    }
+
+    /// advances the PPU by a single dot
+    pub fn tick(&mut self) {
+        self.dot += 1;
+        let scanlines_per_frame = self.region.scanlines_per_frame();
+        let pre_render_scanline = scanlines_per_frame - 1;
+        // with rendering enabled, the idle dot at the end of the pre-render
+        // scanline is skipped on odd frames, only by the NTSC PPU
+        let skip_idle_dot = self.scanline == pre_render_scanline
+            && self.dot == DOTS_PER_SCANLINE - 1
+            && self.odd_frame
+            && self.rendering_enabled()
+            && self.region == Region::Ntsc;
+        if self.dot == DOTS_PER_SCANLINE || skip_idle_dot {
+            self.dot = 0;
+            self.scanline = (self.scanline + 1) % scanlines_per_frame;
+            if self.scanline == 0 {
+                self.odd_frame = !self.odd_frame;
+                let frame_dots =
+                    scanlines_per_frame * DOTS_PER_SCANLINE - usize::from(skip_idle_dot);
+                self.frame_phase = (self.frame_phase + frame_dots) % 3;
+                self.frame_count += 1;
+            }
+        }
+        match (self.scanline, self.dot) {
+            (scanline, LINE_END_DOT) if scanline < FRAME_HEIGHT => {
+                self.render_scanline(self.scanline);
+                if self.rendering_enabled() {
+                    increment_y(&mut self.v);
+                    if self.evaluate_sprites(self.scanline) {
+                        self.ppu_status |= PPUSTATUS::SPRITE_OVERFLOW.bits();
+                    }
+                }
+            }
+            (scanline, HORIZONTAL_RELOAD_DOT)
+                if (scanline < FRAME_HEIGHT || scanline == pre_render_scanline)
+                    && self.rendering_enabled() =>
+            {
+                self.v = (self.v & !HORIZONTAL_SCROLL_BITS) | (self.t & HORIZONTAL_SCROLL_BITS);
+            }
+            (scanline, VERTICAL_RELOAD_DOT)
+                if scanline == pre_render_scanline && self.rendering_enabled() =>
+            {
+                self.v = (self.v & !VERTICAL_SCROLL_BITS) | (self.t & VERTICAL_SCROLL_BITS);
+            }
+            (VBLANK_SCANLINE, 1) => {
+                self.decay_io_latch();
+                self.start_vblank();
+                self.frame_ready = true;
+            }
+            (scanline, 1) if scanline == pre_render_scanline => self.end_vblank(),
+            _ => {}
+        }
+
+        let a12 = self.fetch_a12(pre_render_scanline);
+        self.a12_rose = a12 && !self.a12 && self.a12_low_dots >= A12_FILTER_DOTS;
+        self.a12_low_dots = if a12 { 0 } else { self.a12_low_dots + 1 };
+        self.a12 = a12;
+    }
+
+    /// returns the level of A12 on the current dot. While rendering, each 8
+    /// dot fetch group reads the nametable and attribute tables, where A12 is
+    /// low, then the two pattern bytes from the background or sprite table.
+    /// Otherwise the address bus holds v
+    fn fetch_a12(&self, pre_render_scanline: usize) -> bool {
+        let fetching = self.scanline < FRAME_HEIGHT || self.scanline == pre_render_scanline;
+        if !self.rendering_enabled() || !fetching {
+            return self.v & 0x1000 != 0;
+        }
+        match self.dot {
+            0 => false,
+            dot if dot > TILE_PREFETCH_END_DOT || (dot - 1) % 8 < 4 => false,
+            dot if (SPRITE_FETCH_DOT..TILE_PREFETCH_DOT).contains(&dot) => {
+                let slot = (dot - SPRITE_FETCH_DOT) / 8;
+                if self.sprite_height() == 16 {
+                    self.secondary_oam[slot * 4 + 1] & 0x01 != 0
+                } else {
+                    self.ppu_ctrl & PPUCTRL::SPRITE_PATTERN_TABLE.bits() != 0
+                }
+            }
+            _ => self.ppu_ctrl & PPUCTRL::BG_PATTERN_TABLE.bits() != 0,
+        }
+    }
+
+    /// returns the scanline being drawn, 0-239 visible, then post-render,
+    /// vblank, and the pre-render line last
+    pub fn scanline(&self) -> usize {
+        self.scanline
+    }
+
+    /// returns the dot within the scanline, 0-340
+    pub fn dot(&self) -> usize {
+        self.dot
+    }
+
+    /// returns the number of frames completed since power on
+    pub fn frame_count(&self) -> u64 {
+        self.frame_count
+    }
+
+    /// returns whether or not PPUMASK enables background or sprite rendering
+    pub fn rendering_enabled(&self) -> bool {
+        self.ppu_mask & (PPUMASK::SHOW_BG.bits() | PPUMASK::SHOW_SPRITE.bits()) != 0
+    }
+
+    /// returns whether or not the PPU is fetching from VRAM to draw the
+    /// picture, during which $2007 accesses collide with the fetches
+    pub fn rendering_in_progress(&self) -> bool {
+        self.rendering_enabled() && !self.in_vblank_period
+    }
+
+    fn sprite_height(&self) -> usize {
+        if self.ppu_ctrl & PPUCTRL::SPRITE_SIZE.bits() == PPUCTRL::SPRITE_SIZE.bits() {
+            16
         } else {
-            // top left quadrant
-            attribute_byte & 0b00000011
+            8
         }
     }
 
-    fn fetch_line_from_pattern_table(&self, nametable_index: u8, y_pixel: &mut usize) -> (u8, u8) {
+    /// returns the low and high pattern bytes of a row of a sprite's tile,
+    /// row counts down from the top of the sprite before any flipping. 8x16
+    /// sprites take their pattern table from bit 0 of the tile number and
+    /// are drawn from an even/odd pair of tiles
+    fn fetch_sprite_pattern_row(&self, tile: u8, row: usize) -> (u8, u8) {
+        let address = if self.sprite_height() == 16 {
+            let table = usize::from(tile & 0x01) * 0x1000;
+            let tile = usize::from(tile & 0xfe) + row / TILE_SIZE;
+            table + tile * 16 + row % TILE_SIZE
+        } else {
+            let table = if self.ppu_ctrl & PPUCTRL::SPRITE_PATTERN_TABLE.bits() != 0 {
+                0x1000
+            } else {
+                0x0000
+            };
+            table + usize::from(tile) * 16 + row
+        };
+        self.address_space.pattern_row(address as u16)
+    }
+
+    /// returns whether or not the sprite with a given y coordinate is drawn
+    /// on the scanline after this one
+    fn sprite_in_range(&self, y: u8, scanline: usize) -> bool {
+        scanline.wrapping_sub(usize::from(y)) < self.sprite_height()
+    }
+
+    /// copies the sprites on the scanline after this one into secondary OAM,
+    /// the way the PPU does during dots 65-256, and returns whether or not
+    /// the sprite overflow flag should be set
+    pub fn evaluate_sprites(&mut self, scanline: usize) -> bool {
+        let mut secondary_oam = [0xff; SECONDARY_OAM_SIZE];
+        let mut sprite_count = 0;
+        let mut sprite_zero = false;
+        let overflow = self.scan_oam(scanline, |n, sprite| {
+            secondary_oam[sprite_count * 4..sprite_count * 4 + 4].copy_from_slice(sprite);
+            sprite_count += 1;
+            sprite_zero |= n == 0;
+        });
+        self.secondary_oam = secondary_oam;
+        self.secondary_oam_sprites = sprite_count;
+        self.sprite_zero_on_line = sprite_zero;
+        overflow
+    }
+
+    /// returns whether or not sprite evaluation on a scanline finds more than
+    /// eight sprites, without touching secondary OAM
+    pub fn evaluate_sprite_overflow(&self, scanline: usize) -> bool {
+        self.scan_oam(scanline, |_, _| {})
+    }
+
+    /// steps through OAM in order, passing the index and bytes of each of the
+    /// first eight sprites in range to found, then keeps looking for a ninth
+    /// to decide sprite overflow. Under AccuracyProfile::Accurate this
+    /// reproduces the hardware bug where, after the eighth sprite is found,
+    /// the PPU steps through OAM diagonally and compares tile, attribute, and
+    /// x bytes as if they were y coordinates, producing false positives and
+    /// negatives
+    fn scan_oam(&self, scanline: usize, mut found: impl FnMut(usize, &[u8])) -> bool {
+        let mut n = 0;
+        let mut count = 0;
+        while n < OAM_SPRITE_COUNT && count < SPRITES_PER_SCANLINE {
+            if self.sprite_in_range(self.oam_ram[n * 4], scanline) {
+                found(n, &self.oam_ram[n * 4..n * 4 + 4]);
+                count += 1;
+            }
+            n += 1;
+        }
+
+        let mut m = 0;
+        while n < OAM_SPRITE_COUNT && count == SPRITES_PER_SCANLINE {
+            if self.sprite_in_range(self.oam_ram[n * 4 + m], scanline) {
+                return true;
+            }
+            n += 1;
+            if self.accuracy == AccuracyProfile::Accurate {
+                m = (m + 1) % 4;
+            }
+        }
+        false
+    }
+
+    /// $2003 write
+    pub fn oam_addr_write(&mut self, data: u8) {
+        self.oam_addr = data;
+    }
+
+    /// $2004 read, returns the OAM byte at OAMADDR without incrementing it.
+    /// Bits 2-4 of each sprite's attribute byte are not stored and read as 0
+    pub fn oam_data_read(&mut self) -> u8 {
+        let data = self.oam_data_peek();
+        self.oam_data = data;
+        self.refresh_io_latch(data, 0xff);
+        data
+    }
+
+    /// returns what a $2004 read would without touching the I/O latch
+    pub fn oam_data_peek(&self) -> u8 {
+        let data = self.oam_ram[usize::from(self.oam_addr)];
+        if self.oam_addr & 0x03 == OAM_ATTRIBUTE_BYTE {
+            return data & OAM_ATTRIBUTE_BITS;
+        }
+        data
+    }
+
+    /// $2004 write, stores data at OAMADDR and increments OAMADDR. While
+    /// rendering, OAM is busy with sprite evaluation so the write is dropped
+    /// and OAMADDR takes a glitchy step of 4, skipping its low two bits
+    pub fn oam_data_write(&mut self, data: u8) {
+        self.oam_data = data;
+        if self.rendering_in_progress() {
+            self.oam_addr = self.oam_addr.wrapping_add(4);
+            return;
+        }
+        self.oam_ram[usize::from(self.oam_addr)] = data;
+        self.oam_addr = self.oam_addr.wrapping_add(1);
+    }
+
+    /// $2005 write, the first write of a pair sets X scroll, the second Y
+    pub fn ppu_scroll_write(&mut self, data: u8) {
+        if !self.w {
+            self.t = (self.t & !0x001f) | u16::from(data >> 3);
+            self.fine_x = data & 0x07;
+        } else {
+            self.t = (self.t & !0x73e0)
+                | ((u16::from(data) & 0x07) << 12)
+                | ((u16::from(data) & 0xf8) << 2);
+        }
+        self.w = !self.w;
+    }
+
+    /// $2006 write, the first write of a pair sets the high byte of the
+    /// address, the second the low byte and then copies t into v
+    pub fn ppu_addr_write(&mut self, data: u8) {
+        if !self.w {
+            // the PPU address space is 14 bits wide
+            self.t = (self.t & 0x00ff) | ((u16::from(data) & 0x3f) << 8);
+        } else {
+            self.t = (self.t & 0xff00) | u16::from(data);
+            self.v = self.t;
+        }
+        self.w = !self.w;
+    }
+
+    /// returns the address held in PPUADDR
+    pub fn ppu_addr(&self) -> u16 {
+        self.v & 0x3fff
+    }
+
+    /// steps PPUADDR by 1 or 32 depending on PPUCTRL's increment mode. While
+    /// rendering, v is the scroll position and a $2007 access instead bumps
+    /// coarse X and Y together
+    fn increment_ppu_addr(&mut self) {
+        if self.rendering_in_progress() {
+            increment_coarse_x(&mut self.v);
+            increment_y(&mut self.v);
+            return;
+        }
+        let increment = if self.ppu_ctrl & PPUCTRL::VRAM_INCR.bits() == PPUCTRL::VRAM_INCR.bits() {
+            32
+        } else {
+            1
+        };
+        self.v = self.v.wrapping_add(increment) & 0x7fff;
+    }
+
+    /// $2007 read. Reads below the palette return the contents of the
+    /// internal buffer and refill it, so they lag one read behind. Palette
+    /// reads return immediately, while the buffer is filled with the
+    /// nametable byte underneath the palette
+    pub fn ppu_data_read(&mut self) -> u8 {
+        let address = self.ppu_addr();
+        if address & 0x3fff >= 0x3f00 {
+            self.ppu_data_buffer = self.address_space.read(address - 0x1000);
+            // palette entries are six bits, the top two come from the latch
+            let color = self.address_space.read(address);
+            self.refresh_io_latch(color, 0x3f);
+        } else {
+            let buffered = self.ppu_data_buffer;
+            self.ppu_data_buffer = self.address_space.read(address);
+            self.refresh_io_latch(buffered, 0xff);
+        }
+        self.increment_ppu_addr();
+        self.io_latch
+    }
+
+    /// returns what a $2007 read would without refilling the read buffer or
+    /// moving PPUADDR
+    pub fn ppu_data_peek(&self) -> u8 {
+        let address = self.ppu_addr();
+        if address >= 0x3f00 {
+            (self.io_latch & 0xc0) | (self.address_space.read(address) & 0x3f)
+        } else {
+            self.ppu_data_buffer
+        }
+    }
+
+    /// $2007 write, stores data at PPUADDR and increments PPUADDR
+    pub fn ppu_data_write(&mut self, data: u8) {
+        let address = self.ppu_addr();
+        self.address_space.write(address, data);
+        self.increment_ppu_addr();
+    }
+
+    fn fetch_nametable_byte(&self, v: u16) -> u8 {
+        self.address_space.read(0x2000 | (v & 0x0fff))
+    }
+
+    fn fetch_attribute_byte(&self, v: u16) -> u8 {
+        // each attribute byte covers a 4x4 tile area, so the coarse
+        // coordinates are divided by 4 to index the attribute table
+        let index = 0x23c0 | (v & 0x0c00) | ((v >> 4) & 0x38) | ((v >> 2) & 0x07);
+        self.address_space.read(index)
+    }
+
+    /// returns back subpalette index in the lowest two bytes of a u8
+    fn fetch_palette_index_from_attribute_byte(&self, attribute_byte: u8, v: u16) -> u8 {
+        // deconstruct the attribute byte to determine subpalette index. Bit 1
+        // of coarse X and coarse Y pick the quadrant of the 4x4 tile area
+        let shift = ((v >> 4) & 0x04) | (v & 0x02);
+        (attribute_byte >> shift) & 0b00000011
+    }
+
+    fn fetch_line_from_pattern_table(&self, nametable_index: u8, fine_y: usize) -> (u8, u8) {
         let background_pattern_table: usize = if self.ppu_ctrl & PPUCTRL::BG_PATTERN_TABLE.bits()
             == PPUCTRL::BG_PATTERN_TABLE.bits()
         {
@@ -118,84 +824,236 @@ impl PPU {
             0x0000
         };
         let index = background_pattern_table + usize::from(nametable_index) * 16;
-        let line_within_tile = *y_pixel % TILE_SIZE;
-        let index = index + line_within_tile;
-        (self.address_space[index], self.address_space[index + 8])
+        let index = index + fine_y;
+        self.address_space.pattern_row(index as u16)
     }
 
-    fn render_tile_line(
-        &self,
-        buffer: &mut [(u8, u8, u8)],
-        x_pixel: &mut usize,
-        y_pixel: &mut usize,
-    ) {
-        let n = self.fetch_nametable_byte(x_pixel, y_pixel);
-        let a = self.fetch_attribute_byte(x_pixel, y_pixel);
-        let (tile_line_low, tile_line_high) = self.fetch_line_from_pattern_table(n, y_pixel);
+    /// returns the system palette index stored at a palette RAM address,
+    /// limited to the grey column when greyscale is on
+    fn fetch_color_index(&self, address: usize) -> usize {
+        let index = usize::from(self.address_space.read(address as u16)) & 0x3f;
+        if self.ppu_mask & PPUMASK::GREYSCALE.bits() == PPUMASK::GREYSCALE.bits() {
+            index & 0x30
+        } else {
+            index
+        }
+    }
 
-        // determine the tile's color palette
-        let palette_index = self.fetch_palette_index_from_attribute_byte(a, x_pixel, y_pixel);
+    /// returns the pixel drawn where no background or sprite pixel is
+    fn backdrop_pixel(&self) -> u8 {
+        self.fetch_color_index(0x3f00) as u8 | PIXEL_BACKDROP
+    }
 
+    /// returns the pixels for each color of the four background palettes,
+    /// color 0 being the backdrop. Palette RAM cannot change part way
+    /// through drawing a line, so a line looks these up once rather than for
+    /// every tile
+    fn background_palettes(&self) -> [[u8; 4]; 4] {
         // $3F00 	    Universal background color
         // $3F01-$3F03 	Background palette 0
         // $3F05-$3F07 	Background palette 1
         // $3F09-$3F0B 	Background palette 2
         // $3F0D-$3F0F 	Background palette 3
+        let backdrop = self.backdrop_pixel();
+        std::array::from_fn(|palette_index| {
+            std::array::from_fn(|color| match color {
+                0 => backdrop,
+                _ => {
+                    self.fetch_color_index(0x3f00 + palette_index * 4 + color) as u8
+                        | PIXEL_BACKGROUND
+                }
+            })
+        })
+    }
 
-        // store each system color palette index
-        let color_0_index = self.address_space[0x3f00];
-        let color_1_index = self.address_space[0x3f01 + usize::from(palette_index) * 4];
-        let color_2_index = self.address_space[0x3f02 + usize::from(palette_index) * 4];
-        let color_3_index = self.address_space[0x3f03 + usize::from(palette_index) * 4];
-
-        // fetch rgb values for each color in color palette
-        let color_0 = SYSTEM_COLOR_PALETTE[usize::from(color_0_index)];
-        let color_1 = SYSTEM_COLOR_PALETTE[usize::from(color_1_index)];
-        let color_2 = SYSTEM_COLOR_PALETTE[usize::from(color_2_index)];
-        let color_3 = SYSTEM_COLOR_PALETTE[usize::from(color_3_index)];
+    fn render_tile_line(&self, buffer: &mut [u8], v: u16, palettes: &[[u8; 4]; 4]) {
+        let n = self.fetch_nametable_byte(v);
+        let a = self.fetch_attribute_byte(v);
+        let fine_y = usize::from(v >> 12) & 0x07;
+        let (tile_line_low, tile_line_high) = self.fetch_line_from_pattern_table(n, fine_y);
 
+        // determine the tile's color palette
+        let palette_index = self.fetch_palette_index_from_attribute_byte(a, v);
+        let colors = &palettes[usize::from(palette_index)];
 
         // merge the low and high byte for each pixel and assign color to buffer
-        let mut line_index: u8 = 0x80;
-        for i in 0..8 {
-            if line_index & tile_line_low == line_index && line_index & tile_line_high == line_index
-            {
-                buffer[i] = color_3;
-            } else if line_index & tile_line_high == line_index {
-                buffer[i] = color_2;
-            } else if line_index & tile_line_low == line_index {
-                buffer[i] = color_1;
+        for (i, pixel) in buffer.iter_mut().enumerate() {
+            let bit = 7 - i;
+            let color = (tile_line_low >> bit & 1) | (tile_line_high >> bit & 1) << 1;
+            *pixel = colors[usize::from(color)];
+        }
+    }
+
+    fn render_scanline(&mut self, scanline: usize) {
+        let mut buffer = [0; FRAME_WIDTH];
+        if self.ppu_mask & PPUMASK::SHOW_BG.bits() == 0 {
+            buffer.fill(self.backdrop_pixel());
+        } else {
+            let palettes = self.background_palettes();
+            let mut line = [0; FRAME_WIDTH + TILE_SIZE];
+            let mut v = self.v;
+            for i in 0..=FRAME_WIDTH_IN_TILES {
+                let tile_ref = &mut line[TILE_SIZE * i..TILE_SIZE * i + TILE_SIZE];
+                self.render_tile_line(tile_ref, v, &palettes);
+                increment_coarse_x(&mut v);
+            }
+            let fine_x = usize::from(self.fine_x);
+            buffer.copy_from_slice(&line[fine_x..fine_x + FRAME_WIDTH]);
+
+            if self.ppu_mask & PPUMASK::SHOW_BG_LEFT.bits() == 0 {
+                buffer[..TILE_SIZE].fill(self.backdrop_pixel());
+            }
+        }
+        if self.ppu_mask & PPUMASK::SHOW_SPRITE.bits() != 0 {
+            self.composite_sprites(&mut buffer, scanline);
+        }
+
+        let start = FRAME_WIDTH * scanline;
+        self.pixels[start..start + FRAME_WIDTH].copy_from_slice(&buffer);
+        self.line_masks[scanline] = self.ppu_mask;
+    }
+
+    /// draws the sprites in secondary OAM over a line of background pixels.
+    /// Where sprites overlap, the first opaque one in OAM order decides the
+    /// pixel, even if it is behind the background and so hides the sprites
+    /// after it. A sprite behind the background only shows where the
+    /// background is transparent
+    fn composite_sprites(&self, buffer: &mut [u8; FRAME_WIDTH], scanline: usize) {
+        let height = self.sprite_height();
+        let mut claimed = [false; FRAME_WIDTH];
+        let left_edge = if self.ppu_mask & PPUMASK::SHOW_SPRITE_LEFT.bits() == 0 {
+            TILE_SIZE
+        } else {
+            0
+        };
+        for sprite in self
+            .secondary_oam
+            .chunks_exact(4)
+            .take(self.secondary_oam_sprites)
+        {
+            let [y, tile, attributes, x] = [sprite[0], sprite[1], sprite[2], sprite[3]];
+            // sprites were found on the line before the one they are drawn on
+            let row = scanline.wrapping_sub(usize::from(y) + 1);
+            if row >= height {
+                continue;
+            }
+            let row = if attributes & SPRITE_FLIP_VERTICAL != 0 {
+                height - 1 - row
+            } else {
+                row
+            };
+            let (low, high) = self.fetch_sprite_pattern_row(tile, row);
+            let palette = 0x3f10 + usize::from(attributes & SPRITE_PALETTE) * 4;
+            let source = if attributes & SPRITE_BEHIND_BACKGROUND != 0 {
+                PIXEL_SPRITE_BEHIND
             } else {
-                buffer[i] = color_0;
+                PIXEL_SPRITE_FRONT
+            };
+
+            for column in 0..TILE_SIZE {
+                let x = usize::from(x) + column;
+                if x >= FRAME_WIDTH || x < left_edge || claimed[x] {
+                    continue;
+                }
+                let bit = if attributes & SPRITE_FLIP_HORIZONTAL != 0 {
+                    column
+                } else {
+                    7 - column
+                };
+                let color = (low >> bit & 1) | (high >> bit & 1) << 1;
+                if color == 0 {
+                    continue;
+                }
+                claimed[x] = true;
+                let background_opaque = buffer[x] & !PIXEL_COLOR == PIXEL_BACKGROUND;
+                if source == PIXEL_SPRITE_FRONT || !background_opaque {
+                    buffer[x] = self.fetch_color_index(palette + usize::from(color)) as u8 | source;
+                }
             }
-            line_index = line_index >> 1;
         }
     }
 
-    fn render_frame_line(
-        &self,
-        buffer: &mut [(u8, u8, u8)],
-        x_pixel: &mut usize,
-        y_pixel: &mut usize,
-    ) {
-        for i in 0..FRAME_WIDTH_IN_TILES {
-            let tile_ref = &mut buffer[TILE_SIZE * i..TILE_SIZE * i + TILE_SIZE];
-            self.render_tile_line(tile_ref, x_pixel, y_pixel);
-            *x_pixel += 8;
+    /// returns the system palette tinted by the emphasis bits of mask
+    fn emphasis_palette(&self, mask: u8) -> &[(u8, u8, u8); 64] {
+        &self.palettes[usize::from(mask >> 5)]
+    }
+
+    /// converts the pixels drawn this frame to RGBA, applying the color
+    /// emphasis each line was drawn with
+    pub fn framebuffer(&self) -> Frame {
+        if self.render_mode == RenderMode::Ntsc {
+            return ntsc::filter_frame(&self.pixels, &self.line_masks, self.frame_phase);
+        }
+        let mut frame = Frame::new(FRAME_WIDTH, FRAME_HEIGHT);
+        for (y, pixels) in self.pixels.chunks_exact(FRAME_WIDTH).enumerate() {
+            let palette = self.emphasis_palette(self.line_masks[y]);
+            for (x, &pixel) in pixels.iter().enumerate() {
+                let color = match self.render_mode {
+                    RenderMode::PriorityDebug(colors) => match pixel & !PIXEL_COLOR {
+                        PIXEL_BACKDROP => colors.backdrop,
+                        PIXEL_BACKGROUND => colors.background,
+                        PIXEL_SPRITE_FRONT => colors.sprite_front,
+                        _ => colors.sprite_behind,
+                    },
+                    _ => palette[usize::from(pixel & PIXEL_COLOR)],
+                };
+                frame.set_pixel(x, y, color);
+            }
+        }
+
+        frame
+    }
+}
+
+/// The PPU's registers, $2000-$2007, mirrored through $3FFF
+impl Device for PPU {
+    fn read(&mut self, address: u16) -> u8 {
+        match 0x2000 | (address & 0x0007) {
+            0x2002 => self.ppu_status_read(),
+            0x2004 => self.oam_data_read(),
+            0x2007 => self.ppu_data_read(),
+            _ => self.open_bus_read(),
         }
     }
 
-    pub fn render_frame(&self) -> [(u8, u8, u8); FRAME_BUFFER_SIZE] {
-        let mut frame_buffer: [(u8, u8, u8); FRAME_BUFFER_SIZE] = [(0, 0, 0); FRAME_BUFFER_SIZE];
-        let mut x_pixel: usize = 0;
-        let mut y_pixel: usize = 0;
-        for i in 0..FRAME_HEIGHT {
-            let line_ref = &mut frame_buffer[FRAME_WIDTH * i..FRAME_WIDTH * i + FRAME_WIDTH];
-            self.render_frame_line(line_ref, &mut x_pixel, &mut y_pixel);
-            y_pixel += 1;
-            x_pixel = 0;
+    fn write(&mut self, address: u16, data: u8) {
+        self.refresh_io_latch(data, 0xff);
+        match 0x2000 | (address & 0x0007) {
+            0x2000 => self.ppu_ctrl_write(data),
+            0x2001 => self.ppu_mask_write(data),
+            0x2003 => self.oam_addr_write(data),
+            0x2004 => self.oam_data_write(data),
+            0x2005 => self.ppu_scroll_write(data),
+            0x2006 => self.ppu_addr_write(data),
+            0x2007 => self.ppu_data_write(data),
+            // PPUSTATUS is read-only
+            _ => {}
         }
+    }
+
+    fn peek(&self, address: u16) -> Option<u8> {
+        Some(match 0x2000 | (address & 0x0007) {
+            0x2002 => self.ppu_status_peek(),
+            0x2004 => self.oam_data_peek(),
+            0x2007 => self.ppu_data_peek(),
+            _ => self.io_latch,
+        })
+    }
 
-        frame_buffer
+    /// sets PPUCTRL, PPUMASK, and OAMADDR outright, and stores to OAM and
+    /// VRAM without moving OAMADDR or PPUADDR. The scroll and address
+    /// registers only feed the write latch, so pokes to them are ignored
+    fn poke(&mut self, address: u16, data: u8) {
+        match 0x2000 | (address & 0x0007) {
+            0x2000 => self.ppu_ctrl = data,
+            0x2001 => self.ppu_mask = data,
+            0x2003 => self.oam_addr = data,
+            0x2004 => self.oam_ram[usize::from(self.oam_addr)] = data,
+            0x2007 => {
+                let address = self.ppu_addr();
+                self.address_space.write(address, data);
+            }
+            _ => {}
+        }
     }
 }
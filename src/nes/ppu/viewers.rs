@@ -0,0 +1,221 @@
+// Debug views of PPU memory for frontends and tools. They read VRAM through
+// the same paths rendering does, with the registers as they are now, and
+// have no side effects.
+
+use crate::nes::frame::Frame;
+use crate::nes::ppu::{
+    FRAME_HEIGHT, FRAME_HEIGHT_IN_TILES, FRAME_WIDTH, FRAME_WIDTH_IN_TILES, OAM_ATTRIBUTE_BITS,
+    PIXEL_COLOR, PPU, TILE_SIZE,
+};
+use crate::nes::ppu_structs::PPUMASK;
+
+/// width and height of a pattern table drawn as a 16x16 grid of tiles
+const PATTERN_TABLE_SIZE: usize = 16 * TILE_SIZE;
+
+/// A sprite decoded from OAM
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SpriteView {
+    /// position in OAM, 0-63
+    pub index: usize,
+    pub x: u8,
+    /// y coordinate as stored, one less than the first scanline drawn on
+    pub y: u8,
+    pub tile: u8,
+    /// raw attribute byte, without the bits OAM does not store
+    pub attributes: u8,
+    /// sprite palette 0-3
+    pub palette: u8,
+    pub behind_background: bool,
+    pub flip_horizontal: bool,
+    pub flip_vertical: bool,
+    /// the sprite as drawn, 8x8 or 8x16 depending on PPUCTRL, with the
+    /// backdrop color where it is transparent
+    pub thumbnail: Frame,
+}
+
+/// A single palette RAM entry
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct PaletteEntry {
+    /// PPU address of the entry, $3F00-$3F1F
+    pub address: u16,
+    /// system palette index stored at the entry, after mirroring
+    pub index: u8,
+    /// color the entry is drawn in, with greyscale and emphasis applied
+    pub rgb: (u8, u8, u8),
+}
+
+/// Palette RAM as a debugger would show it, along with the PPUMASK bits that
+/// change how it is drawn
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct PaletteView {
+    /// background palettes 0-3 followed by sprite palettes 0-3, 4 entries each
+    pub entries: [PaletteEntry; 32],
+    pub greyscale: bool,
+    /// emphasized color channels, only the EMPH_* bits are set
+    pub emphasis: PPUMASK,
+}
+
+impl PPU {
+    /// returns the system palette with the current emphasis applied
+    fn current_palette(&self) -> [(u8, u8, u8); 64] {
+        *self.emphasis_palette(self.ppu_mask)
+    }
+
+    /// draws all four nametables as a 512x480 image, $2000 top left, $2400
+    /// top right, $2800 bottom left, and $2C00 bottom right. If an overlay
+    /// color is given, the 256x240 area the scroll in t selects is outlined in
+    /// it, wrapping around the edges as the picture does
+    pub fn render_nametables(&self, scroll_overlay: Option<(u8, u8, u8)>) -> Frame {
+        let width = FRAME_WIDTH * 2;
+        let height = FRAME_HEIGHT * 2;
+        let palette = self.current_palette();
+        let background_palettes = self.background_palettes();
+        let mut frame = Frame::new(width, height);
+        let mut tile_line = [0; TILE_SIZE];
+        for nametable in 0..4 {
+            let left = (nametable & 1) * FRAME_WIDTH;
+            let top = (nametable >> 1) * FRAME_HEIGHT;
+            for coarse_y in 0..FRAME_HEIGHT_IN_TILES {
+                for coarse_x in 0..FRAME_WIDTH_IN_TILES {
+                    for fine_y in 0..TILE_SIZE {
+                        let v = (fine_y << 12 | nametable << 10 | coarse_y << 5 | coarse_x) as u16;
+                        self.render_tile_line(&mut tile_line, v, &background_palettes);
+                        for (fine_x, &pixel) in tile_line.iter().enumerate() {
+                            frame.set_pixel(
+                                left + coarse_x * TILE_SIZE + fine_x,
+                                top + coarse_y * TILE_SIZE + fine_y,
+                                palette[usize::from(pixel & PIXEL_COLOR)],
+                            );
+                        }
+                    }
+                }
+            }
+        }
+
+        if let Some(color) = scroll_overlay {
+            let t = usize::from(self.t);
+            let scroll_x = (t & 0x1f) << 3 | usize::from(self.fine_x);
+            let scroll_x = scroll_x + (t >> 10 & 1) * FRAME_WIDTH;
+            let scroll_y = (t >> 5 & 0x1f) << 3 | t >> 12 & 0x07;
+            let scroll_y = scroll_y + (t >> 11 & 1) * FRAME_HEIGHT;
+            for x in 0..FRAME_WIDTH {
+                let x = (scroll_x + x) % width;
+                frame.set_pixel(x, scroll_y % height, color);
+                frame.set_pixel(x, (scroll_y + FRAME_HEIGHT - 1) % height, color);
+            }
+            for y in 0..FRAME_HEIGHT {
+                let y = (scroll_y + y) % height;
+                frame.set_pixel(scroll_x % width, y, color);
+                frame.set_pixel((scroll_x + FRAME_WIDTH - 1) % width, y, color);
+            }
+        }
+
+        frame
+    }
+
+    /// draws the pattern tables at $0000 and $1000 as 128x128 images of
+    /// 16x16 tiles, colored with one of the 8 palettes, 0-3 for the
+    /// background and 4-7 for sprites
+    pub fn render_pattern_tables(&self, palette_index: usize) -> [Frame; 2] {
+        let system_palette = self.current_palette();
+        let colors = [0, 1, 2, 3].map(|color| {
+            let address = if color == 0 {
+                0x3f00
+            } else {
+                0x3f00 + (palette_index % 8) * 4 + color
+            };
+            system_palette[self.fetch_color_index(address)]
+        });
+        [0x0000, 0x1000].map(|table| {
+            let mut frame = Frame::new(PATTERN_TABLE_SIZE, PATTERN_TABLE_SIZE);
+            for tile in 0..256 {
+                let tile_address = table + tile * 16;
+                for fine_y in 0..TILE_SIZE {
+                    let (low, high) = self
+                        .address_space
+                        .pattern_row((tile_address + fine_y) as u16);
+                    for fine_x in 0..TILE_SIZE {
+                        let bit = 7 - fine_x;
+                        let color = (low >> bit & 1) | (high >> bit & 1) << 1;
+                        frame.set_pixel(
+                            tile % 16 * TILE_SIZE + fine_x,
+                            tile / 16 * TILE_SIZE + fine_y,
+                            colors[usize::from(color)],
+                        );
+                    }
+                }
+            }
+            frame
+        })
+    }
+
+    /// returns the 32 palette RAM entries resolved to colors, along with the
+    /// current greyscale and emphasis settings
+    pub fn palette_view(&self) -> PaletteView {
+        let system_palette = self.current_palette();
+        let mask = PPUMASK::from_bits_truncate(self.ppu_mask);
+        let entries = std::array::from_fn(|i| {
+            let address = 0x3f00 + i as u16;
+            PaletteEntry {
+                address,
+                index: self.address_space.read(address) & 0x3f,
+                rgb: system_palette[self.fetch_color_index(usize::from(address))],
+            }
+        });
+        PaletteView {
+            entries,
+            greyscale: mask.contains(PPUMASK::GREYSCALE),
+            emphasis: mask & (PPUMASK::EMPH_RED | PPUMASK::EMPH_GREEN | PPUMASK::EMPH_BLUE),
+        }
+    }
+
+    /// returns the 64 sprites in OAM, decoded and drawn with the current
+    /// sprite size, pattern table, and palettes
+    pub fn sprite_views(&self) -> Vec<SpriteView> {
+        let system_palette = self.current_palette();
+        let height = self.sprite_height();
+        self.oam_ram
+            .chunks_exact(4)
+            .enumerate()
+            .map(|(index, sprite)| {
+                let [y, tile, attributes, x] = [sprite[0], sprite[1], sprite[2], sprite[3]];
+                let attributes = attributes & OAM_ATTRIBUTE_BITS;
+                let palette = attributes & 0x03;
+                let flip_horizontal = attributes & 0x40 != 0;
+                let flip_vertical = attributes & 0x80 != 0;
+
+                let colors = [0, 1, 2, 3].map(|color| {
+                    let address = if color == 0 {
+                        0x3f00
+                    } else {
+                        0x3f10 + usize::from(palette) * 4 + color
+                    };
+                    system_palette[self.fetch_color_index(address)]
+                });
+                let mut thumbnail = Frame::new(TILE_SIZE, height);
+                for row in 0..height {
+                    let source_row = if flip_vertical { height - 1 - row } else { row };
+                    let (low, high) = self.fetch_sprite_pattern_row(tile, source_row);
+                    for column in 0..TILE_SIZE {
+                        let bit = if flip_horizontal { column } else { 7 - column };
+                        let color = (low >> bit & 1) | (high >> bit & 1) << 1;
+                        thumbnail.set_pixel(column, row, colors[usize::from(color)]);
+                    }
+                }
+
+                SpriteView {
+                    index,
+                    x,
+                    y,
+                    tile,
+                    attributes,
+                    palette,
+                    behind_background: attributes & 0x20 != 0,
+                    flip_horizontal,
+                    flip_vertical,
+                    thumbnail,
+                }
+            })
+            .collect()
+    }
+}
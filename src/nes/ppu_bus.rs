@@ -0,0 +1,155 @@
+/// size of the pattern tables at $0000-$1FFF, the cartridge's CHR memory
+pub const CHR_SIZE: usize = 0x2000;
+/// size of one nametable and its attribute table
+pub const NAMETABLE_SIZE: usize = 0x400;
+const NAMETABLE_COUNT: usize = 4;
+pub const PALETTE_RAM_SIZE: usize = 0x20;
+
+/// How the four logical nametables at $2000-$2FFF map onto physical memory.
+/// The console only has room for two, the cartridge wires up which of them
+/// each logical nametable uses
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum Mirroring {
+    /// $2000 and $2400 share a table, as do $2800 and $2C00, for vertical
+    /// scrolling
+    Horizontal,
+    /// $2000 and $2800 share a table, as do $2400 and $2C00, for horizontal
+    /// scrolling
+    Vertical,
+    /// every nametable is the first physical table
+    SingleScreenLower,
+    /// every nametable is the second physical table
+    SingleScreenUpper,
+    /// the cartridge supplies the other two tables, so all four are distinct.
+    /// Used until a ROM says otherwise
+    #[default]
+    FourScreen,
+}
+
+impl Mirroring {
+    /// reads the mirroring from flags 6 of an iNES header
+    pub fn from_ines_header(header: &[u8; 16]) -> Mirroring {
+        if header[6] & 0b0000_1000 != 0 {
+            Mirroring::FourScreen
+        } else if header[6] & 0b0000_0001 != 0 {
+            Mirroring::Vertical
+        } else {
+            Mirroring::Horizontal
+        }
+    }
+
+    /// returns the physical table backing one of the four logical nametables
+    pub fn physical_nametable(self, nametable: usize) -> usize {
+        match self {
+            Mirroring::Horizontal => nametable / 2,
+            Mirroring::Vertical => nametable % 2,
+            Mirroring::SingleScreenLower => 0,
+            Mirroring::SingleScreenUpper => 1,
+            Mirroring::FourScreen => nametable,
+        }
+    }
+}
+
+/// Pattern table memory on the cartridge. Every pattern fetch goes through
+/// read, which is where CHR bank switching slots in
+#[derive(Clone, Debug)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct CartridgeChr {
+    pub bytes: Box<[u8]>,
+    /// set for boards with CHR RAM, CHR ROM ignores writes
+    pub writable: bool,
+}
+
+impl Default for CartridgeChr {
+    fn default() -> CartridgeChr {
+        CartridgeChr {
+            bytes: vec![0; CHR_SIZE].into_boxed_slice(),
+            writable: true,
+        }
+    }
+}
+
+impl CartridgeChr {
+    pub fn read(&self, address: u16) -> u8 {
+        self.bytes[usize::from(address) % CHR_SIZE]
+    }
+
+    pub fn write(&mut self, address: u16, data: u8) {
+        if self.writable {
+            self.bytes[usize::from(address) % CHR_SIZE] = data;
+        }
+    }
+}
+
+/// The PPU's 14-bit address space:
+/// - `$0000-$1FFF` | pattern tables, on the cartridge
+/// - `$2000-$2FFF` | nametables, mirrored onto nametable RAM
+/// - `$3000-$3EFF` | mirror of $2000-$2EFF
+/// - `$3F00-$3FFF` | palette RAM, 32 bytes repeated
+#[derive(Clone, Debug)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct PpuBus {
+    pub chr: CartridgeChr,
+    /// room for four nametables, only the first two are used unless
+    /// mirroring is FourScreen
+    pub nametables: Box<[u8]>,
+    pub palette: [u8; PALETTE_RAM_SIZE],
+    pub mirroring: Mirroring,
+}
+
+impl Default for PpuBus {
+    fn default() -> PpuBus {
+        PpuBus {
+            chr: Default::default(),
+            nametables: vec![0; NAMETABLE_SIZE * NAMETABLE_COUNT].into_boxed_slice(),
+            palette: [0; PALETTE_RAM_SIZE],
+            mirroring: Default::default(),
+        }
+    }
+}
+
+/// returns the index into palette RAM of a palette address, the sprite
+/// palettes' color 0 entries mirror the background's
+pub fn mirror_palette_address(address: u16) -> usize {
+    let palette_index = address & 0x1f;
+    if palette_index & 0x13 == 0x10 {
+        usize::from(palette_index & 0x0f)
+    } else {
+        usize::from(palette_index)
+    }
+}
+
+impl PpuBus {
+    /// returns the index into nametable RAM of an address in $2000-$3EFF
+    pub fn nametable_index(&self, address: u16) -> usize {
+        let offset = usize::from(address) & 0x0fff;
+        let nametable = self.mirroring.physical_nametable(offset / NAMETABLE_SIZE);
+        nametable * NAMETABLE_SIZE + offset % NAMETABLE_SIZE
+    }
+
+    pub fn read(&self, address: u16) -> u8 {
+        match address & 0x3fff {
+            address @ 0x0000..=0x1fff => self.chr.read(address),
+            address @ 0x2000..=0x3eff => self.nametables[self.nametable_index(address)],
+            address => self.palette[mirror_palette_address(address)],
+        }
+    }
+
+    pub fn write(&mut self, address: u16, data: u8) {
+        match address & 0x3fff {
+            address @ 0x0000..=0x1fff => self.chr.write(address, data),
+            address @ 0x2000..=0x3eff => {
+                let index = self.nametable_index(address);
+                self.nametables[index] = data;
+            }
+            address => self.palette[mirror_palette_address(address)] = data,
+        }
+    }
+
+    /// returns the two bit planes of a row of a tile, address being the row
+    /// within the low plane
+    pub fn pattern_row(&self, address: u16) -> (u8, u8) {
+        (self.chr.read(address), self.chr.read(address + 8))
+    }
+}
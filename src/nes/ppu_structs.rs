@@ -1,3 +1,6 @@
+use std::fs;
+use std::io;
+
 use bitflags::bitflags;
 
 bitflags! {
@@ -36,6 +39,133 @@ bitflags! {
     }
 }
 
+/// Trade-off between emulation speed and reproducing hardware quirks
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub enum AccuracyProfile {
+    /// behaves as the hardware was documented to behave
+    #[default]
+    Fast,
+    /// reproduces hardware bugs that some games and test ROMs depend on
+    Accurate,
+}
+
+/// Colors drawn in place of palette colors by the priority debug view, one
+/// per pixel source
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct PriorityDebugColors {
+    /// backdrop, where the background is color 0 or not drawn
+    pub backdrop: (u8, u8, u8),
+    /// opaque background pixel
+    pub background: (u8, u8, u8),
+    /// sprite drawn in front of the background
+    pub sprite_front: (u8, u8, u8),
+    /// sprite with the behind-background priority bit, where the background
+    /// is transparent
+    pub sprite_behind: (u8, u8, u8),
+}
+
+impl Default for PriorityDebugColors {
+    fn default() -> PriorityDebugColors {
+        PriorityDebugColors {
+            backdrop: (0, 0, 0),
+            background: (0, 0, 255),
+            sprite_front: (255, 0, 0),
+            sprite_behind: (255, 255, 0),
+        }
+    }
+}
+
+/// How the PPU turns pixels into colors
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub enum RenderMode {
+    /// palette colors, as the console draws them
+    #[default]
+    Normal,
+    /// flat colors showing where each pixel came from, for finding
+    /// priority and transparency bugs
+    PriorityDebug(PriorityDebugColors),
+    /// palette indices run through a simulated composite video signal, with
+    /// the color fringing and dot crawl of a real television
+    Ntsc,
+}
+
+/// strength of a color channel that is not emphasized while emphasis is on,
+/// as a fraction of NUMERATOR / DENOMINATOR
+const DEEMPHASIS_NUMERATOR: u16 = 3;
+const DEEMPHASIS_DENOMINATOR: u16 = 4;
+
+/// builds the variant of palette produced by the emphasis bits of mask.
+/// Emphasizing a channel darkens the other two, and the blacks in columns
+/// $xE/$xF are left untouched.
+pub fn emphasized_palette(palette: &[(u8, u8, u8); 64], mask: PPUMASK) -> [(u8, u8, u8); 64] {
+    let mut emphasized = *palette;
+    if !mask.intersects(PPUMASK::EMPH_RED | PPUMASK::EMPH_GREEN | PPUMASK::EMPH_BLUE) {
+        return emphasized;
+    }
+    let dim = |channel: u8, emphasize: bool| -> u8 {
+        if emphasize {
+            channel
+        } else {
+            (u16::from(channel) * DEEMPHASIS_NUMERATOR / DEEMPHASIS_DENOMINATOR) as u8
+        }
+    };
+    for (index, color) in emphasized.iter_mut().enumerate() {
+        if index & 0x0e == 0x0e {
+            continue;
+        }
+        let (r, g, b) = *color;
+        *color = (
+            dim(r, mask.contains(PPUMASK::EMPH_RED)),
+            dim(g, mask.contains(PPUMASK::EMPH_GREEN)),
+            dim(b, mask.contains(PPUMASK::EMPH_BLUE)),
+        );
+    }
+    emphasized
+}
+
+/// size of a .pal file holding the 64 system colors as RGB triplets
+const PAL_FILE_SIZE: usize = 64 * 3;
+/// size of a .pal file that also holds the 7 emphasized variants
+const PAL_FILE_WITH_EMPHASIS_SIZE: usize = PAL_FILE_SIZE * 8;
+
+/// the system palette under each combination of emphasis bits, indexed by
+/// the top three bits of PPUMASK shifted down
+pub type EmphasisPalettes = [[(u8, u8, u8); 64]; 8];
+
+/// builds the 8 emphasis variants of palette, so rendering can look colors
+/// up instead of tinting them
+pub fn emphasis_palettes(palette: &[(u8, u8, u8); 64]) -> EmphasisPalettes {
+    std::array::from_fn(|emphasis| {
+        emphasized_palette(palette, PPUMASK::from_bits_truncate((emphasis as u8) << 5))
+    })
+}
+
+/// reads the system colors from a .pal file as exported by FCEUX, Nestopia,
+/// and palette generators. Files that include emphasized variants have them
+/// used as is, otherwise the variants are derived with emphasized_palette
+pub fn read_palette_file(filename: &str) -> io::Result<EmphasisPalettes> {
+    let bytes = fs::read(filename)?;
+    if bytes.len() != PAL_FILE_SIZE && bytes.len() != PAL_FILE_WITH_EMPHASIS_SIZE {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidData,
+            format!(
+                "{filename} is {} bytes, expected {PAL_FILE_SIZE} or {PAL_FILE_WITH_EMPHASIS_SIZE}",
+                bytes.len()
+            ),
+        ));
+    }
+    let mut palettes = [[(0, 0, 0); 64]; 8];
+    for (palette, colors) in palettes.iter_mut().zip(bytes.chunks_exact(PAL_FILE_SIZE)) {
+        for (color, rgb) in palette.iter_mut().zip(colors.chunks_exact(3)) {
+            *color = (rgb[0], rgb[1], rgb[2]);
+        }
+    }
+    if bytes.len() == PAL_FILE_SIZE {
+        palettes = emphasis_palettes(&palettes[0]);
+    }
+    Ok(palettes)
+}
+
 // Derived from https://www.nesdev.org/wiki/PPU_palettes
 pub const SYSTEM_COLOR_PALETTE: [(u8, u8, u8); 64] = [
     (84, 84, 84),
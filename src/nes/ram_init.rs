@@ -0,0 +1,74 @@
+use std::str::FromStr;
+
+/// seed used by --ram-init random when none is given
+const DEFAULT_SEED: u64 = 0x2a03_2c02_1985;
+
+/// What internal RAM holds at power-on. Real consoles come up with
+/// whatever the RAM chip settles to, and a few games read it before
+/// writing it, so a fixed pattern keeps runs reproducible
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq)]
+pub enum RamInit {
+    #[default]
+    Zero,
+    /// every byte $FF
+    Ones,
+    /// runs of four $00 bytes then four $FF bytes, a pattern common on
+    /// front-loading consoles
+    Alternating,
+    /// pseudo-random bytes, the same for the same seed
+    Random(u64),
+}
+
+impl RamInit {
+    /// overwrites ram with the pattern
+    pub fn fill(self, ram: &mut [u8]) {
+        match self {
+            RamInit::Zero => ram.fill(0x00),
+            RamInit::Ones => ram.fill(0xff),
+            RamInit::Alternating => {
+                for (index, byte) in ram.iter_mut().enumerate() {
+                    *byte = if index & 4 == 0 { 0x00 } else { 0xff };
+                }
+            }
+            RamInit::Random(seed) => {
+                let mut state = seed;
+                for byte in ram {
+                    *byte = random_byte(&mut state);
+                }
+            }
+        }
+    }
+}
+
+/// steps a xorshift64* generator, returning its next byte. The same state
+/// always gives the same sequence
+pub fn random_byte(state: &mut u64) -> u8 {
+    // the generator sticks at 0
+    *state = (*state).max(1);
+    *state ^= *state >> 12;
+    *state ^= *state << 25;
+    *state ^= *state >> 27;
+    (state.wrapping_mul(0x2545_f491_4f6c_dd1d) >> 56) as u8
+}
+
+/// parses `zero`, `ff`, `alternating`, `random`, or `random:<seed>`
+impl FromStr for RamInit {
+    type Err = String;
+
+    fn from_str(text: &str) -> Result<RamInit, String> {
+        match text.split_once(':') {
+            Some(("random", seed)) => seed
+                .parse()
+                .map(RamInit::Random)
+                .map_err(|error| format!("bad seed {seed}: {error}")),
+            Some(_) => Err(format!("unknown RAM pattern {text}")),
+            None => match text {
+                "zero" => Ok(RamInit::Zero),
+                "ff" => Ok(RamInit::Ones),
+                "alternating" => Ok(RamInit::Alternating),
+                "random" => Ok(RamInit::Random(DEFAULT_SEED)),
+                _ => Err(format!("unknown RAM pattern {text}")),
+            },
+        }
+    }
+}
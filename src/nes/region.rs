@@ -0,0 +1,136 @@
+use std::mem;
+
+use crate::nes::media_clock::{NTSC_FRAME_RATE, PAL_FRAME_RATE};
+use crate::nes::ppu::{NTSC_SCANLINES_PER_FRAME, PAL_SCANLINES_PER_FRAME};
+use crate::nes::timer::{CPU_CLOCK_RATE, PAL_CPU_CLOCK_RATE};
+
+/// Television system a game was made for
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum Region {
+    #[default]
+    Ntsc,
+    Pal,
+}
+
+impl Region {
+    pub fn frame_rate(&self) -> f64 {
+        match self {
+            Region::Ntsc => NTSC_FRAME_RATE,
+            Region::Pal => PAL_FRAME_RATE,
+        }
+    }
+
+    /// CPU cycles per second
+    pub fn cpu_clock_rate(&self) -> u64 {
+        match self {
+            Region::Ntsc => CPU_CLOCK_RATE,
+            Region::Pal => PAL_CPU_CLOCK_RATE,
+        }
+    }
+
+    /// scanlines per frame, including vblank and the pre-render line
+    pub fn scanlines_per_frame(&self) -> usize {
+        match self {
+            Region::Ntsc => NTSC_SCANLINES_PER_FRAME,
+            Region::Pal => PAL_SCANLINES_PER_FRAME,
+        }
+    }
+
+    /// PPU dots run per CPU cycle as a fraction, numerator first. PAL's 3.2
+    /// dots means an extra dot every 5 CPU cycles
+    pub fn ppu_dots_per_cpu_cycle(&self) -> (u64, u64) {
+        match self {
+            Region::Ntsc => (3, 1),
+            Region::Pal => (16, 5),
+        }
+    }
+}
+
+/// A region the loader believes fits the ROM better than the one it is
+/// running as, waiting for the user to accept it
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct RegionSuggestion {
+    pub region: Region,
+    pub reason: String,
+}
+
+/// filename tags used by ROM sets to mark PAL releases
+const PAL_FILENAME_TAGS: [&str; 6] = ["(E)", "(Europe)", "(PAL)", "(A)", "(Australia)", "(G)"];
+
+/// guesses whether a ROM running as NTSC is actually a PAL game, checking
+/// the TV system bits of the iNES header first and then the region tags
+/// ROM sets put in filenames, which catches unheadered and mislabeled ROMs
+pub fn detect_region(header: &[u8], filename: &str) -> Option<RegionSuggestion> {
+    let is_ines = header.len() >= 16 && header[0..4] == [0x4e, 0x45, 0x53, 0x1a];
+    if is_ines {
+        let is_nes_2 = header[7] & 0x0c == 0x08;
+        let header_says_pal = if is_nes_2 {
+            header[12] & 0x03 == 0x01
+        } else {
+            header[9] & 0x01 == 0x01
+        };
+        if header_says_pal {
+            return Some(RegionSuggestion {
+                region: Region::Pal,
+                reason: String::from("header marks the ROM as PAL"),
+            });
+        }
+    }
+
+    PAL_FILENAME_TAGS
+        .iter()
+        .find(|tag| filename.contains(*tag))
+        .map(|tag| RegionSuggestion {
+            region: Region::Pal,
+            reason: format!("filename is tagged {tag}"),
+        })
+}
+
+/// frames RegionDetector watches before deciding, two seconds of NTSC
+pub const DETECTION_FRAMES: u64 = 120;
+
+/// Watches an NTSC run for a PAL game the header and filename missed. A
+/// PAL game sizes its vblank work for the 70 scanlines of PAL vblank
+/// rather than NTSC's 20, so on NTSC its PPUDATA writes run on into the
+/// picture. A run where most frames overrun vblank suggests PAL
+#[derive(Copy, Clone, Debug, Default)]
+pub struct RegionDetector {
+    frames: u64,
+    overrun_frames: u64,
+    /// whether or not the current frame has written PPUDATA while rendering
+    overran: bool,
+    decided: bool,
+}
+
+impl RegionDetector {
+    /// notes a write to PPUDATA, rendering being whether or not the PPU was
+    /// drawing the picture at the time
+    pub fn ppu_data_write(&mut self, rendering: bool) {
+        self.overran |= rendering;
+    }
+
+    /// counts a finished frame, returns a suggestion once DETECTION_FRAMES
+    /// have run if most of them overran vblank. Decides once, returning
+    /// None from then on
+    pub fn end_frame(&mut self) -> Option<RegionSuggestion> {
+        if self.decided {
+            return None;
+        }
+        self.frames += 1;
+        if mem::take(&mut self.overran) {
+            self.overrun_frames += 1;
+        }
+        if self.frames < DETECTION_FRAMES {
+            return None;
+        }
+        self.decided = true;
+        (self.overrun_frames * 2 > self.frames).then(|| RegionSuggestion {
+            region: Region::Pal,
+            reason: format!(
+                "game wrote VRAM after vblank in {} of {} frames",
+                self.overrun_frames, self.frames
+            ),
+        })
+    }
+}
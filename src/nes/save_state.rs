@@ -0,0 +1,658 @@
+use std::io;
+
+use crate::nes::apu::envelope::Envelope;
+use crate::nes::apu::length_counter::LengthCounter;
+use crate::nes::apu::APU;
+use crate::nes::bus::Bus;
+use crate::nes::dma::{OamTransfer, OAM_DMA_BYTES};
+use crate::nes::irq::IrqSource;
+use crate::nes::ppu::{DOTS_PER_SCANLINE, PPU};
+use crate::nes::ppu_bus::Mirroring;
+use crate::nes::region::Region;
+
+/// bumped whenever the layout of a saved component changes, so old states
+/// are rejected instead of misread
+const PPU_STATE_VERSION: u8 = 4;
+const PPU_STATE_TAG: &[u8; 4] = b"PPU\0";
+const APU_STATE_VERSION: u8 = 1;
+const APU_STATE_TAG: &[u8; 4] = b"APU\0";
+const BUS_STATE_VERSION: u8 = 2;
+const BUS_STATE_TAG: &[u8; 4] = b"BUS\0";
+
+/// Appends emulator state to a byte buffer. Values are little endian and
+/// fixed width so states load the same on every host
+#[derive(Clone, Debug, Default)]
+pub struct StateWriter {
+    pub bytes: Vec<u8>,
+}
+
+impl StateWriter {
+    pub fn u8(&mut self, value: u8) {
+        self.bytes.push(value);
+    }
+
+    pub fn bool(&mut self, value: bool) {
+        self.u8(u8::from(value));
+    }
+
+    pub fn u16(&mut self, value: u16) {
+        self.bytes.extend_from_slice(&value.to_le_bytes());
+    }
+
+    pub fn u32(&mut self, value: u32) {
+        self.bytes.extend_from_slice(&value.to_le_bytes());
+    }
+
+    pub fn u64(&mut self, value: u64) {
+        self.bytes.extend_from_slice(&value.to_le_bytes());
+    }
+
+    pub fn usize(&mut self, value: usize) {
+        self.u64(value as u64);
+    }
+
+    pub fn bytes(&mut self, bytes: &[u8]) {
+        self.bytes.extend_from_slice(bytes);
+    }
+
+    /// marks the start of a component's state
+    pub fn tag(&mut self, tag: &[u8; 4], version: u8) {
+        self.bytes(tag);
+        self.u8(version);
+    }
+}
+
+/// Reads back state written by a StateWriter, in the same order
+#[derive(Clone, Debug)]
+pub struct StateReader<'a> {
+    bytes: &'a [u8],
+}
+
+fn invalid(message: String) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, message)
+}
+
+impl<'a> StateReader<'a> {
+    pub fn new(bytes: &'a [u8]) -> StateReader<'a> {
+        StateReader { bytes }
+    }
+
+    /// returns whether or not every byte has been read
+    pub fn is_empty(&self) -> bool {
+        self.bytes.is_empty()
+    }
+
+    pub fn take(&mut self, length: usize) -> io::Result<&'a [u8]> {
+        if length > self.bytes.len() {
+            return Err(io::Error::new(
+                io::ErrorKind::UnexpectedEof,
+                "save state is truncated",
+            ));
+        }
+        let (taken, rest) = self.bytes.split_at(length);
+        self.bytes = rest;
+        Ok(taken)
+    }
+
+    pub fn u8(&mut self) -> io::Result<u8> {
+        Ok(self.take(1)?[0])
+    }
+
+    pub fn bool(&mut self) -> io::Result<bool> {
+        match self.u8()? {
+            0 => Ok(false),
+            1 => Ok(true),
+            value => Err(invalid(format!("{value} is not a bool"))),
+        }
+    }
+
+    /// reads a u8, rejecting values of limit or more
+    pub fn u8_below(&mut self, limit: u8) -> io::Result<u8> {
+        match self.u8()? {
+            value if value < limit => Ok(value),
+            value => Err(invalid(format!("{value} is out of range, limit {limit}"))),
+        }
+    }
+
+    pub fn u16(&mut self) -> io::Result<u16> {
+        Ok(u16::from_le_bytes(self.take(2)?.try_into().unwrap()))
+    }
+
+    pub fn u32(&mut self) -> io::Result<u32> {
+        Ok(u32::from_le_bytes(self.take(4)?.try_into().unwrap()))
+    }
+
+    pub fn u64(&mut self) -> io::Result<u64> {
+        Ok(u64::from_le_bytes(self.take(8)?.try_into().unwrap()))
+    }
+
+    /// reads a usize, rejecting values of limit or more
+    pub fn usize(&mut self, limit: usize) -> io::Result<usize> {
+        let value = self.u64()?;
+        match usize::try_from(value) {
+            Ok(value) if value < limit => Ok(value),
+            _ => Err(invalid(format!("{value} is out of range, limit {limit}"))),
+        }
+    }
+
+    /// fills destination with the next bytes
+    pub fn bytes(&mut self, destination: &mut [u8]) -> io::Result<()> {
+        destination.copy_from_slice(self.take(destination.len())?);
+        Ok(())
+    }
+
+    /// checks that a component's state starts here, in a version this build
+    /// understands
+    pub fn tag(&mut self, tag: &[u8; 4], version: u8) -> io::Result<()> {
+        let found = self.take(4)?;
+        if found != tag {
+            return Err(invalid(format!("expected {tag:?} state, found {found:?}")));
+        }
+        match self.u8()? {
+            found if found == version => Ok(()),
+            found => Err(invalid(format!(
+                "{tag:?} state is version {found}, expected {version}"
+            ))),
+        }
+    }
+}
+
+/// serializes fixed size byte arrays as sequences, serde only derives
+/// arrays of up to 32 elements. For #[serde(with = "byte_array")]
+#[cfg(feature = "serde")]
+pub mod byte_array {
+    use serde::de::Error;
+    use serde::{Deserialize, Deserializer, Serialize, Serializer};
+
+    pub fn serialize<S: Serializer, const N: usize>(
+        array: &[u8; N],
+        serializer: S,
+    ) -> Result<S::Ok, S::Error> {
+        array.as_slice().serialize(serializer)
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>, const N: usize>(
+        deserializer: D,
+    ) -> Result<[u8; N], D::Error> {
+        let bytes = Vec::<u8>::deserialize(deserializer)?;
+        let length = bytes.len();
+        bytes
+            .try_into()
+            .map_err(|_| D::Error::invalid_length(length, &format!("{N} bytes").as_str()))
+    }
+}
+
+fn write_region(state: &mut StateWriter, region: Region) {
+    state.u8(match region {
+        Region::Ntsc => 0,
+        Region::Pal => 1,
+    });
+}
+
+fn read_region(state: &mut StateReader) -> io::Result<Region> {
+    match state.u8()? {
+        0 => Ok(Region::Ntsc),
+        1 => Ok(Region::Pal),
+        region => Err(invalid(format!("unknown region {region}"))),
+    }
+}
+
+impl PPU {
+    /// appends everything the PPU needs to resume where it is: registers,
+    /// internal scroll state, VRAM, OAM, the beam position, and the frame
+    /// drawn so far. Settings such as accuracy, render mode, and the system
+    /// palette are left out
+    pub fn write_state(&self, state: &mut StateWriter) {
+        state.tag(PPU_STATE_TAG, PPU_STATE_VERSION);
+        for register in [
+            self.ppu_ctrl,
+            self.ppu_mask,
+            self.ppu_status,
+            self.oam_addr,
+            self.oam_data,
+            self.fine_x,
+            self.ppu_data_buffer,
+            self.io_latch,
+            self.oam_dma,
+        ] {
+            state.u8(register);
+        }
+        state.u16(self.v);
+        state.u16(self.t);
+        state.bool(self.w);
+        state.bytes(&self.io_latch_decay);
+        state.bytes(&self.address_space.chr.bytes);
+        state.bool(self.address_space.chr.writable);
+        state.bytes(&self.address_space.nametables);
+        state.bytes(&self.address_space.palette);
+        state.u8(match self.address_space.mirroring {
+            Mirroring::Horizontal => 0,
+            Mirroring::Vertical => 1,
+            Mirroring::SingleScreenLower => 2,
+            Mirroring::SingleScreenUpper => 3,
+            Mirroring::FourScreen => 4,
+        });
+        state.bytes(&self.oam_ram);
+        state.bytes(&self.secondary_oam);
+        state.usize(self.secondary_oam_sprites);
+        state.bool(self.sprite_zero_on_line);
+        write_region(state, self.region);
+        state.usize(self.scanline);
+        state.usize(self.dot);
+        state.bool(self.in_vblank_period);
+        state.bool(self.nmi_pending);
+        state.bool(self.frame_ready);
+        state.bool(self.odd_frame);
+        state.usize(self.frame_phase);
+        state.u64(self.frame_count);
+        state.bool(self.a12);
+        state.usize(self.a12_low_dots);
+        state.bool(self.a12_rose);
+        state.bytes(&self.pixels);
+        state.bytes(&self.line_masks);
+    }
+
+    /// restores state written by write_state. On error the PPU may be left
+    /// partly restored
+    pub fn read_state(&mut self, state: &mut StateReader) -> io::Result<()> {
+        state.tag(PPU_STATE_TAG, PPU_STATE_VERSION)?;
+        for register in [
+            &mut self.ppu_ctrl,
+            &mut self.ppu_mask,
+            &mut self.ppu_status,
+            &mut self.oam_addr,
+            &mut self.oam_data,
+            &mut self.fine_x,
+            &mut self.ppu_data_buffer,
+            &mut self.io_latch,
+            &mut self.oam_dma,
+        ] {
+            *register = state.u8()?;
+        }
+        self.v = state.u16()?;
+        self.t = state.u16()?;
+        self.w = state.bool()?;
+        state.bytes(&mut self.io_latch_decay)?;
+        state.bytes(&mut self.address_space.chr.bytes)?;
+        self.address_space.chr.writable = state.bool()?;
+        state.bytes(&mut self.address_space.nametables)?;
+        state.bytes(&mut self.address_space.palette)?;
+        self.address_space.mirroring = match state.u8()? {
+            0 => Mirroring::Horizontal,
+            1 => Mirroring::Vertical,
+            2 => Mirroring::SingleScreenLower,
+            3 => Mirroring::SingleScreenUpper,
+            4 => Mirroring::FourScreen,
+            mirroring => return Err(invalid(format!("unknown mirroring {mirroring}"))),
+        };
+        state.bytes(&mut self.oam_ram)?;
+        state.bytes(&mut self.secondary_oam)?;
+        self.secondary_oam_sprites = state.usize(self.secondary_oam.len() / 4 + 1)?;
+        self.sprite_zero_on_line = state.bool()?;
+        self.region = read_region(state)?;
+        self.scanline = state.usize(self.region.scanlines_per_frame())?;
+        self.dot = state.usize(DOTS_PER_SCANLINE)?;
+        self.in_vblank_period = state.bool()?;
+        self.nmi_pending = state.bool()?;
+        self.frame_ready = state.bool()?;
+        self.odd_frame = state.bool()?;
+        self.frame_phase = state.usize(3)?;
+        self.frame_count = state.u64()?;
+        self.a12 = state.bool()?;
+        self.a12_low_dots = state.usize(usize::MAX)?;
+        self.a12_rose = state.bool()?;
+        state.bytes(&mut self.pixels)?;
+        state.bytes(&mut self.line_masks)?;
+        Ok(())
+    }
+
+    /// returns a snapshot of the PPU's state
+    pub fn save_state(&self) -> Vec<u8> {
+        let mut state = StateWriter::default();
+        self.write_state(&mut state);
+        state.bytes
+    }
+
+    /// restores a snapshot taken by save_state, leaving the PPU untouched if
+    /// the snapshot is invalid
+    pub fn load_state(&mut self, bytes: &[u8]) -> io::Result<()> {
+        let mut state = StateReader::new(bytes);
+        let mut ppu = self.clone();
+        ppu.read_state(&mut state)?;
+        if !state.is_empty() {
+            return Err(invalid(String::from("save state has trailing bytes")));
+        }
+        *self = ppu;
+        Ok(())
+    }
+}
+
+fn write_envelope(state: &mut StateWriter, envelope: &Envelope) {
+    state.bool(envelope.start);
+    state.bool(envelope.loop_decay);
+    state.bool(envelope.constant_volume);
+    state.u8(envelope.volume);
+    state.u8(envelope.divider);
+    state.u8(envelope.decay);
+}
+
+fn read_envelope(state: &mut StateReader, envelope: &mut Envelope) -> io::Result<()> {
+    envelope.start = state.bool()?;
+    envelope.loop_decay = state.bool()?;
+    envelope.constant_volume = state.bool()?;
+    envelope.volume = state.u8_below(16)?;
+    envelope.divider = state.u8_below(16)?;
+    envelope.decay = state.u8_below(16)?;
+    Ok(())
+}
+
+fn write_length_counter(state: &mut StateWriter, length_counter: &LengthCounter) {
+    state.bool(length_counter.enabled);
+    state.bool(length_counter.halt);
+    state.u8(length_counter.value);
+}
+
+fn read_length_counter(
+    state: &mut StateReader,
+    length_counter: &mut LengthCounter,
+) -> io::Result<()> {
+    length_counter.enabled = state.bool()?;
+    length_counter.halt = state.bool()?;
+    length_counter.value = state.u8()?;
+    Ok(())
+}
+
+fn write_optional_u8(state: &mut StateWriter, value: Option<u8>) {
+    state.bool(value.is_some());
+    state.u8(value.unwrap_or_default());
+}
+
+fn read_optional_u8(state: &mut StateReader) -> io::Result<Option<u8>> {
+    let is_some = state.bool()?;
+    let value = state.u8()?;
+    Ok(is_some.then_some(value))
+}
+
+impl APU {
+    /// appends everything the APU needs to resume where it is: every
+    /// channel's timers, counters, and shift registers, and the frame
+    /// counter's phase. The mixer volumes, output filters, and samples
+    /// waiting in the resampler are left out
+    pub fn write_state(&self, state: &mut StateWriter) {
+        state.tag(APU_STATE_TAG, APU_STATE_VERSION);
+        for pulse in &self.pulse {
+            write_envelope(state, &pulse.envelope);
+            write_length_counter(state, &pulse.length_counter);
+            let sweep = &pulse.sweep;
+            state.bool(sweep.enabled);
+            state.u8(sweep.divider_period);
+            state.bool(sweep.negate);
+            state.u8(sweep.shift);
+            state.u8(sweep.divider);
+            state.bool(sweep.reload);
+            state.u16(pulse.timer_period);
+            state.u16(pulse.timer);
+            state.u8(pulse.duty);
+            state.u8(pulse.sequence_step);
+        }
+
+        let triangle = &self.triangle;
+        write_length_counter(state, &triangle.length_counter);
+        state.u8(triangle.linear_reload_value);
+        state.u8(triangle.linear_counter);
+        state.bool(triangle.linear_reload);
+        state.u16(triangle.timer_period);
+        state.u16(triangle.timer);
+        state.u8(triangle.sequence_step);
+
+        let noise = &self.noise;
+        write_envelope(state, &noise.envelope);
+        write_length_counter(state, &noise.length_counter);
+        state.bool(noise.short_mode);
+        state.u8(noise.period_index);
+        state.u16(noise.timer);
+        state.u16(noise.shift_register);
+
+        let dmc = &self.dmc;
+        state.bool(dmc.irq_enabled);
+        state.bool(dmc.loop_sample);
+        state.u8(dmc.rate_index);
+        state.u8(dmc.output_level);
+        state.u16(dmc.sample_address);
+        state.u16(dmc.sample_length);
+        state.u16(dmc.current_address);
+        state.u16(dmc.bytes_remaining);
+        write_optional_u8(state, dmc.sample_buffer);
+        state.u8(dmc.shift_register);
+        state.u8(dmc.bits_remaining);
+        state.bool(dmc.silence);
+        state.u16(dmc.timer);
+        state.bool(dmc.interrupt);
+
+        let frame_counter = &self.frame_counter;
+        state.bool(frame_counter.five_step);
+        state.bool(frame_counter.irq_inhibit);
+        state.u32(frame_counter.cycle);
+        state.bool(frame_counter.interrupt);
+        write_optional_u8(state, frame_counter.reset_delay);
+        state.u64(frame_counter.cpu_cycles);
+
+        write_region(state, self.region);
+        state.bool(self.odd_cycle);
+    }
+
+    /// restores state written by write_state. On error the APU may be left
+    /// partly restored
+    pub fn read_state(&mut self, state: &mut StateReader) -> io::Result<()> {
+        state.tag(APU_STATE_TAG, APU_STATE_VERSION)?;
+        for pulse in &mut self.pulse {
+            read_envelope(state, &mut pulse.envelope)?;
+            read_length_counter(state, &mut pulse.length_counter)?;
+            let sweep = &mut pulse.sweep;
+            sweep.enabled = state.bool()?;
+            sweep.divider_period = state.u8_below(8)?;
+            sweep.negate = state.bool()?;
+            sweep.shift = state.u8_below(8)?;
+            sweep.divider = state.u8_below(8)?;
+            sweep.reload = state.bool()?;
+            pulse.timer_period = state.u16()?;
+            pulse.timer = state.u16()?;
+            pulse.duty = state.u8_below(4)?;
+            pulse.sequence_step = state.u8_below(8)?;
+        }
+
+        let triangle = &mut self.triangle;
+        read_length_counter(state, &mut triangle.length_counter)?;
+        triangle.linear_reload_value = state.u8_below(0x80)?;
+        triangle.linear_counter = state.u8_below(0x80)?;
+        triangle.linear_reload = state.bool()?;
+        triangle.timer_period = state.u16()?;
+        triangle.timer = state.u16()?;
+        triangle.sequence_step = state.u8_below(32)?;
+
+        let noise = &mut self.noise;
+        read_envelope(state, &mut noise.envelope)?;
+        read_length_counter(state, &mut noise.length_counter)?;
+        noise.short_mode = state.bool()?;
+        noise.period_index = state.u8_below(16)?;
+        noise.timer = state.u16()?;
+        noise.shift_register = state.u16()?;
+
+        let dmc = &mut self.dmc;
+        dmc.irq_enabled = state.bool()?;
+        dmc.loop_sample = state.bool()?;
+        dmc.rate_index = state.u8_below(16)?;
+        dmc.output_level = state.u8_below(0x80)?;
+        dmc.sample_address = state.u16()?;
+        dmc.sample_length = state.u16()?;
+        dmc.current_address = state.u16()?;
+        dmc.bytes_remaining = state.u16()?;
+        dmc.sample_buffer = read_optional_u8(state)?;
+        dmc.shift_register = state.u8()?;
+        dmc.bits_remaining = state.u8_below(9)?;
+        dmc.silence = state.bool()?;
+        dmc.timer = state.u16()?;
+        dmc.interrupt = state.bool()?;
+
+        let frame_counter = &mut self.frame_counter;
+        frame_counter.five_step = state.bool()?;
+        frame_counter.irq_inhibit = state.bool()?;
+        frame_counter.cycle = state.u32()?;
+        frame_counter.interrupt = state.bool()?;
+        frame_counter.reset_delay = read_optional_u8(state)?;
+        frame_counter.cpu_cycles = state.u64()?;
+
+        self.region = read_region(state)?;
+        self.odd_cycle = state.bool()?;
+        Ok(())
+    }
+
+    /// returns a snapshot of the APU's state
+    pub fn save_state(&self) -> Vec<u8> {
+        let mut state = StateWriter::default();
+        self.write_state(&mut state);
+        state.bytes
+    }
+
+    /// restores a snapshot taken by save_state, leaving the APU untouched if
+    /// the snapshot is invalid
+    pub fn load_state(&mut self, bytes: &[u8]) -> io::Result<()> {
+        let mut state = StateReader::new(bytes);
+        let mut apu = *self;
+        apu.read_state(&mut state)?;
+        if !state.is_empty() {
+            return Err(invalid(String::from("save state has trailing bytes")));
+        }
+        *self = apu;
+        Ok(())
+    }
+}
+
+impl Bus {
+    /// appends everything behind the CPU: memory and PRG RAM, the data bus
+    /// and master clock, the controllers' shift registers, DMA and /IRQ
+    /// state, then the PPU, APU, mapper, and each mapped device. Debugging
+    /// aids such as the tracer, watchpoints, and callbacks are left out, as
+    /// are the buttons held, which the frontend sets
+    pub fn write_state(&self, state: &mut StateWriter) {
+        state.tag(BUS_STATE_TAG, BUS_STATE_VERSION);
+        state.bytes(&self.bytes);
+        state.u8(self.data_bus);
+        state.u16(self.address_bus);
+        state.u64(self.cycle);
+        state.u64(self.ppu_dot_remainder);
+        state.u8(self.irq_sources.bits());
+        let oam = self.dma.oam.unwrap_or_default();
+        state.bool(self.dma.oam.is_some());
+        state.u8(oam.page);
+        state.u16(oam.copied);
+        write_optional_u8(state, oam.latch);
+        state.bool(self.dma.dmc_address.is_some());
+        state.u16(self.dma.dmc_address.unwrap_or_default());
+        state.bool(self.dma.halted);
+        state.bool(self.dma.dmc_dummy);
+        for controller in &self.controllers {
+            state.bool(controller.strobe);
+            state.u8(controller.shift_register);
+        }
+        state.bool(self.cartridge.prg_ram_enabled);
+        state.bytes(&self.cartridge.prg_ram);
+        self.ppu.write_state(state);
+        self.apu.write_state(state);
+        self.cartridge.mapper.write_state(state);
+        state.usize(self.devices.len());
+        for mapped in &self.devices {
+            state.u16(*mapped.range.start());
+            state.u16(*mapped.range.end());
+            mapped.device.write_state(state);
+        }
+    }
+
+    /// restores state written by write_state. The same devices must be
+    /// mapped as when it was written. On error the Bus may be left partly
+    /// restored
+    pub fn read_state(&mut self, state: &mut StateReader) -> io::Result<()> {
+        state.tag(BUS_STATE_TAG, BUS_STATE_VERSION)?;
+        state.bytes(&mut self.bytes)?;
+        self.data_bus = state.u8()?;
+        self.address_bus = state.u16()?;
+        self.cycle = state.u64()?;
+        self.ppu_dot_remainder = state.u64()?;
+        self.irq_sources = IrqSource::from_bits_truncate(state.u8()?);
+        let oam_active = state.bool()?;
+        let oam = OamTransfer {
+            page: state.u8()?,
+            copied: state.u16()?,
+            latch: read_optional_u8(state)?,
+        };
+        if oam.copied >= OAM_DMA_BYTES {
+            return Err(invalid(format!(
+                "OAM DMA has copied {} of {OAM_DMA_BYTES} bytes",
+                oam.copied
+            )));
+        }
+        self.dma.oam = oam_active.then_some(oam);
+        let dmc_requested = state.bool()?;
+        let dmc_address = state.u16()?;
+        self.dma.dmc_address = dmc_requested.then_some(dmc_address);
+        self.dma.halted = state.bool()?;
+        self.dma.dmc_dummy = state.bool()?;
+        for controller in &mut self.controllers {
+            controller.strobe = state.bool()?;
+            controller.shift_register = state.u8()?;
+        }
+        self.cartridge.prg_ram_enabled = state.bool()?;
+        state.bytes(&mut self.cartridge.prg_ram)?;
+        self.ppu.read_state(state)?;
+        self.apu.read_state(state)?;
+        self.cartridge.mapper.read_state(state)?;
+        let devices = state.usize(usize::MAX)?;
+        if devices != self.devices.len() {
+            return Err(invalid(format!(
+                "save state has {devices} mapped devices, the bus has {}",
+                self.devices.len()
+            )));
+        }
+        for mapped in &mut self.devices {
+            let range = state.u16()?..=state.u16()?;
+            if range != mapped.range {
+                return Err(invalid(format!(
+                    "save state maps a device at ${:04X}-${:04X}, the bus at ${:04X}-${:04X}",
+                    range.start(),
+                    range.end(),
+                    mapped.range.start(),
+                    mapped.range.end()
+                )));
+            }
+            mapped.device.read_state(state)?;
+        }
+        Ok(())
+    }
+
+    /// returns a snapshot of the Bus's state
+    pub fn save_state(&self) -> Vec<u8> {
+        let mut state = StateWriter::default();
+        self.write_state(&mut state);
+        state.bytes
+    }
+
+    /// restores a snapshot taken by save_state. The Bus holds devices that
+    /// cannot be copied aside, so if the snapshot is invalid it rolls back
+    /// by reloading its own state from before the attempt
+    pub fn load_state(&mut self, bytes: &[u8]) -> io::Result<()> {
+        let backup = self.save_state();
+        let mut state = StateReader::new(bytes);
+        let result = self.read_state(&mut state).and_then(|()| {
+            if state.is_empty() {
+                Ok(())
+            } else {
+                Err(invalid(String::from("save state has trailing bytes")))
+            }
+        });
+        if result.is_err() {
+            self.read_state(&mut StateReader::new(&backup))
+                .expect("a bus reloads the state it just saved");
+        }
+        result
+    }
+}
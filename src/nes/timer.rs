@@ -0,0 +1,75 @@
+/// NTSC 2A03 CPU clock rate, used to convert cycles into emulated time
+pub const CPU_CLOCK_RATE: u64 = 1_789_773;
+/// PAL 2A07 CPU clock rate
+pub const PAL_CPU_CLOCK_RATE: u64 = 1_662_607;
+
+pub const TIMER_REGISTER_COUNT: usize = 8;
+
+/// Memory-mapped timer for the BusConfig::FlatRam computer. All values are
+/// derived from the emulated cycle count rather than host time, so programs
+/// that read it behave identically on every run.
+///
+/// Register layout relative to `base_address`:
+/// - `+0..+3` | CPU cycles elapsed, 32-bit little endian
+/// - `+4..+7` | emulated milliseconds elapsed, 32-bit little endian
+///
+/// Reading either low byte, `+0` or `+4`, latches all eight, so a program
+/// that reads a value a byte at a time sees one consistent count even if
+/// it carries between the reads.
+#[derive(Copy, Clone, Debug)]
+pub struct CycleTimer {
+    pub base_address: u16,
+    pub registers: [u8; TIMER_REGISTER_COUNT],
+    /// CPU clock as of the last sync, latched into registers on a read
+    pub clock: u64,
+    /// cycles per second of the CPU the clock counts, for the milliseconds
+    pub clock_rate: u64,
+}
+
+impl CycleTimer {
+    pub fn new(base_address: u16) -> CycleTimer {
+        CycleTimer {
+            base_address,
+            registers: [0; TIMER_REGISTER_COUNT],
+            clock: 0,
+            clock_rate: CPU_CLOCK_RATE,
+        }
+    }
+
+    /// returns whether or not an address falls within the timer's registers
+    pub fn contains(&self, address: usize) -> bool {
+        let base = usize::from(self.base_address);
+        address >= base && address < base + TIMER_REGISTER_COUNT
+    }
+
+    /// returns the register backing an address within the timer's range as
+    /// last latched, without latching
+    pub fn register(&self, address: usize) -> u8 {
+        self.registers[address - usize::from(self.base_address)]
+    }
+
+    /// returns the register backing an address within the timer's range,
+    /// latching every register first if it is a low byte
+    pub fn read(&mut self, address: usize) -> u8 {
+        let offset = address - usize::from(self.base_address);
+        if offset.is_multiple_of(4) {
+            self.latch();
+        }
+        self.registers[offset]
+    }
+
+    /// records the current CPU clock and its rate for the next latch
+    pub fn sync(&mut self, clock: u64, clock_rate: u64) {
+        self.clock = clock;
+        self.clock_rate = clock_rate;
+    }
+
+    /// copies the cycles and milliseconds as of the last sync into the
+    /// registers
+    pub fn latch(&mut self) {
+        let cycles = self.clock as u32;
+        let milliseconds = (u128::from(self.clock) * 1000 / u128::from(self.clock_rate)) as u32;
+        self.registers[..4].copy_from_slice(&cycles.to_le_bytes());
+        self.registers[4..].copy_from_slice(&milliseconds.to_le_bytes());
+    }
+}
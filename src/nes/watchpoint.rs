@@ -0,0 +1,94 @@
+use bitflags::bitflags;
+
+use crate::nes::bus_trace::{AccessKind, BusAccess};
+
+/// watchpoints the bus can hold at once
+pub const MAX_WATCHPOINTS: usize = 16;
+
+bitflags! {
+    /// Accesses a watchpoint stops on
+    #[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Hash)]
+    pub struct WatchAccess: u8 {
+        const READ  = 0b0000_0001;
+        const WRITE = 0b0000_0010;
+    }
+}
+
+impl WatchAccess {
+    fn matches(self, kind: AccessKind) -> bool {
+        match kind {
+            AccessKind::Read => self.contains(WatchAccess::READ),
+            AccessKind::Write => self.contains(WatchAccess::WRITE),
+        }
+    }
+}
+
+/// Addresses start..=end watched for the given accesses
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub struct Watchpoint {
+    pub start: u16,
+    pub end: u16,
+    pub access: WatchAccess,
+}
+
+/// The first access to hit a watchpoint during a step
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub struct WatchpointHit {
+    /// index of the watchpoint in the bus's list
+    pub index: usize,
+    pub access: BusAccess,
+}
+
+/// Data breakpoints checked on every CPU bus access. A hit does not
+/// interrupt the instruction making it, the run loop stops once it ends
+#[derive(Copy, Clone, Debug, Default)]
+pub struct Watchpoints {
+    slots: [Option<Watchpoint>; MAX_WATCHPOINTS],
+    /// slots in use, so accesses are only checked while there are any
+    count: usize,
+    /// first hit not yet taken by the run loop
+    pub hit: Option<WatchpointHit>,
+}
+
+impl Watchpoints {
+    /// watches start..=end, returns the watchpoint's index or None if all
+    /// MAX_WATCHPOINTS are in use
+    pub fn add(&mut self, start: u16, end: u16, access: WatchAccess) -> Option<usize> {
+        let index = self.slots.iter().position(Option::is_none)?;
+        self.slots[index] = Some(Watchpoint { start, end, access });
+        self.count += 1;
+        Some(index)
+    }
+
+    pub fn remove(&mut self, index: usize) {
+        if self.slots[index].take().is_some() {
+            self.count -= 1;
+        }
+    }
+
+    pub fn clear(&mut self) {
+        self.slots = Default::default();
+        self.count = 0;
+        self.hit = None;
+    }
+
+    pub fn get(&self, index: usize) -> Option<Watchpoint> {
+        self.slots[index]
+    }
+
+    /// records access as the hit if it is the first to match a watchpoint
+    pub fn check(&mut self, access: BusAccess) {
+        if self.count == 0 || self.hit.is_some() {
+            return;
+        }
+        let index = self.slots.iter().position(|slot| {
+            slot.is_some_and(|watchpoint| {
+                (watchpoint.start..=watchpoint.end).contains(&access.address)
+                    && watchpoint.access.matches(access.kind)
+            })
+        });
+        if let Some(index) = index {
+            self.hit = Some(WatchpointHit { index, access });
+        }
+    }
+}
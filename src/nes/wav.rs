@@ -0,0 +1,46 @@
+use std::fs::File;
+use std::io::{self, BufWriter, Write};
+
+/// bytes of the RIFF header before the samples
+const HEADER_SIZE: u32 = 44;
+const BITS_PER_SAMPLE: u16 = 16;
+const CHANNELS: u16 = 1;
+
+/// converts a mixer sample to 16-bit PCM, clipping anything outside -1..=1
+fn to_pcm(sample: f32) -> i16 {
+    (sample.clamp(-1.0, 1.0) * f32::from(i16::MAX)) as i16
+}
+
+/// writes samples as a mono 16-bit PCM WAV file
+pub fn write_wav<W: Write>(mut writer: W, samples: &[f32], sample_rate: u32) -> io::Result<()> {
+    let block_align = CHANNELS * BITS_PER_SAMPLE / 8;
+    let data_size = samples.len() as u32 * u32::from(block_align);
+
+    writer.write_all(b"RIFF")?;
+    writer.write_all(&(HEADER_SIZE - 8 + data_size).to_le_bytes())?;
+    writer.write_all(b"WAVE")?;
+    writer.write_all(b"fmt ")?;
+    writer.write_all(&16u32.to_le_bytes())?;
+    // PCM
+    writer.write_all(&1u16.to_le_bytes())?;
+    writer.write_all(&CHANNELS.to_le_bytes())?;
+    writer.write_all(&sample_rate.to_le_bytes())?;
+    writer.write_all(&(sample_rate * u32::from(block_align)).to_le_bytes())?;
+    writer.write_all(&block_align.to_le_bytes())?;
+    writer.write_all(&BITS_PER_SAMPLE.to_le_bytes())?;
+    writer.write_all(b"data")?;
+    writer.write_all(&data_size.to_le_bytes())?;
+    for &sample in samples {
+        writer.write_all(&to_pcm(sample).to_le_bytes())?;
+    }
+    writer.flush()
+}
+
+/// writes samples to a WAV file
+pub fn save_wav(filename: &str, samples: &[f32], sample_rate: u32) -> io::Result<()> {
+    write_wav(
+        BufWriter::new(File::create(filename)?),
+        samples,
+        sample_rate,
+    )
+}
@@ -0,0 +1,163 @@
+// Built-in health checks run by `disco5 selftest`, so users can verify their
+// build before filing bugs
+
+use crate::nes::bus::BusConfig;
+use crate::nes::cpu_structs::{decode_instruction, Instruction};
+use crate::nes::NES;
+use crate::testing::run_frames_and_hash;
+
+/// number of opcodes documented for the 6502
+const DOCUMENTED_OPCODE_COUNT: usize = 151;
+
+/// number of CPU cycles a self-test program may run for
+const PROGRAM_CYCLE_LIMIT: u64 = 1_000_000;
+
+const CPU_PROGRAM_ENTRY_POINT: u16 = 0x0600;
+const CPU_PROGRAM_SUCCESS: u16 = 0x062a;
+const CPU_PROGRAM_FAILURE: u16 = 0x0631;
+const CPU_PROGRAM_RESULT: usize = 0x10;
+
+/// Exercises arithmetic, flags, branches, the stack, and subroutines. Stores
+/// 0x01 to $10 and spins at $062a on success, or stores 0xff and spins at
+/// $0631 on failure. Assembled by hand for disco5 and released into the
+/// public domain.
+const CPU_PROGRAM: [u8; 0x36] = [
+    0xa2, 0xff, // LDX #$ff
+    0x9a, // TXS
+    0xa2, 0x00, // LDX #$00
+    0xa9, 0x05, // LDA #$05
+    0x18, // CLC
+    0x69, 0x03, // ADC #$03
+    0xc9, 0x08, // CMP #$08
+    0xd0, 0x1f, // BNE fail
+    0x38, // SEC
+    0xe9, 0x09, // SBC #$09
+    0xb0, 0x1a, // BCS fail
+    0xc9, 0xff, // CMP #$ff
+    0xd0, 0x16, // BNE fail
+    0x48, // PHA
+    0xa9, 0x00, // LDA #$00
+    0x68, // PLA
+    0xc9, 0xff, // CMP #$ff
+    0xd0, 0x0e, // BNE fail
+    0x20, 0x34, 0x06, // JSR sub
+    0xe0, 0x01, // CPX #$01
+    0xd0, 0x07, // BNE fail
+    0xa9, 0x01, // LDA #$01
+    0x85, 0x10, // STA $10
+    0x4c, 0x2a, 0x06, // done: JMP done
+    0xa9, 0xff, // fail: LDA #$ff
+    0x85, 0x10, // STA $10
+    0x4c, 0x31, 0x06, // JMP fail + 4
+    0xe8, // sub: INX
+    0x60, // RTS
+];
+
+/// frames of the embedded demo ROM hashed by the demo check
+const DEMO_FRAMES: usize = 10;
+
+/// hash of the demo's framebuffer after DEMO_FRAMES frames
+const DEMO_FRAME_HASH: u64 = 0xb266_4ccc_f7a9_d825;
+
+/// Outcome of a single self-test check
+#[derive(Clone, Debug)]
+pub struct SelfTestResult {
+    pub name: &'static str,
+    pub outcome: Result<(), String>,
+}
+
+/// checks that every documented opcode decodes and takes at least one cycle
+fn check_opcode_table() -> Result<(), String> {
+    let mut documented = 0;
+    for opcode in 0..=0xff {
+        match decode_instruction(opcode) {
+            (Instruction::Invalid(_), _) => {}
+            (instruction, 0) => {
+                return Err(format!("0x{opcode:0>2x} {instruction:?} takes no cycles"))
+            }
+            _ => documented += 1,
+        }
+    }
+    if documented == DOCUMENTED_OPCODE_COUNT {
+        Ok(())
+    } else {
+        Err(format!(
+            "{documented} opcodes decode, expected {DOCUMENTED_OPCODE_COUNT}"
+        ))
+    }
+}
+
+/// runs the embedded CPU program on flat RAM
+fn check_cpu_program() -> Result<(), String> {
+    let mut computer: NES = Default::default();
+    computer.address_space.config = BusConfig::FlatRam;
+    computer.headless = true;
+
+    let entry_point = usize::from(CPU_PROGRAM_ENTRY_POINT);
+    computer.address_space.bytes[entry_point..entry_point + CPU_PROGRAM.len()]
+        .copy_from_slice(&CPU_PROGRAM);
+    computer.cpu.pc = CPU_PROGRAM_ENTRY_POINT;
+
+    while computer.cpu.clock < PROGRAM_CYCLE_LIMIT {
+        match computer.cpu.pc {
+            CPU_PROGRAM_SUCCESS => return Ok(()),
+            CPU_PROGRAM_FAILURE => {
+                return Err(format!(
+                    "program failed with result 0x{:0>2x}",
+                    computer.address_space.bytes[CPU_PROGRAM_RESULT]
+                ))
+            }
+            _ => {
+                computer.step(false);
+            }
+        }
+    }
+    Err(format!("program hung near 0x{:0>4x}", computer.cpu.pc))
+}
+
+/// boots the embedded demo ROM and compares its rendered frames
+fn check_demo_frames() -> Result<(), String> {
+    let mut nes = NES {
+        headless: true,
+        ..Default::default()
+    };
+    nes.load_demo().map_err(|error| error.to_string())?;
+
+    let hash = run_frames_and_hash(&mut nes, DEMO_FRAMES);
+    if hash == DEMO_FRAME_HASH {
+        Ok(())
+    } else {
+        Err(format!(
+            "frame hash 0x{hash:0>16x}, expected 0x{DEMO_FRAME_HASH:0>16x}"
+        ))
+    }
+}
+
+/// runs every check and returns their results
+pub fn run_selftest() -> Vec<SelfTestResult> {
+    vec![
+        SelfTestResult {
+            name: "opcode table",
+            outcome: check_opcode_table(),
+        },
+        SelfTestResult {
+            name: "cpu program",
+            outcome: check_cpu_program(),
+        },
+        SelfTestResult {
+            name: "demo frame hash",
+            outcome: check_demo_frames(),
+        },
+    ]
+}
+
+/// prints a pass/fail line for each result, returns whether all passed
+pub fn print_report(results: &[SelfTestResult]) -> bool {
+    for result in results {
+        match result.outcome {
+            Ok(()) => println!("PASS | {}", result.name),
+            Err(ref reason) => println!("FAIL | {} | {}", result.name, reason),
+        }
+    }
+    results.iter().all(|result| result.outcome.is_ok())
+}
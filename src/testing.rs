@@ -0,0 +1,232 @@
+// Helpers for unit testing 6502 programs and NES ROMs with disco5 as a
+// dev-dependency
+
+use std::env;
+use std::ops::Range;
+use std::path::PathBuf;
+use std::process::Command;
+
+use crate::nes::frame::Frame;
+use crate::nes::ppu::{FRAME_HEIGHT, FRAME_WIDTH, PPU};
+use crate::nes::NES;
+
+/// environment variable overriding the directory fixtures are loaded from
+pub const FIXTURE_DIR_VAR: &str = "DISCO5_FIXTURE_DIR";
+/// environment variable naming a base URL missing fixtures are downloaded from
+pub const FIXTURE_URL_VAR: &str = "DISCO5_FIXTURE_URL";
+/// directory fixtures are loaded from when FIXTURE_DIR_VAR is unset
+pub const DEFAULT_FIXTURE_DIR: &str = "sample_programs";
+/// environment variable that, when set, makes a missing fixture fail the
+/// test instead of skipping it, for CI runs that provide the fixtures
+pub const REQUIRE_FIXTURES_VAR: &str = "DISCO5_REQUIRE_FIXTURES";
+
+/// returns the path of a test program or ROM, downloading it first if it is
+/// missing and FIXTURE_URL_VAR is set. Returns None when the fixture is not
+/// available so tests can skip instead of failing for users who can't ship
+/// the file, unless REQUIRE_FIXTURES_VAR is set, which panics instead:
+///
+/// let Some(path) = fixture_path("countdown.txt") else { return; };
+pub fn fixture_path(name: &str) -> Option<String> {
+    let dir = env::var(FIXTURE_DIR_VAR).unwrap_or_else(|_| String::from(DEFAULT_FIXTURE_DIR));
+    let path: PathBuf = [dir.as_str(), name].iter().collect();
+
+    if !path.exists() {
+        if let Ok(base_url) = env::var(FIXTURE_URL_VAR) {
+            let url = format!("{}/{}", base_url.trim_end_matches('/'), name);
+            let downloaded = Command::new("curl")
+                .args(["--fail", "--silent", "--location", "--output"])
+                .arg(&path)
+                .arg(&url)
+                .status()
+                .map(|status| status.success())
+                .unwrap_or(false);
+            if !downloaded {
+                println!("failed to download fixture {name} from {url}");
+            }
+        }
+    }
+
+    if path.exists() {
+        Some(path.to_string_lossy().into_owned())
+    } else if env::var_os(REQUIRE_FIXTURES_VAR).is_some() {
+        panic!("fixture {name} is missing from {dir} and {REQUIRE_FIXTURES_VAR} is set");
+    } else {
+        println!("skipping, fixture {name} is missing from {dir}");
+        None
+    }
+}
+
+/// number of CPU cycles run_until_pc executes before giving up
+pub const RUN_UNTIL_PC_CYCLE_LIMIT: u64 = 100_000_000;
+
+/// asserts that CPU memory within range matches expected, reading it the
+/// way the CPU would see it, through mirrors and PRG RAM, with Bus::peek
+pub fn assert_memory_eq(nes: &NES, range: Range<usize>, expected: &[u8]) {
+    let actual: Vec<u8> = range
+        .clone()
+        .map(|address| nes.address_space.peek(address as u16))
+        .collect();
+    assert_eq!(
+        actual.as_slice(),
+        expected,
+        "memory mismatch in 0x{:0>4x}..0x{:0>4x}",
+        range.start,
+        range.end
+    );
+}
+
+/// steps the CPU until the program counter reaches pc, panicking if that
+/// does not happen within RUN_UNTIL_PC_CYCLE_LIMIT cycles
+pub fn run_until_pc(nes: &mut NES, pc: u16) {
+    let mut cycles: u64 = 0;
+    while nes.cpu.pc != pc {
+        assert!(
+            cycles < RUN_UNTIL_PC_CYCLE_LIMIT,
+            "PC never reached 0x{:0>4x}, stuck near 0x{:0>4x}",
+            pc,
+            nes.cpu.pc
+        );
+        cycles += nes.step(false);
+    }
+}
+
+/// runs the given number of frames, returns the hash of the final frame
+pub fn run_frames_and_hash(nes: &mut NES, frames: usize) -> u64 {
+    let mut frame = Frame::new(FRAME_WIDTH, FRAME_HEIGHT);
+    for _ in 0..frames {
+        frame = nes.run_frame(false);
+    }
+    frame.hash()
+}
+
+/// runs the given number of frames, returns the hash of each, so a
+/// regression test can pin down every frame of a run and report the first
+/// one that changed
+pub fn frame_hashes(nes: &mut NES, frames: usize) -> Vec<u64> {
+    (0..frames).map(|_| nes.run_frame(false).hash()).collect()
+}
+
+/// frames run_blargg_rom emulates before giving up, 30 seconds at 60 fps
+pub const BLARGG_FRAME_LIMIT: usize = 1800;
+/// frames a blargg test ROM is left running after asking for a reset before
+/// it gets one, the ROMs ask for at least 100ms
+const BLARGG_RESET_DELAY_FRAMES: usize = 6;
+/// $6000 status while a blargg test ROM is still running
+pub const BLARGG_RUNNING: u8 = 0x80;
+/// $6000 status asking for the reset button to be pressed
+pub const BLARGG_NEEDS_RESET: u8 = 0x81;
+/// bytes at $6001-$6003 marking the status block as valid
+const BLARGG_SIGNATURE: [u8; 3] = [0xde, 0xb0, 0x61];
+
+/// Outcome of one of blargg's test ROMs, as reported through $6000
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct BlarggResult {
+    /// 0 on a pass, otherwise the number of the failing check
+    pub code: u8,
+    /// the message the ROM printed, from the zero terminated text at $6004
+    pub text: String,
+}
+
+impl BlarggResult {
+    pub fn passed(&self) -> bool {
+        self.code == 0
+    }
+}
+
+/// returns the status at $6000 once a blargg test ROM has written its
+/// signature
+fn blargg_status(nes: &NES) -> Option<u8> {
+    let bytes = &nes.address_space.cartridge.prg_ram;
+    (bytes[0x0001..0x0004] == BLARGG_SIGNATURE).then_some(bytes[0x0000])
+}
+
+/// runs one of blargg's test ROMs until it reports a result through the
+/// $6000 status block, pressing reset whenever it asks. Panics if there is
+/// no result within BLARGG_FRAME_LIMIT frames
+pub fn run_blargg_rom(nes: &mut NES) -> BlarggResult {
+    let mut frame = 0;
+    while frame < BLARGG_FRAME_LIMIT {
+        nes.run_frame(false);
+        frame += 1;
+        match blargg_status(nes) {
+            None | Some(BLARGG_RUNNING) => {}
+            Some(BLARGG_NEEDS_RESET) => {
+                for _ in 0..BLARGG_RESET_DELAY_FRAMES {
+                    nes.run_frame(false);
+                }
+                frame += BLARGG_RESET_DELAY_FRAMES;
+                nes.reset();
+            }
+            Some(code) => {
+                let text = nes.address_space.cartridge.prg_ram[0x0004..]
+                    .iter()
+                    .take_while(|&&byte| byte != 0)
+                    .map(|&byte| char::from(byte))
+                    .collect();
+                return BlarggResult { code, text };
+            }
+        }
+    }
+    panic!(
+        "no result within {BLARGG_FRAME_LIMIT} frames, status {:?}",
+        blargg_status(nes)
+    );
+}
+
+/// runs the blargg test ROM fixture name, panicking with its message if it
+/// fails or cannot be loaded. Does nothing if the fixture is not available,
+/// see fixture_path
+pub fn assert_blargg_rom_passes(name: &str) {
+    let Some(path) = fixture_path(name) else {
+        return;
+    };
+    assert_blargg_rom_file_passes(&path);
+}
+
+/// runs the blargg test ROM at path, panicking with its message if it fails
+/// or cannot be loaded
+pub fn assert_blargg_rom_file_passes(path: &str) {
+    let mut nes = NES {
+        headless: true,
+        ..Default::default()
+    };
+    if let Err(error) = nes.load_nrom_128(path, 0x8000) {
+        panic!("{error}");
+    }
+    let result = run_blargg_rom(&mut nes);
+    assert!(
+        result.passed(),
+        "{path} failed #{}: {}",
+        result.code,
+        result.text
+    );
+}
+
+/// defines a test for each of blargg's test ROMs, named test => "dir/rom.nes".
+/// The ROMs are not distributed with disco5, so each test is skipped unless
+/// its ROM is in the fixture directory, or fails if REQUIRE_FIXTURES_VAR is
+/// set, see fixture_path. Attributes before a test apply to it, e.g.
+/// #[ignore = "reason"] for cases disco5 is known to fail, which can be run
+/// with --ignored when working on them
+#[macro_export]
+macro_rules! blargg_tests {
+    ($($(#[$attribute:meta])* $test:ident => $rom:expr,)*) => {
+        $(
+            #[test]
+            $(#[$attribute])*
+            fn $test() {
+                $crate::testing::assert_blargg_rom_passes($rom);
+            }
+        )*
+    };
+}
+
+/// clocks the PPU on its own until it finishes a frame, returns the frame.
+/// Lets PPU tests draw without a program driving the CPU
+pub fn draw_ppu_frame(ppu: &mut PPU) -> Frame {
+    ppu.frame_ready = false;
+    while !ppu.frame_ready {
+        ppu.tick();
+    }
+    ppu.framebuffer()
+}
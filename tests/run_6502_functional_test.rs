@@ -1,17 +1,18 @@
+use disco5::nes::bus::BusConfig;
 use disco5::nes::*;
+use disco5::testing::fixture_path;
 
 #[test]
 fn test_6502_functional() {
+    let Some(path) = fixture_path("6502_functional_test.bin") else {
+        return;
+    };
+
     let mut computer: NES = Default::default();
-    computer.address_space.cpu_only_mode = true;
+    computer.address_space.config = BusConfig::FlatRam;
+    computer.headless = true;
 
-    computer
-        .load_asm_as65(
-            &String::from("sample_programs/6502_functional_test.bin"),
-            0x000a,
-            0x400,
-        )
-        .unwrap(); // NOTE: verifies that program loaded without errors
+    computer.load_asm_as65(&path, 0x000a, 0x400).unwrap(); // NOTE: verifies that program loaded without errors
 
     assert_eq!(
         &computer.address_space.bytes[0x400..0x410],
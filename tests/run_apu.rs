@@ -0,0 +1,574 @@
+use disco5::nes::apu::filter::{Filter, FilterKind};
+use disco5::nes::apu::frame_counter::{FrameCounter, FrameSignal};
+use disco5::nes::apu::mixer::{pulse_out, tnd_out, MixerConfig};
+use disco5::nes::apu::resampler::Resampler;
+use disco5::nes::bus::Bus;
+use disco5::nes::irq::IrqSource;
+use disco5::nes::region::Region;
+use disco5::nes::timer::CPU_CLOCK_RATE;
+use disco5::nes::*;
+
+/// CPU cycles per output bit at the fastest DMC rate
+const FASTEST_RATE: usize = 54;
+
+/// a one byte sample of all 1 bits at $C000, set to play at the fastest rate
+fn dmc_with_sample(flags: u8) -> NES {
+    let mut computer: NES = Default::default();
+    let bus = &mut computer.address_space;
+    bus.bytes[0xc000] = 0xff;
+    bus.write(0x4010, flags | 0x0f);
+    bus.write(0x4011, 0x20);
+    bus.write(0x4012, 0x00);
+    bus.write(0x4013, 0x00);
+    computer
+}
+
+/// runs cycles as a CPU would, halting it on those the DMA unit needs
+fn run_cycles(bus: &mut Bus, cycles: usize) {
+    for _ in 0..cycles {
+        if bus.dma.busy() {
+            bus.dma_cycle();
+        } else {
+            bus.tick();
+        }
+    }
+}
+
+#[test]
+fn dmc_plays_a_sample_fetched_by_dma() {
+    let mut computer = dmc_with_sample(0x00);
+    let bus = &mut computer.address_space;
+    assert_eq!(bus.apu.dmc.output_level, 0x20);
+    // the write's own cycle clocks the DMC, which asks for the sample
+    // straight away. The CPU halts on cycle 5, then a dummy cycle and the
+    // get cycle 7 fetch it
+    bus.write(0x4015, 0x10);
+    assert!(bus.dma.busy());
+    assert_eq!(bus.run_dma(), 3);
+    assert!(!bus.apu.dmc.active());
+
+    // the 8 bits already in the shift register play as silence first, then
+    // each bit of the sample raises the level by 2
+    run_cycles(bus, 20 * FASTEST_RATE);
+    assert_eq!(bus.apu.dmc.output_level, 0x30);
+    assert!(!bus.dma.busy());
+    assert!(!bus.irq_sources.contains(IrqSource::DMC));
+}
+
+#[test]
+fn dmc_raises_its_irq_when_a_sample_ends() {
+    let mut computer = dmc_with_sample(0x80);
+    let bus = &mut computer.address_space;
+    bus.write(0x4015, 0x10);
+    bus.run_dma();
+    assert!(bus.apu.dmc.interrupt);
+    assert!(bus.irq_sources.contains(IrqSource::DMC));
+
+    // any $4015 write acknowledges it
+    bus.write(0x4015, 0x00);
+    bus.clock_apu();
+    assert!(!bus.irq_sources.contains(IrqSource::DMC));
+
+    // as does disabling the IRQ. The first byte is still waiting in the
+    // sample buffer, so the replay ends once it has moved on
+    bus.write(0x4015, 0x10);
+    run_cycles(bus, 16 * FASTEST_RATE);
+    assert!(bus.irq_sources.contains(IrqSource::DMC));
+    bus.write(0x4010, 0x0f);
+    bus.clock_apu();
+    assert!(!bus.irq_sources.contains(IrqSource::DMC));
+}
+
+#[test]
+fn looping_dmc_samples_restart_without_an_irq() {
+    let mut computer = dmc_with_sample(0xc0);
+    let bus = &mut computer.address_space;
+    bus.write(0x4015, 0x10);
+    run_cycles(bus, 100 * FASTEST_RATE);
+    assert!(bus.apu.dmc.active());
+    assert!(!bus.apu.dmc.interrupt);
+    // the level saturates at the top of its 7 bits
+    assert_eq!(bus.apu.dmc.output_level, 0x7e);
+}
+
+#[test]
+fn dmc_sample_address_wraps_to_8000() {
+    let mut computer: NES = Default::default();
+    let dmc = &mut computer.address_space.apu.dmc;
+    dmc.sample_address_write(0xff);
+    dmc.sample_length_write(0x04);
+    assert_eq!((dmc.sample_address, dmc.sample_length), (0xffc0, 0x41));
+    dmc.set_enabled(true);
+    for _ in 0..0x40 {
+        dmc.fill_sample_buffer(0);
+        dmc.sample_buffer = None;
+    }
+    assert_eq!(dmc.dma_address(), Some(0x8000));
+}
+
+#[test]
+fn dmc_fetches_stall_the_cpu() {
+    let mut computer = dmc_with_sample(0x00);
+    // LDA #$10, STA $4015, then a NOP sled
+    computer.address_space.bytes[0x8000..0xc000].fill(0xea);
+    computer.address_space.bytes[0x8000..0x8005].copy_from_slice(&[0xa9, 0x10, 0x8d, 0x15, 0x40]);
+    computer.cpu.pc = 0x8000;
+    assert_eq!(computer.step(false), 2);
+    // the DMC fetches on the write's cycle, and the CPU stalls for it
+    // before the next instruction
+    assert_eq!(computer.step(false), 4 + 4);
+    assert_eq!(computer.step(false), 2);
+}
+
+/// clocks a frame counter through one sequence, returning the cycles on
+/// which it sent each signal
+fn frame_signals(frame_counter: &mut FrameCounter, cycles: u32) -> Vec<(u32, FrameSignal)> {
+    (1..=cycles)
+        .filter_map(|cycle| {
+            let signal = frame_counter.clock(Region::Ntsc);
+            (!signal.is_empty()).then_some((cycle, signal))
+        })
+        .collect()
+}
+
+#[test]
+fn frame_counter_steps_through_both_sequences() {
+    let both = FrameSignal::QUARTER | FrameSignal::HALF;
+    let mut frame_counter: FrameCounter = Default::default();
+    let four_step = vec![
+        (7457, FrameSignal::QUARTER),
+        (14913, both),
+        (22371, FrameSignal::QUARTER),
+        (29829, both),
+    ];
+    assert_eq!(frame_signals(&mut frame_counter, 29830), four_step);
+    // the sequence repeats from the cycle after its last step
+    assert_eq!(frame_signals(&mut frame_counter, 29830), four_step);
+
+    // the write lands on an even cycle, so the restart takes 3 cycles and
+    // clocks everything at once
+    frame_counter.write(0x80);
+    assert_eq!(frame_signals(&mut frame_counter, 3), vec![(3, both)]);
+    assert_eq!(
+        frame_signals(&mut frame_counter, 37282),
+        vec![
+            (7457, FrameSignal::QUARTER),
+            (14913, both),
+            (22371, FrameSignal::QUARTER),
+            (37281, both),
+        ]
+    );
+    // only the inhibit flag clears the IRQ raised by the 4-step sequences
+    assert!(frame_counter.interrupt);
+}
+
+#[test]
+fn frame_counter_restarts_later_on_odd_cycles() {
+    let mut frame_counter: FrameCounter = Default::default();
+    frame_counter.clock(Region::Ntsc);
+    frame_counter.write(0x80);
+    assert!(frame_signals(&mut frame_counter, 3).is_empty());
+    assert_eq!(
+        frame_signals(&mut frame_counter, 1),
+        vec![(1, FrameSignal::QUARTER | FrameSignal::HALF)]
+    );
+}
+
+#[test]
+fn frame_irq_is_raised_at_the_end_of_4_step_sequences() {
+    let mut computer: NES = Default::default();
+    let bus = &mut computer.address_space;
+    for _ in 0..29827 {
+        bus.clock_apu();
+    }
+    assert!(!bus.irq_sources.contains(IrqSource::APU_FRAME));
+    bus.clock_apu();
+    assert!(bus.irq_sources.contains(IrqSource::APU_FRAME));
+
+    // setting the inhibit flag acknowledges it and keeps it from coming back
+    bus.write(0x4017, 0x40);
+    bus.clock_apu();
+    assert!(!bus.irq_sources.contains(IrqSource::APU_FRAME));
+    for _ in 0..2 * 29830 {
+        bus.clock_apu();
+    }
+    assert!(!bus.apu.frame_counter.interrupt);
+
+    // as does the 5-step sequence
+    bus.write(0x4017, 0x80);
+    for _ in 0..2 * 37282 {
+        bus.clock_apu();
+    }
+    assert!(!bus.irq_sources.contains(IrqSource::APU_FRAME));
+}
+
+/// reads $4015 as LDA $4015 would, which leaves the operand's high byte on
+/// the data bus for the undriven bit 5
+fn status(bus: &mut Bus) -> u8 {
+    bus.data_bus = 0x40;
+    bus.read(0x4015)
+}
+
+#[test]
+fn status_reads_report_the_dmc_and_acknowledge_the_frame_irq() {
+    let mut computer = dmc_with_sample(0x80);
+    let bus = &mut computer.address_space;
+    // the one byte sample is fetched once the CPU halts, ending it
+    bus.write(0x4015, 0x10);
+    assert_eq!(status(bus) & 0x10, 0x10);
+    bus.run_dma();
+    assert_eq!(status(bus), 0x80);
+    for _ in 0..29828 {
+        bus.clock_apu();
+    }
+    assert!(bus
+        .irq_sources
+        .contains(IrqSource::APU_FRAME | IrqSource::DMC));
+    assert_eq!(status(bus), 0xc0);
+    assert!(!bus.irq_sources.contains(IrqSource::APU_FRAME));
+    // the DMC's flag stays until $4015 is written
+    assert_eq!(status(bus), 0x80);
+    bus.write(0x4015, 0x00);
+    assert_eq!(status(bus), 0x00);
+}
+
+#[test]
+fn apu_registers_are_not_memory() {
+    let mut computer: NES = Default::default();
+    let bus = &mut computer.address_space;
+    bus.write(0x4000, 0x3f);
+    bus.write(0x4017, 0x40);
+    assert_eq!(bus.bytes[0x4000], 0x00);
+    assert_eq!(bus.bytes[0x4017], 0x00);
+
+    // write-only registers read back as open bus, as does bit 5 of $4015
+    bus.data_bus = 0xa5;
+    assert_eq!(bus.read(0x4000), 0xa5);
+    assert_eq!(bus.read(0x4013), 0xa5);
+    assert_eq!(bus.read(0x4015), 0x20);
+}
+
+#[test]
+fn length_counters_silence_notes_after_their_length() {
+    let mut computer: NES = Default::default();
+    let bus = &mut computer.address_space;
+    // loading needs the channel enabled
+    bus.write(0x4003, 0x08);
+    assert_eq!(status(bus), 0x00);
+
+    bus.write(0x4015, 0x0f);
+    // index 1 is the longest note, 254 half frames
+    bus.write(0x4003, 0x08);
+    bus.write(0x4007, 0x00);
+    bus.write(0x400b, 0x18);
+    bus.write(0x400f, 0x00);
+    // the noise channel's counter is halted
+    bus.write(0x400c, 0x20);
+    assert_eq!(bus.apu.pulse[0].length_counter.value, 254);
+    assert_eq!(bus.apu.triangle.length_counter.value, 2);
+    assert_eq!(status(bus), 0x0f);
+
+    // restarting in 5-step mode clocks a half frame, then there are two
+    // per sequence. The write clocks the first cycle itself
+    bus.write(0x4017, 0x80);
+    for _ in 0..4 + 5 * 37282 - 1 {
+        bus.clock_apu();
+    }
+    assert_eq!(bus.apu.pulse[0].length_counter.value, 254 - 11);
+    assert_eq!(status(bus), 0x09);
+
+    bus.write(0x4015, 0x08);
+    assert_eq!(status(bus), 0x08);
+    assert_eq!(bus.apu.noise.length_counter.value, 10);
+}
+
+#[test]
+fn pulse_sweeps_negate_differently() {
+    let mut computer: NES = Default::default();
+    let apu = &mut computer.address_space.apu;
+    for pulse in 0..2 {
+        let base = 0x4000 + 4 * pulse;
+        // negate, shift 1, every half frame
+        apu.write_register(base + 1, 0x89);
+        apu.write_register(base + 2, 0x00);
+        apu.write_register(base + 3, 0x01);
+    }
+    assert_eq!(apu.pulse[0].sweep.target_period(0x100), 0x7f);
+    assert_eq!(apu.pulse[1].sweep.target_period(0x100), 0x80);
+
+    // the divider starts expired, so the first half frame moves the period
+    for pulse in &mut apu.pulse {
+        pulse.clock_sweep();
+    }
+    assert_eq!(apu.pulse[0].timer_period, 0x7f);
+    assert_eq!(apu.pulse[1].timer_period, 0x80);
+}
+
+#[test]
+fn sweeps_mute_out_of_range_periods() {
+    let mut computer: NES = Default::default();
+    let pulse = &mut computer.address_space.apu.pulse[1];
+    // disabled, but an upward sweep past $7FF still mutes
+    pulse.sweep_write(0x01);
+    pulse.timer_low_write(0x00);
+    pulse.length_write(0x06);
+    assert!(pulse.muted());
+    pulse.timer_low_write(0x00);
+    pulse.length_write(0x05);
+    assert!(!pulse.muted());
+    // muted channels keep their period
+    pulse.sweep_write(0x81);
+    pulse.length_write(0x06);
+    pulse.clock_sweep();
+    pulse.clock_sweep();
+    assert_eq!(pulse.timer_period, 0x600);
+
+    pulse.sweep_write(0x00);
+    pulse.timer_low_write(0x07);
+    pulse.length_write(0x00);
+    assert!(pulse.muted());
+}
+
+#[test]
+fn envelopes_decay_and_loop() {
+    let mut computer: NES = Default::default();
+    let apu = &mut computer.address_space.apu;
+    // decay, divider period 2 quarter frames
+    apu.write_register(0x400c, 0x01);
+    apu.write_register(0x400f, 0x00);
+    let noise = &mut apu.noise;
+    noise.envelope.clock();
+    assert_eq!(noise.envelope.output(), 15);
+    for _ in 0..2 * 15 {
+        noise.envelope.clock();
+    }
+    assert_eq!(noise.envelope.output(), 0);
+    // without the loop flag it stays silent
+    for _ in 0..4 {
+        noise.envelope.clock();
+    }
+    assert_eq!(noise.envelope.output(), 0);
+
+    noise.control_write(0x21);
+    noise.envelope.clock();
+    noise.envelope.clock();
+    assert_eq!(noise.envelope.output(), 15);
+
+    // constant volume ignores the decay
+    noise.control_write(0x17);
+    assert_eq!(noise.envelope.output(), 7);
+}
+
+#[test]
+fn quarter_frames_clock_the_envelopes() {
+    let mut computer: NES = Default::default();
+    let bus = &mut computer.address_space;
+    bus.write(0x4004, 0x00);
+    bus.write(0x4007, 0x00);
+    for _ in 0..7457 {
+        bus.clock_apu();
+    }
+    assert_eq!(bus.apu.pulse[1].envelope.output(), 15);
+    for _ in 7457..14913 {
+        bus.clock_apu();
+    }
+    assert_eq!(bus.apu.pulse[1].envelope.output(), 14);
+}
+
+#[test]
+fn resampler_produces_the_output_rate() {
+    let mut resampler = Resampler::new(48_000);
+    let mut samples = Vec::new();
+    for _ in 0..CPU_CLOCK_RATE / 20 {
+        resampler.add(0.5, CPU_CLOCK_RATE);
+    }
+    resampler.take_samples(&mut samples);
+    // a twentieth of a second, the last sample is still being written
+    assert_eq!(samples.len(), 2399);
+    assert!(samples[100..]
+        .iter()
+        .all(|&sample| (sample - 0.5).abs() < 1e-3));
+
+    // once the buffer is full the oldest samples are dropped
+    for _ in 0..CPU_CLOCK_RATE {
+        resampler.add(0.5, CPU_CLOCK_RATE);
+    }
+    samples.clear();
+    resampler.take_samples(&mut samples);
+    assert!(samples.len() < 4096);
+}
+
+#[test]
+fn resampler_filters_out_ultrasonic_tones() {
+    let mut resampler = Resampler::new(44_100);
+    let mut samples = Vec::new();
+    // a square wave at half the CPU clock, far above what 44.1 kHz can
+    // carry, averages out instead of aliasing into audible tones
+    for cycle in 0..CPU_CLOCK_RATE / 20 {
+        resampler.add((cycle % 2) as f32, CPU_CLOCK_RATE);
+    }
+    resampler.take_samples(&mut samples);
+    assert!(samples[100..]
+        .iter()
+        .all(|&sample| (sample - 0.5).abs() < 0.02));
+}
+
+#[test]
+fn pulse_channel_is_heard() {
+    let mut computer: NES = Default::default();
+    let bus = &mut computer.address_space;
+    // the output filters would tilt the square wave's flat tops
+    bus.apu.filters.enabled = false;
+    bus.write(0x4015, 0x01);
+    // 50% duty at constant volume 15, about 440 Hz
+    bus.write(0x4000, 0xbf);
+    bus.write(0x4002, 0xfd);
+    bus.write(0x4003, 0x08);
+    // the four writes clocked the APU too
+    for _ in 0..CPU_CLOCK_RATE / 60 - 4 {
+        bus.clock_apu();
+    }
+    let mut samples = Vec::new();
+    bus.apu.take_samples(&mut samples);
+    assert_eq!(samples.len(), 799);
+    // the triangle idles at level 15, so the square wave swings above that
+    let (min, max) = samples[16..]
+        .iter()
+        .fold((f32::MAX, f32::MIN), |(min, max), &sample| {
+            (min.min(sample), max.max(sample))
+        });
+    assert!(max - min > 0.1);
+    let middle = (min + max) / 2.0;
+    let high = samples.iter().filter(|&&sample| sample > middle).count();
+    assert!((350..450).contains(&high), "{high}");
+
+    bus.write(0x4015, 0x00);
+    for _ in 0..CPU_CLOCK_RATE / 60 {
+        bus.clock_apu();
+    }
+    samples.clear();
+    bus.apu.take_samples(&mut samples);
+    let idle = samples[samples.len() - 1];
+    assert!(samples[16..]
+        .iter()
+        .all(|&sample| (sample - idle).abs() < 1e-3));
+}
+
+#[test]
+fn output_filters_remove_the_dc_offset() {
+    let mut computer: NES = Default::default();
+    let apu = &mut computer.address_space.apu;
+    // the idle triangle holds level 15, which the high-passes decay away
+    for _ in 0..CPU_CLOCK_RATE / 20 {
+        apu.clock();
+    }
+    let mut samples = Vec::new();
+    apu.take_samples(&mut samples);
+    assert!(samples.iter().any(|&sample| sample > 0.1));
+    assert!(samples[samples.len() - 1].abs() < 1e-3);
+
+    apu.filters.enabled = false;
+    for _ in 0..CPU_CLOCK_RATE / 20 {
+        apu.clock();
+    }
+    samples.clear();
+    apu.take_samples(&mut samples);
+    assert!(samples[samples.len() - 1] > 0.1);
+}
+
+#[test]
+fn output_filters_pass_the_audible_band() {
+    let rate = 48_000;
+    let tone = |filter: &mut Filter, frequency: f32| {
+        (0..rate)
+            .map(|i| {
+                let phase = 2.0 * std::f32::consts::PI * frequency * i as f32 / rate as f32;
+                filter.process(phase.sin())
+            })
+            .skip(rate as usize / 2)
+            .fold(0.0f32, |peak, sample| peak.max(sample.abs()))
+    };
+    let mut high_pass = Filter::new(FilterKind::HighPass, 440.0, rate);
+    assert!(tone(&mut high_pass, 50.0) < 0.2);
+    assert!(tone(&mut high_pass, 5000.0) > 0.95);
+    let mut low_pass = Filter::new(FilterKind::LowPass, 14_000.0, rate);
+    assert!(tone(&mut low_pass, 1000.0) > 0.95);
+    assert!(tone(&mut low_pass, 20_000.0) < 0.7);
+}
+
+#[test]
+fn triangle_follows_its_linear_counter() {
+    let mut computer: NES = Default::default();
+    let apu = &mut computer.address_space.apu;
+    apu.write_register(0x4015, 0x04);
+    apu.write_register(0x4008, 0x02);
+    apu.write_register(0x400a, 0x00);
+    apu.write_register(0x400b, 0x08);
+    apu.triangle.clock_linear_counter();
+    assert_eq!(apu.triangle.linear_counter, 2);
+    for _ in 0..5 {
+        apu.triangle.clock_timer();
+    }
+    assert_eq!(apu.triangle.output(), 15 - 5);
+
+    // once the linear counter runs out the level holds
+    apu.triangle.clock_linear_counter();
+    apu.triangle.clock_linear_counter();
+    apu.triangle.clock_timer();
+    assert_eq!(apu.triangle.output(), 15 - 5);
+}
+
+#[test]
+fn noise_is_pseudo_random() {
+    let mut computer: NES = Default::default();
+    let noise = &mut computer.address_space.apu.noise;
+    let mut states = std::collections::HashSet::new();
+    for _ in 0..0x8000 {
+        states.insert(noise.shift_register);
+        noise.clock_timer(Region::Ntsc);
+        for _ in 1..4 {
+            noise.clock_timer(Region::Ntsc);
+        }
+    }
+    // the long sequence visits every non-zero state
+    assert_eq!(states.len(), 0x7fff);
+
+    noise.period_write(0x80);
+    states.clear();
+    for _ in 0..0x8000 {
+        states.insert(noise.shift_register);
+        for _ in 0..4 {
+            noise.clock_timer(Region::Ntsc);
+        }
+    }
+    assert!(states.len() <= 93, "{}", states.len());
+}
+
+#[test]
+fn mixer_config_mutes_and_attenuates_channels() {
+    let mut computer = dmc_with_sample(0x00);
+    let apu = &mut computer.address_space.apu;
+    // the idle triangle holds level 15
+    let everything = apu.mix();
+    apu.mixer = MixerConfig::silent();
+    assert_eq!(apu.mix(), 0.0);
+
+    apu.mixer.dmc = 1.0;
+    let dmc = apu.mix();
+    assert!(dmc > 0.0 && dmc < everything);
+    // the mix is non-linear, attenuating a channel compresses less
+    apu.mixer.dmc = 0.5;
+    assert!(apu.mix() > dmc / 2.0 && apu.mix() < dmc);
+}
+
+#[test]
+fn mixer_is_non_linear() {
+    // the first entries of nesdev's lookup tables
+    assert_eq!(pulse_out(0.0), 0.0);
+    assert!((pulse_out(1.0) - 0.011609).abs() < 1e-6);
+    assert!((tnd_out(1.0) - 0.006700).abs() < 1e-6);
+    // doubling a level less than doubles the output
+    assert!(pulse_out(30.0) < 15.0 * pulse_out(2.0));
+    assert!((pulse_out(30.0) - 0.2575).abs() < 1e-4);
+    assert!((tnd_out(202.0) - 0.7425).abs() < 1e-4);
+}
@@ -0,0 +1,132 @@
+use std::cell::RefCell;
+use std::rc::Rc;
+use std::sync::{Arc, Mutex};
+use std::thread;
+use std::time::{Duration, Instant};
+
+use disco5::nes::audio::SampleRing;
+use disco5::nes::*;
+
+#[test]
+fn sample_ring_holds_the_last_sample_through_underruns() {
+    let mut ring = SampleRing::new(4);
+    ring.push(0.25);
+    ring.push(0.5);
+    let mut buffer = [1.0; 4];
+    ring.fill(&mut buffer);
+    assert_eq!(buffer, [0.25, 0.5, 0.5, 0.5]);
+    assert_eq!(ring.underruns, 1);
+    assert!(ring.is_empty());
+
+    ring.push(-0.5);
+    ring.fill(&mut buffer[..1]);
+    assert_eq!(buffer[0], -0.5);
+    assert_eq!(ring.underruns, 1);
+}
+
+#[test]
+fn full_sample_ring_drops_the_oldest_samples() {
+    let mut ring = SampleRing::new(2);
+    for sample in [0.1, 0.2, 0.3] {
+        ring.push(sample);
+    }
+    assert_eq!((ring.len(), ring.overruns), (2, 1));
+    let mut buffer = [0.0; 2];
+    ring.fill(&mut buffer);
+    assert_eq!(buffer, [0.2, 0.3]);
+}
+
+#[test]
+fn sample_ring_fills_every_channel_of_a_frame() {
+    let mut ring = SampleRing::new(4);
+    ring.push(0.25);
+    ring.push(-0.5);
+    let mut buffer = [1.0; 6];
+    ring.fill_frames(&mut buffer, 2);
+    assert_eq!(buffer, [0.25, 0.25, -0.5, -0.5, -0.5, -0.5]);
+    assert_eq!(ring.underruns, 1);
+}
+
+fn demo_with_audio_output() -> (NES, Arc<Mutex<SampleRing>>) {
+    let mut computer: NES = Default::default();
+    computer.headless = true;
+    computer.load_demo().unwrap();
+    let output = Arc::new(Mutex::new(SampleRing::new(48_000)));
+    computer.audio_output = Some(output.clone());
+    (computer, output)
+}
+
+#[test]
+fn frames_feed_the_audio_output() {
+    let (mut computer, output) = demo_with_audio_output();
+    computer.run_frame(false);
+    computer.run_frame(false);
+    // the first frame is cut short by power on
+    let queued = output.lock().unwrap().len();
+    assert!((1300..1600).contains(&queued), "{queued}");
+}
+
+#[test]
+fn audio_sync_waits_for_the_backend() {
+    let (mut computer, output) = demo_with_audio_output();
+    assert!(computer.wait_for_audio(Duration::ZERO));
+    for _ in 0..10 {
+        computer.run_frame(false);
+    }
+    // nothing is draining the queue
+    assert!(!computer.wait_for_audio(Duration::from_millis(5)));
+
+    let backend = thread::spawn(move || {
+        let mut buffer = [0.0; 256];
+        // about 10 frames
+        for _ in 0..32 {
+            output.lock().unwrap().fill(&mut buffer);
+            thread::sleep(Duration::from_millis(1));
+        }
+    });
+    assert!(computer.wait_for_audio(Duration::from_secs(10)));
+    backend.join().unwrap();
+}
+
+#[test]
+fn audio_callback_gets_each_frames_samples() {
+    let (mut computer, output) = demo_with_audio_output();
+    let lengths = Rc::new(RefCell::new(Vec::new()));
+    let seen = lengths.clone();
+    computer.set_audio_callback(move |samples| seen.borrow_mut().push(samples.len()));
+    for _ in 0..3 {
+        computer.run_frame(false);
+    }
+    let lengths = lengths.borrow();
+    assert_eq!(lengths.len(), 3);
+    // about 800 samples a frame at 48 kHz
+    assert!(lengths[1..]
+        .iter()
+        .all(|length| (795..=805).contains(length)));
+    // the audio output is fed the same samples
+    assert_eq!(output.lock().unwrap().len(), lengths.iter().sum::<usize>());
+}
+
+#[test]
+fn a_stalled_audio_backend_is_not_slept_on() {
+    let (mut computer, _output) = demo_with_audio_output();
+    computer.headless = false;
+    computer.sync = SyncMode::Audio;
+    for _ in 0..10 {
+        computer.run_frame(false);
+    }
+    // the wait already ran past the frame, so it goes ahead at once
+    assert_eq!(
+        computer.pace_frame(Instant::now()),
+        FramePacing::AudioStalled
+    );
+
+    computer.audio_output = None;
+    let FramePacing::Slept(slept) = computer.pace_frame(Instant::now()) else {
+        panic!("without an audio output frames are paced by sleeping");
+    };
+    assert!(slept <= Duration::from_secs_f64(1.0 / 60.0));
+
+    computer.headless = true;
+    assert_eq!(computer.pace_frame(Instant::now()), FramePacing::Unpaced);
+}
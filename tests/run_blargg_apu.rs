@@ -0,0 +1,59 @@
+// blargg's APU test ROMs, which report through the status block at $6000,
+// see testing::blargg_tests
+
+use disco5::blargg_tests;
+use disco5::testing::assert_blargg_rom_file_passes;
+
+/// a 32 KB NROM-256 image whose second bank writes the status block and
+/// passes if a length counter loaded through $4003 shows in $4015. The
+/// first bank is all BRKs, so the ROM only passes if both banks load
+fn length_counter_rom() -> Vec<u8> {
+    let program = [
+        0x78, // $C000 SEI
+        0xa9, 0x80, 0x8d, 0x00, 0x60, // $C001 LDA #$80, STA $6000
+        0xa9, 0xde, 0x8d, 0x01, 0x60, // $C006 LDA #$DE, STA $6001
+        0xa9, 0xb0, 0x8d, 0x02, 0x60, // $C00B LDA #$B0, STA $6002
+        0xa9, 0x61, 0x8d, 0x03, 0x60, // $C010 LDA #$61, STA $6003
+        0xa9, 0x01, 0x8d, 0x15, 0x40, // $C015 LDA #$01, STA $4015
+        0xa9, 0x18, 0x8d, 0x03, 0x40, // $C01A LDA #$18, STA $4003
+        0xad, 0x15, 0x40, // $C01F LDA $4015
+        0x29, 0x01, // $C022 AND #$01
+        0xf0, 0x15, // $C024 BEQ $C03B
+        0xa9, 0x6f, 0x8d, 0x04, 0x60, // $C026 LDA #'o', STA $6004
+        0xa9, 0x6b, 0x8d, 0x05, 0x60, // $C02B LDA #'k', STA $6005
+        0xa9, 0x00, 0x8d, 0x06, 0x60, // $C030 LDA #0, STA $6006
+        0x8d, 0x00, 0x60, // $C035 STA $6000
+        0x4c, 0x38, 0xc0, // $C038 JMP $C038
+        0xa9, 0x02, 0x8d, 0x00, 0x60, // $C03B LDA #2, STA $6000
+        0x4c, 0x40, 0xc0, // $C040 JMP $C040
+    ];
+    let mut prg_rom = vec![0x00; 0x8000];
+    prg_rom[0x4000..0x4000 + program.len()].copy_from_slice(&program);
+    // NMI and IRQ park on the pass loop, reset starts the program
+    prg_rom[0x7ffa..].copy_from_slice(&[0x38, 0xc0, 0x00, 0xc0, 0x38, 0xc0]);
+    let header = [0x4e, 0x45, 0x53, 0x1a, 2, 1, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0];
+    [header.as_slice(), &prg_rom, &[0; 0x2000]].concat()
+}
+
+#[test]
+fn harness_runs_a_32_kb_rom() {
+    let path = std::env::temp_dir().join("disco5_blargg_length_counter.nes");
+    std::fs::write(&path, length_counter_rom()).unwrap();
+    assert_blargg_rom_file_passes(path.to_str().unwrap());
+    std::fs::remove_file(path).unwrap();
+}
+
+blargg_tests! {
+    length_counter => "apu_test/1-len_ctr.nes",
+    length_table => "apu_test/2-len_table.nes",
+    irq_flag => "apu_test/3-irq_flag.nes",
+    jitter => "apu_test/4-jitter.nes",
+    length_timing => "apu_test/5-len_timing.nes",
+    irq_flag_timing => "apu_test/6-irq_flag_timing.nes",
+    dmc_basics => "apu_test/7-dmc_basics.nes",
+    dmc_rates => "apu_test/8-dmc_rates.nes",
+    mixer_dmc => "apu_mixer/dmc.nes",
+    mixer_noise => "apu_mixer/noise.nes",
+    mixer_square => "apu_mixer/square.nes",
+    mixer_triangle => "apu_mixer/triangle.nes",
+}
@@ -0,0 +1,80 @@
+// blargg's PPU test ROMs, which report through the status block at $6000,
+// see testing::blargg_tests
+
+use disco5::blargg_tests;
+use disco5::nes::*;
+use disco5::testing::run_blargg_rom;
+
+/// writes the status block, asks for a reset, then passes after the reset
+/// with the message "ok"
+const RESET_THEN_PASS: [u8; 55] = [
+    0xa9, 0x80, 0x8d, 0x00, 0x60, // $8000 LDA #$80, STA $6000
+    0xa9, 0xde, 0x8d, 0x01, 0x60, // $8005 LDA #$DE, STA $6001
+    0xa9, 0xb0, 0x8d, 0x02, 0x60, // $800A LDA #$B0, STA $6002
+    0xa9, 0x61, 0x8d, 0x03, 0x60, // $800F LDA #$61, STA $6003
+    0xa5, 0x10, 0xd0, 0x0a, // $8014 LDA $10, BNE $8022
+    0xe6, 0x10, // $8018 INC $10
+    0xa9, 0x81, 0x8d, 0x00, 0x60, // $801A LDA #$81, STA $6000
+    0x4c, 0x1f, 0x80, // $801F JMP $801F
+    0xa9, 0x6f, 0x8d, 0x04, 0x60, // $8022 LDA #'o', STA $6004
+    0xa9, 0x6b, 0x8d, 0x05, 0x60, // $8027 LDA #'k', STA $6005
+    0xa9, 0x00, 0x8d, 0x06, 0x60, // $802C LDA #0, STA $6006
+    0x8d, 0x00, 0x60, // $8031 STA $6000
+    0x4c, 0x34, 0x80, // $8034 JMP $8034
+];
+
+#[test]
+fn harness_resets_and_reads_the_result() {
+    let mut computer: NES = Default::default();
+    computer.headless = true;
+    let bytes = &mut computer.address_space.bytes;
+    bytes[0x8000..0x8000 + RESET_THEN_PASS.len()].copy_from_slice(&RESET_THEN_PASS);
+    bytes[0xfffc..0xfffe].copy_from_slice(&[0x00, 0x80]);
+    computer.reset();
+
+    let result = run_blargg_rom(&mut computer);
+    assert!(result.passed());
+    assert_eq!(result.text, "ok");
+    assert_eq!(computer.address_space.bytes[0x10], 1);
+}
+
+blargg_tests! {
+    vbl_basics => "ppu_vbl_nmi/01-vbl_basics.nes",
+    vbl_set_time => "ppu_vbl_nmi/02-vbl_set_time.nes",
+    vbl_clear_time => "ppu_vbl_nmi/03-vbl_clear_time.nes",
+    nmi_control => "ppu_vbl_nmi/04-nmi_control.nes",
+    nmi_timing => "ppu_vbl_nmi/05-nmi_timing.nes",
+    suppression => "ppu_vbl_nmi/06-suppression.nes",
+    nmi_on_timing => "ppu_vbl_nmi/07-nmi_on_timing.nes",
+    nmi_off_timing => "ppu_vbl_nmi/08-nmi_off_timing.nes",
+    even_odd_frames => "ppu_vbl_nmi/09-even_odd_frames.nes",
+    even_odd_timing => "ppu_vbl_nmi/10-even_odd_timing.nes",
+    #[ignore = "sprite 0 hit is not implemented, PPUSTATUS::SPRITE_ZERO_HIT is never set"]
+    sprite_hit_basics => "ppu_sprite_hit/01-basics.nes",
+    #[ignore = "sprite 0 hit is not implemented, PPUSTATUS::SPRITE_ZERO_HIT is never set"]
+    sprite_hit_alignment => "ppu_sprite_hit/02-alignment.nes",
+    #[ignore = "sprite 0 hit is not implemented, PPUSTATUS::SPRITE_ZERO_HIT is never set"]
+    sprite_hit_corners => "ppu_sprite_hit/03-corners.nes",
+    #[ignore = "sprite 0 hit is not implemented, PPUSTATUS::SPRITE_ZERO_HIT is never set"]
+    sprite_hit_flip => "ppu_sprite_hit/04-flip.nes",
+    #[ignore = "sprite 0 hit is not implemented, PPUSTATUS::SPRITE_ZERO_HIT is never set"]
+    sprite_hit_left_clip => "ppu_sprite_hit/05-left_clip.nes",
+    #[ignore = "sprite 0 hit is not implemented, PPUSTATUS::SPRITE_ZERO_HIT is never set"]
+    sprite_hit_right_edge => "ppu_sprite_hit/06-right_edge.nes",
+    #[ignore = "sprite 0 hit is not implemented, PPUSTATUS::SPRITE_ZERO_HIT is never set"]
+    sprite_hit_screen_bottom => "ppu_sprite_hit/07-screen_bottom.nes",
+    #[ignore = "sprite 0 hit is not implemented, PPUSTATUS::SPRITE_ZERO_HIT is never set"]
+    sprite_hit_double_height => "ppu_sprite_hit/08-double_height.nes",
+    #[ignore = "sprite 0 hit is not implemented, PPUSTATUS::SPRITE_ZERO_HIT is never set"]
+    sprite_hit_timing => "ppu_sprite_hit/09-timing.nes",
+    #[ignore = "sprite 0 hit is not implemented, PPUSTATUS::SPRITE_ZERO_HIT is never set"]
+    sprite_hit_timing_order => "ppu_sprite_hit/10-timing_order.nes",
+    sprite_overflow_basics => "ppu_sprite_overflow/01-basics.nes",
+    sprite_overflow_details => "ppu_sprite_overflow/02-details.nes",
+    sprite_overflow_timing => "ppu_sprite_overflow/03-timing.nes",
+    sprite_overflow_obscure => "ppu_sprite_overflow/04-obscure.nes",
+    sprite_overflow_emulator => "ppu_sprite_overflow/05-emulator.nes",
+    ppu_open_bus => "ppu_open_bus.nes",
+    ppu_read_buffer => "test_ppu_read_buffer.nes",
+    oam_read => "oam_read.nes",
+}
@@ -0,0 +1,1013 @@
+use std::cell::RefCell;
+use std::rc::Rc;
+
+use disco5::nes::bus::BusConfig;
+use disco5::nes::bus_trace::{AccessKind, BusTracer};
+use disco5::nes::controller::Buttons;
+use disco5::nes::cpu::ReadWrite;
+use disco5::nes::device::Device;
+use disco5::nes::dma::{DmaController, DmaCycle};
+use disco5::nes::mapper::Mapper;
+use disco5::nes::memory_map::{MemoryMapEntry, RegionKind};
+use disco5::nes::ram_init::RamInit;
+use disco5::nes::watchpoint::WatchAccess;
+use disco5::nes::*;
+
+/// loads program at $0200 without going through the bus
+fn load_program(computer: &mut NES, program: &[u8]) {
+    computer.address_space.bytes[0x0200..0x0200 + program.len()].copy_from_slice(program);
+    computer.cpu.pc = 0x0200;
+}
+
+#[test]
+fn internal_ram_is_mirrored_through_1fff() {
+    let mut computer: NES = Default::default();
+    let bus = &mut computer.address_space;
+
+    bus.write(0x0801, 0x42);
+    for address in [0x0001, 0x0801, 0x1001, 0x1801] {
+        assert_eq!(bus.read(address), 0x42);
+    }
+    bus.write(0x1fff, 0x24);
+    assert_eq!(bus.read(0x07ff), 0x24);
+    assert_eq!(bus.bytes[0x07ff], 0x24);
+}
+
+#[test]
+fn stack_works_through_a_mirror() {
+    // LDA #$5A, STA $1900 (a mirror of $0100), LDX #$00, TXS, PLA, STA $10
+    let program = [
+        0xa9, 0x5a, 0x8d, 0x01, 0x19, 0xa2, 0x00, 0x9a, 0x68, 0x85, 0x10,
+    ];
+    let mut computer: NES = Default::default();
+    load_program(&mut computer, &program);
+    for _ in 0..6 {
+        computer.step(false);
+    }
+    assert_eq!(computer.address_space.read(0x0010), 0x5a);
+}
+
+#[test]
+fn unmapped_reads_return_the_last_value_on_the_bus() {
+    let mut computer: NES = Default::default();
+    let bus = &mut computer.address_space;
+
+    bus.write(0x0010, 0x37);
+    assert_eq!(bus.read(0x4020), 0x37);
+    assert_eq!(bus.read(0x0010), 0x37);
+    bus.write(0x0011, 0x99);
+    assert_eq!(bus.read(0x0010), 0x37);
+    assert_eq!(bus.read(0x5fff), 0x37);
+
+    // nothing stores writes there either
+    bus.write(0x5000, 0x12);
+    bus.read(0x0011);
+    assert_eq!(bus.read(0x5000), 0x99);
+}
+
+#[test]
+fn unmapped_cpu_loads_see_the_address_high_byte() {
+    // LDA $5123, STA $10
+    let program = [0xad, 0x23, 0x51, 0x85, 0x10];
+    let mut computer: NES = Default::default();
+    load_program(&mut computer, &program);
+    for _ in 0..2 {
+        computer.step(false);
+    }
+    assert_eq!(computer.address_space.read(0x0010), 0x51);
+}
+
+#[test]
+fn controllers_shift_out_their_buttons() {
+    let mut computer: NES = Default::default();
+    computer.set_buttons(0, Buttons::A | Buttons::START | Buttons::RIGHT);
+    computer.set_buttons(1, Buttons::B);
+    let bus = &mut computer.address_space;
+
+    bus.write(0x4016, 0x01);
+    bus.write(0x4016, 0x00);
+    let first: Vec<u8> = (0..8).map(|_| bus.read(0x4016) & 1).collect();
+    assert_eq!(first, [1, 0, 0, 1, 0, 0, 0, 1]);
+    let second: Vec<u8> = (0..8).map(|_| bus.read(0x4017) & 1).collect();
+    assert_eq!(second, [0, 1, 0, 0, 0, 0, 0, 0]);
+    // official controllers report 1s once all 8 buttons are read
+    assert_eq!(bus.read(0x4016) & 1, 1);
+}
+
+#[test]
+fn strobed_controllers_keep_reporting_a() {
+    let mut computer: NES = Default::default();
+    computer.set_buttons(0, Buttons::A);
+    let bus = &mut computer.address_space;
+    bus.write(0x4016, 0x01);
+    for _ in 0..10 {
+        assert_eq!(bus.read(0x4016) & 1, 1);
+    }
+    // the buttons are latched when the strobe drops
+    bus.controllers[0].buttons = Buttons::B;
+    bus.write(0x4016, 0x00);
+    bus.controllers[0].buttons = Buttons::empty();
+    assert_eq!(bus.read(0x4016) & 1, 0);
+    assert_eq!(bus.read(0x4016) & 1, 1);
+}
+
+#[test]
+fn controller_reads_leave_the_upper_bits_open() {
+    // LDA $4016, STA $10
+    let program = [0xad, 0x16, 0x40, 0x85, 0x10];
+    let mut computer: NES = Default::default();
+    load_program(&mut computer, &program);
+    for _ in 0..2 {
+        computer.step(false);
+    }
+    // $40 from the operand, with the first button in bit 0
+    assert_eq!(computer.address_space.read(0x0010), 0x40);
+}
+
+#[test]
+fn bus_trace_logs_accesses_within_its_ranges() {
+    // LDA #$80, STA $2001, STA $10, LDA $2002
+    let program = [0xa9, 0x80, 0x8d, 0x01, 0x20, 0x85, 0x10, 0xad, 0x02, 0x20];
+    let mut computer: NES = Default::default();
+    computer.trace_bus(BusTracer::new(vec![0x2000..=0x3fff]));
+    load_program(&mut computer, &program);
+    for _ in 0..4 {
+        computer.step(false);
+    }
+    let tracer = computer.stop_bus_trace().unwrap();
+
+    let accesses: Vec<(u16, AccessKind)> = tracer
+        .log
+        .iter()
+        .map(|access| (access.address, access.kind))
+        .collect();
+    assert_eq!(
+        accesses,
+        [(0x2001, AccessKind::Write), (0x2002, AccessKind::Read)]
+    );
+    assert_eq!(tracer.log[0].data, 0x80);
+    assert!(tracer.log[1].cycle > tracer.log[0].cycle);
+
+    let mut dump = Vec::new();
+    tracer.dump(&mut dump).unwrap();
+    let dump = String::from_utf8(dump).unwrap();
+    assert_eq!(dump.lines().count(), 2);
+    assert!(dump.lines().next().unwrap().ends_with("W $2001 = $80"));
+}
+
+#[test]
+fn bus_trace_can_stream_every_access() {
+    // LDA #$01, STA $10
+    let program = [0xa9, 0x01, 0x85, 0x10];
+    let mut computer: NES = Default::default();
+    let streamed = Rc::new(RefCell::new(Vec::new()));
+    let mut tracer = BusTracer::new(Vec::new());
+    let sink = streamed.clone();
+    tracer.set_stream(move |access| sink.borrow_mut().push(*access));
+    computer.trace_bus(tracer);
+    load_program(&mut computer, &program);
+    for _ in 0..2 {
+        computer.step(false);
+    }
+
+    let streamed = streamed.borrow();
+    // opcode and operand fetches, then the store
+    assert_eq!(streamed.len(), 5);
+    assert_eq!(streamed[4].address, 0x0010);
+    assert_eq!(streamed[4].kind, AccessKind::Write);
+    assert!(computer.stop_bus_trace().unwrap().log.is_empty());
+}
+
+#[test]
+fn watchpoints_stop_the_run_loop() {
+    // LDA $10, LDX #$07, STX $0300, JMP $0200
+    let program = [0xa5, 0x10, 0xa2, 0x07, 0x8e, 0x00, 0x03, 0x4c, 0x00, 0x02];
+    let mut computer: NES = Default::default();
+    load_program(&mut computer, &program);
+    let bus = &mut computer.address_space;
+    let index = bus
+        .watchpoints
+        .add(0x0300, 0x03ff, WatchAccess::WRITE)
+        .unwrap();
+
+    let StopReason::Watchpoint(hit) = computer.run_until(1000) else {
+        panic!("watchpoint not hit");
+    };
+    assert_eq!(hit.index, index);
+    assert_eq!(hit.access.address, 0x0300);
+    assert_eq!(hit.access.data, 0x07);
+    assert_eq!(hit.access.kind, AccessKind::Write);
+    // the store has completed
+    assert_eq!(computer.cpu.pc, 0x0207);
+    assert_eq!(computer.address_space.bytes[0x0300], 0x07);
+
+    // reads of the zero page don't match a write watchpoint
+    computer.address_space.watchpoints.remove(index);
+    computer
+        .address_space
+        .watchpoints
+        .add(0x0010, 0x0010, WatchAccess::READ | WatchAccess::WRITE);
+    let StopReason::Watchpoint(hit) = computer.run_until(1000) else {
+        panic!("watchpoint not hit");
+    };
+    assert_eq!(hit.access.kind, AccessKind::Read);
+    assert_eq!(computer.cpu.pc, 0x0202);
+
+    computer.address_space.watchpoints.clear();
+    assert_eq!(computer.run_until(1000), StopReason::CyclesElapsed);
+}
+
+/// device counting CPU cycles, readable at its base address, that keeps the
+/// last byte written to it
+struct CycleCounter {
+    cycles: Rc<RefCell<u64>>,
+    latch: u8,
+}
+
+impl Device for CycleCounter {
+    fn read(&mut self, address: u16) -> u8 {
+        match address & 1 {
+            0 => *self.cycles.borrow() as u8,
+            _ => self.latch,
+        }
+    }
+
+    fn write(&mut self, _address: u16, data: u8) {
+        self.latch = data;
+    }
+
+    fn tick(&mut self) {
+        *self.cycles.borrow_mut() += 1;
+    }
+}
+
+#[test]
+fn mapped_devices_answer_their_range() {
+    let mut computer: NES = Default::default();
+    let cycles = Rc::new(RefCell::new(0));
+    computer.address_space.map_device(
+        0x5000..=0x5001,
+        Box::new(CycleCounter {
+            cycles: cycles.clone(),
+            latch: 0,
+        }),
+    );
+    // NOP, NOP, LDA $5000, STA $5001
+    load_program(
+        &mut computer,
+        &[0xea, 0xea, 0xad, 0x00, 0x50, 0x8d, 0x01, 0x50],
+    );
+    for _ in 0..4 {
+        computer.step(false);
+    }
+    // ticked on every bus cycle, so the load saw the NOPs' cycles and the
+    // three before its own read
+    assert_eq!(*cycles.borrow(), 2 + 2 + 4 + 4);
+    assert_eq!(computer.cpu.a, 7);
+    assert_eq!(computer.address_space.read(0x5001), 7);
+    // nothing leaks into the memory map
+    assert_eq!(computer.address_space.bytes[0x5001], 0);
+}
+
+#[test]
+fn mapped_devices_tick_on_a_flat_ram_bus() {
+    let mut computer: NES = Default::default();
+    computer.address_space.config = BusConfig::FlatRam;
+    let cycles = Rc::new(RefCell::new(0));
+    computer.address_space.map_device(
+        0x5000..=0x5001,
+        Box::new(CycleCounter {
+            cycles: cycles.clone(),
+            latch: 0,
+        }),
+    );
+    // NOP, LDA $5000
+    load_program(&mut computer, &[0xea, 0xad, 0x00, 0x50]);
+    computer.step(false);
+    computer.step(false);
+    assert_eq!(*cycles.borrow(), 2 + 4);
+    assert_eq!(computer.cpu.a, 2 + 3);
+}
+
+#[test]
+fn mapped_devices_take_priority_over_the_memory_map() {
+    let mut computer: NES = Default::default();
+    let bus = &mut computer.address_space;
+    bus.write(0x0010, 0x11);
+    bus.map_device(
+        0x0010..=0x0011,
+        Box::new(CycleCounter {
+            cycles: Default::default(),
+            latch: 0x22,
+        }),
+    );
+    assert_eq!(bus.read(0x0011), 0x22);
+    bus.write(0x0011, 0x33);
+    assert_eq!(bus.read(0x0011), 0x33);
+    assert_eq!(bus.bytes[0x0011], 0x00);
+    // addresses around it are untouched
+    assert_eq!(bus.read(0x0012), 0x00);
+    bus.write(0x0012, 0x44);
+    assert_eq!(bus.read(0x0012), 0x44);
+}
+
+#[test]
+fn oam_dma_waits_a_cycle_to_align_on_odd_cycles() {
+    // LDA #$02, STA $4014, twice
+    let program = [0xa9, 0x02, 0x8d, 0x14, 0x40, 0xa9, 0x02, 0x8d, 0x14, 0x40];
+    let mut computer: NES = Default::default();
+    load_program(&mut computer, &program);
+    computer.step(false);
+    // the transfer starts on cycle 2 + 4
+    assert_eq!(computer.step(false), 4 + 513);
+    computer.step(false);
+    // and here on the odd cycle 2 + 4 + 513 + 2 + 4
+    assert_eq!(computer.step(false), 4 + 514);
+}
+
+/// clocks dma from cycle until it lets go of the bus, standing in for the
+/// Bus by latching a byte for each read. The DMC asks for a byte just
+/// before dmc_request_at, returns the cycle each action ran on
+fn clock_dma_unit(
+    dma: &mut DmaController,
+    mut cycle: u64,
+    dmc_request_at: Option<u64>,
+) -> Vec<(u64, DmaCycle)> {
+    let mut actions = Vec::new();
+    while dma.busy() || Some(cycle) <= dmc_request_at {
+        if Some(cycle) == dmc_request_at {
+            dma.dmc_address = Some(0xc000);
+        }
+        let action = dma.clock(cycle);
+        if let DmaCycle::OamRead(address) = action {
+            dma.latch_oam_byte(address as u8);
+        }
+        actions.push((cycle, action));
+        cycle += 1;
+    }
+    actions
+}
+
+#[test]
+fn oam_dma_waits_for_a_get_cycle_to_read() {
+    let mut dma = DmaController::default();
+    dma.start_oam(0x02);
+    // halted on a put cycle, the first read comes straight after
+    let actions = clock_dma_unit(&mut dma, 6, None);
+    assert_eq!(actions.len(), 513);
+    assert_eq!(
+        actions[..3],
+        [
+            (6, DmaCycle::Idle),
+            (7, DmaCycle::OamRead(0x0200)),
+            (8, DmaCycle::OamWrite(0x00)),
+        ]
+    );
+    assert_eq!(actions[512], (518, DmaCycle::OamWrite(0xff)));
+
+    // halted on a get cycle, it takes a cycle to line up, +1
+    dma.start_oam(0x02);
+    let actions = clock_dma_unit(&mut dma, 7, None);
+    assert_eq!(actions.len(), 514);
+    assert_eq!(
+        actions[..3],
+        [
+            (7, DmaCycle::Idle),
+            (8, DmaCycle::Idle),
+            (9, DmaCycle::OamRead(0x0200)),
+        ]
+    );
+    assert!(!dma.busy());
+}
+
+#[test]
+fn dmc_fetches_take_a_get_cycle_from_oam_dma() {
+    // on its own a fetch takes a halt and a dummy cycle before its get
+    let mut dma = DmaController::default();
+    let actions = clock_dma_unit(&mut dma, 7, Some(7));
+    assert_eq!(actions.last(), Some(&(9, DmaCycle::DmcRead(0xc000))));
+    assert_eq!(actions.len(), 3);
+    let actions = clock_dma_unit(&mut dma, 6, Some(6));
+    assert_eq!(actions.last(), Some(&(9, DmaCycle::DmcRead(0xc000))));
+    assert_eq!(actions.len(), 4);
+
+    // during OAM DMA the halt is shared, and the fetch pushes the next
+    // read and write back a get and put cycle, +2
+    dma.start_oam(0x02);
+    let actions = clock_dma_unit(&mut dma, 6, Some(100));
+    assert_eq!(actions.len(), 513 + 2);
+    assert_eq!(
+        actions[94..98],
+        [
+            (100, DmaCycle::OamWrite(0x2e)),
+            (101, DmaCycle::DmcRead(0xc000)),
+            (102, DmaCycle::Idle),
+            (103, DmaCycle::OamRead(0x022f)),
+        ]
+    );
+    let writes = actions
+        .iter()
+        .filter(|(_, action)| matches!(action, DmaCycle::OamWrite(_)))
+        .count();
+    assert_eq!(writes, 256);
+}
+
+#[test]
+fn dmc_fetches_interleave_with_a_cpu_started_oam_dma() {
+    let mut computer: NES = Default::default();
+    // SEI, LDA #$10, STA $4015, LDA #$03, STA $4014, NOP
+    load_program(
+        &mut computer,
+        &[
+            0x78, 0xa9, 0x10, 0x8d, 0x15, 0x40, 0xa9, 0x03, 0x8d, 0x14, 0x40, 0xea,
+        ],
+    );
+    let bus = &mut computer.address_space;
+    for (offset, byte) in bus.bytes[0x0300..0x0400].iter_mut().enumerate() {
+        *byte = offset as u8;
+    }
+    // a looping 17 byte sample at $C000, at the fastest rate
+    bus.write(0x4010, 0x4f);
+    bus.write(0x4012, 0x00);
+    bus.write(0x4013, 0x01);
+    computer.cpu.clock = computer.address_space.cycle;
+    let seen = Rc::new(RefCell::new(Vec::new()));
+    let log = Rc::clone(&seen);
+    computer
+        .address_space
+        .on_transaction(move |access| log.borrow_mut().push(access.address));
+    for _ in 0..4 {
+        computer.step(false);
+    }
+    seen.borrow_mut().clear();
+
+    let halt = computer.cpu.clock + 4;
+    let cycles = computer.step(false);
+    let dmc_fetches = seen
+        .borrow()
+        .iter()
+        .filter(|&&address| address >= 0xc000)
+        .count() as u64;
+    assert!(dmc_fetches > 0);
+    assert_eq!(cycles, 4 + 513 + (halt & 1) + 2 * dmc_fetches);
+    assert!(computer
+        .address_space
+        .ppu
+        .oam_ram
+        .iter()
+        .enumerate()
+        .all(|(index, &byte)| byte == index as u8));
+}
+
+#[test]
+fn prg_ram_is_mapped_at_6000() {
+    let mut computer: NES = Default::default();
+    let bus = &mut computer.address_space;
+
+    bus.write(0x6000, 0x12);
+    bus.write(0x7fff, 0x34);
+    assert_eq!(bus.read(0x6000), 0x12);
+    assert_eq!(bus.read(0x7fff), 0x34);
+    assert_eq!(bus.cartridge.prg_ram[0x1fff], 0x34);
+}
+
+#[test]
+fn boards_without_prg_ram_leave_6000_open() {
+    let mut computer: NES = Default::default();
+    let bus = &mut computer.address_space;
+    bus.cartridge.prg_ram_enabled = false;
+
+    bus.write(0x6000, 0x12);
+    assert_eq!(bus.cartridge.prg_ram[0], 0);
+    bus.data_bus = 0x5a;
+    assert_eq!(bus.read(0x6000), 0x5a);
+}
+
+#[test]
+fn battery_backed_prg_ram_is_saved_beside_the_rom() {
+    let directory = std::env::temp_dir().join("disco5_battery");
+    std::fs::create_dir_all(&directory).unwrap();
+    let rom = directory.join("demo.nes");
+    let save = directory.join("demo.sav");
+    let mut image = DEMO_ROM.to_vec();
+    image[6] |= 0b10;
+    std::fs::write(&rom, image).unwrap();
+    let _ = std::fs::remove_file(&save);
+
+    let mut computer: NES = Default::default();
+    computer.headless = true;
+    computer
+        .load_nrom_128(rom.to_str().unwrap(), 0x8000)
+        .unwrap();
+    assert!(computer.address_space.cartridge.battery);
+    computer.address_space.write(0x6010, 0x77);
+    computer.save_battery().unwrap();
+    assert_eq!(std::fs::read(&save).unwrap()[0x10], 0x77);
+
+    let mut computer: NES = Default::default();
+    computer.headless = true;
+    computer
+        .load_nrom_128(rom.to_str().unwrap(), 0x8000)
+        .unwrap();
+    assert_eq!(computer.address_space.read(0x6010), 0x77);
+}
+
+#[test]
+fn nrom_256_images_fill_both_halves_of_prg_rom() {
+    let directory = std::env::temp_dir().join("disco5_nrom_256");
+    std::fs::create_dir_all(&directory).unwrap();
+    let rom = directory.join("big.nes");
+    let mut image = DEMO_ROM[..16].to_vec();
+    image[4] = 2;
+    // the second bank is all $EA, apart from the demo's vectors
+    let mut second_bank = vec![0xea; 0x4000];
+    second_bank[0x3ffa..].copy_from_slice(&DEMO_ROM[0x400a..0x4010]);
+    image.extend(&DEMO_ROM[16..0x4010]);
+    image.extend(&second_bank);
+    image.extend(&DEMO_ROM[0x4010..]);
+    std::fs::write(&rom, &image).unwrap();
+
+    let mut computer: NES = Default::default();
+    computer.headless = true;
+    computer
+        .load_nrom_128(rom.to_str().unwrap(), 0x8000)
+        .unwrap();
+    let bus = &computer.address_space;
+    assert_eq!(&bus.bytes[0x8000..0xc000], &DEMO_ROM[16..0x4010]);
+    assert_eq!(&bus.bytes[0xc000..0x10000], second_bank.as_slice());
+    assert_eq!(&bus.ppu.address_space.chr.bytes[..], &DEMO_ROM[0x4010..]);
+
+    // 64 KB is more than NROM can hold
+    image[4] = 4;
+    image.splice(0x8010..0x8010, second_bank.repeat(2));
+    std::fs::write(&rom, image).unwrap();
+    let error = computer
+        .load_nrom_128(rom.to_str().unwrap(), 0x8000)
+        .unwrap_err();
+    assert_eq!(error.kind(), std::io::ErrorKind::InvalidData);
+    assert!(error.to_string().contains("64 KB of PRG ROM on mapper 0"));
+}
+
+#[test]
+fn images_without_chr_rom_get_blank_chr_ram() {
+    let directory = std::env::temp_dir().join("disco5_chr_ram");
+    std::fs::create_dir_all(&directory).unwrap();
+    let rom = directory.join("chr_ram.nes");
+    // the demo's header and PRG ROM with its CHR ROM cut off
+    let mut image = DEMO_ROM[..0x4010].to_vec();
+    image[5] = 0;
+    std::fs::write(&rom, &image).unwrap();
+
+    let mut computer: NES = Default::default();
+    computer.headless = true;
+    computer
+        .load_nrom_128(rom.to_str().unwrap(), 0x8000)
+        .unwrap();
+    let chr = &computer.address_space.ppu.address_space.chr;
+    assert!(chr.writable);
+    assert!(chr.bytes.iter().all(|&byte| byte == 0));
+    assert_eq!(
+        &computer.address_space.bytes[0x8000..0xc000],
+        &DEMO_ROM[16..0x4010]
+    );
+}
+
+#[test]
+fn peeking_ppu_registers_has_no_side_effects() {
+    let mut computer: NES = Default::default();
+    let bus = &mut computer.address_space;
+    bus.ppu.ppu_status = 0x80;
+    bus.write(0x2006, 0x21);
+    bus.write(0x2006, 0x00);
+    bus.ppu.ppu_data_buffer = 0x33;
+
+    assert_eq!(bus.peek(0x2002) & 0x80, 0x80);
+    assert_eq!(bus.peek(0x2002) & 0x80, 0x80);
+    assert_eq!(bus.peek(0x2007), 0x33);
+    assert_eq!(bus.ppu.ppu_addr(), 0x2100);
+    assert_eq!(bus.ppu.ppu_data_buffer, 0x33);
+
+    assert_eq!(bus.read(0x2002) & 0x80, 0x80);
+    assert_eq!(bus.peek(0x200a) & 0x80, 0);
+}
+
+#[test]
+fn peeks_are_not_traced_or_watched() {
+    let mut computer: NES = Default::default();
+    let bus = &mut computer.address_space;
+    bus.accesses.enabled = true;
+    bus.watchpoints
+        .add(0x0000, 0x07ff, WatchAccess::READ | WatchAccess::WRITE);
+    bus.data_bus = 0x5a;
+
+    bus.poke(0x0010, 0x42);
+    assert_eq!(bus.peek(0x0810), 0x42);
+    assert!(bus.accesses.entries().is_empty());
+    assert!(bus.watchpoints.hit.is_none());
+    assert_eq!(bus.data_bus, 0x5a);
+}
+
+#[test]
+fn peeking_a_controller_does_not_shift_it() {
+    let mut computer: NES = Default::default();
+    computer.set_buttons(0, Buttons::A);
+    let bus = &mut computer.address_space;
+    bus.write(0x4016, 1);
+    bus.write(0x4016, 0);
+
+    assert_eq!(bus.peek(0x4016) & 1, 1);
+    assert_eq!(bus.peek(0x4016) & 1, 1);
+    assert_eq!(bus.read(0x4016) & 1, 1);
+    assert_eq!(bus.read(0x4016) & 1, 0);
+}
+
+#[test]
+fn pokes_skip_register_side_effects() {
+    let mut computer: NES = Default::default();
+    let bus = &mut computer.address_space;
+    bus.write(0x2003, 0x10);
+    bus.write(0x2006, 0x21);
+    bus.write(0x2006, 0x00);
+
+    bus.poke(0x2004, 0x77);
+    bus.poke(0x2007, 0x66);
+    assert_eq!(bus.ppu.oam_ram[0x10], 0x77);
+    assert_eq!(bus.ppu.oam_addr, 0x10);
+    assert_eq!(bus.ppu.ppu_addr(), 0x2100);
+    assert_eq!(bus.ppu.address_space.read(0x2100), 0x66);
+
+    bus.poke(0xc000, 0xea);
+    assert_eq!(bus.peek(0xc000), 0xea);
+}
+
+#[test]
+fn every_access_ticks_the_master_clock() {
+    let mut computer: NES = Default::default();
+    let bus = &mut computer.address_space;
+    let dot = |bus: &bus::Bus| bus.ppu.scanline() * 341 + bus.ppu.dot();
+    let start = dot(bus);
+
+    bus.read(0x0000);
+    bus.write(0x0000, 0x01);
+    assert_eq!(bus.cycle, 2);
+    assert_eq!(dot(bus) - start, 2 * 3);
+    assert_eq!(bus.peek(0x0000), 0x01);
+    assert_eq!(bus.cycle, 2);
+}
+
+#[test]
+fn registers_see_the_ppu_partway_through_an_instruction() {
+    let mut computer: NES = Default::default();
+    // LDA $2002
+    load_program(&mut computer, &[0xad, 0x02, 0x20]);
+    // vblank starts 8 dots on, during the opcode and operand fetches
+    let ppu = &mut computer.address_space.ppu;
+    while (ppu.scanline(), ppu.dot()) != (240, 334) {
+        ppu.tick();
+    }
+    computer.step(false);
+    assert_eq!(computer.cpu.a & 0x80, 0x80);
+    assert_eq!(computer.address_space.cycle, computer.cpu.clock);
+}
+
+/// latches writes to $8000-$FFFF in a bank register and answers reads of
+/// $1000, a RAM mirror, with it
+#[derive(Debug)]
+struct BankLatch {
+    bank: Rc<RefCell<u8>>,
+}
+
+impl Mapper for BankLatch {
+    fn name(&self) -> &str {
+        "bank latch"
+    }
+
+    fn cpu_read(&mut self, address: u16) -> Option<u8> {
+        (address == 0x1000).then(|| *self.bank.borrow())
+    }
+
+    fn cpu_peek(&self, address: u16) -> Option<u8> {
+        (address == 0x1000).then(|| *self.bank.borrow())
+    }
+
+    fn cpu_write(&mut self, address: u16, data: u8) -> bool {
+        if address < 0x8000 {
+            return false;
+        }
+        *self.bank.borrow_mut() = data;
+        true
+    }
+}
+
+#[test]
+fn mappers_see_accesses_before_the_memory_map() {
+    let mut computer: NES = Default::default();
+    let bank = Rc::new(RefCell::new(0));
+    let bus = &mut computer.address_space;
+    bus.cartridge.mapper = Box::new(BankLatch { bank: bank.clone() });
+    bus.bytes[0xc000] = 0xea;
+
+    bus.write(0xc000, 0x03);
+    assert_eq!(*bank.borrow(), 3);
+    assert_eq!(bus.bytes[0xc000], 0xea);
+    assert_eq!(bus.read(0xc000), 0xea);
+
+    // the mapper gets the address before RAM mirroring folds it
+    bus.write(0x0000, 0x55);
+    assert_eq!(bus.read(0x1000), 0x03);
+    assert_eq!(bus.peek(0x1000), 0x03);
+    assert_eq!(bus.read(0x0800), 0x55);
+}
+
+/// a board with a byte of RAM at $5000
+#[derive(Debug, Default)]
+struct ExpansionRam {
+    byte: u8,
+}
+
+impl Mapper for ExpansionRam {
+    fn name(&self) -> &str {
+        "expansion RAM"
+    }
+
+    fn cpu_read(&mut self, address: u16) -> Option<u8> {
+        (address == 0x5000).then_some(self.byte)
+    }
+
+    fn cpu_peek(&self, address: u16) -> Option<u8> {
+        (address == 0x5000).then_some(self.byte)
+    }
+
+    fn cpu_write(&mut self, address: u16, data: u8) -> bool {
+        if address != 0x5000 {
+            return false;
+        }
+        self.byte = data;
+        true
+    }
+}
+
+#[test]
+fn mappers_answer_the_expansion_area() {
+    let mut computer: NES = Default::default();
+    let bus = &mut computer.address_space;
+    bus.cartridge.mapper = Box::<ExpansionRam>::default();
+
+    bus.write(0x5000, 0x42);
+    bus.write(0x0010, 0x11);
+    assert_eq!(bus.read(0x5000), 0x42);
+    assert_eq!(bus.peek(0x5000), 0x42);
+    // the rest of the area is still open bus
+    assert_eq!(bus.read(0x5001), 0x42);
+}
+
+#[test]
+fn word_reads_wrap_like_the_6502() {
+    let mut computer: NES = Default::default();
+    let bus = &mut computer.address_space;
+    bus.bytes[0x0000] = 0x12;
+    bus.bytes[0x00ff] = 0x34;
+    bus.bytes[0x0100] = 0x56;
+    bus.bytes[0x0300] = 0x78;
+    bus.bytes[0x03ff] = 0x9a;
+    bus.bytes[0x0400] = 0xbc;
+
+    assert_eq!(bus.read_u16(0x00ff), 0x5634);
+    assert_eq!(bus.read_u16_zp_wrapped(0xff), 0x1234);
+    assert_eq!(bus.read_u16(0x03ff), 0xbc9a);
+    assert_eq!(bus.read_u16_page_wrapped(0x03ff), 0x789a);
+    assert_eq!(bus.read_u16_page_wrapped(0x0300), 0x0078);
+}
+
+#[test]
+fn indirect_indexed_pointers_wrap_within_the_zero_page() {
+    // LDY #$01, LDA ($FF),Y
+    let mut computer: NES = Default::default();
+    load_program(&mut computer, &[0xa0, 0x01, 0xb1, 0xff]);
+    let bus = &mut computer.address_space;
+    bus.bytes[0x00ff] = 0x00;
+    bus.bytes[0x0000] = 0x04;
+    bus.bytes[0x0100] = 0x05;
+    bus.bytes[0x0401] = 0x44;
+    bus.bytes[0x0501] = 0x55;
+    computer.step(false);
+    computer.step(false);
+    assert_eq!(computer.cpu.a, 0x44);
+}
+
+#[test]
+fn the_data_bus_follows_every_access() {
+    let mut computer: NES = Default::default();
+    let bus = &mut computer.address_space;
+    bus.bytes[0x0300..0x0400].fill(0x11);
+    bus.bytes[0x03ff] = 0x77;
+
+    bus.write(0x0010, 0x22);
+    assert_eq!(bus.data_bus, 0x22);
+    bus.read(0x0300);
+    assert_eq!(bus.data_bus, 0x11);
+    // the status register is read inside the 2A03
+    bus.read(0x4015);
+    assert_eq!(bus.data_bus, 0x11);
+    // OAM DMA leaves the last byte it copied
+    bus.write(0x4014, 0x03);
+    bus.run_dma();
+    assert_eq!(bus.data_bus, 0x77);
+    assert_eq!(bus.read(0x4018), 0x77);
+}
+
+#[test]
+fn the_pins_show_the_last_access() {
+    let mut computer: NES = Default::default();
+    let bus = &mut computer.address_space;
+
+    bus.write(0x0810, 0x5a);
+    assert_eq!(
+        (bus.address_bus, bus.data_bus, bus.read_write),
+        (0x0810, 0x5a, ReadWrite::Write)
+    );
+    bus.read(0x0010);
+    assert_eq!(
+        (bus.address_bus, bus.data_bus, bus.read_write),
+        (0x0010, 0x5a, ReadWrite::Read)
+    );
+
+    // execute drives the same access from the pins
+    bus.address_bus = 0x0020;
+    bus.data_bus = 0x33;
+    bus.execute(ReadWrite::Write);
+    assert_eq!(bus.bytes[0x0020], 0x33);
+    bus.data_bus = 0;
+    bus.address_bus = 0x0820;
+    bus.execute(ReadWrite::Read);
+    assert_eq!(bus.data_bus, 0x33);
+    assert_eq!(bus.cycle, 4);
+}
+
+#[test]
+fn on_transaction_sees_every_access_of_an_instruction() {
+    let mut computer: NES = Default::default();
+    // INC $10
+    load_program(&mut computer, &[0xe6, 0x10]);
+    computer.address_space.bytes[0x0010] = 0x41;
+    let seen = Rc::new(RefCell::new(Vec::new()));
+    let log = Rc::clone(&seen);
+    computer.address_space.on_transaction(move |access| {
+        log.borrow_mut()
+            .push((access.cycle, access.address, access.data, access.kind))
+    });
+
+    let start = computer.cpu.clock;
+    assert_eq!(computer.step(false), 5);
+    assert_eq!(
+        *seen.borrow(),
+        [
+            (start, 0x0200, 0xe6, AccessKind::Read),
+            (start + 1, 0x0201, 0x10, AccessKind::Read),
+            (start + 2, 0x0010, 0x41, AccessKind::Read),
+            (start + 3, 0x0010, 0x42, AccessKind::Write),
+        ]
+    );
+    let bus = &computer.address_space;
+    assert_eq!(
+        (bus.address_bus, bus.data_bus, bus.read_write),
+        (0x0010, 0x42, ReadWrite::Write)
+    );
+}
+
+#[test]
+fn memory_map_describes_the_attached_hardware() {
+    let mut computer: NES = Default::default();
+    let bus = &mut computer.address_space;
+    let map = bus.memory_map();
+    let answering = |map: &[MemoryMapEntry], address| {
+        map.iter()
+            .find(|entry| entry.range.contains(&address))
+            .map(|entry| (entry.name.clone(), entry.kind))
+            .unwrap()
+    };
+    assert_eq!(
+        answering(&map, 0x0123),
+        (String::from("internal RAM"), RegionKind::Ram)
+    );
+    assert_eq!(answering(&map, 0x3456).1, RegionKind::Mirror);
+    assert_eq!(
+        answering(&map, 0x8000),
+        (String::from("NROM PRG ROM"), RegionKind::Rom)
+    );
+    assert_eq!(map[0].to_string(), "$0000-$07FF Ram       internal RAM");
+
+    bus.cartridge.mapper = Box::new(BankLatch {
+        bank: Default::default(),
+    });
+    bus.cartridge.prg_ram_enabled = false;
+    bus.map_device(
+        0x6000..=0x6001,
+        Box::new(CycleCounter {
+            cycles: Default::default(),
+            latch: 0,
+        }),
+    );
+    let map = bus.memory_map();
+    assert_eq!(
+        answering(&map, 0x6000),
+        (String::from("mapped device"), RegionKind::Device)
+    );
+    assert_eq!(answering(&map, 0x6002).1, RegionKind::OpenBus);
+    assert_eq!(answering(&map, 0xc000).0, "bank latch PRG ROM");
+}
+
+#[test]
+fn flat_ram_maps_everything_but_the_timer_to_ram() {
+    let mut computer: NES = Default::default();
+    let bus = &mut computer.address_space;
+    bus.config = bus::BusConfig::FlatRam;
+    bus.attach_timer(0xfff0);
+    let map = bus.memory_map();
+    assert_eq!(map.len(), 2);
+    assert_eq!(map[0].range, 0xfff0..=0xfff7);
+    assert_eq!(map[1].range, 0x0000..=0xffff);
+}
+
+#[test]
+fn ram_is_filled_with_a_pattern_at_power_on() {
+    let mut ram = [0x55; 16];
+    RamInit::Ones.fill(&mut ram);
+    assert_eq!(ram, [0xff; 16]);
+    RamInit::Alternating.fill(&mut ram);
+    assert_eq!(&ram[..8], &[0, 0, 0, 0, 0xff, 0xff, 0xff, 0xff]);
+    assert_eq!(ram[..8], ram[8..]);
+
+    let mut again = [0; 16];
+    RamInit::Random(7).fill(&mut ram);
+    RamInit::Random(7).fill(&mut again);
+    assert_eq!(ram, again);
+    RamInit::Random(8).fill(&mut again);
+    assert_ne!(ram, again);
+
+    let mut computer: NES = Default::default();
+    computer.headless = true;
+    computer.address_space.ram_init = RamInit::Ones;
+    computer.address_space.bytes[0x0800] = 0x12;
+    computer.load_demo().unwrap();
+    assert!(computer.address_space.bytes[..0x800]
+        .iter()
+        .all(|&byte| byte == 0xff));
+    // only internal RAM is filled
+    assert_eq!(computer.address_space.bytes[0x0800], 0x12);
+}
+
+#[test]
+fn ram_patterns_parse_from_the_command_line() {
+    assert_eq!("zero".parse(), Ok(RamInit::Zero));
+    assert_eq!("ff".parse(), Ok(RamInit::Ones));
+    assert_eq!("alternating".parse(), Ok(RamInit::Alternating));
+    assert_eq!("random:42".parse(), Ok(RamInit::Random(42)));
+    assert!(matches!("random".parse(), Ok(RamInit::Random(_))));
+    assert!("random:x".parse::<RamInit>().is_err());
+    assert!("checkerboard".parse::<RamInit>().is_err());
+}
+
+#[test]
+fn fuzzing_randomizes_unmapped_reads_and_logs_writes() {
+    let mut computer: NES = Default::default();
+    let bus = &mut computer.address_space;
+    bus.fuzz_unmapped(3);
+    bus.cartridge.prg_ram_enabled = false;
+
+    let reads: Vec<u8> = [0x4018, 0x5000, 0x6000, 0x5fff]
+        .into_iter()
+        .map(|address| {
+            bus.data_bus = 0x00;
+            bus.read(address)
+        })
+        .collect();
+    assert!(reads.iter().any(|&data| data != 0x00));
+    // mapped memory and open bus registers are left alone
+    bus.write(0x0010, 0x21);
+    assert_eq!(bus.read(0x0010), 0x21);
+    assert_eq!(bus.read(0x4000), 0x21);
+
+    bus.write(0x5123, 0x45);
+    bus.write(0x7000, 0x67);
+    let fuzz = bus.unmapped_fuzz.as_ref().unwrap();
+    assert_eq!(fuzz.reads, 4);
+    let writes: Vec<(u16, u8)> = fuzz
+        .writes
+        .iter()
+        .map(|access| (access.address, access.data))
+        .collect();
+    assert_eq!(writes, [(0x5123, 0x45), (0x7000, 0x67)]);
+    assert_eq!(fuzz.report()[0], "unmapped accesses: 4 reads, 2 writes");
+
+    // the same seed gives the same bytes
+    let mut computer: NES = Default::default();
+    let bus = &mut computer.address_space;
+    bus.fuzz_unmapped(3);
+    bus.cartridge.prg_ram_enabled = false;
+    let again: Vec<u8> = [0x4018, 0x5000, 0x6000, 0x5fff]
+        .into_iter()
+        .map(|address| bus.read(address))
+        .collect();
+    assert_eq!(again, reads);
+}
@@ -0,0 +1,60 @@
+use disco5::nes::cheats::{parse_par_code, Cheats};
+use disco5::nes::freeze::FreezeTiming;
+use disco5::nes::*;
+
+#[test]
+fn par_codes_parse_with_or_without_a_colon() {
+    assert_eq!(parse_par_code("0055:09"), Ok((0x0055, 0x09)));
+    assert_eq!(parse_par_code("6010ff"), Ok((0x6010, 0xff)));
+    assert!(parse_par_code("0055:9").is_err());
+    assert!(parse_par_code("00G5:09").is_err());
+    assert_eq!(parse_par_code("1fff:01"), Ok((0x1fff, 0x01)));
+    assert_eq!(parse_par_code("7fff:01"), Ok((0x7fff, 0x01)));
+    // registers, the expansion area, and ROM are out of a RAM code's reach
+    for code in ["2007ff", "4015:0f", "5000:01", "8000:ea"] {
+        assert!(parse_par_code(code).is_err(), "{code}");
+    }
+}
+
+#[test]
+fn cheats_hold_memory_each_frame() {
+    let mut computer: NES = Default::default();
+    computer.headless = true;
+    computer.load_demo().unwrap();
+    let lives = computer.address_space.cheats.add_code("0855:09").unwrap();
+    let save = computer.address_space.cheats.add(0x6000, 0x42);
+
+    computer.run_frame(false);
+    // $0855 is a mirror of $0055
+    assert_eq!(computer.address_space.bytes[0x0055], 0x09);
+    assert_eq!(computer.address_space.cartridge.prg_ram[0], 0x42);
+
+    computer.address_space.cheats.set_enabled(lives, false);
+    computer.address_space.write(0x0055, 0x01);
+    computer.address_space.cheats.remove(save);
+    computer.address_space.write(0x6000, 0x02);
+    computer.run_frame(false);
+    assert_eq!(computer.address_space.bytes[0x0055], 0x01);
+    assert_eq!(computer.address_space.cartridge.prg_ram[0], 0x02);
+    assert!(!computer.address_space.cheats.get(lives).unwrap().enabled);
+    assert_eq!(computer.address_space.cheats.get(save), None);
+
+    // freed indices are reused
+    assert_eq!(computer.address_space.cheats.add(0x0001, 0x01), save);
+}
+
+#[test]
+fn enabled_cheats_are_held_by_a_freezer() {
+    let mut cheats = Cheats::default();
+    let first = cheats.add(0x0010, 0x01);
+    cheats.add(0x0020, 0x02);
+    cheats.set_enabled(first, false);
+
+    let freezer = cheats.freezer();
+    assert_eq!(freezer.timing, FreezeTiming::EveryFrame);
+    assert_eq!(freezer.frozen_value(0x0010), None);
+    assert_eq!(freezer.frozen_value(0x0020), Some(0x02));
+
+    cheats.clear();
+    assert!(cheats.freezer().is_empty());
+}
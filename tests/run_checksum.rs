@@ -0,0 +1,117 @@
+use disco5::nes::checksum::*;
+use disco5::nes::controller::Buttons;
+use disco5::nes::*;
+
+fn demo() -> NES {
+    let mut computer = NES {
+        headless: true,
+        checksum_channel: Some(ChecksumChannel::new(4)),
+        ..Default::default()
+    };
+    computer.load_demo().unwrap();
+    computer
+}
+
+#[test]
+fn replay_matches_recorded_checksums() {
+    let mut recording = demo();
+    for _ in 0..12 {
+        recording.run_frame(false);
+    }
+    let recorded = recording.checksum_channel.unwrap();
+    let frames: Vec<u64> = recorded.checksums.iter().map(|entry| entry.frame).collect();
+    assert_eq!(frames, [4, 8, 12]);
+
+    assert_eq!(verify_checksums(&mut demo(), &recorded), Ok(()));
+}
+
+/// the demo with a program in RAM that keeps copying controller 1's A
+/// button into $10
+fn polling_controller() -> NES {
+    let mut computer = demo();
+    // SEI, loop: LDA #1, STA $4016, LDA #0, STA $4016, LDA $4016, STA $10,
+    // JMP loop
+    let program = [
+        0x78, 0xa9, 0x01, 0x8d, 0x16, 0x40, 0xa9, 0x00, 0x8d, 0x16, 0x40, 0xad, 0x16, 0x40, 0x85,
+        0x10, 0x4c, 0x01, 0x02,
+    ];
+    computer.address_space.bytes[0x0200..0x0200 + program.len()].copy_from_slice(&program);
+    computer.cpu.pc = 0x0200;
+    computer
+}
+
+#[test]
+fn replay_feeds_back_recorded_input() {
+    let mut recording = polling_controller();
+    for frame in 1..=12 {
+        let buttons = if (3..7).contains(&frame) {
+            Buttons::A
+        } else {
+            Buttons::empty()
+        };
+        recording.address_space.controllers[0].buttons = buttons;
+        recording.run_frame(false);
+    }
+    let recorded = recording.checksum_channel.unwrap();
+    let changes: Vec<u64> = recorded.inputs.iter().map(|input| input.frame).collect();
+    assert_eq!(changes, [1, 3, 7]);
+
+    let mut replay = polling_controller();
+    assert_eq!(verify_checksums(&mut replay, &recorded), Ok(()));
+    assert_eq!(
+        replay.address_space.controllers[0].buttons,
+        Buttons::empty()
+    );
+
+    let mut ignoring_input = recorded.clone();
+    ignoring_input.inputs.clear();
+    assert!(matches!(
+        verify_checksums(&mut polling_controller(), &ignoring_input),
+        Err(ReplayError::Desync(_))
+    ));
+}
+
+#[test]
+fn frames_already_run_are_rejected() {
+    let mut recording = demo();
+    for _ in 0..8 {
+        recording.run_frame(false);
+    }
+    let recorded = recording.checksum_channel.unwrap();
+
+    let mut replay = demo();
+    for _ in 0..5 {
+        replay.run_frame(false);
+    }
+    assert_eq!(
+        verify_checksums(&mut replay, &recorded),
+        Err(ReplayError::FrameAlreadyRun {
+            frame: 4,
+            current: 5
+        })
+    );
+}
+
+#[test]
+fn desync_reports_first_differing_frame() {
+    let mut recording = demo();
+    for _ in 0..12 {
+        recording.run_frame(false);
+    }
+    let recorded = recording.checksum_channel.unwrap();
+    let mut first = recorded.clone();
+    first.checksums.truncate(1);
+    let mut rest = recorded.clone();
+    rest.checksums.remove(0);
+
+    let mut replay = demo();
+    assert_eq!(verify_checksums(&mut replay, &first), Ok(()));
+    replay.run_frame(false);
+    replay.address_space.bytes[0x0300] ^= 0xff;
+
+    let Err(ReplayError::Desync(desync)) = verify_checksums(&mut replay, &rest) else {
+        panic!("the replay should have desynced");
+    };
+    assert_eq!(desync.frame, 8);
+    assert_eq!(desync.expected, recorded.checksums[1].checksum);
+}
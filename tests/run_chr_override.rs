@@ -0,0 +1,54 @@
+use std::fs::{self, File};
+use std::time::{Duration, SystemTime};
+
+use disco5::nes::*;
+
+#[test]
+fn chr_override_reloads_when_file_changes() {
+    let path = std::env::temp_dir().join("disco5_chr_override.chr");
+    let filename = path.to_str().unwrap();
+    fs::write(filename, [0xaa; 16]).unwrap();
+
+    let mut computer: NES = Default::default();
+    computer.headless = true;
+    computer.load_demo().unwrap();
+    computer.set_chr_override(filename).unwrap();
+    assert_eq!(
+        &computer.address_space.ppu.address_space.chr.bytes[..16],
+        &[0xaa; 16]
+    );
+    // tiles past the end of the file keep the cartridge's data
+    assert_eq!(
+        &computer.address_space.ppu.address_space.chr.bytes[16..32],
+        &DEMO_ROM[16 + 0x4000 + 16..16 + 0x4000 + 32]
+    );
+
+    fs::write(filename, [0x55; 16]).unwrap();
+    // filesystems with coarse timestamps could otherwise miss the change
+    File::options()
+        .write(true)
+        .open(filename)
+        .unwrap()
+        .set_modified(SystemTime::now() + Duration::from_secs(2))
+        .unwrap();
+    computer.run_frame(false);
+    assert_eq!(
+        &computer.address_space.ppu.address_space.chr.bytes[..16],
+        &[0x55; 16]
+    );
+
+    fs::remove_file(filename).unwrap();
+}
+
+#[test]
+fn chr_override_rejects_partial_tiles() {
+    let path = std::env::temp_dir().join("disco5_chr_override_partial.chr");
+    let filename = path.to_str().unwrap();
+    fs::write(filename, [0xaa; 15]).unwrap();
+
+    let mut computer: NES = Default::default();
+    assert!(computer.set_chr_override(filename).is_err());
+    assert!(computer.chr_override.is_none());
+
+    fs::remove_file(filename).unwrap();
+}
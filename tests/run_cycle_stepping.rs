@@ -0,0 +1,177 @@
+use std::cell::RefCell;
+use std::rc::Rc;
+
+use disco5::nes::bus::BusConfig;
+use disco5::nes::bus_trace::AccessKind;
+use disco5::nes::*;
+use disco5::testing::fixture_path;
+
+/// loads program at $0200 without going through the bus
+fn load_program(computer: &mut NES, program: &[u8]) {
+    computer.address_space.bytes[0x0200..0x0200 + program.len()].copy_from_slice(program);
+    computer.cpu.pc = 0x0200;
+}
+
+/// records every bus access as (cycle, address, data, kind)
+fn log_transactions(computer: &mut NES) -> Rc<RefCell<Vec<(u64, u16, u8, AccessKind)>>> {
+    let seen = Rc::new(RefCell::new(Vec::new()));
+    let log = Rc::clone(&seen);
+    computer.address_space.on_transaction(move |access| {
+        log.borrow_mut()
+            .push((access.cycle, access.address, access.data, access.kind))
+    });
+    seen
+}
+
+#[test]
+fn restore_mid_instruction_snapshot() {
+    let Some(path) = fixture_path("countdown.txt") else {
+        return;
+    };
+
+    let mut computer: NES = Default::default();
+    computer.address_space.config = BusConfig::FlatRam;
+
+    computer.load_asm_6502js(&path).unwrap();
+
+    // LDX #$10 takes 2 cycles, so the snapshot lands in the middle of it
+    computer.step_cycle();
+    let snapshot = computer.snapshot_cpu();
+    assert_eq!(snapshot.in_flight.unwrap().micro_op, 1);
+
+    for _ in 0..25 {
+        computer.step_cycle();
+    }
+    let expected = computer.snapshot_cpu();
+
+    computer.restore_cpu(snapshot);
+    for _ in 0..25 {
+        computer.step_cycle();
+    }
+    let actual = computer.snapshot_cpu();
+
+    assert_eq!(actual.pc, expected.pc);
+    assert_eq!(actual.clock, expected.clock);
+    assert_eq!(
+        actual.in_flight.map(|in_flight| in_flight.micro_op),
+        expected.in_flight.map(|in_flight| in_flight.micro_op)
+    );
+    assert_eq!(
+        (actual.a, actual.x, actual.y),
+        (expected.a, expected.x, expected.y)
+    );
+}
+
+#[test]
+fn each_cycle_makes_one_access() {
+    let mut computer: NES = Default::default();
+    // INC $10
+    load_program(&mut computer, &[0xe6, 0x10]);
+    computer.address_space.bytes[0x0010] = 0x41;
+    let seen = log_transactions(&mut computer);
+
+    let expected = [
+        (0, 0x0200, 0xe6, AccessKind::Read),
+        (1, 0x0201, 0x10, AccessKind::Read),
+        (2, 0x0010, 0x41, AccessKind::Read),
+        (3, 0x0010, 0x42, AccessKind::Write),
+    ];
+    for (cycle, access) in expected.iter().enumerate() {
+        computer.step_cycle();
+        assert_eq!(seen.borrow().len(), cycle + 1);
+        assert_eq!(seen.borrow()[cycle], *access);
+        assert_eq!(computer.cpu.clock, cycle as u64 + 1);
+    }
+    // the write lands on the fourth cycle, the fifth is spent off the bus
+    assert_eq!(computer.address_space.bytes[0x0010], 0x42);
+    assert!(computer.cpu.in_flight.is_some());
+    computer.step_cycle();
+    assert!(computer.cpu.in_flight.is_none());
+    assert_eq!(seen.borrow().len(), 4);
+    assert_eq!((computer.cpu.clock, computer.cpu.pc), (5, 0x0202));
+}
+
+#[test]
+fn registers_hold_until_the_last_access() {
+    let mut computer: NES = Default::default();
+    // LDA $0300
+    load_program(&mut computer, &[0xad, 0x00, 0x03]);
+    computer.address_space.bytes[0x0300] = 0x99;
+
+    for _ in 0..3 {
+        computer.step_cycle();
+        assert_eq!((computer.cpu.a, computer.cpu.pc), (0, 0x0200));
+    }
+    computer.step_cycle();
+    assert_eq!((computer.cpu.a, computer.cpu.pc), (0x99, 0x0203));
+    assert!(computer.cpu.in_flight.is_none());
+}
+
+#[test]
+fn restored_snapshot_resumes_with_the_next_access() {
+    let mut computer: NES = Default::default();
+    // INC $10, then LDA $10
+    load_program(&mut computer, &[0xe6, 0x10, 0xa5, 0x10]);
+    computer.address_space.bytes[0x0010] = 0x41;
+    let seen = log_transactions(&mut computer);
+
+    // stop after the opcode and operand have been fetched
+    computer.step_cycle();
+    computer.step_cycle();
+    let cpu = computer.snapshot_cpu();
+    let bus = computer.address_space.save_state();
+    assert_eq!(cpu.in_flight.unwrap().micro_op, 2);
+    assert_eq!(cpu.in_flight.unwrap().latched[..2], [0xe6, 0x10]);
+
+    for _ in 0..6 {
+        computer.step_cycle();
+    }
+    let after_snapshot = seen.borrow_mut().split_off(2);
+    assert_eq!(
+        after_snapshot,
+        [
+            (2, 0x0010, 0x41, AccessKind::Read),
+            (3, 0x0010, 0x42, AccessKind::Write),
+            (5, 0x0202, 0xa5, AccessKind::Read),
+            (6, 0x0203, 0x10, AccessKind::Read),
+            (7, 0x0010, 0x42, AccessKind::Read),
+        ]
+    );
+    assert_eq!(computer.cpu.a, 0x42);
+
+    computer.address_space.load_state(&bus).unwrap();
+    computer.restore_cpu(cpu);
+    seen.borrow_mut().clear();
+    for _ in 0..6 {
+        computer.step_cycle();
+    }
+    // no refetch of the opcode or operand, the instruction picks up where
+    // the snapshot left it
+    assert_eq!(*seen.borrow(), after_snapshot);
+    assert_eq!(computer.cpu.a, 0x42);
+    assert_eq!(computer.cpu.clock, 8);
+}
+
+#[test]
+fn cycle_stepping_matches_instruction_stepping() {
+    // LDX #$03, loop: DEX, STX $20,Y, BNE loop, JSR $0300, with RTS at $0300
+    let program = [0xa2, 0x03, 0xca, 0x96, 0x20, 0xd0, 0xfb, 0x20, 0x00, 0x03];
+    let run = |by_cycle: bool| {
+        let mut computer: NES = Default::default();
+        load_program(&mut computer, &program);
+        computer.address_space.bytes[0x0300] = 0x60;
+        computer.cpu.sp = 0xfd;
+        let seen = log_transactions(&mut computer);
+        while computer.cpu.pc != 0x020a || computer.cpu.in_flight.is_some() {
+            if by_cycle {
+                computer.step_cycle();
+            } else {
+                computer.step(false);
+            }
+        }
+        let cpu = computer.cpu;
+        let accesses = seen.borrow().clone();
+        ((cpu.a, cpu.x, cpu.y, cpu.sp, cpu.clock), accesses)
+    };
+    assert_eq!(run(true), run(false));
+}
@@ -0,0 +1,77 @@
+use std::time::Duration;
+
+use disco5::nes::bus::BusConfig;
+use disco5::nes::region::Region;
+use disco5::nes::*;
+
+#[test]
+fn cycle_timer_program() {
+    let mut computer: NES = Default::default();
+    computer.address_space.config = BusConfig::FlatRam;
+    computer.address_space.attach_timer(0xf000);
+
+    // NOP, NOP, LDA $f000, STA $10
+    let program = [0xea, 0xea, 0xad, 0x00, 0xf0, 0x85, 0x10];
+    computer.address_space.bytes[0x600..0x607].copy_from_slice(&program);
+    computer.cpu.pc = 0x600;
+
+    let closure = |num: u16| -> bool { num == 0x607 };
+    computer.run_cpu_program(false, closure);
+
+    // both NOPs (2 ticks each) complete before the timer is read
+    assert_eq!(computer.address_space.bytes[0x10], 4);
+}
+
+#[test]
+fn reading_a_low_byte_latches_the_whole_count() {
+    let mut computer: NES = Default::default();
+    computer.address_space.config = BusConfig::FlatRam;
+    computer.address_space.attach_timer(0xf000);
+
+    // LDA $f000, STA $10, LDA $f001, STA $11, with the cycle count carrying
+    // into its second byte between the two loads
+    let program = [0xad, 0x00, 0xf0, 0x85, 0x10, 0xad, 0x01, 0xf0, 0x85, 0x11];
+    computer.address_space.bytes[0x600..0x60a].copy_from_slice(&program);
+    computer.cpu.pc = 0x600;
+    computer.cpu.clock = 0xfa;
+
+    computer.run_cpu_program(false, |pc| pc == 0x60a);
+
+    assert_eq!(computer.address_space.bytes[0x10..0x12], [0xfa, 0x00]);
+    assert!(computer.cpu.clock > 0x100);
+}
+
+#[test]
+fn milliseconds_use_the_regions_clock_rate() {
+    let mut computer: NES = Default::default();
+    computer.address_space.config = BusConfig::FlatRam;
+    computer.address_space.attach_timer(0xf000);
+    computer.set_region(Region::Pal);
+
+    // LDA $f004, STA $10, LDA $f005, STA $11
+    let program = [0xad, 0x04, 0xf0, 0x85, 0x10, 0xad, 0x05, 0xf0, 0x85, 0x11];
+    computer.address_space.bytes[0x600..0x60a].copy_from_slice(&program);
+    computer.cpu.pc = 0x600;
+    computer.cpu.clock = 1_662_607;
+
+    computer.run_cpu_program(false, |pc| pc == 0x60a);
+
+    let milliseconds = u16::from_le_bytes([
+        computer.address_space.bytes[0x10],
+        computer.address_space.bytes[0x11],
+    ]);
+    assert_eq!(milliseconds, 1000);
+}
+
+#[test]
+fn emulated_time_follows_cycles_and_region() {
+    let mut computer: NES = Default::default();
+    assert_eq!(computer.emulated_time(), Duration::ZERO);
+
+    computer.cpu.clock = 1_789_773;
+    assert_eq!(computer.emulated_time(), Duration::from_secs(1));
+
+    computer.set_region(Region::Pal);
+    computer.cpu.clock = 1_662_607 / 2;
+    assert_eq!(computer.emulated_time().as_millis(), 499);
+}
@@ -0,0 +1,40 @@
+use disco5::nes::bus::BusConfig;
+use disco5::nes::diagnostics::Unimplemented;
+use disco5::nes::*;
+
+#[test]
+fn stubbed_registers_are_recorded() {
+    let mut computer: NES = Default::default();
+    assert!(computer.unimplemented_report().is_empty());
+
+    let bus = &mut computer.address_space;
+    bus.write(0x2000, 0x80);
+    bus.write(0x2001, 0x08);
+    bus.write(0x4014, 0x02);
+    assert_eq!(bus.unimplemented, Unimplemented::empty());
+
+    // the DMC, frame counter, and controllers are emulated
+    bus.write(0x4011, 0x40);
+    bus.write(0x4017, 0x40);
+    bus.write(0x4016, 0x01);
+    bus.read(0x4016);
+    assert_eq!(bus.unimplemented, Unimplemented::empty());
+
+    bus.write(0x4000, 0x3f);
+    assert_eq!(bus.unimplemented, Unimplemented::APU);
+    assert_eq!(
+        computer.unimplemented_report(),
+        ["unimplemented apu: no sound is produced"]
+    );
+}
+
+#[test]
+fn flat_ram_has_no_stubs() {
+    let mut computer: NES = Default::default();
+    let bus = &mut computer.address_space;
+    bus.config = BusConfig::FlatRam;
+    bus.write(0x4015, 0x0f);
+    bus.write(0x8000, 0x01);
+    bus.read(0x4016);
+    assert_eq!(bus.unimplemented, Unimplemented::empty());
+}
@@ -0,0 +1,51 @@
+use std::cell::RefCell;
+use std::rc::Rc;
+
+use disco5::nes::*;
+
+#[test]
+fn callback_sees_each_frame_once() {
+    let mut computer: NES = Default::default();
+    computer.headless = true;
+    computer.load_demo().unwrap();
+    assert!(computer.front_buffer.is_none());
+
+    let hashes = Rc::new(RefCell::new(Vec::new()));
+    let recorded = Rc::clone(&hashes);
+    computer.set_frame_callback(move |frame| recorded.borrow_mut().push(frame.hash()));
+
+    let returned: Vec<u64> = (0..3).map(|_| computer.run_frame(false).hash()).collect();
+    assert_eq!(*hashes.borrow(), returned);
+    assert_eq!(computer.front_buffer.as_ref().unwrap().hash(), returned[2]);
+
+    // stepping part way into the next frame leaves the front buffer alone
+    for _ in 0..1000 {
+        computer.step(false);
+    }
+    assert_eq!(hashes.borrow().len(), 3);
+    assert_eq!(computer.front_buffer.as_ref().unwrap().hash(), returned[2]);
+}
+
+#[test]
+fn scanline_callback_runs_once_per_line_at_its_dot() {
+    let mut computer: NES = Default::default();
+    computer.headless = true;
+    computer.load_demo().unwrap();
+
+    let lines = Rc::new(RefCell::new(Vec::new()));
+    let recorded = Rc::clone(&lines);
+    computer.on_scanline(100, move |scanline, ppu| {
+        assert_eq!(ppu.dot, 100);
+        assert_eq!(usize::from(scanline), ppu.scanline);
+        recorded.borrow_mut().push(scanline);
+    });
+    for _ in 0..3 {
+        computer.run_frame(false);
+    }
+
+    let lines = lines.borrow();
+    assert!(lines.len() > 2 * 262);
+    for pair in lines.windows(2) {
+        assert_eq!(pair[1], (pair[0] + 1) % 262);
+    }
+}
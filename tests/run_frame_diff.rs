@@ -0,0 +1,77 @@
+use disco5::frame_diff::*;
+use disco5::nes::frame::Frame;
+
+#[test]
+fn identical_frames_have_no_diff() {
+    let frame = Frame::from_rgb(4, 4, [(10, 20, 30); 16]);
+    let diff = compare_frames(&frame, &frame);
+
+    assert!(diff.is_identical());
+    assert_eq!(diff.bounding_box, None);
+}
+
+#[test]
+fn diff_bounds_changed_pixels() {
+    let a = Frame::from_rgb(256, 240, [(10, 20, 30); 256 * 240]);
+    let mut b = a.clone();
+    b.set_pixel(40, 3, (0, 0, 0));
+    b.set_pixel(12, 9, (10, 20, 31));
+
+    let diff = compare_frames(&a, &b);
+    assert_eq!(diff.differing_pixels, 2);
+    assert_eq!(
+        diff.bounding_box,
+        Some(BoundingBox {
+            left: 12,
+            top: 3,
+            right: 40,
+            bottom: 9,
+        })
+    );
+    assert_eq!(diff.heatmap.pixel(0, 0), (5, 5, 5));
+    assert!(diff.heatmap.pixel(40, 3).0 > diff.heatmap.pixel(12, 9).0);
+}
+
+#[cfg(feature = "png")]
+#[test]
+fn png_round_trip() {
+    let frame = Frame::from_rgb(
+        256,
+        240,
+        (0..256 * 240).map(|i| (i as u8, (i >> 8) as u8, 0x55)),
+    );
+    let path = std::env::temp_dir().join("disco5_png_round_trip.png");
+    let filename = path.to_str().unwrap();
+
+    save_png(filename, &frame).unwrap();
+    assert_eq!(read_png(filename).unwrap(), frame);
+    std::fs::remove_file(filename).unwrap();
+
+    let heatmap = compare_frames(&frame, &frame).heatmap_png().unwrap();
+    assert_eq!(&heatmap[..8], b"\x89PNG\r\n\x1a\n");
+}
+
+#[cfg(feature = "png")]
+#[test]
+fn screenshot_saves_the_front_buffer() {
+    let mut computer: disco5::nes::NES = Default::default();
+    computer.headless = true;
+    computer.load_demo().unwrap();
+    let path = std::env::temp_dir().join("disco5_screenshot.png");
+    let filename = path.to_str().unwrap();
+
+    // before the first frame completes, the partly drawn frame is saved
+    computer.screenshot(filename).unwrap();
+    assert_eq!(
+        read_png(filename).unwrap(),
+        computer.address_space.ppu.framebuffer()
+    );
+
+    let frame = computer.run_frame(false);
+    for _ in 0..1000 {
+        computer.step(false);
+    }
+    computer.screenshot(filename).unwrap();
+    assert_eq!(read_png(filename).unwrap(), frame);
+    std::fs::remove_file(filename).unwrap();
+}
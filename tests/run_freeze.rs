@@ -0,0 +1,85 @@
+use disco5::nes::freeze::FreezeTiming;
+use disco5::nes::*;
+
+#[test]
+fn frozen_prg_ram_and_ram_mirrors_land_where_the_cpu_reads() {
+    let mut computer: NES = Default::default();
+    let bus = &mut computer.address_space;
+    bus.freezer.freeze(0x6000, 0x12);
+    bus.freezer.freeze(0x0855, 0x34);
+
+    bus.apply_freezer();
+    assert_eq!(bus.read(0x6000), 0x12);
+    assert_eq!(bus.cartridge.prg_ram[0], 0x12);
+    assert_eq!(bus.read(0x0055), 0x34);
+    assert_eq!(bus.read(0x1855), 0x34);
+}
+
+/// loads program at $0200 without going through the bus
+fn load_program(computer: &mut NES, program: &[u8]) {
+    computer.address_space.bytes[0x0200..0x0200 + program.len()].copy_from_slice(program);
+    computer.cpu.pc = 0x0200;
+}
+
+#[test]
+fn every_write_timing_undoes_each_cpu_write() {
+    let mut computer: NES = Default::default();
+    // LDA #$05, STA $10, STA $0810, LDX $10
+    load_program(
+        &mut computer,
+        &[0xa9, 0x05, 0x85, 0x10, 0x8d, 0x10, 0x08, 0xa6, 0x10],
+    );
+    computer.address_space.freezer.freeze(0x0010, 0x09);
+
+    computer.step(false);
+    computer.step(false);
+    assert_eq!(computer.address_space.bytes[0x0010], 0x09);
+    // a write through a mirror is undone too
+    computer.step(false);
+    assert_eq!(computer.address_space.bytes[0x0010], 0x09);
+    computer.step(false);
+    assert_eq!(computer.cpu.x, 0x09);
+}
+
+#[test]
+fn every_write_timing_ignores_writes_elsewhere() {
+    let mut computer: NES = Default::default();
+    let bus = &mut computer.address_space;
+    bus.freezer.freeze(0x0010, 0x09);
+    bus.write(0x0011, 0x22);
+    assert_eq!((bus.bytes[0x0010], bus.bytes[0x0011]), (0x09, 0x22));
+    assert_eq!(bus.freezer.frozen_value(0x0010), Some(0x09));
+    assert_eq!(bus.freezer.frozen_value(0x0011), None);
+}
+
+#[test]
+fn unfrozen_addresses_keep_writes() {
+    let mut computer: NES = Default::default();
+    let bus = &mut computer.address_space;
+    bus.freezer.freeze(0x0010, 0x09);
+    bus.freezer.freeze(0x0020, 0x0a);
+    bus.freezer.unfreeze(0x0010);
+    bus.write(0x0010, 0x01);
+    assert_eq!(bus.bytes[0x0010], 0x01);
+    assert_eq!(bus.bytes[0x0020], 0x0a);
+
+    bus.freezer.clear();
+    assert!(bus.freezer.is_empty());
+    bus.write(0x0020, 0x02);
+    assert_eq!(bus.bytes[0x0020], 0x02);
+}
+
+#[test]
+fn every_frame_timing_lets_writes_stand_until_the_frame_ends() {
+    let mut computer: NES = Default::default();
+    computer.headless = true;
+    computer.load_demo().unwrap();
+    let bus = &mut computer.address_space;
+    bus.freezer.timing = FreezeTiming::EveryFrame;
+    bus.freezer.freeze(0x6000, 0x09);
+
+    bus.write(0x6000, 0x01);
+    assert_eq!(bus.read(0x6000), 0x01);
+    computer.run_frame(false);
+    assert_eq!(computer.address_space.read(0x6000), 0x09);
+}
@@ -0,0 +1,136 @@
+use disco5::nes::bus::BusConfig;
+use disco5::nes::irq::IrqSource;
+use disco5::nes::*;
+
+const PROGRAM_ENTRY_POINT: usize = 0x0600;
+const HANDLER_ENTRY_POINT: usize = 0x0700;
+const IRQ_COUNT: usize = 0x10;
+
+/// spins with interrupts enabled, the handler counts IRQs in $10
+fn irq_counter() -> NES {
+    let mut computer: NES = Default::default();
+    computer.address_space.config = BusConfig::FlatRam;
+
+    let program = [
+        0x58, // CLI
+        0x4c, 0x01, 0x06, // loop: JMP loop
+    ];
+    let handler = [
+        0xe6, 0x10, // INC $10
+        0x40, // RTI
+    ];
+    computer.address_space.bytes[PROGRAM_ENTRY_POINT..PROGRAM_ENTRY_POINT + program.len()]
+        .copy_from_slice(&program);
+    computer.address_space.bytes[HANDLER_ENTRY_POINT..HANDLER_ENTRY_POINT + handler.len()]
+        .copy_from_slice(&handler);
+    computer.address_space.bytes[0xfffe] = 0x00;
+    computer.address_space.bytes[0xffff] = 0x07;
+    computer.cpu.pc = 0x0600;
+    computer.cpu.sp = 0xff;
+    computer
+}
+
+#[test]
+fn irq_line_is_wired_or() {
+    let mut computer = irq_counter();
+    assert!(!computer.irq_line());
+
+    computer.assert_irq(IrqSource::MAPPER);
+    computer.assert_irq(IrqSource::EXTERNAL);
+    computer.release_irq(IrqSource::MAPPER);
+    assert!(computer.irq_line());
+
+    computer.release_irq(IrqSource::EXTERNAL);
+    assert!(!computer.irq_line());
+}
+
+#[test]
+fn asserted_irq_runs_handler_until_released() {
+    let mut computer = irq_counter();
+    computer.step(false);
+
+    computer.assert_irq(IrqSource::EXTERNAL);
+    computer.step(false);
+    assert_eq!(computer.cpu.pc, 0x0700);
+    for _ in 0..2 {
+        computer.step(false);
+    }
+    assert_eq!(computer.address_space.bytes[IRQ_COUNT], 1);
+
+    computer.release_irq(IrqSource::EXTERNAL);
+    for _ in 0..100 {
+        computer.step(false);
+    }
+    assert_eq!(computer.address_space.bytes[IRQ_COUNT], 1);
+}
+
+#[test]
+fn interrupt_disable_masks_irq() {
+    let mut computer = irq_counter();
+    computer.assert_irq(IrqSource::APU_FRAME);
+
+    // the I flag is still set before CLI executes
+    computer.cpu.p.i = true;
+    computer.step(false);
+    assert_eq!(computer.cpu.pc, 0x0601);
+}
+
+const LAST_STATUS: usize = 0x11;
+
+/// irq_counter with the APU mapped and a handler that first runs
+/// acknowledge, then stores A in $11 and counts the IRQ
+fn apu_irq_counter(acknowledge: &[u8]) -> NES {
+    let mut computer = irq_counter();
+    computer.address_space.config = BusConfig::Nes;
+    computer.headless = true;
+    let handler = [acknowledge, &[0x85, 0x11, 0xe6, 0x10, 0x40]].concat(); // STA $11, INC $10, RTI
+    computer.address_space.bytes[HANDLER_ENTRY_POINT..HANDLER_ENTRY_POINT + handler.len()]
+        .copy_from_slice(&handler);
+    computer
+}
+
+fn run_cycles(computer: &mut NES, cycles: u64) {
+    let end = computer.cpu.clock + cycles;
+    while computer.cpu.clock < end {
+        computer.step(false);
+    }
+}
+
+#[test]
+fn frame_irq_times_a_program() {
+    // LDA $4015
+    let mut computer = apu_irq_counter(&[0xad, 0x15, 0x40]);
+    run_cycles(&mut computer, 4 * 29830 + 100);
+    assert_eq!(computer.address_space.bytes[IRQ_COUNT], 4);
+    assert_eq!(computer.address_space.bytes[LAST_STATUS] & 0x40, 0x40);
+    assert!(!computer.irq_line());
+}
+
+#[test]
+fn unacknowledged_frame_irq_fires_again() {
+    let mut computer = apu_irq_counter(&[]);
+    run_cycles(&mut computer, 29830 + 100);
+    assert!(computer.address_space.bytes[IRQ_COUNT] > 1);
+
+    // inhibiting the IRQ from outside the handler lets the program go
+    computer.address_space.write(0x4017, 0x40);
+    assert!(!computer.irq_line());
+    let count = computer.address_space.bytes[IRQ_COUNT];
+    run_cycles(&mut computer, 2 * 29830);
+    assert_eq!(computer.address_space.bytes[IRQ_COUNT], count);
+}
+
+#[test]
+fn dmc_irq_is_delivered_once_acknowledged() {
+    // LDA $4015, then STX $4015 with X = 0
+    let mut computer = apu_irq_counter(&[0xad, 0x15, 0x40, 0x8e, 0x15, 0x40]);
+    let bus = &mut computer.address_space;
+    bus.write(0x4017, 0x40);
+    bus.write(0x4010, 0x8f);
+    bus.write(0x4013, 0x00);
+    bus.write(0x4015, 0x10);
+    run_cycles(&mut computer, 1000);
+    assert_eq!(computer.address_space.bytes[IRQ_COUNT], 1);
+    assert_eq!(computer.address_space.bytes[LAST_STATUS], 0x80);
+    assert!(!computer.irq_line());
+}
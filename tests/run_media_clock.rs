@@ -0,0 +1,98 @@
+use std::time::Duration;
+
+use disco5::nes::media_clock::{MediaClock, NTSC_FRAME_RATE, PAL_FRAME_RATE};
+use disco5::nes::region::Region;
+use disco5::nes::*;
+
+#[test]
+fn timestamps_follow_the_console_frame_rate() {
+    let ntsc = MediaClock::default();
+    assert_eq!(ntsc.timestamp(0), Duration::ZERO);
+    assert_eq!(
+        ntsc.timestamp(600_988),
+        Duration::from_secs_f64(600_988.0 / NTSC_FRAME_RATE)
+    );
+    assert!((ntsc.timestamp(600_988).as_secs_f64() - 10_000.0).abs() < 1e-6);
+
+    let pal = MediaClock::new(PAL_FRAME_RATE);
+    assert!((pal.timestamp(50_007).as_secs_f64() - 1_000.0).abs() < 1e-6);
+    // the same frame lands later on PAL's slower clock
+    assert!(pal.timestamp(300) > ntsc.timestamp(300));
+}
+
+/// runs frames at speed, returning the emulated time they took
+fn emulated_time_of(frames: u64, speed: SpeedSetting, region: Region) -> Duration {
+    let mut computer: NES = Default::default();
+    computer.headless = true;
+    computer.set_region(region);
+    computer.set_speed(speed);
+    // SEI, then JMP to itself
+    computer.address_space.bytes[0x0200..0x0204].copy_from_slice(&[0x78, 0x4c, 0x01, 0x02]);
+    computer.cpu.pc = 0x0200;
+    for _ in 0..frames {
+        computer.run_frame(false);
+    }
+    assert_eq!(computer.media_clock.frames, frames);
+    computer.media_clock.now()
+}
+
+#[test]
+fn emulated_time_ignores_the_speed_setting() {
+    let full_speed = emulated_time_of(30, SpeedSetting::default(), Region::Ntsc);
+    assert_eq!(
+        full_speed,
+        emulated_time_of(30, SpeedSetting::Unlimited, Region::Ntsc)
+    );
+    assert_eq!(
+        full_speed,
+        emulated_time_of(30, SpeedSetting::Percent(25), Region::Ntsc)
+    );
+    assert_eq!(full_speed, MediaClock::default().timestamp(30));
+
+    let pal = emulated_time_of(30, SpeedSetting::Unlimited, Region::Pal);
+    assert_eq!(pal, MediaClock::new(PAL_FRAME_RATE).timestamp(30));
+}
+
+/// returns the keyword and text of each tEXt chunk in a PNG image
+#[cfg(feature = "png")]
+fn png_text_chunks(png: &[u8]) -> Vec<(String, String)> {
+    let mut chunks = Vec::new();
+    // past the signature, each chunk is a length, type, data, and CRC
+    let mut rest = &png[8..];
+    while rest.len() >= 12 {
+        let length = u32::from_be_bytes(rest[..4].try_into().unwrap()) as usize;
+        let data = &rest[8..8 + length];
+        if &rest[4..8] == b"tEXt" {
+            let separator = data.iter().position(|&byte| byte == 0).unwrap();
+            chunks.push((
+                String::from_utf8(data[..separator].to_vec()).unwrap(),
+                String::from_utf8(data[separator + 1..].to_vec()).unwrap(),
+            ));
+        }
+        rest = &rest[12 + length..];
+    }
+    chunks
+}
+
+#[cfg(feature = "png")]
+#[test]
+fn screenshots_record_the_emulated_time() {
+    let mut computer: NES = Default::default();
+    computer.headless = true;
+    computer.load_demo().unwrap();
+    for _ in 0..31 {
+        computer.run_frame(false);
+    }
+    let path = std::env::temp_dir().join("disco5_screenshot_time.png");
+    let filename = path.to_str().unwrap();
+    computer.screenshot(filename).unwrap();
+    let png = std::fs::read(filename).unwrap();
+    std::fs::remove_file(filename).unwrap();
+
+    // the last frame completed is frame 30, counting from 0
+    let seconds = format!("{:.6}", computer.media_clock.timestamp(30).as_secs_f64());
+    assert_eq!(
+        png_text_chunks(&png),
+        [(SCREENSHOT_TIME_KEYWORD.to_string(), seconds)]
+    );
+}
@@ -0,0 +1,162 @@
+use disco5::nes::memory_editor::{CartridgeMemory, MemoryEditor, MemoryImport, MemoryRegion};
+use disco5::nes::*;
+
+#[test]
+fn edit_and_undo_memory() {
+    let mut computer: NES = Default::default();
+    let mut editor: MemoryEditor = Default::default();
+    let bus = &mut computer.address_space;
+
+    editor.fill(bus, MemoryRegion::Oam, 0..4, 0xff).unwrap();
+    editor
+        .write(bus, MemoryRegion::Oam, 2, &[0x12, 0x34])
+        .unwrap();
+    editor.copy(bus, MemoryRegion::Oam, 0..4, 8).unwrap();
+    assert_eq!(
+        &bus.ppu.oam_ram[..12],
+        &[0xff, 0xff, 0x12, 0x34, 0, 0, 0, 0, 0xff, 0xff, 0x12, 0x34]
+    );
+
+    assert!(editor.undo(bus));
+    assert!(editor.undo(bus));
+    assert_eq!(
+        &bus.ppu.oam_ram[..12],
+        &[0xff, 0xff, 0xff, 0xff, 0, 0, 0, 0, 0, 0, 0, 0]
+    );
+
+    assert!(editor.undo(bus));
+    assert!(!editor.undo(bus));
+    assert_eq!(&bus.ppu.oam_ram[..4], &[0, 0, 0, 0]);
+}
+
+#[test]
+fn out_of_range_edits_are_rejected() {
+    let mut computer: NES = Default::default();
+    let mut editor: MemoryEditor = Default::default();
+    let bus = &mut computer.address_space;
+
+    // OAM is 256 bytes, palette RAM 32
+    assert!(editor.write(bus, MemoryRegion::Oam, 255, &[1, 2]).is_err());
+    assert!(editor
+        .write(bus, MemoryRegion::Oam, usize::MAX, &[1])
+        .is_err());
+    assert!(editor
+        .fill(bus, MemoryRegion::Palette, 16..33, 0x0f)
+        .is_err());
+    assert!(editor.copy(bus, MemoryRegion::Oam, 250..260, 0).is_err());
+    assert!(editor.copy(bus, MemoryRegion::Oam, 0..8, 252).is_err());
+    // nothing was written, so there is nothing to undo
+    assert!(bus.ppu.oam_ram.iter().all(|&byte| byte == 0));
+    assert!(!editor.undo(bus));
+
+    let path = std::env::temp_dir().join("disco5_out_of_range_edits.bin");
+    let filename = path.to_str().unwrap();
+    assert!(MemoryEditor::export(bus, MemoryRegion::Palette, 0..64, filename).is_err());
+}
+
+#[test]
+fn import_what_was_exported() {
+    let mut computer: NES = Default::default();
+    let mut editor: MemoryEditor = Default::default();
+    let bus = &mut computer.address_space;
+    let path = std::env::temp_dir().join("disco5_import_what_was_exported.bin");
+    let filename = path.to_str().unwrap();
+
+    editor
+        .write(bus, MemoryRegion::Palette, 0, &[0x0f, 0x16, 0x27, 0x18])
+        .unwrap();
+    MemoryEditor::export(bus, MemoryRegion::Palette, 0..4, filename).unwrap();
+    assert_eq!(std::fs::read(filename).unwrap(), [0x0f, 0x16, 0x27, 0x18]);
+
+    assert_eq!(
+        editor
+            .import(bus, MemoryRegion::Vram, 0x3c0, filename)
+            .unwrap(),
+        4
+    );
+    assert_eq!(
+        &bus.ppu.address_space.nametables[0x3c0..0x3c4],
+        &[0x0f, 0x16, 0x27, 0x18]
+    );
+    // a file that runs past the end of the region is not written at all
+    assert!(editor
+        .import(bus, MemoryRegion::Oam, 254, filename)
+        .is_err());
+    assert_eq!(&bus.ppu.oam_ram[254..], &[0, 0]);
+
+    assert!(editor.undo(bus));
+    assert_eq!(&bus.ppu.address_space.nametables[0x3c0..0x3c4], &[0; 4]);
+    std::fs::remove_file(filename).unwrap();
+}
+
+#[test]
+fn export_demo_cartridge() {
+    let mut computer: NES = Default::default();
+    computer.headless = true;
+    computer.load_demo().unwrap();
+    let path = std::env::temp_dir().join("disco5_export_demo_cartridge.bin");
+    let filename = path.to_str().unwrap();
+
+    MemoryEditor::export_cartridge(&computer.address_space, CartridgeMemory::PrgRom, filename)
+        .unwrap();
+    let prg_rom = std::fs::read(filename).unwrap();
+    // NROM-128 mirrors its only bank into both halves of $8000-$FFFF
+    assert_eq!(prg_rom.len(), 0x8000);
+    assert_eq!(&prg_rom[..0x4000], &DEMO_ROM[16..16 + 0x4000]);
+    assert_eq!(&prg_rom[0x4000..], &DEMO_ROM[16..16 + 0x4000]);
+
+    MemoryEditor::export_cartridge(&computer.address_space, CartridgeMemory::ChrRam, filename)
+        .unwrap();
+    assert_eq!(std::fs::read(filename).unwrap(), &DEMO_ROM[16 + 0x4000..]);
+    std::fs::remove_file(filename).unwrap();
+}
+
+#[test]
+fn ram_edits_go_through_mirrors_and_prg_ram() {
+    let mut computer: NES = Default::default();
+    computer.load_demo().unwrap();
+    let mut editor: MemoryEditor = Default::default();
+    let bus = &mut computer.address_space;
+
+    editor
+        .write(bus, MemoryRegion::Ram, 0x0801, &[0x12, 0x34])
+        .unwrap();
+    editor
+        .write(bus, MemoryRegion::Ram, 0x6000, &[0x56])
+        .unwrap();
+    assert_eq!(&bus.bytes[0x0001..0x0003], &[0x12, 0x34]);
+    assert_eq!(bus.cartridge.prg_ram[0], 0x56);
+    assert_eq!(bus.peek(0x1801), 0x12);
+
+    editor
+        .copy(bus, MemoryRegion::Ram, 0x1001..0x1003, 0x6001)
+        .unwrap();
+    assert_eq!(&bus.cartridge.prg_ram[..3], &[0x56, 0x12, 0x34]);
+
+    assert!(editor.undo(bus));
+    assert!(editor.undo(bus));
+    assert_eq!(bus.cartridge.prg_ram[0], 0);
+    assert!(editor.undo(bus));
+    assert_eq!(&bus.bytes[0x0001..0x0003], &[0, 0]);
+}
+
+#[test]
+fn imports_parse_region_address_and_file() {
+    assert_eq!(
+        "chr:1000:tiles.chr".parse::<MemoryImport>(),
+        Ok(MemoryImport {
+            region: MemoryRegion::Chr,
+            address: 0x1000,
+            filename: String::from("tiles.chr"),
+        })
+    );
+    assert_eq!(
+        "prg-ram:0:C:/saves/game.sav"
+            .parse::<MemoryImport>()
+            .map(|import| (import.region, import.filename)),
+        Ok((MemoryRegion::PrgRam, String::from("C:/saves/game.sav")))
+    );
+    assert!("sram:0:save.sav".parse::<MemoryImport>().is_err());
+    assert!("ram:zz:level.bin".parse::<MemoryImport>().is_err());
+    assert!("ram:0300".parse::<MemoryImport>().is_err());
+}
@@ -0,0 +1,106 @@
+use disco5::nes::patch::*;
+use disco5::nes::*;
+
+fn crc32(bytes: &[u8]) -> u32 {
+    let mut crc = !0u32;
+    for &byte in bytes {
+        crc ^= u32::from(byte);
+        for _ in 0..8 {
+            crc = (crc >> 1) ^ (0xedb8_8320 & (crc & 1).wrapping_neg());
+        }
+    }
+    !crc
+}
+
+#[test]
+fn ips_records_and_runs() {
+    let rom = [0u8; 8];
+    let patch = [
+        b"PATCH".as_slice(),
+        &[0x00, 0x00, 0x01, 0x00, 0x02, 0xaa, 0xbb], // 2 bytes at 1
+        &[0x00, 0x00, 0x06, 0x00, 0x00, 0x00, 0x04, 0xcc], // 4 x 0xcc at 6
+        b"EOF",
+    ]
+    .concat();
+
+    assert_eq!(
+        apply_patch(&rom, &patch).unwrap(),
+        [0, 0xaa, 0xbb, 0, 0, 0, 0xcc, 0xcc, 0xcc, 0xcc]
+    );
+
+    let truncating = [patch.as_slice(), &[0x00, 0x00, 0x03]].concat();
+    assert_eq!(apply_patch(&rom, &truncating).unwrap(), [0, 0xaa, 0xbb]);
+}
+
+#[test]
+fn bps_actions() {
+    let source = [1, 2, 3, 4];
+    let target = [1, 2, 9, 9, 9, 3, 4];
+    let mut patch = [
+        b"BPS1".as_slice(),
+        &[0x84, 0x87, 0x80], // source size, target size, no metadata
+        &[0x84],             // source read 2
+        &[0x81, 9],          // target read 1
+        &[0x87, 0x84],       // target copy 2 from +2
+        &[0x86, 0x84],       // source copy 2 from +2
+        &crc32(&source).to_le_bytes(),
+        &crc32(&target).to_le_bytes(),
+    ]
+    .concat();
+    patch.extend_from_slice(&crc32(&patch).to_le_bytes());
+
+    assert_eq!(apply_patch(&source, &patch).unwrap(), target);
+    assert!(apply_patch(&[1, 2, 3, 5], &patch).is_err());
+}
+
+/// wraps actions in a BPS patch for source with valid CRCs
+fn bps_patch(source: &[u8], actions: &[u8]) -> Vec<u8> {
+    let mut patch = [
+        b"BPS1".as_slice(),
+        actions,
+        &crc32(source).to_le_bytes(),
+        &crc32(&[]).to_le_bytes(),
+    ]
+    .concat();
+    patch.extend_from_slice(&crc32(&patch).to_le_bytes());
+    patch
+}
+
+#[test]
+fn bps_numbers_too_large_are_rejected() {
+    let source = [1, 2, 3, 4];
+    // eleven 7-bit groups hold more than 64 bits
+    let mut huge_size = vec![0x7f; 10];
+    huge_size.push(0xff);
+    let error = apply_patch(&source, &bps_patch(&source, &huge_size)).unwrap_err();
+    assert_eq!(error.kind(), std::io::ErrorKind::InvalidData);
+    assert_eq!(error.to_string(), "BPS number is too large");
+
+    // a metadata size that fits but runs past the end of the patch
+    let mut huge_metadata = vec![0x84, 0x84];
+    huge_metadata.extend_from_slice(&[0x7f; 8]);
+    huge_metadata.push(0x81);
+    let error = apply_patch(&source, &bps_patch(&source, &huge_metadata)).unwrap_err();
+    assert_eq!(error.to_string(), "patch ends mid-record");
+}
+
+#[test]
+fn rom_is_soft_patched_by_matching_patch() {
+    let directory = std::env::temp_dir().join("disco5_soft_patch");
+    std::fs::create_dir_all(&directory).unwrap();
+    let rom = directory.join("demo.nes");
+    std::fs::write(&rom, DEMO_ROM).unwrap();
+    // replace the first byte of PRG ROM, just past the 16-byte header
+    let patch = [b"PATCH".as_slice(), &[0, 0, 16, 0, 1, 0xea], b"EOF"].concat();
+    std::fs::write(directory.join("demo.ips"), patch).unwrap();
+
+    let mut computer: NES = Default::default();
+    computer.headless = true;
+    computer
+        .load_nrom_128(rom.to_str().unwrap(), 0x8000)
+        .unwrap();
+
+    assert_eq!(computer.address_space.bytes[0x8000], 0xea);
+    assert_eq!(std::fs::read(&rom).unwrap(), DEMO_ROM);
+    std::fs::remove_dir_all(directory).unwrap();
+}
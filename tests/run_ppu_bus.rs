@@ -0,0 +1,77 @@
+use disco5::nes::ppu_bus::{Mirroring, PpuBus};
+use disco5::nes::*;
+
+/// writes a marker to the first byte of each logical nametable, then returns
+/// what each one reads back
+fn nametables_seen(mirroring: Mirroring) -> [u8; 4] {
+    let mut bus = PpuBus {
+        mirroring,
+        ..Default::default()
+    };
+    for nametable in 0..4 {
+        bus.write(0x2000 + nametable * 0x400, nametable as u8 + 1);
+    }
+    [0, 1, 2, 3].map(|nametable| bus.read(0x2000 + nametable * 0x400))
+}
+
+#[test]
+fn nametables_follow_mirroring() {
+    assert_eq!(nametables_seen(Mirroring::Horizontal), [2, 2, 4, 4]);
+    assert_eq!(nametables_seen(Mirroring::Vertical), [3, 4, 3, 4]);
+    assert_eq!(nametables_seen(Mirroring::SingleScreenLower), [4, 4, 4, 4]);
+    assert_eq!(nametables_seen(Mirroring::SingleScreenUpper), [4, 4, 4, 4]);
+    assert_eq!(nametables_seen(Mirroring::FourScreen), [1, 2, 3, 4]);
+
+    let mut bus = PpuBus {
+        mirroring: Mirroring::SingleScreenUpper,
+        ..Default::default()
+    };
+    bus.write(0x2c05, 0x42);
+    assert_eq!(bus.nametables[0x405], 0x42);
+    // $3000-$3EFF mirrors the nametables
+    assert_eq!(bus.read(0x3005), 0x42);
+}
+
+#[test]
+fn mirroring_comes_from_the_ines_header() {
+    let mut header = [0; 16];
+    assert_eq!(Mirroring::from_ines_header(&header), Mirroring::Horizontal);
+    header[6] = 0b0000_0001;
+    assert_eq!(Mirroring::from_ines_header(&header), Mirroring::Vertical);
+    header[6] = 0b0000_1001;
+    assert_eq!(Mirroring::from_ines_header(&header), Mirroring::FourScreen);
+}
+
+#[test]
+fn palette_ram_repeats_and_shares_backdrops() {
+    let mut bus: PpuBus = Default::default();
+    bus.write(0x3f10, 0x21);
+    assert_eq!(bus.palette[0x00], 0x21);
+    bus.write(0x3f2d, 0x15);
+    assert_eq!(bus.read(0x3f0d), 0x15);
+    assert_eq!(bus.read(0x3fed), 0x15);
+    // the other sprite palette entries are their own
+    bus.write(0x3f11, 0x30);
+    assert_eq!(bus.read(0x3f01), 0x00);
+}
+
+#[test]
+fn chr_rom_ignores_writes() {
+    let mut bus: PpuBus = Default::default();
+    bus.write(0x1ff0, 0xaa);
+    assert_eq!(bus.read(0x1ff0), 0xaa);
+    bus.chr.writable = false;
+    bus.write(0x1ff0, 0x55);
+    assert_eq!(bus.read(0x1ff0), 0xaa);
+    assert_eq!(bus.pattern_row(0x1fe8), (0x00, 0xaa));
+}
+
+#[test]
+fn demo_cartridge_sets_up_the_ppu_bus() {
+    let mut computer: NES = Default::default();
+    computer.headless = true;
+    computer.load_demo().unwrap();
+    let bus = &computer.address_space.ppu.address_space;
+    assert_ne!(bus.mirroring, Mirroring::FourScreen);
+    assert!(!bus.chr.writable);
+}
@@ -0,0 +1,414 @@
+use disco5::nes::ppu_structs::AccuracyProfile;
+use disco5::nes::region::Region;
+use disco5::nes::*;
+use disco5::testing::draw_ppu_frame;
+
+#[test]
+fn ppu_data_read_is_buffered() {
+    let mut computer: NES = Default::default();
+    let bus = &mut computer.address_space;
+
+    bus.write(0x2006, 0x21);
+    bus.write(0x2006, 0x00);
+    bus.write(0x2007, 0xaa);
+    bus.write(0x2007, 0xbb);
+
+    bus.write(0x2006, 0x21);
+    bus.write(0x2006, 0x00);
+    // the first read returns the stale buffer contents
+    bus.read(0x2007);
+    assert_eq!(bus.read(0x2007), 0xaa);
+    assert_eq!(bus.read(0x2007), 0xbb);
+}
+
+#[test]
+fn ppu_data_palette_mirrors() {
+    let mut computer: NES = Default::default();
+    let bus = &mut computer.address_space;
+
+    bus.write(0x2006, 0x3f);
+    bus.write(0x2006, 0x10);
+    bus.write(0x2007, 0x0f);
+
+    // palette reads are not buffered
+    bus.write(0x2006, 0x3f);
+    bus.write(0x2006, 0x00);
+    assert_eq!(bus.read(0x2007), 0x0f);
+}
+
+#[test]
+fn oam_dma_copies_page() {
+    let mut computer: NES = Default::default();
+    let bus = &mut computer.address_space;
+
+    for i in 0..0x100 {
+        bus.write(0x0200 + i, i as u8);
+    }
+    bus.write(0x2003, 0x00);
+    bus.write(0x4014, 0x02);
+    // nothing is copied until the CPU halts for the transfer
+    assert_eq!(bus.ppu.oam_ram[0xff], 0x00);
+
+    // halted on the even cycle 258, so the first read lands on a get cycle
+    assert_eq!(bus.run_dma(), 513);
+    assert_eq!(bus.ppu.oam_ram[0x00], 0x00);
+    assert_eq!(bus.ppu.oam_ram[0xff], 0xff);
+    assert_eq!(bus.ppu.oam_dma, 0x02);
+}
+
+#[test]
+fn ppu_status_read_side_effects() {
+    let mut computer: NES = Default::default();
+    let bus = &mut computer.address_space;
+
+    bus.write(0x2006, 0x3f);
+    bus.ppu.start_vblank();
+    assert_eq!(bus.read(0x2002) & 0x80, 0x80);
+    assert_eq!(bus.read(0x2002) & 0x80, 0x00);
+
+    // the read reset the latch, so this is a high byte write again
+    bus.write(0x2006, 0x23);
+    bus.write(0x2006, 0xc0);
+    assert_eq!(bus.ppu.ppu_addr(), 0x23c0);
+}
+
+#[test]
+fn loopy_scroll_registers() {
+    let mut computer: NES = Default::default();
+    let bus = &mut computer.address_space;
+
+    bus.write(0x2000, 0x00);
+    bus.read(0x2002);
+    bus.write(0x2005, 0x7d);
+    assert_eq!((bus.ppu.t, bus.ppu.fine_x, bus.ppu.w), (0x000f, 5, true));
+    bus.write(0x2005, 0x5e);
+    assert_eq!((bus.ppu.t, bus.ppu.w), (0x616f, false));
+    bus.write(0x2006, 0x3d);
+    assert_eq!(bus.ppu.t, 0x3d6f);
+    bus.write(0x2006, 0xf0);
+    assert_eq!((bus.ppu.t, bus.ppu.v), (0x3df0, 0x3df0));
+}
+
+#[test]
+fn vblank_and_nmi_follow_the_ppu() {
+    let mut computer: NES = Default::default();
+    let ppu = &mut computer.address_space.ppu;
+    ppu.ppu_status_read();
+
+    while (ppu.scanline, ppu.dot) != (241, 0) {
+        ppu.tick();
+    }
+    assert_eq!(ppu.ppu_status & 0x80, 0);
+    ppu.tick();
+    assert_eq!(ppu.ppu_status & 0x80, 0x80);
+    assert!(!ppu.nmi_pending);
+
+    // enabling NMI part way through vblank raises one immediately
+    ppu.ppu_ctrl_write(0x80);
+    assert!(ppu.nmi_pending);
+    ppu.nmi_pending = false;
+    ppu.ppu_ctrl_write(0x00);
+    ppu.ppu_status_read();
+    ppu.ppu_ctrl_write(0x80);
+    assert!(!ppu.nmi_pending);
+
+    while (ppu.scanline, ppu.dot) != (261, 1) {
+        ppu.tick();
+    }
+    assert!(!ppu.in_vblank_period);
+}
+
+#[test]
+fn demo_takes_nmi_at_vblank() {
+    let mut computer: NES = Default::default();
+    computer.headless = true;
+    computer.load_demo().unwrap();
+
+    // the demo enables NMI once it has finished drawing the nametables
+    for _ in 0..5 {
+        computer.run_frame(false);
+    }
+    assert_eq!(computer.beam_position().0, 241);
+    assert!(computer.address_space.ppu.nmi_pending);
+
+    computer.step(false);
+    assert_eq!(computer.cpu.pc, 0xc060);
+}
+
+/// counts dots from vblank to vblank
+fn dots_in_frame(computer: &mut NES) -> usize {
+    let ppu = &mut computer.address_space.ppu;
+    let mut dots = 0;
+    ppu.frame_ready = false;
+    while !ppu.frame_ready {
+        ppu.tick();
+        dots += 1;
+    }
+    dots
+}
+
+#[test]
+fn odd_frames_skip_a_dot_while_rendering() {
+    let mut computer: NES = Default::default();
+    dots_in_frame(&mut computer);
+    assert_eq!(dots_in_frame(&mut computer), 89342);
+    assert_eq!(dots_in_frame(&mut computer), 89342);
+
+    // the write's cycle moves the beam on, so run back to vblank first
+    computer.address_space.write(0x2001, 0x08);
+    dots_in_frame(&mut computer);
+    let pair = [dots_in_frame(&mut computer), dots_in_frame(&mut computer)];
+    assert!(pair == [89341, 89342] || pair == [89342, 89341]);
+}
+
+#[test]
+fn pal_frames_are_longer_and_never_skip() {
+    let mut computer: NES = Default::default();
+    computer.set_region(Region::Pal);
+    computer.address_space.write(0x2001, 0x08);
+    dots_in_frame(&mut computer);
+    assert_eq!(dots_in_frame(&mut computer), 312 * 341);
+    assert_eq!(dots_in_frame(&mut computer), 312 * 341);
+
+    // vblank still begins after line 240 and ends on the last line
+    let ppu = &mut computer.address_space.ppu;
+    assert_eq!((ppu.scanline, ppu.dot), (241, 1));
+    while ppu.in_vblank_period {
+        ppu.tick();
+    }
+    assert_eq!((ppu.scanline, ppu.dot), (311, 1));
+}
+
+#[test]
+fn pal_runs_sixteen_dots_per_five_cycles() {
+    let mut computer: NES = Default::default();
+    computer.set_region(Region::Pal);
+    // NOP, 2 cycles each
+    computer.address_space.bytes[..0x10].fill(0xea);
+
+    let dot = |computer: &NES| {
+        let (scanline, dot) = computer.beam_position();
+        scanline * 341 + dot
+    };
+    let start = dot(&computer);
+    for _ in 0..5 {
+        computer.step(false);
+    }
+    assert_eq!(dot(&computer) - start, 32);
+    computer.step(false);
+    assert_eq!(dot(&computer) - start, 38);
+    assert_eq!(computer.address_space.ppu_dot_remainder, 2);
+}
+
+#[test]
+fn write_only_registers_read_the_io_latch() {
+    let mut computer: NES = Default::default();
+    let bus = &mut computer.address_space;
+
+    bus.write(0x2003, 0x5a);
+    assert_eq!(bus.read(0x2000), 0x5a);
+    assert_eq!(bus.read(0x2005), 0x5a);
+    // vblank is set at power on, the low five bits are the latch's and the
+    // top three refresh it
+    assert_eq!(bus.read(0x2002), 0x9a);
+    assert_eq!(bus.read(0x2006), 0x9a);
+
+    // palette reads fill in the top two bits from the latch
+    bus.write(0x2006, 0x3f);
+    bus.write(0x2006, 0x00);
+    bus.write(0x2007, 0x16);
+    bus.write(0x2006, 0x3f);
+    bus.write(0x2006, 0x00);
+    bus.write(0x2001, 0xc0);
+    assert_eq!(bus.read(0x2007), 0xd6);
+}
+
+#[test]
+fn io_latch_decays_when_accurate() {
+    let mut computer: NES = Default::default();
+    computer.address_space.write(0x2000, 0x01);
+    let ppu = &mut computer.address_space.ppu;
+    ppu.ppu_ctrl = 0;
+    for _ in 0..40 {
+        draw_ppu_frame(ppu);
+    }
+    assert_eq!(ppu.open_bus_read(), 0x01);
+
+    ppu.accuracy = AccuracyProfile::Accurate;
+    ppu.refresh_io_latch(0xff, 0xf0);
+    for _ in 0..35 {
+        draw_ppu_frame(ppu);
+    }
+    assert_eq!(ppu.open_bus_read(), 0xf1);
+    draw_ppu_frame(ppu);
+    assert_eq!(ppu.open_bus_read(), 0x00);
+}
+
+#[test]
+fn oam_data_masks_attributes_and_ignores_writes_while_rendering() {
+    let mut computer: NES = Default::default();
+    let bus = &mut computer.address_space;
+
+    bus.write(0x2003, 0x04);
+    for byte in [0xff, 0xff, 0xff, 0xff] {
+        bus.write(0x2004, byte);
+    }
+    assert_eq!(bus.ppu.oam_addr, 0x08);
+    let read_at = |bus: &mut bus::Bus, address| {
+        bus.write(0x2003, address);
+        bus.read(0x2004)
+    };
+    assert_eq!(read_at(bus, 0x05), 0xff);
+    assert_eq!(read_at(bus, 0x06), 0xe3);
+    // reads do not increment OAMADDR
+    assert_eq!(bus.ppu.oam_addr, 0x06);
+
+    bus.ppu.end_vblank();
+    bus.write(0x2001, 0x08);
+    bus.write(0x2003, 0x11);
+    bus.write(0x2004, 0x42);
+    assert_eq!(bus.ppu.oam_ram[0x11], 0x00);
+    assert_eq!(bus.ppu.oam_addr, 0x15);
+}
+
+#[test]
+fn registers_repeat_every_eight_bytes() {
+    let mut computer: NES = Default::default();
+    let bus = &mut computer.address_space;
+
+    bus.write(0x3ffe, 0x21);
+    bus.write(0x200e, 0x08);
+    assert_eq!(bus.ppu.ppu_addr(), 0x2108);
+    bus.write(0x2ff7, 0x99);
+    assert_eq!(bus.ppu.address_space.read(0x2108), 0x99);
+
+    bus.ppu.start_vblank();
+    assert_eq!(bus.read(0x3ffa) & 0x80, 0x80);
+    assert_eq!(bus.read(0x2002) & 0x80, 0x00);
+    assert_eq!(bus.bytes[0x3ffe], 0x00);
+}
+
+/// (scanline, dot) of every filtered A12 rise over the PPU's next two frames
+fn a12_rises(ppu: &mut ppu::PPU) -> Vec<(usize, usize)> {
+    let mut rises = Vec::new();
+    for _ in 0..2 * 262 * 341 {
+        ppu.tick();
+        if ppu.a12_rose {
+            rises.push((ppu.scanline, ppu.dot));
+        }
+    }
+    rises
+}
+
+#[test]
+fn a12_rises_once_per_fetched_scanline() {
+    let mut ppu: ppu::PPU = Default::default();
+    ppu.ppu_mask = 0x18;
+
+    // sprites from $1000: the rise lands on the first sprite pattern fetch
+    ppu.ppu_ctrl = 0x08;
+    let rises = a12_rises(&mut ppu);
+    assert!(rises.iter().all(|&(_, dot)| dot == 261));
+    let mut scanlines: Vec<usize> = rises.iter().map(|&(scanline, _)| scanline).collect();
+    scanlines.sort();
+    scanlines.dedup();
+    let expected: Vec<usize> = (0..240).chain([261]).collect();
+    assert_eq!(scanlines, expected);
+
+    // background from $1000: the rise waits for the next line's tile
+    // fetches, except after vblank where the first fetch of the pre-render
+    // line follows a long stretch of A12 low. The first rise comes from
+    // switching tables mid-frame.
+    ppu.ppu_ctrl = 0x10;
+    let rises = a12_rises(&mut ppu);
+    assert!(rises.len() > 240);
+    assert!(rises[1..]
+        .iter()
+        .all(|&rise| rise.1 == 325 || rise == (261, 5)));
+
+    // with both tables at $1000 the nametable fetches are too short to get
+    // past the filter, so only the end of vblank counts
+    ppu.ppu_ctrl = 0x18;
+    assert_eq!(a12_rises(&mut ppu), vec![(261, 5), (261, 5)]);
+
+    // with rendering off A12 follows v, which nothing is moving
+    ppu.ppu_mask = 0x00;
+    ppu.v = 0x2000;
+    assert!(a12_rises(&mut ppu).is_empty());
+}
+
+#[test]
+fn a12_callback_runs_on_each_rise() {
+    use std::cell::Cell;
+    use std::rc::Rc;
+
+    let mut computer: NES = Default::default();
+    let rises = Rc::new(Cell::new(0));
+    let counted = Rc::clone(&rises);
+    computer.set_a12_callback(move |ppu| {
+        assert!(ppu.a12_rose);
+        counted.set(counted.get() + 1);
+    });
+    let bus = &mut computer.address_space;
+    bus.write(0x2000, 0x08);
+    bus.write(0x2001, 0x18);
+    // NOP sled
+    for address in 0x8000..0x10000 {
+        bus.bytes[address] = 0xea;
+    }
+    bus.bytes[0xfffc] = 0x00;
+    bus.bytes[0xfffd] = 0x80;
+    computer.cpu.pc = 0x8000;
+    for _ in 0..30_000 {
+        computer.step(false);
+    }
+    assert!(rises.get() > 240);
+}
+
+#[test]
+fn beam_position_and_frame_count_accessors() {
+    let mut ppu: ppu::PPU = Default::default();
+    assert_eq!((ppu.scanline(), ppu.dot(), ppu.frame_count()), (0, 0, 0));
+    for _ in 0..ppu::DOTS_PER_SCANLINE + 5 {
+        ppu.tick();
+    }
+    assert_eq!((ppu.scanline(), ppu.dot()), (1, 5));
+
+    // rendering is off, so every frame is a full 262 lines
+    for _ in 0..2 * 262 * ppu::DOTS_PER_SCANLINE {
+        ppu.tick();
+    }
+    assert_eq!((ppu.scanline(), ppu.dot()), (1, 5));
+    assert_eq!(ppu.frame_count(), 2);
+}
+
+#[test]
+fn ppu_write_log_records_register_writes_with_the_beam_position() {
+    // LDA #$1E, STA $2001, STA $10, LDA #$3F, STA $2006, LDA #$02, STA $4014
+    let program = [
+        0xa9, 0x1e, 0x8d, 0x01, 0x20, 0x85, 0x10, 0xa9, 0x3f, 0x8d, 0x06, 0x20, 0xa9, 0x02, 0x8d,
+        0x14, 0x40,
+    ];
+    let mut computer: NES = Default::default();
+    computer.address_space.bytes[0x0200..0x0200 + program.len()].copy_from_slice(&program);
+    computer.cpu.pc = 0x0200;
+    computer.step(false);
+    computer.log_ppu_writes(true);
+    for _ in 0..6 {
+        computer.step(false);
+    }
+    let log = computer.take_ppu_write_log();
+    let addresses: Vec<u16> = log.iter().map(|logged| logged.write.address).collect();
+    assert_eq!(addresses, [0x2001, 0x2006, 0x4014]);
+    assert!(log[1].dot > log[0].dot || log[1].scanline > log[0].scanline);
+    let line = log[0].to_string();
+    assert!(line.starts_with("frame 0 scanline"), "{line}");
+    assert!(line.contains("| PPUMASK: BG on"), "{line}");
+    assert!(computer.take_ppu_write_log().is_empty());
+
+    computer.log_ppu_writes(false);
+    computer.address_space.write(0x2001, 0x00);
+    computer.cpu.pc = 0x0202;
+    computer.step(false);
+    assert!(computer.take_ppu_write_log().is_empty());
+}
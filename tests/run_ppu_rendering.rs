@@ -0,0 +1,327 @@
+use disco5::nes::ppu::{FRAME_BUFFER_SIZE, FRAME_HEIGHT};
+use disco5::nes::ppu_structs::{
+    emphasized_palette, PriorityDebugColors, RenderMode, PPUMASK, SYSTEM_COLOR_PALETTE,
+};
+use disco5::nes::*;
+use disco5::testing::draw_ppu_frame;
+
+/// fills the universal background color, blank pattern tables leave every
+/// pixel that color
+fn backdrop(color: u8, mask: u8) -> (u8, u8, u8) {
+    let mut computer: NES = Default::default();
+    let bus = &mut computer.address_space;
+
+    bus.write(0x2006, 0x3f);
+    bus.write(0x2006, 0x00);
+    bus.write(0x2007, color);
+    bus.write(0x2001, mask);
+
+    draw_ppu_frame(&mut bus.ppu).pixel(0, 0)
+}
+
+#[test]
+fn greyscale_uses_grey_column() {
+    assert_eq!(backdrop(0x16, 0x00), SYSTEM_COLOR_PALETTE[0x16]);
+    assert_eq!(backdrop(0x16, 0x01), SYSTEM_COLOR_PALETTE[0x10]);
+}
+
+#[test]
+fn emphasis_darkens_other_channels() {
+    let emphasized = emphasized_palette(&SYSTEM_COLOR_PALETTE, PPUMASK::EMPH_RED);
+    let (r, g, b) = SYSTEM_COLOR_PALETTE[0x20];
+    let (er, eg, eb) = emphasized[0x20];
+    assert_eq!(er, r);
+    assert!(eg < g && eb < b);
+
+    // the blacks in columns $xE and $xF are not tinted
+    assert_eq!(emphasized[0x0f], SYSTEM_COLOR_PALETTE[0x0f]);
+
+    assert_eq!(backdrop(0x20, 0x20), emphasized[0x20]);
+    assert_eq!(
+        emphasized_palette(&SYSTEM_COLOR_PALETTE, PPUMASK::empty()),
+        SYSTEM_COLOR_PALETTE
+    );
+}
+
+/// draws a solid tile 0 with palette color 1 across the nametable
+fn solid_background(mask: u8) -> [(u8, u8, u8); 3] {
+    let mut computer: NES = Default::default();
+    let bus = &mut computer.address_space;
+    bus.ppu.address_space.chr.bytes[..8].fill(0xff);
+
+    bus.write(0x2006, 0x3f);
+    bus.write(0x2006, 0x00);
+    bus.write(0x2007, 0x0f);
+    bus.write(0x2007, 0x16);
+    bus.write(0x2001, mask);
+
+    let frame = draw_ppu_frame(&mut bus.ppu);
+    [frame.pixel(0, 0), frame.pixel(7, 0), frame.pixel(8, 0)]
+}
+
+#[test]
+fn background_enable_and_left_column_mask() {
+    let red = SYSTEM_COLOR_PALETTE[0x16];
+    let black = SYSTEM_COLOR_PALETTE[0x0f];
+
+    assert_eq!(solid_background(0x0a), [red, red, red]);
+    assert_eq!(solid_background(0x08), [black, black, red]);
+    assert_eq!(solid_background(0x00), [black, black, black]);
+    // sprites alone still leave the background undrawn
+    assert_eq!(solid_background(0x14), [black, black, black]);
+}
+
+#[test]
+fn ppu_data_access_while_rendering_bumps_scroll() {
+    let mut computer: NES = Default::default();
+    let bus = &mut computer.address_space;
+
+    bus.write(0x2001, 0x08);
+    bus.ppu.end_vblank();
+    bus.write(0x2006, 0x20);
+    bus.write(0x2006, 0x00);
+    bus.write(0x2007, 0xaa);
+    // coarse X and fine Y both step instead of the address advancing by 1
+    assert_eq!(bus.ppu.v, 0x3001);
+
+    bus.ppu.start_vblank();
+    bus.write(0x2006, 0x20);
+    bus.write(0x2006, 0x00);
+    bus.write(0x2007, 0xaa);
+    assert_eq!(bus.ppu.v, 0x2001);
+}
+
+#[test]
+fn priority_debug_colors_pixels_by_source() {
+    let colors = PriorityDebugColors::default();
+    let mut computer: NES = Default::default();
+    computer.toggle_priority_debug();
+    let bus = &mut computer.address_space;
+    assert_eq!(bus.ppu.render_mode, RenderMode::PriorityDebug(colors));
+
+    // the left half of tile 0 is opaque, the right half is color 0
+    bus.ppu.address_space.chr.bytes[..8].fill(0xf0);
+    bus.write(0x2001, 0x0a);
+
+    let frame = draw_ppu_frame(&mut bus.ppu);
+    assert_eq!(frame.pixel(0, 0), colors.background);
+    assert_eq!(frame.pixel(4, 0), colors.backdrop);
+
+    computer.toggle_priority_debug();
+    assert_eq!(computer.address_space.ppu.render_mode, RenderMode::Normal);
+}
+
+#[test]
+fn mid_frame_writes_affect_later_scanlines() {
+    let mut computer: NES = Default::default();
+    let ppu = &mut computer.address_space.ppu;
+    ppu.address_space.palette[0x00] = 0x0f;
+    ppu.address_space.palette[0x01] = 0x16;
+    // tile 1 is solid color 1, drawn across the second nametable
+    ppu.address_space.chr.bytes[0x10..0x18].fill(0xff);
+    ppu.address_space.nametables[0x400..0x7c0].fill(0x01);
+    ppu.ppu_mask_write(0x0a);
+    draw_ppu_frame(ppu);
+
+    // a status bar split: switch to the second nametable at scanline 100,
+    // then turn on greyscale at scanline 200
+    while (ppu.scanline, ppu.dot) != (100, 0) {
+        ppu.tick();
+    }
+    ppu.ppu_ctrl_write(0x01);
+    ppu.ppu_scroll_write(0x00);
+    ppu.ppu_scroll_write(0x00);
+    while (ppu.scanline, ppu.dot) != (200, 0) {
+        ppu.tick();
+    }
+    ppu.ppu_mask_write(0x0b);
+    let frame = draw_ppu_frame(ppu);
+
+    // the horizontal scroll bits in t reach v at the end of the line
+    let black = SYSTEM_COLOR_PALETTE[0x0f];
+    assert_eq!(frame.pixel(8, 100), black);
+    assert_eq!(frame.pixel(8, 101), SYSTEM_COLOR_PALETTE[0x16]);
+    assert_eq!(frame.pixel(8, 200), SYSTEM_COLOR_PALETTE[0x10]);
+}
+
+#[test]
+fn framebuffer_is_rgba() {
+    let mut computer: NES = Default::default();
+    computer.headless = true;
+    computer.load_demo().unwrap();
+    let frame = computer.run_frame(false);
+
+    assert_eq!((frame.width, frame.height), (256, 240));
+    assert_eq!(frame.rgba.len(), 256 * 240 * 4);
+    let (r, g, b) = frame.pixel(1, 0);
+    assert_eq!(&frame.rgba[4..8], &[r, g, b, 0xff]);
+}
+
+#[test]
+fn pal_file_replaces_system_colors() {
+    let path = std::env::temp_dir().join("disco5_palette.pal");
+    let filename = path.to_str().unwrap();
+    let bytes: Vec<u8> = (0..64u8).flat_map(|i| [i, i * 2, i * 3]).collect();
+    std::fs::write(filename, &bytes).unwrap();
+
+    let mut computer: NES = Default::default();
+    computer.load_palette(filename).unwrap();
+    let bus = &mut computer.address_space;
+    bus.write(0x2006, 0x3f);
+    bus.write(0x2006, 0x00);
+    bus.write(0x2007, 0x16);
+    assert_eq!(draw_ppu_frame(&mut bus.ppu).pixel(0, 0), (0x16, 0x2c, 0x42));
+
+    // emphasized variants are derived unless the file has its own
+    let palettes = &computer.address_space.ppu.palettes;
+    assert_eq!(
+        palettes[1],
+        emphasized_palette(&palettes[0], PPUMASK::EMPH_RED)
+    );
+    let mut extended = bytes.clone();
+    extended.extend(std::iter::repeat_n(0xff, bytes.len() * 7));
+    std::fs::write(filename, &extended).unwrap();
+    computer.load_palette(filename).unwrap();
+    let palettes = &computer.address_space.ppu.palettes;
+    assert_eq!(palettes[0][0x16], (0x16, 0x2c, 0x42));
+    assert_eq!(palettes[7][0x16], (0xff, 0xff, 0xff));
+
+    std::fs::write(filename, &bytes[..100]).unwrap();
+    assert!(computer.load_palette(filename).is_err());
+    assert_eq!(
+        computer.address_space.ppu.palettes[0][0x16],
+        (0x16, 0x2c, 0x42)
+    );
+
+    std::fs::remove_file(filename).unwrap();
+}
+
+#[test]
+fn ntsc_filter_keeps_hues_and_crawls() {
+    let flat = |color: u8| ntsc::filter_frame(&[color; FRAME_BUFFER_SIZE], &[0; FRAME_HEIGHT], 0);
+    let (r, g, b) = flat(0x16).pixel(100, 100);
+    assert!(r > g && r > b);
+    let (r, g, b) = flat(0x12).pixel(100, 100);
+    assert!(b > r && b > g);
+    let (r, g, b) = flat(0x10).pixel(100, 100);
+    assert!(r.abs_diff(g) < 4 && g.abs_diff(b) < 4);
+    assert_eq!(flat(0x0f).pixel(0, 0), (0, 0, 0));
+
+    // a checkerboard of two hues fringes differently as the subcarrier
+    // phase moves from frame to frame
+    let stripes: Vec<u8> = (0..FRAME_BUFFER_SIZE)
+        .map(|i| if i % 2 == 0 { 0x16 } else { 0x2a })
+        .collect();
+    let frame = |phase| ntsc::filter_frame(&stripes, &[0; FRAME_HEIGHT], phase);
+    assert_eq!(frame(1), frame(1));
+    assert_ne!(frame(0), frame(1));
+}
+
+#[test]
+fn ntsc_render_mode_filters_framebuffer() {
+    let mut computer: NES = Default::default();
+    computer.toggle_ntsc_filter();
+    let ppu = &mut computer.address_space.ppu;
+    let frame = draw_ppu_frame(ppu);
+    assert_eq!(
+        frame,
+        ntsc::filter_frame(&ppu.pixels, &ppu.line_masks, ppu.frame_phase)
+    );
+    computer.toggle_ntsc_filter();
+    assert_eq!(computer.address_space.ppu.render_mode, RenderMode::Normal);
+}
+
+/// a background tile of color 1 at tile (2, 2) and (2, 4), and sprite tile 2
+/// whose left half is color 2
+fn sprite_scene() -> NES {
+    let mut computer: NES = Default::default();
+    let ppu = &mut computer.address_space.ppu;
+    ppu.address_space.chr.bytes[0x10..0x18].fill(0xff);
+    ppu.address_space.chr.bytes[0x28..0x30].fill(0xf0);
+    ppu.address_space.nametables[2 * 32 + 2] = 1;
+    ppu.address_space.nametables[4 * 32 + 2] = 1;
+    ppu.address_space.palette[0x00..0x02].copy_from_slice(&[0x0f, 0x01]);
+    ppu.address_space.palette[0x12] = 0x16;
+    ppu.address_space.palette[0x16] = 0x2a;
+    let sprites: [[u8; 4]; 7] = [
+        [15, 2, 0x00, 16], // in front of the background
+        [15, 2, 0x21, 20], // behind the background
+        [15, 2, 0x21, 24], // behind, over transparent background
+        [31, 2, 0x20, 16], // behind, hiding the sprite after it
+        [31, 2, 0x01, 16],
+        [50, 2, 0x40, 40], // flipped horizontally
+        [60, 2, 0x00, 0],  // in the left column
+    ];
+    ppu.oam_ram = [0xff; 0x100];
+    for (i, sprite) in sprites.iter().enumerate() {
+        ppu.oam_ram[i * 4..i * 4 + 4].copy_from_slice(sprite);
+    }
+    computer
+}
+
+#[test]
+fn sprites_composite_by_priority() {
+    let mut computer = sprite_scene();
+    let ppu = &mut computer.address_space.ppu;
+    ppu.ppu_mask_write(0x18);
+    let frame = draw_ppu_frame(ppu);
+    let red = SYSTEM_COLOR_PALETTE[0x16];
+    let green = SYSTEM_COLOR_PALETTE[0x2a];
+    let background = SYSTEM_COLOR_PALETTE[0x01];
+    let backdrop = SYSTEM_COLOR_PALETTE[0x0f];
+
+    assert_eq!(frame.pixel(16, 16), red);
+    assert_eq!(frame.pixel(20, 16), background);
+    assert_eq!(frame.pixel(24, 16), green);
+    // the transparent right half of the sprite lets the backdrop through
+    assert_eq!(frame.pixel(28, 16), backdrop);
+    // sprites start on the line after their y coordinate
+    assert_eq!(frame.pixel(16, 15), backdrop);
+    assert_eq!(frame.pixel(16, 32), background);
+    assert_eq!(frame.pixel(40, 51), backdrop);
+    assert_eq!(frame.pixel(44, 51), red);
+    assert_eq!(frame.pixel(0, 61), backdrop);
+
+    ppu.ppu_mask_write(0x1c);
+    assert_eq!(draw_ppu_frame(ppu).pixel(0, 61), red);
+}
+
+#[test]
+fn behind_sprites_show_where_the_background_is_not_drawn() {
+    let mut computer = sprite_scene();
+    let ppu = &mut computer.address_space.ppu;
+    // an opaque background tile at tile (0, 1), under a behind sprite
+    ppu.address_space.nametables[32] = 1;
+    ppu.oam_ram[7 * 4..8 * 4].copy_from_slice(&[7, 2, 0x21, 0]);
+    let green = SYSTEM_COLOR_PALETTE[0x2a];
+    let background = SYSTEM_COLOR_PALETTE[0x01];
+
+    ppu.ppu_mask_write(0x1e);
+    let frame = draw_ppu_frame(ppu);
+    assert_eq!(frame.pixel(0, 8), background);
+    assert_eq!(frame.pixel(20, 16), background);
+
+    // a clipped left column leaves only the backdrop for the sprite to cover
+    ppu.ppu_mask_write(0x1c);
+    assert_eq!(draw_ppu_frame(ppu).pixel(0, 8), green);
+
+    ppu.ppu_mask_write(0x14);
+    let frame = draw_ppu_frame(ppu);
+    assert_eq!(frame.pixel(0, 8), green);
+    assert_eq!(frame.pixel(20, 16), green);
+}
+
+#[test]
+fn priority_debug_tags_sprite_pixels() {
+    let mut computer = sprite_scene();
+    computer.toggle_priority_debug();
+    let ppu = &mut computer.address_space.ppu;
+    ppu.ppu_mask_write(0x1e);
+    let frame = draw_ppu_frame(ppu);
+    let colors = PriorityDebugColors::default();
+
+    assert_eq!(frame.pixel(16, 16), colors.sprite_front);
+    assert_eq!(frame.pixel(20, 16), colors.background);
+    assert_eq!(frame.pixel(24, 16), colors.sprite_behind);
+    assert_eq!(frame.pixel(28, 16), colors.backdrop);
+}
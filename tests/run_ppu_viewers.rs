@@ -0,0 +1,130 @@
+use disco5::nes::ppu::PPU;
+use disco5::nes::ppu_structs::{emphasized_palette, PPUMASK, SYSTEM_COLOR_PALETTE};
+
+/// a PPU whose tile 1 is solid color 3, with distinct colors for each
+/// background palette's color 3
+fn ppu_with_solid_tile() -> PPU {
+    let mut ppu: PPU = Default::default();
+    ppu.address_space.chr.bytes[0x10..0x20].fill(0xff);
+    ppu.address_space.palette[0x00] = 0x0f;
+    for (palette, color) in [0x16, 0x1a, 0x12, 0x28].into_iter().enumerate() {
+        ppu.address_space.palette[0x03 + palette * 4] = color;
+    }
+    ppu
+}
+
+#[test]
+fn nametables_are_laid_out_in_quadrants() {
+    let mut ppu = ppu_with_solid_tile();
+    // first tile of each nametable, using palettes 0 to 3
+    for nametable in 0..4 {
+        let base = nametable * 0x400;
+        ppu.address_space.nametables[base] = 1;
+        ppu.address_space.nametables[base + 0x3c0] = nametable as u8;
+    }
+
+    let frame = ppu.render_nametables(None);
+    assert_eq!((frame.width, frame.height), (512, 480));
+    assert_eq!(frame.pixel(0, 0), SYSTEM_COLOR_PALETTE[0x16]);
+    assert_eq!(frame.pixel(263, 7), SYSTEM_COLOR_PALETTE[0x1a]);
+    assert_eq!(frame.pixel(0, 240), SYSTEM_COLOR_PALETTE[0x12]);
+    assert_eq!(frame.pixel(256, 247), SYSTEM_COLOR_PALETTE[0x28]);
+    assert_eq!(frame.pixel(8, 0), SYSTEM_COLOR_PALETTE[0x0f]);
+}
+
+#[test]
+fn scroll_overlay_outlines_the_visible_area() {
+    let mut ppu = ppu_with_solid_tile();
+    // scroll to (300, 100)
+    ppu.t = 0x0400 | (100 >> 3) << 5 | (100 & 7) << 12 | (44 >> 3);
+    ppu.fine_x = 44 & 7;
+    let magenta = (255, 0, 255);
+
+    let frame = ppu.render_nametables(Some(magenta));
+    assert_eq!(frame.pixel(300, 100), magenta);
+    // the right edge wraps into the left nametables
+    assert_eq!(frame.pixel((300 + 255) % 512, 150), magenta);
+    assert_eq!(frame.pixel(400, 100 + 239), magenta);
+    assert_eq!(frame.pixel(400, 150), SYSTEM_COLOR_PALETTE[0x0f]);
+}
+
+#[test]
+fn pattern_tables_use_the_chosen_palette() {
+    let mut ppu = ppu_with_solid_tile();
+    // tile $FF of the second table, columns of colors 0 to 3 in pairs
+    let tile = 0x1000 + 0xff * 16;
+    ppu.address_space.chr.bytes[tile..tile + 8].fill(0b0011_0011);
+    ppu.address_space.chr.bytes[tile + 8..tile + 16].fill(0b0000_1111);
+    ppu.address_space.palette[0x11..0x14].copy_from_slice(&[0x21, 0x22, 0x23]);
+
+    let [left, right] = ppu.render_pattern_tables(4);
+    assert_eq!((left.width, left.height), (128, 128));
+    // tile 1 is solid color 3
+    assert_eq!(left.pixel(8, 0), SYSTEM_COLOR_PALETTE[0x23]);
+    assert_eq!(left.pixel(0, 0), SYSTEM_COLOR_PALETTE[0x0f]);
+    let row: Vec<_> = (120..128).step_by(2).map(|x| right.pixel(x, 127)).collect();
+    let expected: Vec<_> = [0x0f, 0x21, 0x22, 0x23]
+        .map(|color| SYSTEM_COLOR_PALETTE[color])
+        .into();
+    assert_eq!(row, expected);
+
+    let [left, _] = ppu.render_pattern_tables(2);
+    assert_eq!(left.pixel(8, 0), SYSTEM_COLOR_PALETTE[0x12]);
+}
+
+#[test]
+fn palette_view_resolves_entries() {
+    let mut ppu = ppu_with_solid_tile();
+    ppu.address_space.palette[0x15] = 0x2a;
+
+    let view = ppu.palette_view();
+    assert_eq!(view.entries[3].address, 0x3f03);
+    assert_eq!(view.entries[3].index, 0x16);
+    assert_eq!(view.entries[3].rgb, SYSTEM_COLOR_PALETTE[0x16]);
+    assert_eq!(view.entries[0x15].rgb, SYSTEM_COLOR_PALETTE[0x2a]);
+    // $3F10 mirrors the backdrop
+    assert_eq!(view.entries[0x10].index, 0x0f);
+    assert!(!view.greyscale);
+    assert!(view.emphasis.is_empty());
+
+    ppu.ppu_mask_write(0x41);
+    let view = ppu.palette_view();
+    assert!(view.greyscale);
+    assert_eq!(view.emphasis, PPUMASK::EMPH_GREEN);
+    assert_eq!(view.entries[3].index, 0x16);
+    let grey = emphasized_palette(&SYSTEM_COLOR_PALETTE, PPUMASK::EMPH_GREEN)[0x10];
+    assert_eq!(view.entries[3].rgb, grey);
+}
+
+#[test]
+fn sprite_views_decode_oam() {
+    let mut ppu = ppu_with_solid_tile();
+    // tile 2 has a single pixel of color 1 in its top left corner
+    ppu.address_space.chr.bytes[0x20] = 0x80;
+    ppu.address_space.palette[0x15] = 0x2a;
+    ppu.oam_ram[4..8].copy_from_slice(&[0x40, 0x02, 0xff, 0x80]);
+
+    let sprites = ppu.sprite_views();
+    assert_eq!(sprites.len(), 64);
+    let sprite = &sprites[1];
+    assert_eq!(
+        (sprite.index, sprite.x, sprite.y, sprite.tile),
+        (1, 0x80, 0x40, 2)
+    );
+    assert_eq!(sprite.attributes, 0xe3);
+    assert_eq!(sprite.palette, 3);
+    assert!(sprite.behind_background && sprite.flip_horizontal && sprite.flip_vertical);
+    // flipped both ways, the pixel lands in the bottom right corner
+    let thumbnail = &sprite.thumbnail;
+    assert_eq!((thumbnail.width, thumbnail.height), (8, 8));
+    assert_eq!(thumbnail.pixel(7, 7), SYSTEM_COLOR_PALETTE[0x00]);
+    assert_eq!(thumbnail.pixel(0, 0), SYSTEM_COLOR_PALETTE[0x0f]);
+
+    // 8x16 sprites draw tiles 2 and 3 from the table in bit 0 of the tile
+    ppu.ppu_ctrl = 0x20;
+    ppu.oam_ram[6] = 0x01;
+    let thumbnail = &ppu.sprite_views()[1].thumbnail;
+    assert_eq!(thumbnail.height, 16);
+    assert_eq!(thumbnail.pixel(0, 0), SYSTEM_COLOR_PALETTE[0x2a]);
+    assert_eq!(thumbnail.pixel(0, 8), SYSTEM_COLOR_PALETTE[0x0f]);
+}
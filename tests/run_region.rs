@@ -0,0 +1,95 @@
+use disco5::nes::region::{detect_region, Region, RegionDetector, DETECTION_FRAMES};
+use disco5::nes::*;
+
+fn ines_header(flags_7: u8, flags_9: u8, flags_12: u8) -> [u8; 16] {
+    let mut header = [0; 16];
+    header[..4].copy_from_slice(&[0x4e, 0x45, 0x53, 0x1a]);
+    header[4] = 1;
+    header[7] = flags_7;
+    header[9] = flags_9;
+    header[12] = flags_12;
+    header
+}
+
+#[test]
+fn header_and_filename_mark_pal() {
+    let ntsc = ines_header(0, 0, 0);
+    assert_eq!(detect_region(&ntsc, "game.nes"), None);
+    assert_eq!(
+        detect_region(&ines_header(0, 1, 0), "game.nes")
+            .unwrap()
+            .region,
+        Region::Pal
+    );
+    // NES 2.0 keeps the timing in byte 12 and leaves byte 9 to the ROM sizes
+    assert_eq!(
+        detect_region(&ines_header(0x08, 0, 1), "game.nes")
+            .unwrap()
+            .region,
+        Region::Pal
+    );
+    assert_eq!(detect_region(&ines_header(0x08, 1, 0), "game.nes"), None);
+
+    let suggestion = detect_region(&ntsc, "Game (Europe).nes").unwrap();
+    assert_eq!(suggestion.region, Region::Pal);
+    assert_eq!(suggestion.reason, "filename is tagged (Europe)");
+}
+
+#[test]
+fn detector_decides_once_after_the_detection_window() {
+    let mut detector = RegionDetector::default();
+    for frame in 0..DETECTION_FRAMES - 1 {
+        detector.ppu_data_write(frame % 4 != 0);
+        assert_eq!(detector.end_frame(), None);
+    }
+    detector.ppu_data_write(true);
+    let suggestion = detector.end_frame().unwrap();
+    assert_eq!(suggestion.region, Region::Pal);
+    assert_eq!(
+        suggestion.reason,
+        format!("game wrote VRAM after vblank in 90 of {DETECTION_FRAMES} frames")
+    );
+    detector.ppu_data_write(true);
+    assert_eq!(detector.end_frame(), None);
+
+    // writes made in vblank or with rendering off are what NTSC games do
+    let mut detector = RegionDetector::default();
+    for _ in 0..DETECTION_FRAMES {
+        detector.ppu_data_write(false);
+        assert_eq!(detector.end_frame(), None);
+    }
+}
+
+/// runs program from $0200 until the detection window closes
+fn run_detection(program: &[u8]) -> NES {
+    let mut computer: NES = Default::default();
+    computer.headless = true;
+    computer.address_space.bytes[0x0200..0x0200 + program.len()].copy_from_slice(program);
+    computer.cpu.pc = 0x0200;
+    for _ in 0..DETECTION_FRAMES {
+        computer.run_frame(false);
+    }
+    computer
+}
+
+#[test]
+fn vram_writes_past_vblank_suggest_pal() {
+    // SEI, LDA #$08, STA $2001, then STA $2007 forever
+    let computer = run_detection(&[
+        0x78, 0xa9, 0x08, 0x8d, 0x01, 0x20, 0x8d, 0x07, 0x20, 0x4c, 0x06, 0x02,
+    ]);
+    let suggestion = computer.region_suggestion.clone().unwrap();
+    assert_eq!(suggestion.region, Region::Pal);
+
+    let mut computer = computer;
+    computer.accept_region_suggestion();
+    assert_eq!(computer.region, Region::Pal);
+    assert_eq!(computer.region_suggestion, None);
+}
+
+#[test]
+fn vram_writes_with_rendering_off_suggest_nothing() {
+    // SEI, then STA $2007 forever with PPUMASK left clear
+    let computer = run_detection(&[0x78, 0x8d, 0x07, 0x20, 0x4c, 0x01, 0x02]);
+    assert_eq!(computer.region_suggestion, None);
+}
@@ -1,13 +1,17 @@
+use disco5::nes::bus::BusConfig;
 use disco5::nes::*;
+use disco5::testing::fixture_path;
 
 #[test]
 fn countdown_program() {
+    let Some(path) = fixture_path("countdown.txt") else {
+        return;
+    };
+
     let mut computer: NES = Default::default();
-    computer.address_space.cpu_only_mode = true;
+    computer.address_space.config = BusConfig::FlatRam;
 
-    computer
-        .load_asm_6502js(&String::from("sample_programs/countdown.txt"))
-        .unwrap(); // NOTE: verifies that program loaded without errors
+    computer.load_asm_6502js(&path).unwrap(); // NOTE: verifies that program loaded without errors
 
     assert_eq!(
         &computer.address_space.bytes[600..616],
@@ -0,0 +1,251 @@
+use std::io;
+
+use disco5::nes::apu::APU;
+use disco5::nes::device::Device;
+use disco5::nes::ppu::PPU;
+use disco5::nes::save_state::{StateReader, StateWriter};
+use disco5::nes::*;
+use disco5::testing::draw_ppu_frame;
+
+fn demo_ppu() -> PPU {
+    let mut computer: NES = Default::default();
+    computer.headless = true;
+    computer.load_demo().unwrap();
+    for _ in 0..10 {
+        computer.run_frame(false);
+    }
+    // stop part way through a line
+    for _ in 0..1000 {
+        computer.step(false);
+    }
+    computer.address_space.ppu
+}
+
+#[test]
+fn ppu_state_round_trips() {
+    let mut ppu = demo_ppu();
+    let state = ppu.save_state();
+    let position = (ppu.scanline, ppu.dot);
+    let expected = draw_ppu_frame(&mut ppu);
+
+    let mut restored: PPU = Default::default();
+    restored.load_state(&state).unwrap();
+    assert_eq!((restored.scanline, restored.dot), position);
+    assert_eq!(draw_ppu_frame(&mut restored), expected);
+    assert_eq!(restored.save_state(), ppu.save_state());
+}
+
+#[test]
+fn invalid_ppu_states_are_rejected() {
+    let state = demo_ppu().save_state();
+    let mut ppu: PPU = Default::default();
+    let untouched = ppu.save_state();
+
+    assert!(ppu.load_state(&state[..state.len() - 1]).is_err());
+    assert!(ppu.load_state(&[state.as_slice(), &[0]].concat()).is_err());
+    let mut wrong_version = state.clone();
+    wrong_version[4] += 1;
+    assert!(ppu.load_state(&wrong_version).is_err());
+    assert!(ppu.load_state(b"CPU\0").is_err());
+    assert_eq!(ppu.save_state(), untouched);
+}
+
+#[test]
+fn ppu_state_keeps_the_a12_filter() {
+    let mut ppu = demo_ppu();
+    ppu.a12 = true;
+    ppu.a12_low_dots = 7;
+    ppu.a12_rose = true;
+
+    let mut restored: PPU = Default::default();
+    restored.load_state(&ppu.save_state()).unwrap();
+    assert!(restored.a12);
+    assert_eq!(restored.a12_low_dots, 7);
+    assert!(restored.a12_rose);
+}
+
+#[cfg(feature = "serde")]
+#[test]
+fn ppu_round_trips_through_serde() {
+    let mut ppu = demo_ppu();
+    ppu.a12_low_dots = 3;
+    let json = serde_json::to_string(&ppu).unwrap();
+
+    let mut restored: PPU = serde_json::from_str(&json).unwrap();
+    assert_eq!(restored.save_state(), ppu.save_state());
+    assert_eq!(draw_ppu_frame(&mut restored), draw_ppu_frame(&mut ppu));
+
+    let too_long = json.replacen("\"oam_ram\":[", "\"oam_ram\":[1,", 1);
+    assert!(serde_json::from_str::<PPU>(&too_long).is_err());
+}
+
+/// an APU part way through playing every channel
+fn busy_apu() -> APU {
+    let mut apu: APU = Default::default();
+    for (address, data) in [
+        (0x4015, 0x1f),
+        (0x4000, 0x4f),
+        (0x4001, 0x9a),
+        (0x4002, 0x80),
+        (0x4003, 0x09),
+        (0x4004, 0xb8),
+        (0x4006, 0x40),
+        (0x4007, 0x02),
+        (0x4008, 0x20),
+        (0x400a, 0x30),
+        (0x400b, 0x01),
+        (0x400c, 0x03),
+        (0x400e, 0x85),
+        (0x400f, 0x10),
+        (0x4010, 0x4e),
+        (0x4017, 0x80),
+    ] {
+        apu.write_register(address, data);
+    }
+    for cycle in 0..12_345 {
+        apu.clock();
+        if let Some(address) = apu.dmc.dma_address() {
+            apu.dmc.fill_sample_buffer((cycle ^ address) as u8);
+        }
+    }
+    apu
+}
+
+/// the mixed output of the next cycles
+fn apu_output(apu: &mut APU, cycles: usize) -> Vec<f32> {
+    (0..cycles)
+        .map(|_| {
+            apu.clock();
+            if apu.dmc.dma_address().is_some() {
+                apu.dmc.fill_sample_buffer(0x55);
+            }
+            apu.mix()
+        })
+        .collect()
+}
+
+#[test]
+fn apu_state_round_trips() {
+    let mut apu = busy_apu();
+    let state = apu.save_state();
+
+    let mut restored: APU = Default::default();
+    restored.load_state(&state).unwrap();
+    assert_eq!(restored.save_state(), state);
+    assert_eq!(
+        apu_output(&mut restored, 50_000),
+        apu_output(&mut apu, 50_000)
+    );
+    assert_eq!(restored.save_state(), apu.save_state());
+}
+
+#[test]
+fn bad_apu_states_are_rejected() {
+    let state = busy_apu().save_state();
+    let mut apu: APU = Default::default();
+    let untouched = apu.save_state();
+
+    assert!(apu.load_state(&state[..state.len() - 1]).is_err());
+    assert!(apu.load_state(&[state.as_slice(), &[0]].concat()).is_err());
+    // pulse 1's envelope volume, which is only 4 bits
+    let mut out_of_range = state.clone();
+    out_of_range[5 + 3] = 0x10;
+    assert!(apu.load_state(&out_of_range).is_err());
+    assert!(apu.load_state(&PPU::default().save_state()).is_err());
+    assert_eq!(apu.save_state(), untouched);
+}
+
+fn demo_computer() -> NES {
+    let mut computer: NES = Default::default();
+    computer.headless = true;
+    computer.load_demo().unwrap();
+    for _ in 0..10 {
+        computer.run_frame(false);
+    }
+    for _ in 0..1000 {
+        computer.step(false);
+    }
+    computer
+}
+
+#[test]
+fn bus_state_round_trips() {
+    let mut computer = demo_computer();
+    let state = computer.address_space.save_state();
+
+    let mut restored: NES = Default::default();
+    restored.headless = true;
+    restored.load_demo().unwrap();
+    restored.address_space.load_state(&state).unwrap();
+    restored.cpu = computer.cpu;
+    assert_eq!(restored.address_space.save_state(), state);
+
+    // both machines carry on identically
+    for _ in 0..5000 {
+        computer.step(false);
+        restored.step(false);
+    }
+    assert_eq!(
+        restored.address_space.save_state(),
+        computer.address_space.save_state()
+    );
+}
+
+/// a device whose only state is a counter of its ticks
+struct Counter(u32);
+
+impl Device for Counter {
+    fn read(&mut self, _address: u16) -> u8 {
+        self.0 as u8
+    }
+
+    fn write(&mut self, _address: u16, _data: u8) {}
+
+    fn tick(&mut self) {
+        self.0 += 1;
+    }
+
+    fn write_state(&self, state: &mut StateWriter) {
+        state.u32(self.0);
+    }
+
+    fn read_state(&mut self, state: &mut StateReader) -> io::Result<()> {
+        self.0 = state.u32()?;
+        Ok(())
+    }
+}
+
+#[test]
+fn mapped_devices_are_saved_with_the_bus() {
+    let mut computer: NES = Default::default();
+    let bus = &mut computer.address_space;
+    bus.map_device(0x5000..=0x5000, Box::new(Counter(0)));
+    for _ in 0..7 {
+        bus.tick();
+    }
+    let state = bus.save_state();
+    bus.tick();
+    assert_eq!(bus.read(0x5000), 8);
+
+    bus.load_state(&state).unwrap();
+    assert_eq!(bus.read(0x5000), 7);
+
+    // the same devices have to be mapped to load it
+    let mut computer: NES = Default::default();
+    assert!(computer.address_space.load_state(&state).is_err());
+}
+
+#[test]
+fn invalid_bus_states_are_rolled_back() {
+    let state = demo_computer().address_space.save_state();
+    let mut computer: NES = Default::default();
+    let bus = &mut computer.address_space;
+    bus.write(0x0000, 0x42);
+    let untouched = bus.save_state();
+
+    assert!(bus.load_state(&state[..state.len() - 1]).is_err());
+    assert!(bus.load_state(&[state.as_slice(), &[0]].concat()).is_err());
+    assert!(bus.load_state(b"PPU\0").is_err());
+    assert_eq!(bus.save_state(), untouched);
+    assert_eq!(bus.read(0x0000), 0x42);
+}
@@ -0,0 +1,24 @@
+use disco5::nes::*;
+use disco5::selftest::run_selftest;
+
+#[test]
+fn selftest_passes() {
+    for result in run_selftest() {
+        assert!(
+            result.outcome.is_ok(),
+            "{}: {:?}",
+            result.name,
+            result.outcome
+        );
+    }
+}
+
+#[test]
+fn demo_rom_boots_to_reset_vector() {
+    let mut nes: NES = Default::default();
+    nes.headless = true;
+    nes.load_demo().unwrap();
+
+    assert_eq!(nes.cpu.pc, 0xc000);
+    assert_eq!(nes.window.title, "Disco5 Demo");
+}
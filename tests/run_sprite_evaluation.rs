@@ -0,0 +1,75 @@
+use disco5::nes::ppu::PPU;
+use disco5::nes::ppu_structs::AccuracyProfile;
+
+/// eight sprites on scanline 10, followed by a sprite whose tile number
+/// happens to look like an in-range y coordinate
+fn overflow_bug_oam() -> PPU {
+    let mut ppu: PPU = Default::default();
+    ppu.oam_ram = [0xff; 0x100];
+    for sprite in 0..8 {
+        ppu.oam_ram[sprite * 4] = 10;
+    }
+    ppu.oam_ram[9 * 4 + 1] = 10;
+    ppu
+}
+
+#[test]
+fn sprite_overflow_fast() {
+    let ppu = overflow_bug_oam();
+    assert!(!ppu.evaluate_sprite_overflow(10));
+}
+
+#[test]
+fn sprite_overflow_accurate_false_positive() {
+    let mut ppu = overflow_bug_oam();
+    ppu.accuracy = AccuracyProfile::Accurate;
+    assert!(ppu.evaluate_sprite_overflow(10));
+}
+
+#[test]
+fn evaluation_fills_secondary_oam_in_oam_order() {
+    let mut ppu: PPU = Default::default();
+    ppu.oam_ram = [0xff; 0x100];
+    // sprites 3 and 7 cover scanline 21, sprite 5 ends just above it
+    ppu.oam_ram[3 * 4..3 * 4 + 4].copy_from_slice(&[20, 0x11, 0x01, 0x30]);
+    ppu.oam_ram[5 * 4..5 * 4 + 4].copy_from_slice(&[12, 0x22, 0x02, 0x40]);
+    ppu.oam_ram[7 * 4..7 * 4 + 4].copy_from_slice(&[14, 0x33, 0x03, 0x50]);
+
+    assert!(!ppu.evaluate_sprites(20));
+    assert_eq!(ppu.secondary_oam_sprites, 2);
+    assert_eq!(
+        &ppu.secondary_oam[..8],
+        &[20, 0x11, 0x01, 0x30, 14, 0x33, 0x03, 0x50]
+    );
+    assert!(ppu.secondary_oam[8..].iter().all(|&byte| byte == 0xff));
+    assert!(!ppu.sprite_zero_on_line);
+
+    // 8x16 sprites bring sprite 5 into range
+    ppu.ppu_ctrl = 0x20;
+    ppu.oam_ram[0] = 10;
+    ppu.evaluate_sprites(20);
+    assert_eq!(ppu.secondary_oam_sprites, 4);
+    assert_eq!(ppu.secondary_oam[..4], [10, 0xff, 0xff, 0xff]);
+    assert!(ppu.sprite_zero_on_line);
+}
+
+#[test]
+fn evaluation_keeps_only_eight_sprites() {
+    let mut ppu: PPU = Default::default();
+    ppu.oam_ram = [0xff; 0x100];
+    for sprite in 0..10 {
+        ppu.oam_ram[sprite * 4] = 10;
+        ppu.oam_ram[sprite * 4 + 1] = sprite as u8;
+    }
+    assert!(ppu.evaluate_sprites(10));
+    assert_eq!(ppu.secondary_oam_sprites, 8);
+    let tiles: Vec<u8> = ppu
+        .secondary_oam
+        .chunks(4)
+        .map(|sprite| sprite[1])
+        .collect();
+    assert_eq!(tiles, [0, 1, 2, 3, 4, 5, 6, 7]);
+
+    ppu.end_vblank();
+    assert_eq!(ppu.secondary_oam_sprites, 0);
+}
@@ -0,0 +1,55 @@
+use disco5::nes::bus::BusConfig;
+use disco5::nes::frame::Frame;
+use disco5::nes::*;
+use disco5::testing::{
+    assert_memory_eq, fixture_path, frame_hashes, run_frames_and_hash, run_until_pc,
+};
+
+#[test]
+fn countdown_program_with_helpers() {
+    let Some(path) = fixture_path("countdown.txt") else {
+        return;
+    };
+
+    let mut computer: NES = Default::default();
+    computer.address_space.config = BusConfig::FlatRam;
+
+    computer.load_asm_6502js(&path).unwrap();
+
+    run_until_pc(&mut computer, 0x0264);
+
+    assert_memory_eq(&computer, 16..27, &[10, 9, 8, 7, 6, 5, 4, 3, 2, 1, 0]);
+}
+
+#[test]
+fn memory_assertions_see_prg_ram_and_mirrors() {
+    let mut computer: NES = Default::default();
+    computer.load_demo().unwrap();
+    let bus = &mut computer.address_space;
+    bus.write(0x6000, 0xde);
+    bus.write(0x6001, 0xb0);
+    bus.write(0x0801, 0x61);
+
+    assert_memory_eq(&computer, 0x6000..0x6002, &[0xde, 0xb0]);
+    assert_memory_eq(&computer, 0x0001..0x0002, &[0x61]);
+}
+
+#[test]
+fn frame_hashes_cover_every_frame() {
+    let mut computer: NES = Default::default();
+    computer.headless = true;
+    computer.load_demo().unwrap();
+    let hashes = frame_hashes(&mut computer, 10);
+    assert_eq!(hashes.len(), 10);
+
+    let mut rerun: NES = Default::default();
+    rerun.headless = true;
+    rerun.load_demo().unwrap();
+    assert_eq!(run_frames_and_hash(&mut rerun, 10), hashes[9]);
+
+    let frame = Frame::new(2, 2);
+    let mut changed = frame.clone();
+    changed.set_pixel(1, 1, (0, 0, 1));
+    assert_eq!(frame.hash(), Frame::new(2, 2).hash());
+    assert_ne!(frame.hash(), changed.hash());
+}
@@ -0,0 +1,40 @@
+use disco5::nes::wav::write_wav;
+use disco5::nes::*;
+
+#[test]
+fn wav_files_hold_16_bit_pcm() {
+    let mut bytes = Vec::new();
+    write_wav(&mut bytes, &[0.0, 0.5, -1.0, 2.0], 48_000).unwrap();
+    assert_eq!(bytes.len(), 44 + 4 * 2);
+    assert_eq!(&bytes[0..4], b"RIFF");
+    assert_eq!(u32::from_le_bytes(bytes[4..8].try_into().unwrap()), 36 + 8);
+    assert_eq!(&bytes[8..16], b"WAVEfmt ");
+    // mono PCM at 48 kHz
+    assert_eq!(&bytes[20..24], &[1, 0, 1, 0]);
+    assert_eq!(
+        u32::from_le_bytes(bytes[24..28].try_into().unwrap()),
+        48_000
+    );
+    assert_eq!(&bytes[36..40], b"data");
+    let samples: Vec<i16> = bytes[44..]
+        .chunks_exact(2)
+        .map(|sample| i16::from_le_bytes([sample[0], sample[1]]))
+        .collect();
+    // samples past full scale are clipped
+    assert_eq!(samples, [0, 16383, -32767, 32767]);
+}
+
+#[test]
+fn recorded_audio_covers_the_frames_run() {
+    let mut computer: NES = Default::default();
+    computer.headless = true;
+    computer.load_demo().unwrap();
+    let samples = computer.record_audio(60);
+    // 60 NTSC frames are a little under a second at 48 kHz, the first one
+    // starting part way through
+    assert!(
+        (47_500..48_000).contains(&samples.len()),
+        "{}",
+        samples.len()
+    );
+}